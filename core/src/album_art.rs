@@ -0,0 +1,116 @@
+//! Colors of the current track's album art, for tinting the overlay
+use std::collections::HashMap;
+
+use thiserror::Error;
+use url::Url;
+
+#[derive(Error, Debug)]
+pub enum AlbumArtError {
+    #[error("Downloading the album art failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Reading the album art failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Decoding the album art failed: {0}")]
+    Decode(#[from] image::ImageError),
+    #[error("Unsupported album art url {0}")]
+    UnsupportedUrl(String),
+}
+
+/// Main and accent color of an album cover
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Most common color
+    pub dominant: [u8; 3],
+    /// Most common colorful one, the dominant color for gray covers
+    pub accent: [u8; 3],
+}
+
+/// Palette of the art at `url`, `http(s)` or a local `file` url as MPRIS players report them
+pub async fn fetch_palette(client: &reqwest::Client, url: &str) -> Result<Palette, AlbumArtError> {
+    let parsed = Url::parse(url).map_err(|_| AlbumArtError::UnsupportedUrl(url.to_owned()))?;
+    let bytes = match parsed.scheme() {
+        "http" | "https" => client
+            .get(parsed)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec(),
+        "file" => {
+            let path = parsed
+                .to_file_path()
+                .map_err(|()| AlbumArtError::UnsupportedUrl(url.to_owned()))?;
+            tokio::fs::read(path).await?
+        }
+        _ => return Err(AlbumArtError::UnsupportedUrl(url.to_owned())),
+    };
+    tokio::task::spawn_blocking(move || extract_palette(&bytes))
+        .await
+        .expect("Palette extraction shouldn't panic")
+}
+
+/// Palette of an encoded jpeg or png
+pub fn extract_palette(bytes: &[u8]) -> Result<Palette, AlbumArtError> {
+    let image = image::load_from_memory(bytes)?.thumbnail(32, 32).to_rgb8();
+    Ok(palette_of(image.pixels().map(|pixel| pixel.0)))
+}
+
+/// Colors are bucketed by their top 4 bits a channel, each bucket stands for the average of
+/// its pixels
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn palette_of(pixels: impl Iterator<Item = [u8; 3]>) -> Palette {
+    let mut buckets: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+    for pixel in pixels {
+        let (count, sum) = buckets.entry(pixel.map(|c| c >> 4)).or_default();
+        *count += 1;
+        for (sum, c) in sum.iter_mut().zip(pixel) {
+            *sum += u32::from(c);
+        }
+    }
+    let colors: Vec<(u32, [u8; 3])> = buckets
+        .into_values()
+        .map(|(count, sum)| (count, sum.map(|sum| (sum / count) as u8)))
+        .collect();
+
+    let dominant = colors
+        .iter()
+        .max_by_key(|(count, _)| *count)
+        .map_or([0; 3], |&(_, color)| color);
+    let accent = colors
+        .iter()
+        .filter(|(_, color)| saturation(*color) > 0.25 && color.iter().any(|&c| c > 50))
+        .max_by(|a, b| {
+            let score = |(count, color): &(u32, [u8; 3])| *count as f32 * saturation(*color);
+            score(a).total_cmp(&score(b))
+        })
+        .map_or(dominant, |&(_, color)| color);
+    Palette { dominant, accent }
+}
+
+fn saturation(color: [u8; 3]) -> f32 {
+    let max = color.iter().copied().max().unwrap_or(0);
+    let min = color.iter().copied().min().unwrap_or(0);
+    if max == 0 {
+        0.0
+    } else {
+        f32::from(max - min) / f32::from(max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gray_cover_with_a_red_spot() {
+        let gray = std::iter::repeat_n([120, 120, 120], 70);
+        let red = std::iter::repeat_n([220, 30, 30], 30);
+        let palette = palette_of(gray.chain(red));
+        assert_eq!(palette.dominant, [120, 120, 120]);
+        assert_eq!(palette.accent, [220, 30, 30]);
+
+        let palette = palette_of(std::iter::repeat_n([10, 10, 10], 10));
+        assert_eq!(palette.accent, palette.dominant);
+    }
+}
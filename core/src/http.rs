@@ -0,0 +1,55 @@
+//! Construction of the HTTP clients, so every request honours the network settings
+use std::time::Duration;
+
+use tracing::error;
+
+use crate::settings::Settings;
+
+/// Client builder for all API requests (not used in oauth requests)
+pub fn client_builder(settings: &Settings) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_millis(settings.connect_timeout_ms))
+        .timeout(Duration::from_millis(settings.request_timeout_ms));
+    if settings.proxy_url.is_empty() {
+        return builder;
+    }
+    match reqwest::Proxy::all(&settings.proxy_url) {
+        Ok(proxy) => builder.proxy(with_auth(proxy, settings, reqwest::Proxy::basic_auth)),
+        Err(err) => {
+            error!("Invalid proxy url {}: {err}", settings.proxy_url);
+            builder
+        }
+    }
+}
+
+/// Client for the OAuth token requests, oauth2 ships its own reqwest version
+pub fn oauth_client(settings: &Settings) -> oauth2::reqwest::Client {
+    let builder = oauth2::reqwest::ClientBuilder::new()
+        .redirect(oauth2::reqwest::redirect::Policy::none())
+        .connect_timeout(Duration::from_millis(settings.connect_timeout_ms))
+        .timeout(Duration::from_millis(settings.request_timeout_ms));
+    let builder = if settings.proxy_url.is_empty() {
+        builder
+    } else {
+        match oauth2::reqwest::Proxy::all(&settings.proxy_url) {
+            Ok(proxy) => builder.proxy(with_auth(
+                proxy,
+                settings,
+                oauth2::reqwest::Proxy::basic_auth,
+            )),
+            Err(err) => {
+                error!("Invalid proxy url {}: {err}", settings.proxy_url);
+                builder
+            }
+        }
+    };
+    builder.build().expect("Client should build")
+}
+
+fn with_auth<P>(proxy: P, settings: &Settings, basic_auth: impl FnOnce(P, &str, &str) -> P) -> P {
+    if settings.proxy_username.is_empty() {
+        proxy
+    } else {
+        basic_auth(proxy, &settings.proxy_username, &settings.proxy_password)
+    }
+}
@@ -0,0 +1,43 @@
+//! Everything of the lyrics overlay but the window: the spotify client and other player
+//! sources, the lyric providers, the lrc parser, the cache and the playback clock.
+//!
+//! `runtime::start_runtime` runs it, talking to a UI over `MessageToRT` and `MessageToUI`.
+//!
+//! Without the default `native` feature only the lrc parser, the lyrics json, the matching and
+//! the lrclib client are built, for `wasm32-unknown-unknown`:
+//! `cargo build -p lyrics-overlay-core --no-default-features --target wasm32-unknown-unknown`
+#![warn(clippy::pedantic)]
+#![allow(
+    clippy::must_use_candidate,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc
+)]
+
+#[cfg(feature = "native")]
+pub mod album_art;
+#[cfg(feature = "native")]
+mod http;
+pub mod lyrics_fetch;
+pub mod lyrics_json;
+pub mod lyrics_parser;
+#[cfg(feature = "native")]
+mod messages;
+#[cfg(feature = "native")]
+mod oauth;
+#[cfg(feature = "native")]
+pub mod paths;
+#[cfg(feature = "native")]
+pub mod playback_clock;
+#[cfg(feature = "native")]
+pub mod player;
+pub mod profanity;
+pub mod romanize;
+#[cfg(feature = "native")]
+pub mod runtime;
+#[cfg(feature = "native")]
+pub mod settings;
+#[cfg(feature = "native")]
+pub mod spotify;
+
+#[cfg(feature = "native")]
+pub use messages::{MessageToRT, MessageToUI, StatusEvent};
@@ -0,0 +1,105 @@
+//! Moving the lyrics cache between machines as a single `.tar.gz` archive
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use tracing::{info, warn};
+
+use crate::{
+    MessageToUI,
+    lyrics_fetch::{
+        LyricsFetcher,
+        cache::{LyricCacheMeta, read_folder_meta, track_folders},
+    },
+    runtime::{Messages, RuntimeError},
+};
+
+/// Archives are unpacked here first, in the cache folder
+const IMPORT_FOLDER: &str = ".import";
+
+impl LyricsFetcher {
+    /// Packs every cached track into an archive at `path`
+    pub async fn export_cache(&self, path: PathBuf) -> Result<Messages, RuntimeError> {
+        let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+        let tracks = write_archive(&cache_folder, &path).map_err(RuntimeError::CacheArchive)?;
+        info!("Exported {tracks} cached tracks to {}", path.display());
+        Ok(Messages::to_ui(MessageToUI::CacheArchived(format!(
+            "Exported {tracks} tracks to {}",
+            path.display()
+        ))))
+    }
+
+    /// Adds the tracks of an archive to the cache, replacing cached tracks only with newer ones
+    pub async fn import_cache(&self, path: PathBuf) -> Result<Messages, RuntimeError> {
+        let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+        let import_folder = cache_folder.join(IMPORT_FOLDER);
+        let res = unpack_archive(&path, &import_folder)
+            .and_then(|()| merge_tracks(&import_folder, &cache_folder));
+        if let Err(err) = fs::remove_dir_all(&import_folder) {
+            warn!("Failed removing {}: {err}", import_folder.display());
+        }
+        let (added, skipped) = res.map_err(RuntimeError::CacheArchive)?;
+        info!(
+            "Imported {added} cached tracks from {}, kept {skipped} newer ones",
+            path.display()
+        );
+        self.cache_index.lock().unwrap().rebuild(&cache_folder);
+        self.recent_lyrics.lock().unwrap().clear();
+        Ok(Messages::to_ui(MessageToUI::CacheArchived(format!(
+            "Imported {added} tracks, kept {skipped} already cached ones"
+        ))))
+    }
+}
+
+/// Writes the track folders with a meta, the number written
+fn write_archive(cache_folder: &Path, path: &Path) -> std::io::Result<usize> {
+    let mut archive =
+        tar::Builder::new(GzEncoder::new(File::create(path)?, Compression::default()));
+    let mut tracks = 0;
+    for track_folder in track_folders(cache_folder) {
+        if read_folder_meta(&track_folder).is_none() {
+            continue;
+        }
+        archive.append_dir_all(track_folder.file_name().unwrap_or_default(), &track_folder)?;
+        tracks += 1;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(tracks)
+}
+
+fn unpack_archive(path: &Path, import_folder: &Path) -> std::io::Result<()> {
+    if import_folder.exists() {
+        fs::remove_dir_all(import_folder)?;
+    }
+    fs::create_dir_all(import_folder)?;
+    // Entries pointing outside the folder are skipped by unpack
+    tar::Archive::new(GzDecoder::new(File::open(path)?)).unpack(import_folder)
+}
+
+/// Moves the unpacked tracks into the cache, the number added and skipped
+fn merge_tracks(import_folder: &Path, cache_folder: &Path) -> std::io::Result<(usize, usize)> {
+    let (mut added, mut skipped) = (0, 0);
+    for entry in fs::read_dir(import_folder)?.flatten() {
+        let Some(imported) = read_folder_meta(&entry.path()) else {
+            continue;
+        };
+        let track_folder = cache_folder.join(entry.file_name());
+        if let Some(cached) = read_folder_meta(&track_folder) {
+            if last_fetched(&cached) >= last_fetched(&imported) {
+                skipped += 1;
+                continue;
+            }
+            fs::remove_dir_all(&track_folder)?;
+        }
+        fs::rename(entry.path(), track_folder)?;
+        added += 1;
+    }
+    Ok((added, skipped))
+}
+
+fn last_fetched(meta: &LyricCacheMeta) -> u64 {
+    meta.fetched_at.or(meta.not_found_at).unwrap_or(0)
+}
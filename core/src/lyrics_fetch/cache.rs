@@ -0,0 +1,1156 @@
+//! Caching module for the fetched lyrics, so we don't spam all our friendly APIs
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{debug, info, trace, warn};
+
+use crate::{
+    MessageToUI,
+    lyrics_fetch::{
+        LyricsFetcher, LyricsFetcherErr, LyricsProviderKind, LyricsRequestInfo, LyricsSource,
+        TranslationBackendKind,
+        lrc::{get_lrclib_by_id, response_lyrics},
+        matching::same_song,
+    },
+    lyrics_json::LyricsJson,
+    lyrics_parser::SongLyrics,
+    runtime::{Messages, RuntimeError},
+};
+
+/// Maps spotify ids to track folders, in the cache folder
+const INDEX_FILE: &str = "index.json";
+/// Entries that couldn't be migrated are moved here, in the cache folder
+const QUARANTINE_FOLDER: &str = "quarantine";
+
+/// Version of the cache entries written, bumped whenever their layout changes with a step in
+/// `migrate_entry`
+///
+/// 1: lyrics stored as lrc files, folders named by `folder_name`
+const CACHE_VERSION: u32 = 1;
+
+/// How often the cache is checked for stale entries and its size
+const REFRESH_INTERVAL: Duration = Duration::from_hours(1);
+/// Parsed lyrics kept in memory, for skipping back and forth between recent tracks
+const RECENT_LYRICS: usize = 16;
+
+/// Longest track folder name before the hash, well under the path limits
+const MAX_FOLDER_NAME_CHARS: usize = 100;
+/// File systems limit names to 255 bytes, this leaves room for the hash
+const MAX_FOLDER_NAME_BYTES: usize = 240;
+
+/// Pause between lrclib requests while refreshing, so foreground fetches aren't held up
+const REFRESH_PAUSE: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(super) struct LyricCacheMeta {
+    /// Layout of the entry, 0 for entries from before we kept it
+    #[serde(default)]
+    pub version: u32,
+    pub spotify_id: Option<String>,
+    /// None if no provider had lyrics
+    pub provider: Option<LyricsProviderKind>,
+    /// Id of the lyrics at the provider
+    pub provider_id: Option<String>,
+    /// How the lyrics were matched, like the query step that found them or a manual pick
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_by: Option<String>,
+    pub track_name: String,
+    pub artist_name: String,
+    pub album_name: String,
+    pub duration_sec: f64,
+    /// Unix time at which no provider had lyrics, they aren't asked again for a while
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_found_at: Option<u64>,
+    /// Lyrics the user flagged as wrong for this track
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<LyricsSource>,
+    /// Lyrics the user pinned for this track, used without searching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<LyricsSource>,
+    /// Unix time at which the lyrics were last fetched, `None` for entries from before we kept it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<u64>,
+    /// Unix time at which the lyrics were last read from the cache, for evicting the oldest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessed_at: Option<u64>,
+}
+
+impl LyricCacheMeta {
+    fn new(req: &LyricsRequestInfo) -> Self {
+        Self {
+            version: CACHE_VERSION,
+            spotify_id: req.spotify_id.clone(),
+            provider: None,
+            provider_id: None,
+            matched_by: None,
+            track_name: req.track_name.clone(),
+            artist_name: req.artist_name.clone(),
+            album_name: req.album_name.clone(),
+            duration_sec: req.duration_sec,
+            not_found_at: None,
+            rejected: Vec::new(),
+            pinned: None,
+            fetched_at: None,
+            accessed_at: None,
+        }
+    }
+
+    /// lrclib lyrics fetched longer than `max_age_secs` ago
+    fn is_stale(&self, max_age_secs: u64) -> bool {
+        self.provider == Some(LyricsProviderKind::Lrclib)
+            && self.provider_id.is_some()
+            && self
+                .fetched_at
+                .is_none_or(|fetched_at| unix_now().saturating_sub(fetched_at) >= max_age_secs)
+    }
+
+    fn request(&self) -> LyricsRequestInfo {
+        LyricsRequestInfo {
+            spotify_id: self.spotify_id.clone(),
+            file_path: None,
+            duration_sec: self.duration_sec,
+            track_name: self.track_name.clone(),
+            artist_name: self.artist_name.clone(),
+            album_name: self.album_name.clone(),
+            rejected: Vec::new(),
+        }
+    }
+}
+
+/// Track folders by spotify id, so cache hits don't depend on the folder name matching the
+/// title and duration we get today. Rebuilt from the `.meta` files when missing or out of date
+#[derive(Debug, Default)]
+pub(super) struct CacheIndex {
+    /// Cache folder the index belongs to
+    cache_folder: Option<PathBuf>,
+    by_spotify_id: HashMap<String, String>,
+}
+
+impl CacheIndex {
+    /// Loads the index of `cache_folder` unless it's the one already loaded
+    fn load(&mut self, cache_folder: &Path) {
+        if self.cache_folder.as_deref() == Some(cache_folder) {
+            return;
+        }
+        self.cache_folder = Some(cache_folder.to_owned());
+        match fs::read_to_string(cache_folder.join(INDEX_FILE))
+            .ok()
+            .and_then(|index| serde_json::from_str(&index).ok())
+        {
+            Some(index) => self.by_spotify_id = index,
+            None => self.rebuild(cache_folder),
+        }
+    }
+
+    /// Indexes every track folder with a spotify id in its meta
+    pub(super) fn rebuild(&mut self, cache_folder: &Path) {
+        if !cache_folder.is_dir() {
+            return;
+        }
+        self.by_spotify_id = track_folders(cache_folder)
+            .filter_map(|track_folder| {
+                let meta = read_folder_meta(&track_folder)?;
+                Some((
+                    meta.spotify_id?,
+                    track_folder.file_name()?.to_str()?.to_owned(),
+                ))
+            })
+            .collect();
+        debug!("Indexed {} cached tracks", self.by_spotify_id.len());
+        self.save(cache_folder);
+    }
+
+    fn save(&self, cache_folder: &Path) {
+        // Written aside and moved, a half written index would be rebuilt from scratch
+        let tmp_path = cache_folder.join(format!("{INDEX_FILE}.tmp"));
+        let res = serde_json::to_string(&self.by_spotify_id)
+            .map_err(std::io::Error::from)
+            .and_then(|index| fs::write(&tmp_path, index))
+            .and_then(|()| fs::rename(&tmp_path, cache_folder.join(INDEX_FILE)));
+        if let Err(err) = res {
+            warn!("Failed to save the cache index: {err}");
+        }
+    }
+
+    /// Track folder of the spotify id, rescanning the cache if it was renamed
+    fn find(&mut self, cache_folder: &Path, spotify_id: &str) -> Option<PathBuf> {
+        self.load(cache_folder);
+        let track_folder = cache_folder.join(self.by_spotify_id.get(spotify_id)?);
+        if track_folder.exists() {
+            return Some(track_folder);
+        }
+        debug!("Cached folder of {spotify_id} is gone, rebuilding the cache index");
+        self.rebuild(cache_folder);
+        self.by_spotify_id
+            .get(spotify_id)
+            .map(|name| cache_folder.join(name))
+    }
+
+    fn insert(&mut self, cache_folder: &Path, spotify_id: &str, track_folder: &Path) {
+        self.load(cache_folder);
+        let Some(name) = track_folder.file_name().and_then(|name| name.to_str()) else {
+            return;
+        };
+        if self.by_spotify_id.get(spotify_id).map(String::as_str) != Some(name) {
+            self.by_spotify_id
+                .insert(spotify_id.to_owned(), name.to_owned());
+            self.save(cache_folder);
+        }
+    }
+}
+
+/// Recently read lyrics by spotify id or track identifier, most recent last
+#[derive(Debug, Default)]
+pub(super) struct RecentLyrics {
+    entries: VecDeque<(String, SongLyrics, Option<LyricsSource>)>,
+    /// When tracks were last served from memory, written to their meta by `flush_accesses`
+    accesses: HashMap<String, (LyricsRequestInfo, u64)>,
+}
+
+impl RecentLyrics {
+    fn key(req: &LyricsRequestInfo) -> String {
+        req.spotify_id
+            .clone()
+            .unwrap_or_else(|| req.get_track_identifier())
+    }
+
+    fn get(&mut self, req: &LyricsRequestInfo) -> Option<(SongLyrics, Option<LyricsSource>)> {
+        let key = Self::key(req);
+        let index = self
+            .entries
+            .iter()
+            .position(|(cached, ..)| *cached == key)?;
+        let entry = self.entries.remove(index)?;
+        let hit = (entry.1.clone(), entry.2.clone());
+        self.entries.push_back(entry);
+        self.accesses.insert(key, (req.clone(), unix_now()));
+        Some(hit)
+    }
+
+    fn take_accesses(&mut self) -> Vec<(LyricsRequestInfo, u64)> {
+        self.accesses.drain().map(|(_, access)| access).collect()
+    }
+
+    fn insert(
+        &mut self,
+        req: &LyricsRequestInfo,
+        lyrics: SongLyrics,
+        source: Option<LyricsSource>,
+    ) {
+        self.remove(req);
+        if self.entries.len() >= RECENT_LYRICS {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((Self::key(req), lyrics, source));
+    }
+
+    fn remove(&mut self, req: &LyricsRequestInfo) {
+        let key = Self::key(req);
+        self.entries.retain(|(cached, ..)| *cached != key);
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A track in the cache, as listed in the settings
+#[derive(Debug, Clone)]
+pub struct CachedTrack {
+    /// Name of the track folder, in the cache folder
+    pub folder: String,
+    pub track_name: String,
+    pub artist_name: String,
+    /// None if no provider had lyrics
+    pub provider: Option<LyricsProviderKind>,
+    pub pinned: bool,
+    pub size_bytes: u64,
+    /// Unix time the lyrics were last shown or fetched
+    pub last_used: Option<u64>,
+}
+
+/// What's in the cache, and how it did this session
+#[derive(Debug, Clone, Default)]
+pub struct CacheListing {
+    /// Most recently used first
+    pub tracks: Vec<CachedTrack>,
+    pub total_bytes: u64,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Translation of a track's lyrics, kept next to them
+#[derive(Deserialize, Serialize, Debug)]
+struct CachedTranslation {
+    backend: TranslationBackendKind,
+    language: String,
+    /// Lines that were translated, a refetch may have changed the lyrics since
+    source: Vec<String>,
+    translated: Vec<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum LyricsCacheCheckErr {
+    #[error("IO error")]
+    IoError(#[from] std::io::Error),
+    #[error("Track not found in cache")]
+    NotInCache(),
+    #[error("No lyrics were found for this track recently")]
+    KnownMissing(),
+    #[error("Serialization failed")]
+    Serde(#[from] serde_json::Error),
+}
+#[derive(Error, Debug)]
+pub enum LyricsCacheCreateErr {
+    #[error("IO error")]
+    IoError(#[from] std::io::Error),
+    #[error("Could not serialize new cache entry")]
+    SerializeErr(#[from] serde_json::Error),
+}
+#[derive(Error, Debug)]
+pub enum LyricsCacheMigrateErr {
+    #[error("IO error")]
+    IoError(#[from] std::io::Error),
+    #[error("Serialization failed")]
+    Serde(#[from] serde_json::Error),
+}
+#[derive(Error, Debug)]
+pub enum LyricsCacheRefreshErr {
+    #[error("IO error")]
+    IoError(#[from] std::io::Error),
+    #[error("Serialization failed")]
+    Serde(#[from] serde_json::Error),
+    #[error("Refetching failed: {0}")]
+    Fetch(#[from] LyricsFetcherErr),
+}
+
+impl LyricsFetcher {
+    /// Folder of the track, by spotify id if it was cached with one, else by its metadata
+    async fn track_cache_dir(&self, req: &LyricsRequestInfo) -> PathBuf {
+        let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+        req.spotify_id
+            .as_deref()
+            .and_then(|spotify_id| {
+                self.cache_index
+                    .lock()
+                    .unwrap()
+                    .find(&cache_folder, spotify_id)
+            })
+            .unwrap_or_else(|| {
+                let identifier = req.get_track_identifier();
+                let track_folder = cache_folder.join(folder_name(&identifier));
+                migrate_legacy_folder(&cache_folder.join(&identifier), &identifier, &track_folder);
+                track_folder
+            })
+    }
+
+    async fn read_meta(&self, req: &LyricsRequestInfo) -> Option<LyricCacheMeta> {
+        let meta_path = self.track_cache_dir(req).await.join(".meta");
+        fs::read_to_string(meta_path)
+            .ok()
+            .and_then(|meta| serde_json::from_str(&meta).ok())
+    }
+
+    /// New meta for the track, keeping what the user flagged and pinned
+    async fn fresh_meta(&self, req: &LyricsRequestInfo) -> LyricCacheMeta {
+        let mut meta = LyricCacheMeta::new(req);
+        if let Some(old) = self.read_meta(req).await {
+            meta.rejected = old.rejected;
+            meta.pinned = old.pinned;
+        }
+        meta
+    }
+
+    async fn write_meta(
+        &self,
+        req: &LyricsRequestInfo,
+        meta: &LyricCacheMeta,
+    ) -> Result<(), LyricsCacheCreateErr> {
+        let track_folder = self.track_cache_dir(req).await;
+        fs::create_dir_all(&track_folder)?;
+        fs::write(
+            track_folder.join(".meta"),
+            serde_json::to_string_pretty(meta)?,
+        )?;
+        if let Some(spotify_id) = &req.spotify_id {
+            let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+            self.cache_index
+                .lock()
+                .unwrap()
+                .insert(&cache_folder, spotify_id, &track_folder);
+        }
+        Ok(())
+    }
+
+    /// Cached lyrics and where they came from
+    pub(super) async fn check_cache(
+        &self,
+        req: &LyricsRequestInfo,
+    ) -> Result<(SongLyrics, Option<LyricsSource>), LyricsCacheCheckErr> {
+        trace!("Checking cache for {req}");
+        let recent = self.recent_lyrics.lock().unwrap().get(req);
+        if let Some(hit) = recent {
+            trace!("Using the lyrics in memory for {req}");
+            return Ok(hit);
+        }
+        let lrc_file_path = self.track_cache_dir(req).await.join("lyrics.lrc");
+
+        if !fs::exists(&lrc_file_path)? {
+            return Err(self.check_not_found(req).await);
+        }
+
+        let lyrics = read_lyrics(&fs::read_to_string(lrc_file_path)?, req.duration_sec)?;
+
+        let meta = self.read_meta(req).await;
+        // Tags naming another song mean the entry got mixed up. Lyrics a provider matched to the
+        // track keep its own naming and pinned ones were picked by the user, those are trusted
+        if let Some(title) = &lyrics.metadata.title
+            && meta
+                .as_ref()
+                .is_none_or(|meta| meta.pinned.is_none() && meta.matched_by.is_none())
+            && !same_song(
+                req,
+                title,
+                lyrics
+                    .metadata
+                    .artist
+                    .as_deref()
+                    .unwrap_or(&req.artist_name),
+            )
+        {
+            warn!("Cached lyrics for {req} are tagged as {title}, looking them up again");
+            let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+            let track_folder = self.track_cache_dir(req).await;
+            if let Err(err) = quarantine(&cache_folder, &track_folder) {
+                warn!("Failed to quarantine the cached lyrics for {req}: {err}");
+            }
+            return Err(LyricsCacheCheckErr::NotInCache());
+        }
+        let source = meta.as_ref().and_then(|meta| {
+            meta.provider.map(|provider| LyricsSource {
+                provider,
+                id: meta.provider_id.clone(),
+            })
+        });
+        self.note_access(req, meta, unix_now()).await;
+
+        self.recent_lyrics
+            .lock()
+            .unwrap()
+            .insert(req, lyrics.clone(), source.clone());
+        Ok((lyrics, source))
+    }
+
+    /// Stamp the entry's access time, least recently used entries are evicted first
+    async fn note_access(
+        &self,
+        req: &LyricsRequestInfo,
+        meta: Option<LyricCacheMeta>,
+        accessed_at: u64,
+    ) {
+        if let Some(mut meta) = meta {
+            meta.accessed_at = meta.accessed_at.max(Some(accessed_at));
+            if let Err(err) = self.write_meta(req, &meta).await {
+                trace!("Failed to note the cache access for {req}: {err}");
+            }
+        }
+    }
+
+    /// Writes the accesses served from memory to the metas, so often played tracks don't look
+    /// unused when the cache is trimmed or listed
+    async fn flush_accesses(&self) {
+        let accesses = self.recent_lyrics.lock().unwrap().take_accesses();
+        for (req, accessed_at) in accesses {
+            self.note_access(&req, self.read_meta(&req).await, accessed_at)
+                .await;
+        }
+    }
+
+    /// Lyrics flagged as wrong for the track
+    pub(super) async fn rejected_lyrics(&self, req: &LyricsRequestInfo) -> Vec<LyricsSource> {
+        self.read_meta(req)
+            .await
+            .map(|meta| meta.rejected)
+            .unwrap_or_default()
+    }
+
+    /// Lyrics pinned for the track
+    pub(super) async fn pinned_lyrics(&self, req: &LyricsRequestInfo) -> Option<LyricsSource> {
+        self.read_meta(req).await.and_then(|meta| meta.pinned)
+    }
+
+    /// `KnownMissing` if the track is cached as having no lyrics and that hasn't expired yet
+    async fn check_not_found(&self, req: &LyricsRequestInfo) -> LyricsCacheCheckErr {
+        let Some(not_found_at) = self.read_meta(req).await.and_then(|meta| meta.not_found_at)
+        else {
+            return LyricsCacheCheckErr::NotInCache();
+        };
+        let ttl_secs = self.settings.read().await.not_found_ttl_hours * 60 * 60;
+        if unix_now().saturating_sub(not_found_at) < ttl_secs {
+            LyricsCacheCheckErr::KnownMissing()
+        } else {
+            LyricsCacheCheckErr::NotInCache()
+        }
+    }
+
+    /// Remember that no provider has lyrics for the track
+    pub(super) async fn store_not_found(
+        &self,
+        req: &LyricsRequestInfo,
+    ) -> Result<(), LyricsCacheCreateErr> {
+        trace!("Caching missing lyrics for {req}");
+        let meta = LyricCacheMeta {
+            not_found_at: Some(unix_now()),
+            ..self.fresh_meta(req).await
+        };
+        self.write_meta(req, &meta).await?;
+        // A refresh may have found nothing where we had lyrics before
+        self.remove_cached_lyrics(req).await
+    }
+
+    pub(super) async fn store_in_cache(
+        &self,
+        req: &LyricsRequestInfo,
+        source: &LyricsSource,
+        matched_by: &str,
+        song_lyrics: &SongLyrics,
+    ) -> Result<(), LyricsCacheCreateErr> {
+        trace!("Creating cache entry for {req}");
+        self.recent_lyrics.lock().unwrap().remove(req);
+        let track_folder = self.track_cache_dir(req).await;
+        trace!("Cache dir: {track_folder:?}");
+
+        let meta = LyricCacheMeta {
+            provider: Some(source.provider),
+            provider_id: source.id.clone(),
+            matched_by: Some(matched_by.to_owned()),
+            fetched_at: Some(unix_now()),
+            ..self.fresh_meta(req).await
+        };
+        self.write_meta(req, &meta).await?;
+
+        write_lyrics(&track_folder, song_lyrics)?;
+
+        Ok(())
+    }
+
+    /// Drops the cached lyrics and remembers not to use them again
+    pub(super) async fn reject_in_cache(
+        &self,
+        req: &LyricsRequestInfo,
+        source: &LyricsSource,
+    ) -> Result<(), LyricsCacheCreateErr> {
+        trace!("Flagging {source:?} as wrong for {req}");
+        let mut meta = self.fresh_meta(req).await;
+        if !meta.rejected.contains(source) {
+            meta.rejected.push(source.clone());
+        }
+        if meta.pinned.as_ref() == Some(source) {
+            meta.pinned = None;
+        }
+
+        self.remove_cached_lyrics(req).await?;
+        self.write_meta(req, &meta).await
+    }
+
+    async fn remove_cached_lyrics(
+        &self,
+        req: &LyricsRequestInfo,
+    ) -> Result<(), LyricsCacheCreateErr> {
+        self.recent_lyrics.lock().unwrap().remove(req);
+        let lrc_file_path = self.track_cache_dir(req).await.join("lyrics.lrc");
+        if fs::exists(&lrc_file_path)? {
+            fs::remove_file(lrc_file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Pins lyrics for the track, or unpins with `None`. The cached lyrics are kept
+    pub(super) async fn pin_in_cache(
+        &self,
+        req: &LyricsRequestInfo,
+        pinned: Option<LyricsSource>,
+    ) -> Result<(), LyricsCacheCreateErr> {
+        trace!("Pinning {pinned:?} for {req}");
+        let mut meta = self
+            .read_meta(req)
+            .await
+            .unwrap_or_else(|| LyricCacheMeta::new(req));
+        meta.pinned = pinned;
+        self.write_meta(req, &meta).await
+    }
+}
+
+impl LyricsFetcher {
+    /// Cached translation of `lines`, if they were translated with the current backend and language
+    pub(super) async fn cached_translation(
+        &self,
+        req: &LyricsRequestInfo,
+        lines: &[String],
+    ) -> Option<Vec<String>> {
+        let path = self.track_cache_dir(req).await.join("translation.json");
+        let cached: CachedTranslation =
+            serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+        let settings = self.settings.read().await;
+        (cached.backend == settings.translation_backend
+            && cached.language == settings.translation_language
+            && cached.source == lines)
+            .then_some(cached.translated)
+    }
+
+    pub(super) async fn store_translation(
+        &self,
+        req: &LyricsRequestInfo,
+        lines: &[String],
+        translated: &[String],
+    ) -> Result<(), LyricsCacheCreateErr> {
+        let cached = {
+            let settings = self.settings.read().await;
+            CachedTranslation {
+                backend: settings.translation_backend,
+                language: settings.translation_language.clone(),
+                source: lines.to_vec(),
+                translated: translated.to_vec(),
+            }
+        };
+        let track_folder = self.track_cache_dir(req).await;
+        fs::create_dir_all(&track_folder)?;
+        fs::write(
+            track_folder.join("translation.json"),
+            serde_json::to_string_pretty(&cached)?,
+        )?;
+        Ok(())
+    }
+}
+
+impl LyricsFetcher {
+    /// Lists the cached tracks for the settings
+    pub async fn list_cache(&self) -> Result<Messages, RuntimeError> {
+        self.flush_accesses().await;
+        let cache_folder = self.settings.read().await.cache_folder.clone();
+        let mut listing = CacheListing {
+            hits: self.cache_hits.load(Ordering::Relaxed),
+            misses: self.cache_misses.load(Ordering::Relaxed),
+            ..CacheListing::default()
+        };
+        for track_folder in track_folders(Path::new(&cache_folder)) {
+            let Some(meta) = read_folder_meta(&track_folder) else {
+                continue;
+            };
+            let size_bytes = folder_size(&track_folder);
+            listing.total_bytes += size_bytes;
+            listing.tracks.push(CachedTrack {
+                folder: track_folder
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                track_name: meta.track_name,
+                artist_name: meta.artist_name,
+                provider: meta.provider,
+                pinned: meta.pinned.is_some(),
+                size_bytes,
+                last_used: meta.accessed_at.or(meta.fetched_at),
+            });
+        }
+        listing
+            .tracks
+            .sort_unstable_by_key(|track| std::cmp::Reverse(track.last_used));
+        Ok(Messages::to_ui(MessageToUI::CacheListing(listing)))
+    }
+
+    /// Removes the given track folders from the cache, every track if `None`
+    pub async fn clear_cache(
+        &self,
+        folders: Option<Vec<String>>,
+    ) -> Result<Messages, RuntimeError> {
+        let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+        let folders = folders.unwrap_or_else(|| {
+            track_folders(&cache_folder)
+                .filter_map(|track_folder| Some(track_folder.file_name()?.to_str()?.to_owned()))
+                .collect()
+        });
+        let mut removed = 0;
+        for folder in folders {
+            // Names come from the listing, never anything outside the cache
+            if folder.is_empty()
+                || folder.contains(['/', '\\'])
+                || folder == ".."
+                || folder == QUARANTINE_FOLDER
+            {
+                warn!("Not removing {folder:?} from the cache");
+                continue;
+            }
+            match fs::remove_dir_all(cache_folder.join(&folder)) {
+                Ok(()) => removed += 1,
+                Err(err) => warn!("Failed removing {folder} from the cache: {err}"),
+            }
+        }
+        info!("Removed {removed} tracks from the cache");
+        self.recent_lyrics.lock().unwrap().clear();
+        self.cache_index.lock().unwrap().rebuild(&cache_folder);
+        self.list_cache().await
+    }
+
+    /// Every so often trims the cache to its size limit and refetches old lrclib entries,
+    /// picking up corrections made since
+    pub async fn maintain_cache(self: Arc<Self>) {
+        if self.settings.read().await.caching_enabled {
+            self.migrate_cache().await;
+        }
+        loop {
+            let (caching_enabled, max_mb) = {
+                let settings = self.settings.read().await;
+                (settings.caching_enabled, settings.cache_max_mb)
+            };
+            if caching_enabled && max_mb > 0 {
+                self.evict_lru_entries(max_mb * 1024 * 1024).await;
+            }
+
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            let (caching_enabled, max_age_days) = {
+                let settings = self.settings.read().await;
+                (settings.caching_enabled, settings.cache_refresh_days)
+            };
+            if caching_enabled && max_age_days > 0 {
+                self.refresh_stale_entries(max_age_days * 24 * 60 * 60)
+                    .await;
+            }
+        }
+    }
+
+    /// Upgrades entries written by older versions, the ones that can't be are quarantined
+    async fn migrate_cache(&self) {
+        let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+        let (mut migrated, mut quarantined) = (0, 0);
+        for track_folder in track_folders(&cache_folder) {
+            let Ok(meta) = fs::read_to_string(track_folder.join(".meta")) else {
+                continue;
+            };
+            let res = serde_json::from_str::<LyricCacheMeta>(&meta)
+                .map_err(LyricsCacheMigrateErr::from)
+                .and_then(|meta| {
+                    if meta.version >= CACHE_VERSION {
+                        return Ok(false);
+                    }
+                    migrate_entry(&cache_folder, &track_folder, meta).map(|()| true)
+                });
+            match res {
+                Ok(true) => migrated += 1,
+                Ok(false) => {}
+                Err(err) => {
+                    warn!("Quarantining {}: {err}", track_folder.display());
+                    match quarantine(&cache_folder, &track_folder) {
+                        Ok(()) => quarantined += 1,
+                        Err(err) => warn!("Failed quarantining {}: {err}", track_folder.display()),
+                    }
+                }
+            }
+        }
+        if migrated > 0 || quarantined > 0 {
+            info!(
+                "Migrated {migrated} cached tracks to version {CACHE_VERSION}, quarantined {quarantined}"
+            );
+            self.cache_index.lock().unwrap().rebuild(&cache_folder);
+        }
+    }
+
+    /// Removes the least recently used tracks until the cache is under `max_bytes`, pinned
+    /// lyrics are kept
+    async fn evict_lru_entries(&self, max_bytes: u64) {
+        self.flush_accesses().await;
+        let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+        let Some((evicted, total)) = evict_lru(&cache_folder, max_bytes) else {
+            return;
+        };
+        self.recent_lyrics.lock().unwrap().clear();
+        info!(
+            "Evicted {evicted} tracks from the cache, {} MB left",
+            total / 1024 / 1024
+        );
+    }
+
+    async fn refresh_stale_entries(&self, max_age_secs: u64) {
+        let cache_folder = PathBuf::from(&self.settings.read().await.cache_folder);
+        let mut changed = 0;
+        for track_folder in track_folders(&cache_folder) {
+            let Some(meta) = fs::read_to_string(track_folder.join(".meta"))
+                .ok()
+                .and_then(|meta| serde_json::from_str::<LyricCacheMeta>(&meta).ok())
+                .filter(|meta| meta.is_stale(max_age_secs))
+            else {
+                continue;
+            };
+            match self.refresh_entry(&track_folder, meta).await {
+                Ok(true) => changed += 1,
+                Ok(false) => {}
+                Err(LyricsCacheRefreshErr::Fetch(LyricsFetcherErr::Timeout())) => {
+                    warn!("lrclib timed out while refreshing the cache, trying again later");
+                    break;
+                }
+                Err(LyricsCacheRefreshErr::Fetch(LyricsFetcherErr::RateLimited())) => {
+                    warn!("lrclib is rate limiting us, refreshing the cache again later");
+                    break;
+                }
+                Err(err) => warn!("Failed refreshing {}: {err}", track_folder.display()),
+            }
+            tokio::time::sleep(REFRESH_PAUSE).await;
+        }
+        if changed > 0 {
+            self.recent_lyrics.lock().unwrap().clear();
+            info!("Refreshed {changed} cached lyrics with newer versions from lrclib");
+        }
+    }
+
+    /// Refetches an entry from lrclib, true if the lyrics changed
+    async fn refresh_entry(
+        &self,
+        track_folder: &Path,
+        mut meta: LyricCacheMeta,
+    ) -> Result<bool, LyricsCacheRefreshErr> {
+        let id = meta
+            .provider_id
+            .as_deref()
+            .and_then(|id| id.parse().ok())
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        trace!("Refreshing lrclib entry {id} in {}", track_folder.display());
+
+        let changed = match get_lrclib_by_id(&self.client, id).await {
+            Ok(response) => {
+                let lyrics = response_lyrics(&response, &meta.request())?.lyrics;
+                let lyrics_path = track_folder.join("lyrics.lrc");
+                let changed = fs::read_to_string(&lyrics_path).ok().as_deref()
+                    != Some(lyrics.to_lrc_file().as_str());
+                if changed {
+                    write_lyrics(track_folder, &lyrics)?;
+                }
+                changed
+            }
+            // Keep what we have, the entry may have been removed
+            Err(LyricsFetcherErr::SongLyricsNotFound()) => {
+                debug!("lrclib entry {id} is gone, keeping the cached lyrics");
+                false
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        meta.fetched_at = Some(unix_now());
+        fs::write(
+            track_folder.join(".meta"),
+            serde_json::to_string_pretty(&meta)?,
+        )?;
+        Ok(changed)
+    }
+}
+
+/// Writes the lyrics as lrc, which the cache reads back, and as json in the stable schema for
+/// other tools
+fn write_lyrics(track_folder: &Path, lyrics: &SongLyrics) -> std::io::Result<()> {
+    fs::write(track_folder.join("lyrics.lrc"), lyrics.to_lrc_file())?;
+    fs::write(
+        track_folder.join("lyrics.json"),
+        serde_json::to_string_pretty(&LyricsJson::from(lyrics))?,
+    )
+}
+
+/// Parses a cached lyrics file, older versions of the cache stored them as json
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn read_lyrics(content: &str, duration_sec: f64) -> Result<SongLyrics, serde_json::Error> {
+    if content.trim_start().starts_with('{') {
+        return serde_json::from_str(content);
+    }
+    Ok(SongLyrics::from_lrc_file(
+        content,
+        (duration_sec * 1000.) as usize,
+    ))
+}
+
+/// Track identifier usable as a folder name on every platform, characters paths can't hold are
+/// percent encoded and a hash of the identifier keeps names that end up alike apart
+fn folder_name(identifier: &str) -> String {
+    let mut name = String::new();
+    for c in identifier.chars().take(MAX_FOLDER_NAME_CHARS) {
+        let mut encoded = String::new();
+        if c.is_control()
+            || matches!(
+                c,
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '%'
+            )
+        {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        } else {
+            encoded.push(c);
+        }
+        if name.len() + encoded.len() > MAX_FOLDER_NAME_BYTES {
+            break;
+        }
+        name.push_str(&encoded);
+    }
+    let hash = Sha256::digest(identifier);
+    let _ = write!(
+        name,
+        " #{:02x}{:02x}{:02x}{:02x}",
+        hash[0], hash[1], hash[2], hash[3]
+    );
+    name
+}
+
+/// Moves a folder named by the raw identifier, as older versions did, to its sanitized name
+fn migrate_legacy_folder(legacy_folder: &Path, identifier: &str, track_folder: &Path) {
+    // Identifiers with separators were nested, those entries are left to be refetched
+    if identifier.contains(['/', '\\']) || track_folder.exists() || !legacy_folder.is_dir() {
+        return;
+    }
+    match fs::rename(legacy_folder, track_folder) {
+        Ok(()) => debug!("Moved cached {identifier} to {}", track_folder.display()),
+        Err(err) => warn!("Failed to move cached {identifier}: {err}"),
+    }
+}
+
+/// Brings an entry up to `CACHE_VERSION`, one version at a time
+fn migrate_entry(
+    cache_folder: &Path,
+    track_folder: &Path,
+    mut meta: LyricCacheMeta,
+) -> Result<(), LyricsCacheMigrateErr> {
+    let mut track_folder = track_folder.to_owned();
+    if meta.version == 0 {
+        let lyrics_path = track_folder.join("lyrics.lrc");
+        if let Ok(content) = fs::read_to_string(&lyrics_path)
+            && content.trim_start().starts_with('{')
+        {
+            let lyrics: SongLyrics = serde_json::from_str(&content)?;
+            write_lyrics(&track_folder, &lyrics)?;
+        }
+        let renamed = cache_folder.join(folder_name(&meta.request().get_track_identifier()));
+        if renamed != track_folder && !renamed.exists() {
+            fs::rename(&track_folder, &renamed)?;
+            track_folder = renamed;
+        }
+        meta.version = 1;
+    }
+    fs::write(
+        track_folder.join(".meta"),
+        serde_json::to_string_pretty(&meta)?,
+    )?;
+    Ok(())
+}
+
+/// Moves an entry out of the way, kept around in case it's needed later
+fn quarantine(cache_folder: &Path, track_folder: &Path) -> std::io::Result<()> {
+    let quarantine_folder = cache_folder.join(QUARANTINE_FOLDER);
+    fs::create_dir_all(&quarantine_folder)?;
+    let name = track_folder.file_name().unwrap_or_default();
+    fs::rename(track_folder, quarantine_folder.join(name))
+}
+
+/// Removes the least recently used entries until the cache is under `max_bytes`. Only folders
+/// with a meta are entries, anything else in the cache folder is left alone.
+/// The number evicted and the size left, `None` if it was already under
+fn evict_lru(cache_folder: &Path, max_bytes: u64) -> Option<(usize, u64)> {
+    let mut total = 0;
+    let mut evictable = Vec::new();
+    for track_folder in track_folders(cache_folder) {
+        let Some(meta) = read_folder_meta(&track_folder) else {
+            continue;
+        };
+        let size = folder_size(&track_folder);
+        total += size;
+        if meta.pinned.is_none() {
+            let last_used = meta.accessed_at.or(meta.fetched_at).unwrap_or(0);
+            evictable.push((last_used, size, track_folder));
+        }
+    }
+    if total <= max_bytes {
+        return None;
+    }
+
+    evictable.sort_unstable_by_key(|(last_used, ..)| *last_used);
+    let mut evicted = 0;
+    for (_, size, track_folder) in evictable {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_dir_all(&track_folder) {
+            Ok(()) => {
+                total -= size;
+                evicted += 1;
+            }
+            Err(err) => warn!("Failed evicting {}: {err}", track_folder.display()),
+        }
+    }
+    Some((evicted, total))
+}
+
+/// Folders in the cache, without the quarantine
+pub(super) fn track_folders(cache_folder: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(cache_folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name() != QUARANTINE_FOLDER)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+}
+
+/// Meta of a track folder, `None` if it has none or it can't be read
+pub(super) fn read_folder_meta(track_folder: &Path) -> Option<LyricCacheMeta> {
+    fs::read_to_string(track_folder.join(".meta"))
+        .ok()
+        .and_then(|meta| serde_json::from_str(&meta).ok())
+}
+
+/// Size of the files in a track folder
+fn folder_size(folder: &Path) -> u64 {
+    fs::read_dir(folder).map_or(0, |entries| {
+        entries
+            .flatten()
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(fs::Metadata::is_file)
+            .map(|metadata| metadata.len())
+            .sum()
+    })
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_folder_names() {
+        let name = folder_name("AC/DC-What? (Live: 1991) 212s");
+        assert!(name.starts_with("AC%2FDC-What%3F (Live%3A 1991) 212s #"));
+        assert_ne!(name, folder_name("AC%2FDC-What? (Live: 1991) 212s"));
+        assert_eq!(name, folder_name("AC/DC-What? (Live: 1991) 212s"));
+        assert!(folder_name(&"a".repeat(500)).chars().count() < 120);
+    }
+
+    #[test]
+    fn caps_folder_names_by_bytes() {
+        let title = "夜に駆ける".repeat(20);
+        let name = folder_name(&format!("YOASOBI-{title} 261s"));
+        assert!(name.len() <= 255);
+        assert!(name.starts_with("YOASOBI-夜に駆ける"));
+        assert!(name.contains(" #"));
+
+        let escaped = folder_name(&"/".repeat(100));
+        assert!(escaped.len() <= 255);
+        assert!(escaped.starts_with("%2F%2F"));
+    }
+
+    #[test]
+    fn memory_hits_are_kept_until_flushed() {
+        let req = LyricsRequestInfo::new("Song", "Artist", "Album", 200.);
+        let mut recent = RecentLyrics::default();
+        recent.insert(&req, SongLyrics::default(), None);
+        assert!(recent.take_accesses().is_empty());
+
+        assert!(recent.get(&req).is_some());
+        assert!(recent.get(&req).is_some());
+        let accesses = recent.take_accesses();
+        assert_eq!(accesses.len(), 1);
+        assert_eq!(
+            accesses[0].0.get_track_identifier(),
+            req.get_track_identifier()
+        );
+        assert!(recent.take_accesses().is_empty());
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let cache_folder =
+            std::env::temp_dir().join(format!("lyrics-overlay-evict-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache_folder);
+        for (name, accessed_at) in [("old", 1), ("recent", 3), ("middle", 2)] {
+            let track_folder = cache_folder.join(name);
+            fs::create_dir_all(&track_folder).unwrap();
+            let meta = format!(
+                r#"{{"spotify_id":null,"provider":null,"track_name":"{name}","artist_name":"","album_name":"","duration_sec":0,"accessed_at":{accessed_at}}}"#
+            );
+            fs::write(track_folder.join(".meta"), meta).unwrap();
+            fs::write(track_folder.join("lyrics.lrc"), "x".repeat(1000)).unwrap();
+        }
+        // Not an entry, never evicted
+        fs::create_dir_all(cache_folder.join("stray")).unwrap();
+
+        let total = folder_size(&cache_folder.join("old")) * 3;
+        let res = evict_lru(&cache_folder, total - 1);
+        let left: Vec<_> = ["old", "middle", "recent", "stray"]
+            .map(|name| cache_folder.join(name).exists())
+            .into();
+        let _ = fs::remove_dir_all(&cache_folder);
+
+        assert_eq!(res.map(|(evicted, _)| evicted), Some(1));
+        assert_eq!(left, [false, true, true, true]);
+    }
+
+    #[test]
+    fn quarantine_isnt_a_track_folder() {
+        let cache_folder =
+            std::env::temp_dir().join(format!("lyrics-overlay-quarantine-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache_folder);
+        fs::create_dir_all(cache_folder.join("track")).unwrap();
+        quarantine(&cache_folder, &cache_folder.join("track")).unwrap();
+        fs::create_dir_all(cache_folder.join("other")).unwrap();
+
+        let folders: Vec<_> = track_folders(&cache_folder).collect();
+        let _ = fs::remove_dir_all(&cache_folder);
+        assert_eq!(folders, [cache_folder.join("other")]);
+    }
+
+    #[tokio::test]
+    async fn quarantines_lyrics_tagged_as_another_song() {
+        let cache_folder =
+            std::env::temp_dir().join(format!("lyrics-overlay-tags-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache_folder);
+        let mut settings = crate::settings::Settings::default();
+        settings.cache_folder = cache_folder.to_string_lossy().into_owned();
+        let fetcher = LyricsFetcher::new(
+            Arc::new(tokio::sync::RwLock::new(settings.clone())),
+            &settings,
+        );
+        let req = LyricsRequestInfo::new("Song", "Artist", "Album", 200.);
+        let track_folder = fetcher.track_cache_dir(&req).await;
+        fs::create_dir_all(&track_folder).unwrap();
+
+        let lrc = "[ti:Song]\n[ar:Artist]\n[00:01.00]Hello";
+        fs::write(track_folder.join("lyrics.lrc"), lrc).unwrap();
+        let matching = fetcher.check_cache(&req).await;
+
+        fetcher.recent_lyrics.lock().unwrap().clear();
+        let lrc = "[ti:Other Song]\n[ar:Someone Else]\n[00:01.00]Hello";
+        fs::write(track_folder.join("lyrics.lrc"), lrc).unwrap();
+        let mismatched = fetcher.check_cache(&req).await;
+        let quarantined = cache_folder
+            .join(QUARANTINE_FOLDER)
+            .join(track_folder.file_name().unwrap())
+            .join("lyrics.lrc")
+            .exists();
+        let moved = !track_folder.exists();
+        let _ = fs::remove_dir_all(&cache_folder);
+
+        assert!(matching.is_ok());
+        assert!(matches!(mismatched, Err(LyricsCacheCheckErr::NotInCache())));
+        assert!(quarantined);
+        assert!(moved);
+    }
+}
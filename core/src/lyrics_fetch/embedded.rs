@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::lyrics_parser::{LrcMetadata, LyricLine, SongLyrics, parse_lrc};
+
+use async_trait::async_trait;
+use id3::frame::TimestampFormat;
+use tracing::{debug, warn};
+
+/// FLAC metadata block holding the vorbis comments
+const FLAC_VORBIS_COMMENT: u8 = 4;
+
+/// Lyrics stored in the tags of the playing file, for player sources that report one
+pub(super) struct EmbeddedTagsProvider;
+
+#[async_trait]
+impl LyricsProvider for EmbeddedTagsProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::Embedded
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let Some(path) = req.file_path.clone() else {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        };
+        let duration_ms = (req.duration_sec * 1000.) as usize;
+        let lyrics = tokio::task::spawn_blocking(move || read_lyrics(&path, duration_ms))
+            .await
+            .expect("tag reading should not panic")
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        debug!("Found lyrics in the tags of the playing file");
+        Ok(FoundLyrics {
+            lyrics,
+            provider_id: None,
+        })
+    }
+}
+
+fn read_lyrics(path: &Path, duration_ms: usize) -> Option<SongLyrics> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let comments = match extension.as_deref() {
+        Some("flac") => read_flac_comments(path),
+        Some("ogg" | "oga" | "opus") => read_ogg_comments(path),
+        _ => return id3_lyrics(path, duration_ms),
+    };
+    let comments = comments
+        .inspect_err(|e| warn!("Failed to read tags of {}: {e}", path.display()))
+        .ok()?;
+    vorbis_lyrics(&comments, duration_ms)
+}
+
+/// Lyrics from text that may or may not be lrc
+fn text_lyrics(text: &str, duration_ms: usize) -> Option<SongLyrics> {
+    if text.trim().is_empty() {
+        return None;
+    }
+    let parsed = parse_lrc(text, false).lyrics;
+    if parsed.synced_lyrics.is_empty() {
+        Some(SongLyrics::from_plain(text.trim(), duration_ms))
+    } else {
+        Some(parsed)
+    }
+}
+
+/// SYLT frames with millisecond timestamps, falling back to USLT
+fn id3_lyrics(path: &Path, duration_ms: usize) -> Option<SongLyrics> {
+    let tag = id3::Tag::read_from_path(path)
+        .inspect_err(|e| debug!("No id3 tag in {}: {e}", path.display()))
+        .ok()?;
+
+    let synced = tag
+        .synchronised_lyrics()
+        .find(|sylt| sylt.timestamp_format == TimestampFormat::Ms && !sylt.content.is_empty());
+    if let Some(sylt) = synced {
+        return Some(SongLyrics {
+            synced_lyrics: sylt_lines(&sylt.content),
+            unsynced: false,
+            length_ms: None,
+            metadata: LrcMetadata::default(),
+        });
+    }
+
+    tag.lyrics()
+        .find_map(|uslt| text_lyrics(&uslt.text, duration_ms))
+}
+
+/// SYLT segments are either whole lines, or syllables where a leading newline starts a line
+fn sylt_lines(content: &[(u32, String)]) -> Vec<LyricLine> {
+    let per_syllable = content
+        .iter()
+        .skip(1)
+        .any(|(_, text)| text.starts_with(['\n', '\r']));
+    let mut lines: Vec<LyricLine> = Vec::new();
+    for (time_ms, text) in content {
+        let starts_line = !per_syllable || text.starts_with(['\n', '\r']);
+        match lines.last_mut() {
+            Some(line) if !starts_line => line.text.push_str(text),
+            _ => lines.push(LyricLine {
+                time_ms: *time_ms as usize,
+                text: text.trim_start().to_owned(),
+                ..Default::default()
+            }),
+        }
+    }
+    for line in &mut lines {
+        line.text = line.text.trim().to_owned();
+    }
+    lines
+}
+
+/// LYRICS, which is often lrc, or UNSYNCEDLYRICS
+fn vorbis_lyrics(comments: &[(String, String)], duration_ms: usize) -> Option<SongLyrics> {
+    ["LYRICS", "UNSYNCEDLYRICS"].iter().find_map(|key| {
+        comments
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(key))
+            .find_map(|(_, value)| text_lyrics(value, duration_ms))
+    })
+}
+
+fn read_u32_le(data: &[u8], at: &mut usize) -> Option<usize> {
+    let bytes = data.get(*at..*at + 4)?;
+    *at += 4;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+}
+
+/// Parses a vorbis comment block, without the packet type prefix
+fn parse_vorbis_comments(data: &[u8]) -> Vec<(String, String)> {
+    let mut at = 0;
+    let mut comments = Vec::new();
+    let Some(vendor_len) = read_u32_le(data, &mut at) else {
+        return comments;
+    };
+    at += vendor_len;
+    let Some(count) = read_u32_le(data, &mut at) else {
+        return comments;
+    };
+    for _ in 0..count {
+        let Some(len) = read_u32_le(data, &mut at) else {
+            break;
+        };
+        let Some(comment) = data.get(at..at + len) else {
+            break;
+        };
+        at += len;
+        if let Some((key, value)) = String::from_utf8_lossy(comment).split_once('=') {
+            comments.push((key.to_owned(), value.to_owned()));
+        }
+    }
+    comments
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+/// Vorbis comments from the FLAC metadata blocks, skipping over the audio and pictures
+fn read_flac_comments(path: &Path) -> io::Result<Vec<(String, String)>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(invalid("not a flac file"));
+    }
+    loop {
+        let mut header = [0; 4];
+        file.read_exact(&mut header)?;
+        let last = header[0] & 0x80 != 0;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+        if header[0] & 0x7f == FLAC_VORBIS_COMMENT {
+            let mut block = vec![0; len as usize];
+            file.read_exact(&mut block)?;
+            return Ok(parse_vorbis_comments(&block));
+        }
+        if last {
+            return Ok(Vec::new());
+        }
+        file.seek(SeekFrom::Current(i64::from(len)))?;
+    }
+}
+
+/// Vorbis comments from the comment header packet of an ogg vorbis or opus file
+fn read_ogg_comments(path: &Path) -> io::Result<Vec<(String, String)>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut packet = Vec::new();
+    // The comment header is the second packet, cover art can spread it over many pages
+    let mut packets_done = 0;
+    while packets_done < 2 {
+        let mut header = [0; 27];
+        file.read_exact(&mut header)?;
+        if &header[..4] != b"OggS" {
+            return Err(invalid("not an ogg page"));
+        }
+        let mut segments = vec![0; usize::from(header[26])];
+        file.read_exact(&mut segments)?;
+        for len in segments {
+            let mut segment = vec![0; usize::from(len)];
+            file.read_exact(&mut segment)?;
+            packet.extend_from_slice(&segment);
+            // A segment shorter than 255 bytes ends its packet
+            if len < 255 {
+                packets_done += 1;
+                if packets_done == 2 {
+                    break;
+                }
+                packet.clear();
+            }
+        }
+    }
+
+    let comments = packet
+        .strip_prefix(b"\x03vorbis")
+        .or_else(|| packet.strip_prefix(b"OpusTags"))
+        .ok_or_else(|| invalid("no comment header"))?;
+    Ok(parse_vorbis_comments(comments))
+}
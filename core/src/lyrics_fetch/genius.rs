@@ -0,0 +1,165 @@
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::lyrics_parser::SongLyrics;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::{debug, trace};
+use url::Url;
+
+/// The website's search, the documented API needs a token
+const GENIUS_SEARCH_URL: &str = "https://genius.com/api/search/song";
+/// Marks the elements holding the lyrics on a song page
+const LYRICS_CONTAINER: &str = "data-lyrics-container=\"true\"";
+
+#[derive(Deserialize, Debug)]
+struct SearchResponse {
+    response: SearchSections,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchSections {
+    sections: Vec<SearchSection>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchSection {
+    hits: Vec<SearchHit>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchHit {
+    result: GeniusSong,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeniusSong {
+    id: u64,
+    url: String,
+    primary_artist: GeniusArtist,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeniusArtist {
+    name: String,
+}
+
+/// Genius, plain lyrics only so it's a last resort
+pub(super) struct GeniusProvider {
+    pub client: reqwest::Client,
+}
+
+#[async_trait]
+impl LyricsProvider for GeniusProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::Genius
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        // Without a duration to compare the artist is all that tells songs apart
+        if req.artist_name.is_empty() {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+        let query = format!("{} {}", req.artist_name, req.track_name);
+        let url = Url::parse_with_params(GENIUS_SEARCH_URL, [("q", &query)])
+            .expect("Genius url should parse");
+        let search: SearchResponse = self.client.get(url).send().await?.json().await?;
+
+        // Search also matches lyrics, make sure we got the right artist
+        let artist = req.artist_name.to_lowercase();
+        let song = search
+            .response
+            .sections
+            .into_iter()
+            .flat_map(|section| section.hits)
+            .map(|hit| hit.result)
+            .find(|song| {
+                let name = song.primary_artist.name.to_lowercase();
+                name.contains(&artist) || artist.contains(&name)
+            })
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        debug!("Genius song page: {}", song.url);
+
+        let page = self.client.get(&song.url).send().await?.text().await?;
+        let text = extract_lyrics(&page);
+        trace!("Genius lyrics: {text:?}");
+        if text.trim().is_empty() {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+
+        Ok(FoundLyrics {
+            lyrics: SongLyrics::from_plain(&text, (req.duration_sec * 1000.) as usize),
+            provider_id: Some(song.id.to_string()),
+        })
+    }
+}
+
+/// Text of all lyrics containers on a song page, dropping section headers like `[Chorus]`
+fn extract_lyrics(page: &str) -> String {
+    let mut lines = Vec::new();
+    let mut rest = page;
+    while let Some(start) = rest.find(LYRICS_CONTAINER) {
+        rest = &rest[start..];
+        let Some(open_end) = rest.find('>') else {
+            break;
+        };
+        rest = &rest[open_end + 1..];
+        let end = closing_div(rest);
+        let html = rest[..end].replace("<br/>", "\n").replace("<br>", "\n");
+        lines.extend(
+            decode_entities(&strip_tags(&html))
+                .lines()
+                .map(str::trim)
+                .filter(|line| !(line.starts_with('[') && line.ends_with(']')))
+                .map(str::to_owned),
+        );
+        rest = &rest[end..];
+    }
+    // Containers split mid song, sections are separated by empty lines already
+    lines.dedup_by(|a, b| a.is_empty() && b.is_empty());
+    lines.join("\n").trim().to_owned()
+}
+
+/// Offset of the `</div>` closing the div whose content starts `html`
+fn closing_div(html: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < html.len() {
+        let rest = &html[i..];
+        if rest.starts_with("<div") {
+            depth += 1;
+        } else if rest.starts_with("</div") {
+            if depth == 0 {
+                return i;
+            }
+            depth -= 1;
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    html.len()
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,157 @@
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::lyrics_fetch::matching::{Candidate as MatchCandidate, best_match};
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::lyrics_parser::parse_krc;
+use crate::settings::Settings;
+
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use flate2::read::ZlibDecoder;
+use serde::Deserialize;
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::{debug, trace, warn};
+use url::Url;
+
+const KUGOU_SEARCH_URL: &str = "https://krcs.kugou.com/search";
+const KUGOU_DOWNLOAD_URL: &str = "https://lyrics.kugou.com/download";
+/// Every KRC file starts with it, the rest is xored with `KRC_KEY` and zlib compressed
+const KRC_HEADER: &[u8] = b"krc1";
+const KRC_KEY: [u8; 16] = [
+    64, 71, 97, 119, 94, 50, 116, 71, 81, 54, 49, 45, 206, 210, 110, 105,
+];
+
+#[derive(Deserialize, Debug)]
+struct SearchResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Candidate {
+    id: String,
+    accesskey: String,
+    #[serde(default)]
+    song: String,
+    #[serde(default)]
+    singer: String,
+    /// Milliseconds
+    #[serde(default)]
+    duration: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct DownloadResponse {
+    /// Base64 encoded KRC
+    content: Option<String>,
+}
+
+/// Kugou, word timed lyrics for Chinese releases
+pub(super) struct KugouProvider {
+    pub client: reqwest::Client,
+    pub settings: Arc<TokioRwLock<Settings>>,
+}
+
+#[async_trait]
+impl LyricsProvider for KugouProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::Kugou
+    }
+
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let candidate = self.search(req).await?;
+        debug!("Kugou lyrics id: {}", candidate.id);
+
+        let url = Url::parse_with_params(
+            KUGOU_DOWNLOAD_URL,
+            [
+                ("ver", "1"),
+                ("client", "pc"),
+                ("id", candidate.id.as_str()),
+                ("accesskey", candidate.accesskey.as_str()),
+                ("fmt", "krc"),
+                ("charset", "utf8"),
+            ],
+        )
+        .expect("Kugou url should parse");
+        let response: DownloadResponse = self.client.get(url).send().await?.json().await?;
+
+        let krc = response
+            .content
+            .and_then(|content| BASE64.decode(content).ok())
+            .and_then(|krc| {
+                decode_krc(&krc)
+                    .inspect_err(|e| warn!("Failed to decode Kugou lyrics: {e}"))
+                    .ok()
+            })
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        trace!("Kugou lyrics: {krc:?}");
+        let parsed = parse_krc(&krc);
+        if parsed.synced_lyrics.is_empty() {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+
+        Ok(FoundLyrics {
+            lyrics: parsed,
+            provider_id: Some(candidate.id),
+        })
+    }
+}
+
+impl KugouProvider {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    async fn search(&self, req: &LyricsRequestInfo) -> Result<Candidate, LyricsFetcherErr> {
+        let keyword = if req.artist_name.is_empty() {
+            req.track_name.clone()
+        } else {
+            format!("{} - {}", req.artist_name, req.track_name)
+        };
+        let duration_ms = (req.duration_sec * 1000.) as u64;
+        let url = Url::parse_with_params(
+            KUGOU_SEARCH_URL,
+            [
+                ("ver", "1"),
+                ("man", "yes"),
+                ("client", "mobi"),
+                ("keyword", keyword.as_str()),
+                ("duration", duration_ms.to_string().as_str()),
+            ],
+        )
+        .expect("Kugou url should parse");
+        let search: SearchResponse = self.client.get(url).send().await?.json().await?;
+
+        let tolerance_sec = self.settings.read().await.duration_tolerance_sec;
+        best_match(req, tolerance_sec, search.candidates, |candidate| {
+            MatchCandidate {
+                title: &candidate.song,
+                artist: &candidate.singer,
+                duration_sec: candidate.duration as f64 / 1000.,
+            }
+        })
+        .ok_or(LyricsFetcherErr::SongLyricsNotFound())
+    }
+}
+
+/// Text of an encoded KRC file
+fn decode_krc(krc: &[u8]) -> std::io::Result<String> {
+    let body = krc.strip_prefix(KRC_HEADER).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "missing krc header")
+    })?;
+    let compressed: Vec<u8> = body
+        .iter()
+        .zip(KRC_KEY.iter().cycle())
+        .map(|(byte, key)| byte ^ key)
+        .collect();
+
+    let mut text = String::new();
+    ZlibDecoder::new(compressed.as_slice()).read_to_string(&mut text)?;
+    Ok(text.trim_start_matches('\u{feff}').to_owned())
+}
@@ -0,0 +1,143 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::lyrics_fetch::matching::same_song;
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::lyrics_json::LyricsJson;
+use crate::lyrics_parser::{SongLyrics, parse_lrc, parse_subtitles, parse_ttml};
+use crate::settings::Settings;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::{debug, warn};
+
+/// Files picked up from the lrc folders, subtitles ripped from music videos work too
+const LYRICS_EXTENSIONS: [&str; 5] = ["lrc", "srt", "vtt", "ttml", "json"];
+
+/// Curated lrc collections on disk, looked up before any network provider
+pub(super) struct LocalLrcProvider {
+    pub settings: Arc<TokioRwLock<Settings>>,
+}
+
+#[async_trait]
+impl LyricsProvider for LocalLrcProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::LocalLrc
+    }
+
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let folders = self.settings.read().await.lrc_folders.clone();
+        if folders.iter().all(|folder| folder.trim().is_empty()) {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+
+        let req = req.clone();
+        let found = tokio::task::spawn_blocking(move || find_lrc(&folders, &req))
+            .await
+            .expect("lrc search should not panic")
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        debug!("Found local lyrics at {}", found.0.display());
+
+        let parsed = parse_lyrics_file(&found.0, &found.1);
+        if parsed.synced_lyrics.is_empty() {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+        Ok(FoundLyrics {
+            lyrics: parsed,
+            provider_id: Some(found.0.display().to_string()),
+        })
+    }
+}
+
+/// Path and content of the lrc file for the request.
+/// File names like `Artist - Title.lrc` or `Title.lrc` are checked first, then the `[ti:]` and `[ar:]` tags
+fn find_lrc(folders: &[String], req: &LyricsRequestInfo) -> Option<(PathBuf, String)> {
+    let mut files = Vec::new();
+    for folder in folders.iter().filter(|folder| !folder.trim().is_empty()) {
+        collect_lrc_files(Path::new(folder.trim()), &mut files);
+    }
+
+    let by_name = files.iter().find(|path| {
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            return false;
+        };
+        match stem.split_once(" - ") {
+            Some((artist, title)) => same_song(req, title, artist),
+            // Without an artist in the name the title has to do
+            None => same_song(req, stem, &req.artist_name),
+        }
+    });
+    if let Some(path) = by_name {
+        return fs::read_to_string(path)
+            .inspect_err(|e| warn!("Failed to read {}: {e}", path.display()))
+            .ok()
+            .map(|content| (path.clone(), content));
+    }
+
+    files.into_iter().find_map(|path| {
+        let content = fs::read_to_string(&path).ok()?;
+        let title = lrc_tag(&content, "ti")?;
+        let artist = lrc_tag(&content, "ar").unwrap_or(&req.artist_name);
+        same_song(req, title, artist).then_some((path, content))
+    })
+}
+
+fn collect_lrc_files(folder: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read lrc folder {}: {e}", folder.display());
+            return;
+        }
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_lrc_files(&path, files);
+        } else if path.extension().is_some_and(|ext| {
+            LYRICS_EXTENSIONS
+                .iter()
+                .any(|lyrics_ext| ext.eq_ignore_ascii_case(lyrics_ext))
+        }) {
+            files.push(path);
+        }
+    }
+}
+
+/// Lrc, subtitles or TTML going by the extension
+fn parse_lyrics_file(path: &Path, content: &str) -> SongLyrics {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "srt" | "vtt" => parse_subtitles(content),
+        "ttml" => parse_ttml(content),
+        "json" => LyricsJson::parse(content).unwrap_or_else(|err| {
+            debug!("Skipped {}: {err}", path.display());
+            SongLyrics::default()
+        }),
+        _ => {
+            let parsed = parse_lrc(content, false);
+            if !parsed.warnings.is_empty() {
+                debug!(
+                    "Skipped {} lines of {}",
+                    parsed.warnings.len(),
+                    path.display()
+                );
+            }
+            parsed.lyrics
+        }
+    }
+}
+
+/// Value of a metadata tag like `[ti:Title]`
+fn lrc_tag<'a>(content: &'a str, tag: &str) -> Option<&'a str> {
+    content.lines().find_map(|line| {
+        let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+        let (key, value) = inner.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(tag).then_some(value.trim())
+    })
+}
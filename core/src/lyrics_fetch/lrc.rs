@@ -0,0 +1,307 @@
+#[cfg(feature = "native")]
+use std::sync::Arc;
+
+use crate::lyrics_fetch::matching::{Candidate, best_match};
+use crate::lyrics_fetch::normalize::{clean_album, clean_artist, clean_title};
+use crate::lyrics_fetch::{FoundLyrics, LyricsFetcherErr, LyricsProviderKind, LyricsRequestInfo};
+use crate::lyrics_parser::{SongLyrics, parse_lrc};
+#[cfg(feature = "native")]
+use crate::{lyrics_fetch::LyricsProvider, settings::Settings};
+
+#[cfg(feature = "native")]
+use async_trait::async_trait;
+use reqwest::StatusCode;
+#[cfg(feature = "native")]
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::debug;
+use url::Url;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::trace;
+
+pub(super) static LRC_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " (github.com/ATantalizedLion/LyricsOverlay)"
+);
+const LRC_LIB_URL: &str = "https://lrclib.net/api/get";
+const LRC_LIB_SEARCH_URL: &str = "https://lrclib.net/api/search";
+#[cfg(feature = "native")]
+const LRC_LIB_BY_ID_URL: &str = "https://lrclib.net/api/get/";
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct LRCOkResponse {
+    /// LRC ID
+    pub id: usize,
+    pub track_name: String,
+    pub artist_name: String,
+    pub album_name: String,
+    pub duration: f32,
+    pub instrumental: bool,
+    pub plain_lyrics: Option<String>,
+    pub synced_lyrics: Option<String>,
+}
+
+/// Body lrclib answers with when a request fails
+#[derive(Deserialize, Debug)]
+struct LRCErrResponse {
+    name: String,
+    message: String,
+}
+
+/// The body of a successful response, or the error lrclib answered with
+async fn lrclib_response<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<T, LyricsFetcherErr> {
+    let status = response.status();
+    let text = response.text().await?;
+    trace!("Response body: {:?}", text);
+    if status.is_success() {
+        return Ok(serde_json::from_str(&text)?);
+    }
+
+    let message = serde_json::from_str::<LRCErrResponse>(&text)
+        .map_or(text, |err| format!("{}, {}", err.name, err.message));
+    debug!("lrclib answered {status}: {message}");
+    Err(match status {
+        StatusCode::NOT_FOUND => LyricsFetcherErr::SongLyricsNotFound(),
+        StatusCode::TOO_MANY_REQUESTS => LyricsFetcherErr::RateLimited(),
+        status => LyricsFetcherErr::ProviderError(status.as_u16(), message),
+    })
+}
+
+/// lrclib.net, community provided synced lyrics
+#[cfg(feature = "native")]
+pub(super) struct LrclibProvider {
+    pub client: reqwest::Client,
+    pub settings: Arc<TokioRwLock<Settings>>,
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl LyricsProvider for LrclibProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::Lrclib
+    }
+
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let tolerance_sec = self.settings.read().await.duration_tolerance_sec;
+        // The fetcher's relaxed queries already retry without the album
+        lookup_lrclib(&self.client, req, tolerance_sec).await
+    }
+}
+
+/// Lyrics for the track from lrclib, the exact match if it has synced lyrics, else the best
+/// search result within `tolerance_sec` of the track's duration. If nothing matches the album,
+/// the lookup is repeated without it
+pub async fn find_lrclib(
+    client: &reqwest::Client,
+    req: &LyricsRequestInfo,
+    tolerance_sec: f64,
+) -> Result<FoundLyrics, LyricsFetcherErr> {
+    match lookup_lrclib(client, req, tolerance_sec).await {
+        Err(LyricsFetcherErr::SongLyricsNotFound()) if !req.album_name.is_empty() => {
+            debug!("Nothing on lrclib for the album, looking up the track without it");
+            let without_album = LyricsRequestInfo {
+                album_name: String::new(),
+                ..req.clone()
+            };
+            lookup_lrclib(client, &without_album, tolerance_sec).await
+        }
+        result => result,
+    }
+}
+
+/// Like `find_lrclib`, but without searching when the exact match misses on the album
+async fn lookup_lrclib(
+    client: &reqwest::Client,
+    req: &LyricsRequestInfo,
+    tolerance_sec: f64,
+) -> Result<FoundLyrics, LyricsFetcherErr> {
+    // The exact match needs an artist
+    let exact = if req.artist_name.is_empty() {
+        Err(LyricsFetcherErr::SongLyricsNotFound())
+    } else {
+        request_track_lrc(
+            client,
+            &req.duration_sec,
+            &clean_title(&req.track_name),
+            &clean_artist(&req.artist_name),
+            &clean_album(&req.album_name),
+        )
+        .await
+    };
+    let response = match exact {
+        // Flagged as wrong, the next best search result may be right
+        Ok(rejected) if is_rejected(req, &rejected) => {
+            debug!(
+                "Exact lrclib match {} was flagged as wrong, searching",
+                rejected.id
+            );
+            search_track_lrc(client, req, tolerance_sec).await?
+        }
+        Ok(response) if has_synced(&response) => response,
+        // Plain lyrics are a fallback, another recording may have synced ones
+        Ok(plain) => match search_track_lrc(client, req, tolerance_sec).await {
+            Ok(found) => found,
+            // Whatever went wrong, the plain lyrics we already have beat none
+            Err(err) => {
+                debug!("No synced lyrics from the lrclib search, using the plain ones: {err}");
+                return plain_lyrics(&plain, req);
+            }
+        },
+        // Search ignores the album, leave it for the relaxed queries without one
+        Err(LyricsFetcherErr::SongLyricsNotFound()) if !req.album_name.is_empty() => {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+        // Remasters and deluxe editions often miss the exact match
+        Err(LyricsFetcherErr::SongLyricsNotFound()) => {
+            debug!("No exact lrclib match, searching");
+            search_track_lrc(client, req, tolerance_sec).await?
+        }
+        Err(err) => return Err(err),
+    };
+    response_lyrics(&response, req)
+}
+
+/// Synced lyrics of an lrclib entry, or its plain ones if it has none
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(super) fn response_lyrics(
+    response: &LRCOkResponse,
+    req: &LyricsRequestInfo,
+) -> Result<FoundLyrics, LyricsFetcherErr> {
+    match response
+        .synced_lyrics
+        .as_deref()
+        .filter(|lyrics| !lyrics.trim().is_empty())
+    {
+        Some(synced) => {
+            let mut lyrics = parse_lrc(synced, false).lyrics;
+            lyrics.length_ms = Some((f64::from(response.duration) * 1000.) as usize);
+            Ok(FoundLyrics {
+                lyrics,
+                provider_id: Some(response.id.to_string()),
+            })
+        }
+        None => plain_lyrics(response, req),
+    }
+}
+
+/// Every lrclib entry matching the query parameters
+pub(super) async fn search_lrclib(
+    client: &reqwest::Client,
+    params: &[(&str, String)],
+) -> Result<Vec<LRCOkResponse>, LyricsFetcherErr> {
+    let url = Url::parse_with_params(LRC_LIB_SEARCH_URL, params).expect("lrclib url should parse");
+    let response = client
+        .get(url)
+        .header("User-Agent", LRC_USER_AGENT)
+        .send()
+        .await?;
+    let results: Vec<LRCOkResponse> = lrclib_response(response).await?;
+    trace!("Search results: {:?}", results);
+    Ok(results)
+}
+
+/// A single lrclib entry by its id
+#[cfg(feature = "native")]
+pub(super) async fn get_lrclib_by_id(
+    client: &reqwest::Client,
+    id: usize,
+) -> Result<LRCOkResponse, LyricsFetcherErr> {
+    let response = client
+        .get(format!("{LRC_LIB_BY_ID_URL}{id}"))
+        .header("User-Agent", LRC_USER_AGENT)
+        .send()
+        .await?;
+    lrclib_response(response).await
+}
+
+/// Unsynced lyrics from a match without synced ones
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn plain_lyrics(
+    response: &LRCOkResponse,
+    req: &LyricsRequestInfo,
+) -> Result<FoundLyrics, LyricsFetcherErr> {
+    let plain = response
+        .plain_lyrics
+        .as_deref()
+        .map(str::trim)
+        .filter(|lyrics| !lyrics.is_empty())
+        .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+    Ok(FoundLyrics {
+        lyrics: SongLyrics::from_plain(plain, (req.duration_sec * 1000.) as usize),
+        provider_id: Some(response.id.to_string()),
+    })
+}
+
+fn is_rejected(req: &LyricsRequestInfo, response: &LRCOkResponse) -> bool {
+    req.is_rejected(LyricsProviderKind::Lrclib, Some(&response.id.to_string()))
+}
+
+fn has_synced(response: &LRCOkResponse) -> bool {
+    response
+        .synced_lyrics
+        .as_ref()
+        .is_some_and(|lyrics| !lyrics.trim().is_empty())
+}
+
+/// Best fuzzy match with synced lyrics by title, artist and duration
+async fn search_track_lrc(
+    client: &reqwest::Client,
+    req: &LyricsRequestInfo,
+    tolerance_sec: f64,
+) -> Result<LRCOkResponse, LyricsFetcherErr> {
+    let mut params = vec![("track_name", clean_title(&req.track_name))];
+    if !req.artist_name.is_empty() {
+        params.push(("artist_name", clean_artist(&req.artist_name)));
+    }
+    let results = search_lrclib(client, &params).await?;
+
+    best_match(
+        req,
+        tolerance_sec,
+        results
+            .into_iter()
+            .filter(|result| has_synced(result) && !is_rejected(req, result)),
+        |result| Candidate {
+            title: &result.track_name,
+            artist: &result.artist_name,
+            duration_sec: f64::from(result.duration),
+        },
+    )
+    .ok_or(LyricsFetcherErr::SongLyricsNotFound())
+}
+
+async fn request_track_lrc(
+    client: &reqwest::Client,
+    duration_sec: &f64,
+    track_name: &str,
+    artist_name: &str,
+    album_name: &str,
+) -> Result<LRCOkResponse, LyricsFetcherErr> {
+    let url = Url::parse_with_params(
+        LRC_LIB_URL,
+        [
+            ("artist_name", artist_name),
+            ("track_name", track_name),
+            ("album_name", album_name),
+            ("duration", &duration_sec.to_string()),
+        ],
+    )
+    .expect("lrclib url should parse");
+    let response: reqwest::Response = client
+        .get(url)
+        .header("User-Agent", LRC_USER_AGENT)
+        .send()
+        .await?;
+    debug!("Response for track request: {:?}", response);
+
+    let lyrics: LRCOkResponse = lrclib_response(response).await?;
+
+    trace!("Lyrics: {:?}", lyrics);
+
+    Ok(lyrics)
+}
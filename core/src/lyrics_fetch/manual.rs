@@ -0,0 +1,93 @@
+//! Looking lyrics up by hand, for when the automatic match is missing or wrong
+
+use tracing::{debug, info};
+
+use crate::{
+    MessageToUI,
+    lyrics_fetch::{
+        LyricsFetcher, LyricsProviderKind, LyricsRequestInfo,
+        lrc::{LRCOkResponse, get_lrclib_by_id, response_lyrics, search_lrclib},
+    },
+    runtime::{Messages, RuntimeError},
+};
+
+/// Query as edited in the search dialog
+#[derive(Debug, Clone, Default)]
+pub struct LyricsSearchQuery {
+    pub track_name: String,
+    pub artist_name: String,
+    pub album_name: String,
+    /// Only used to rank the results, lrclib doesn't search by it
+    pub duration_sec: f64,
+}
+
+/// An lrclib entry to pick from
+#[derive(Debug, Clone)]
+pub struct LyricsSearchResult {
+    pub id: usize,
+    pub track_name: String,
+    pub artist_name: String,
+    pub album_name: String,
+    pub duration_sec: f64,
+    pub synced: bool,
+}
+
+impl From<LRCOkResponse> for LyricsSearchResult {
+    fn from(response: LRCOkResponse) -> Self {
+        Self {
+            id: response.id,
+            synced: response
+                .synced_lyrics
+                .as_ref()
+                .is_some_and(|lyrics| !lyrics.trim().is_empty()),
+            track_name: response.track_name,
+            artist_name: response.artist_name,
+            album_name: response.album_name,
+            duration_sec: f64::from(response.duration),
+        }
+    }
+}
+
+impl LyricsFetcher {
+    /// lrclib entries for the query, synced ones first and then by how close the duration is
+    pub async fn search_lrclib(&self, query: LyricsSearchQuery) -> Result<Messages, RuntimeError> {
+        let mut params = vec![("track_name", query.track_name.trim().to_owned())];
+        for (key, value) in [
+            ("artist_name", &query.artist_name),
+            ("album_name", &query.album_name),
+        ] {
+            if !value.trim().is_empty() {
+                params.push((key, value.trim().to_owned()));
+            }
+        }
+        let mut results: Vec<LyricsSearchResult> = search_lrclib(&self.client, &params)
+            .await?
+            .into_iter()
+            .map(LyricsSearchResult::from)
+            .collect();
+        debug!("Manual search found {} lrclib entries", results.len());
+
+        results.sort_by(|a, b| {
+            b.synced.cmp(&a.synced).then_with(|| {
+                (a.duration_sec - query.duration_sec)
+                    .abs()
+                    .total_cmp(&(b.duration_sec - query.duration_sec).abs())
+            })
+        });
+        Ok(Messages::to_ui(MessageToUI::LyricsSearchResults(results)))
+    }
+
+    /// Shows the picked lrclib entry for the track, caching it so it sticks
+    pub async fn choose_lrclib(
+        &self,
+        req: LyricsRequestInfo,
+        id: usize,
+    ) -> Result<Messages, RuntimeError> {
+        let response = get_lrclib_by_id(&self.client, id).await?;
+        let found = response_lyrics(&response, &req)?;
+        info!("Using lrclib entry {id} for {req}");
+        Ok(self
+            .found_lyrics(req, LyricsProviderKind::Lrclib, found, "manual search")
+            .await)
+    }
+}
@@ -0,0 +1,120 @@
+//! Picking the right song out of a provider's search results
+
+use crate::lyrics_fetch::LyricsRequestInfo;
+use crate::lyrics_fetch::normalize::{clean_artist, clean_title};
+
+/// Minimum title and artist similarity for a candidate to be the same song
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// What a provider knows about one of its search results
+pub struct Candidate<'a> {
+    pub title: &'a str,
+    pub artist: &'a str,
+    pub duration_sec: f64,
+}
+
+/// Score of a candidate, higher is better.
+/// None if it's a different song or its duration is off by more than `tolerance_sec`
+pub(super) fn score(
+    req: &LyricsRequestInfo,
+    candidate: &Candidate,
+    tolerance_sec: f64,
+) -> Option<f64> {
+    let duration_diff = (candidate.duration_sec - req.duration_sec).abs();
+    let (title, artist) = name_similarity(req, candidate.title, candidate.artist);
+    if duration_diff > tolerance_sec || title < MIN_SIMILARITY || artist < MIN_SIMILARITY {
+        return None;
+    }
+    Some(title + artist - duration_diff / tolerance_sec.max(1.))
+}
+
+/// Do the title and artist look like the requested song, for sources without a duration
+pub fn same_song(req: &LyricsRequestInfo, title: &str, artist: &str) -> bool {
+    let (title, artist) = name_similarity(req, title, artist);
+    title >= MIN_SIMILARITY && artist >= MIN_SIMILARITY
+}
+
+/// Title and artist similarity, any artist matches a request without one
+fn name_similarity(req: &LyricsRequestInfo, title: &str, artist: &str) -> (f64, f64) {
+    let title = similarity(&clean_title(title), &clean_title(&req.track_name));
+    let artist = if req.artist_name.is_empty() {
+        1.
+    } else {
+        artist_similarity(&clean_artist(artist), &clean_artist(&req.artist_name))
+    };
+    (title, artist)
+}
+
+/// Best scoring candidate
+pub fn best_match<T>(
+    req: &LyricsRequestInfo,
+    tolerance_sec: f64,
+    candidates: impl IntoIterator<Item = T>,
+    describe: impl Fn(&T) -> Candidate<'_>,
+) -> Option<T> {
+    candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            score(req, &describe(&candidate), tolerance_sec).map(|score| (score, candidate))
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
+
+/// Words without punctuation
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Share of words the two texts have in common, 0 to 1
+#[allow(clippy::cast_precision_loss)]
+fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.;
+    }
+    let common = a.iter().filter(|word| b.contains(word)).count();
+    2. * common as f64 / (a.len() + b.len()) as f64
+}
+
+/// Like `similarity`, but all words of one being in the other counts as a full match, covering
+/// "Artist" vs "Artist & Friend". Not for titles, "Stay" is a different song from "Stay Together"
+fn artist_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.;
+    }
+    if a.iter().all(|word| b.contains(word)) || b.iter().all(|word| a.contains(word)) {
+        return 1.;
+    }
+    similarity(&a.join(" "), &b.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn containment_only_matches_artists() {
+        assert!(similarity(&clean_title("Stay"), &clean_title("Stayin' Alive")) < MIN_SIMILARITY);
+        assert!(similarity(&clean_title("Stay"), &clean_title("Stay Together")) < 1.);
+        assert!((similarity("here comes the sun", "here comes the sun") - 1.).abs() < f64::EPSILON);
+        assert!(
+            (artist_similarity(
+                &clean_artist("Dua Lipa"),
+                &clean_artist("Dua Lipa & DaBaby")
+            ) - 1.)
+                .abs()
+                < f64::EPSILON
+        );
+        assert!(
+            artist_similarity(&clean_artist("Drake"), &clean_artist("Drakeo the Ruler"))
+                < MIN_SIMILARITY
+        );
+        assert!(artist_similarity(&clean_artist("Low"), &clean_artist("Lowkey")) < MIN_SIMILARITY);
+        assert!(artist_similarity(&clean_artist("Lowkey"), &clean_artist("Low")) < MIN_SIMILARITY);
+    }
+}
@@ -0,0 +1,831 @@
+//! Module for fetching (cached) lyrics files for songs
+
+use std::fmt::Display;
+#[cfg(feature = "native")]
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+#[cfg(feature = "native")]
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "native")]
+use tracing::{debug, error, info, warn};
+
+#[cfg(feature = "native")]
+use tokio::sync::RwLock as TokioRwLock;
+
+use thiserror::Error;
+#[cfg(feature = "native")]
+use tracing::trace;
+
+use crate::lyrics_parser::SongLyrics;
+#[cfg(feature = "native")]
+use crate::{
+    MessageToUI, StatusEvent, http,
+    lyrics_fetch::{
+        cache::{CacheIndex, LyricsCacheCheckErr, RecentLyrics},
+        embedded::EmbeddedTagsProvider,
+        genius::GeniusProvider,
+        kugou::KugouProvider,
+        local::LocalLrcProvider,
+        lrc::LrclibProvider,
+        musixmatch::MusixmatchProvider,
+        netease::NeteaseProvider,
+        qq::QqMusicProvider,
+        spotify::SpotifyLyricsProvider,
+    },
+    player::NowPlaying,
+    runtime::{Messages, RuntimeError},
+    settings::Settings,
+    spotify::PlaylistTrack,
+};
+
+#[cfg(feature = "native")]
+mod archive;
+#[cfg(feature = "native")]
+mod cache;
+#[cfg(feature = "native")]
+mod embedded;
+#[cfg(feature = "native")]
+mod genius;
+#[cfg(feature = "native")]
+mod kugou;
+#[cfg(feature = "native")]
+mod local;
+mod lrc;
+#[cfg(feature = "native")]
+mod manual;
+mod matching;
+#[cfg(feature = "native")]
+mod musixmatch;
+#[cfg(feature = "native")]
+mod netease;
+mod normalize;
+#[cfg(feature = "native")]
+mod publish;
+#[cfg(feature = "native")]
+mod qq;
+#[cfg(feature = "native")]
+mod qrc;
+#[cfg(feature = "native")]
+mod spotify;
+#[cfg(feature = "native")]
+mod translate;
+
+#[cfg(feature = "native")]
+pub use cache::{CacheListing, CachedTrack};
+pub use lrc::find_lrclib;
+#[cfg(feature = "native")]
+pub use manual::{LyricsSearchQuery, LyricsSearchResult};
+pub use matching::{Candidate, best_match, same_song};
+#[cfg(feature = "native")]
+pub use publish::PublishRequest;
+#[cfg(feature = "native")]
+pub use translate::{LyricsTranslation, TranslationBackendKind};
+
+#[cfg(feature = "native")]
+pub struct LyricsFetcher {
+    settings: Arc<TokioRwLock<Settings>>,
+    /// For requests outside of a provider, like publishing
+    client: reqwest::Client,
+    /// Every provider, tried in the order of `Settings::lyrics_providers`
+    providers: Vec<Box<dyn LyricsProvider>>,
+    /// Cache folders by spotify id
+    cache_index: Mutex<CacheIndex>,
+    /// Parsed lyrics of the last few tracks, in front of the cache folder
+    recent_lyrics: Mutex<RecentLyrics>,
+    /// Lookups answered by the cache this session
+    cache_hits: AtomicUsize,
+    cache_misses: AtomicUsize,
+}
+
+/// Where lyrics can come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricsProviderKind {
+    /// Spotify's own lyrics, only for tracks with a spotify id
+    Spotify,
+    /// lrclib.net
+    Lrclib,
+    /// Musixmatch, with a user token from its desktop app
+    Musixmatch,
+    /// Genius, plain lyrics only
+    Genius,
+    /// Netease cloud music
+    Netease,
+    /// QQ music, word timed QRC with line timed lyrics as a fallback
+    QqMusic,
+    /// Kugou, word timed
+    Kugou,
+    /// lrc files in the configured folders
+    LocalLrc,
+    /// Lyrics tags of the playing file, for local file players
+    Embedded,
+}
+
+impl LyricsProviderKind {
+    pub const ALL: &[Self] = &[
+        Self::Spotify,
+        Self::Lrclib,
+        Self::Musixmatch,
+        Self::Genius,
+        Self::Netease,
+        Self::QqMusic,
+        Self::Kugou,
+        Self::LocalLrc,
+        Self::Embedded,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Spotify => "Spotify",
+            Self::Lrclib => "LRCLib",
+            Self::Musixmatch => "Musixmatch",
+            Self::Genius => "Genius",
+            Self::Netease => "NetEase",
+            Self::QqMusic => "QQ Music",
+            Self::Kugou => "Kugou",
+            Self::LocalLrc => "Local .lrc files",
+            Self::Embedded => "Embedded tags",
+        }
+    }
+}
+
+/// Which provider some lyrics came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LyricsSource {
+    pub provider: LyricsProviderKind,
+    /// Id of the lyrics at the provider
+    pub id: Option<String>,
+}
+
+/// What asking the providers came up with
+#[cfg(feature = "native")]
+enum Lookup {
+    Found {
+        kind: LyricsProviderKind,
+        found: FoundLyrics,
+        /// The query step that found them, or how else they were picked
+        matched_by: &'static str,
+    },
+    Missing {
+        timed_out: bool,
+        rate_limited: bool,
+        /// A provider failed to answer, so the lyrics may exist after all
+        failed: bool,
+    },
+}
+
+/// Lyrics as found by a provider
+pub struct FoundLyrics {
+    pub lyrics: SongLyrics,
+    /// Id of the lyrics at the provider, kept in the cache meta
+    pub provider_id: Option<String>,
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+pub(super) trait LyricsProvider: Send + Sync {
+    fn kind(&self) -> LyricsProviderKind;
+
+    /// Look up lyrics for the track, `SongLyricsNotFound` passes on to the next provider.
+    /// Unsynced lyrics are only used if no provider has synced ones
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr>;
+}
+
+#[derive(Error, Debug)]
+pub enum LyricsFetcherErr {
+    #[error("Lyrics request timed out")]
+    Timeout(),
+    #[error("Reqwest error: {0}")]
+    ReqwestError(reqwest::Error),
+    #[error("Json: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Song lyrics could not be found")]
+    SongLyricsNotFound(),
+    #[error("Too many requests, the provider is rate limiting us")]
+    RateLimited(),
+    #[error("The provider answered {0}: {1}")]
+    ProviderError(u16, String),
+    #[error("lrclib rejected the lyrics: {0}")]
+    PublishRejected(String),
+    #[error("{0}")]
+    TranslationFailed(String),
+}
+
+impl From<reqwest::Error> for LyricsFetcherErr {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout()
+        } else {
+            Self::ReqwestError(err)
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct SongWithLyrics {
+    pub lyrics: SongLyrics,
+    pub duration_sec: f64,
+    pub track_name: String,
+    pub artist_name: String,
+    album_name: String,
+    /// `None` for our placeholder text
+    pub source: Option<LyricsSource>,
+    /// Pinned by the user for this track
+    pub pinned: bool,
+}
+
+#[cfg(feature = "native")]
+impl Display for SongWithLyrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Lyrics for {} - {}. From {}, {}s",
+            self.track_name, self.artist_name, self.album_name, self.duration_sec
+        ))
+    }
+}
+#[cfg(feature = "native")]
+impl SongWithLyrics {
+    pub fn new(lyrics: SongLyrics, req: LyricsRequestInfo) -> Self {
+        Self {
+            lyrics,
+            duration_sec: req.duration_sec,
+            track_name: req.track_name,
+            artist_name: req.artist_name,
+            album_name: req.album_name,
+            source: None,
+            pinned: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_source(mut self, source: Option<LyricsSource>) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Length of the recording the lyrics were timed to, if it's further than `tolerance_sec`
+    /// from the track. Lyrics running past the end of the track count too
+    #[allow(clippy::cast_precision_loss)]
+    pub fn duration_mismatch(&self, tolerance_sec: f64) -> Option<f64> {
+        if self.source.is_none() || self.lyrics.unsynced {
+            return None;
+        }
+        let lyrics_sec = self
+            .lyrics
+            .length_ms
+            .or_else(|| {
+                self.lyrics
+                    .synced_lyrics
+                    .last()
+                    .map(|line| line.time_ms)
+                    .filter(|&last_ms| last_ms as f64 / 1000. > self.duration_sec + tolerance_sec)
+            })
+            .map(|ms| ms as f64 / 1000.)?;
+        ((lyrics_sec - self.duration_sec).abs() > tolerance_sec).then_some(lyrics_sec)
+    }
+
+    /// Puts the translated lines under the lyrics, if they are for this track
+    pub fn apply_translation(&mut self, translation: LyricsTranslation) {
+        if translation.track_name != self.track_name
+            || translation.artist_name != self.artist_name
+            || translation.lines.len() != self.lyrics.synced_lyrics.len()
+        {
+            return;
+        }
+        for (line, translated) in self.lyrics.synced_lyrics.iter_mut().zip(translation.lines) {
+            line.translation = translated;
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone)]
+pub struct LyricsRequestInfo {
+    #[cfg(feature = "native")]
+    spotify_id: Option<String>,
+    /// Local file being played, for reading its tags
+    #[cfg(feature = "native")]
+    file_path: Option<PathBuf>,
+    duration_sec: f64,
+    track_name: String,
+    artist_name: String,
+    album_name: String,
+    /// Lyrics flagged as wrong for this track, skipped when found again
+    rejected: Vec<LyricsSource>,
+}
+impl Display for LyricsRequestInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{} - {}. From {}, {}s",
+            self.track_name, self.artist_name, self.album_name, self.duration_sec
+        ))
+    }
+}
+impl LyricsRequestInfo {
+    /// Request for a track known only by its tags, like from a browser overlay
+    pub fn new(track_name: &str, artist_name: &str, album_name: &str, duration_sec: f64) -> Self {
+        Self {
+            #[cfg(feature = "native")]
+            spotify_id: None,
+            #[cfg(feature = "native")]
+            file_path: None,
+            duration_sec,
+            track_name: track_name.to_owned(),
+            artist_name: artist_name.to_owned(),
+            album_name: album_name.to_owned(),
+            rejected: Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "native")]
+    pub fn from_playlist_track(track: &PlaylistTrack) -> Self {
+        Self {
+            spotify_id: Some(track.spotify_id.clone()),
+            file_path: None,
+            duration_sec: track.duration_sec,
+            track_name: track.title.clone(),
+            artist_name: track.artist.clone(),
+            album_name: track.album.clone(),
+            rejected: Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "native")]
+    pub fn from_now_playing(playing: &NowPlaying) -> Self {
+        Self {
+            spotify_id: playing.spotify_id.clone(),
+            file_path: playing.file_path.clone(),
+            duration_sec: playing.duration_sec,
+            track_name: playing.title.clone(),
+            artist_name: playing.artist.clone(),
+            album_name: playing.album.clone(),
+            rejected: Vec::new(),
+        }
+    }
+
+    fn is_rejected(&self, provider: LyricsProviderKind, id: Option<&str>) -> bool {
+        id.is_some_and(|id| {
+            self.rejected
+                .iter()
+                .any(|source| source.provider == provider && source.id.as_deref() == Some(id))
+        })
+    }
+
+    /// Progressively looser versions of this request, each labelled for logging.
+    /// Spotify ids and files are only kept for the full query, they match exactly or not at all
+    #[cfg(feature = "native")]
+    fn relaxations(&self) -> Vec<(&'static str, Self)> {
+        let without_album = Self {
+            spotify_id: None,
+            file_path: None,
+            album_name: String::new(),
+            ..self.clone()
+        };
+        let primary_artist = Self {
+            artist_name: normalize::primary_artist(&self.artist_name).to_owned(),
+            ..without_album.clone()
+        };
+        let title_only = Self {
+            artist_name: String::new(),
+            ..without_album.clone()
+        };
+
+        let mut steps = vec![("full query", self.clone())];
+        for (step, relaxed) in [
+            ("without album", without_album),
+            ("primary artist only", primary_artist),
+            ("title only", title_only),
+        ] {
+            let repeated = steps.iter().any(|(_, prev)| {
+                prev.artist_name == relaxed.artist_name && prev.album_name == relaxed.album_name
+            });
+            if !repeated {
+                steps.push((step, relaxed));
+            }
+        }
+        steps
+    }
+
+    pub fn get_track_identifier(&self) -> String {
+        format!(
+            "{}-{} ({}) {}s",
+            self.artist_name.clone(),
+            self.track_name.clone(),
+            self.album_name.clone(),
+            self.duration_sec.clone()
+        )
+    }
+}
+
+/// Shows `message` in place of the lyrics
+#[cfg(feature = "native")]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn not_found(req: LyricsRequestInfo, message: &str) -> Messages {
+    Messages::to_ui(MessageToUI::GotLyrics(SongWithLyrics::new(
+        SongLyrics::display_text_as_lyrics(message.to_owned(), (req.duration_sec * 1000.) as usize),
+        req,
+    )))
+    .with_status(StatusEvent::LyricsFetched { found: false })
+}
+
+#[cfg(feature = "native")]
+impl LyricsFetcher {
+    pub fn new(settings: Arc<TokioRwLock<Settings>>, settings_snapshot: &Settings) -> Self {
+        let client = http::client_builder(settings_snapshot)
+            //  .user_agent(super::APP_USER_AGENT)
+            .build()
+            .unwrap();
+        Self {
+            providers: vec![
+                Box::new(SpotifyLyricsProvider {
+                    client: client.clone(),
+                    settings: settings.clone(),
+                }),
+                Box::new(MusixmatchProvider {
+                    client: client.clone(),
+                    settings: settings.clone(),
+                }),
+                Box::new(LrclibProvider {
+                    client: client.clone(),
+                    settings: settings.clone(),
+                }),
+                Box::new(GeniusProvider {
+                    client: client.clone(),
+                }),
+                Box::new(NeteaseProvider {
+                    client: client.clone(),
+                    settings: settings.clone(),
+                }),
+                Box::new(QqMusicProvider {
+                    client: client.clone(),
+                    settings: settings.clone(),
+                }),
+                Box::new(KugouProvider {
+                    client: client.clone(),
+                    settings: settings.clone(),
+                }),
+                Box::new(LocalLrcProvider {
+                    settings: settings.clone(),
+                }),
+                Box::new(EmbeddedTagsProvider),
+            ],
+            settings,
+            client,
+            cache_index: Mutex::default(),
+            recent_lyrics: Mutex::default(),
+            cache_hits: AtomicUsize::default(),
+            cache_misses: AtomicUsize::default(),
+        }
+    }
+
+    /// Lyrics for the track, from the cache unless `refetch` is set
+    pub async fn get_lyrics(
+        &self,
+        mut req: LyricsRequestInfo,
+        refetch: bool,
+    ) -> Result<Messages, RuntimeError> {
+        let caching_enabled = self.settings.read().await.caching_enabled;
+        if caching_enabled {
+            self.add_rejected(&mut req).await;
+        }
+        if caching_enabled && !refetch {
+            let cache_res = self.check_cache(&req).await;
+            let counter = match cache_res {
+                Ok(_) | Err(LyricsCacheCheckErr::KnownMissing()) => &self.cache_hits,
+                Err(_) => &self.cache_misses,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+            match cache_res {
+                Ok((lyrics, source)) => {
+                    return Ok(self.got_lyrics(lyrics, req, source).await);
+                }
+                Err(LyricsCacheCheckErr::KnownMissing()) => {
+                    debug!("No lyrics for {req} last time, not asking again yet");
+                    return Ok(not_found(req, "Could not find lyrics for this song"));
+                }
+                Err(cache_err) => match cache_err {
+                    LyricsCacheCheckErr::NotInCache() => (),
+                    _ => {
+                        trace!("{cache_err}");
+                    }
+                },
+            }
+        }
+
+        match self.lookup(&req, caching_enabled).await {
+            Lookup::Found {
+                kind,
+                found,
+                matched_by,
+            } => Ok(self.found_lyrics(req, kind, found, matched_by).await),
+            Lookup::Missing {
+                timed_out,
+                rate_limited,
+                failed,
+            } => {
+                if caching_enabled
+                    && !failed
+                    && let Err(cache_err) = self.store_not_found(&req).await
+                {
+                    error!("Failed creating cache entry: {:?}", cache_err);
+                }
+
+                let message = if rate_limited {
+                    "Too many lyrics requests, try again in a minute"
+                } else if timed_out {
+                    "Timed out while fetching lyrics"
+                } else if failed {
+                    "Could not reach the lyrics providers"
+                } else {
+                    "Could not find lyrics for this song"
+                };
+                Ok(not_found(req, message))
+            }
+        }
+    }
+
+    /// Looks the track up and caches the result without showing it, true if lyrics were found
+    pub async fn prefetch(&self, mut req: LyricsRequestInfo) -> bool {
+        self.add_rejected(&mut req).await;
+        match self.check_cache(&req).await {
+            Ok(_) => return true,
+            Err(LyricsCacheCheckErr::KnownMissing()) => return false,
+            Err(_) => {}
+        }
+        match self.lookup(&req, true).await {
+            Lookup::Found {
+                kind,
+                found,
+                matched_by,
+            } => {
+                self.cache_found(&req, kind, &found, matched_by).await;
+                true
+            }
+            Lookup::Missing { failed, .. } => {
+                if !failed && let Err(cache_err) = self.store_not_found(&req).await {
+                    error!("Failed creating cache entry: {:?}", cache_err);
+                }
+                false
+            }
+        }
+    }
+
+    /// Adds the lyrics flagged as wrong in the cache to the request
+    async fn add_rejected(&self, req: &mut LyricsRequestInfo) {
+        for source in self.rejected_lyrics(req).await {
+            if !req.rejected.contains(&source) {
+                req.rejected.push(source);
+            }
+        }
+    }
+
+    /// Asks the pinned provider, or else every provider in the chain with ever looser queries
+    async fn lookup(&self, req: &LyricsRequestInfo, caching_enabled: bool) -> Lookup {
+        let pinned = if caching_enabled {
+            self.pinned_lyrics(req).await
+        } else {
+            None
+        };
+        if let Some(pinned) = pinned {
+            match self.fetch_pinned(req, &pinned).await {
+                Ok(found) => {
+                    return Lookup::Found {
+                        kind: pinned.provider,
+                        found,
+                        matched_by: "pinned",
+                    };
+                }
+                Err(err) => warn!("Pinned lyrics {pinned:?} unavailable, searching instead: {err}"),
+            }
+        }
+
+        // Remember timeouts so we don't claim the lyrics don't exist
+        let mut timed_out = false;
+        let mut rate_limited = false;
+        // Only cache missing lyrics if every provider actually answered
+        let mut failed = false;
+
+        let mut unsynced = None;
+        // Providers that answered with something other than "not found" aren't asked again
+        let mut settled = Vec::new();
+
+        let chain = self.settings.read().await.lyrics_providers.clone();
+        for (step, relaxed) in req.relaxations() {
+            debug!("Looking up lyrics, {step}: {relaxed}");
+            for &kind in &chain {
+                if settled.contains(&kind) {
+                    continue;
+                }
+                let Some(provider) = self.providers.iter().find(|p| p.kind() == kind) else {
+                    continue;
+                };
+                match provider.fetch(&relaxed).await {
+                    Ok(found) if req.is_rejected(kind, found.provider_id.as_deref()) => {
+                        debug!(
+                            "Skipping lyrics flagged as wrong on {} ({step})",
+                            kind.as_str()
+                        );
+                    }
+                    Ok(found) if found.lyrics.unsynced => {
+                        debug!("Found unsynced lyrics on {} ({step})", kind.as_str());
+                        settled.push(kind);
+                        unsynced.get_or_insert((kind, found, step));
+                    }
+                    Ok(found) => {
+                        debug!("Found lyrics on {} ({step})", kind.as_str());
+                        return Lookup::Found {
+                            kind,
+                            found,
+                            matched_by: step,
+                        };
+                    }
+                    Err(LyricsFetcherErr::SongLyricsNotFound()) => {
+                        debug!("No lyrics on {} ({step})", kind.as_str());
+                    }
+                    Err(err) => {
+                        timed_out |= matches!(err, LyricsFetcherErr::Timeout());
+                        rate_limited |= matches!(err, LyricsFetcherErr::RateLimited());
+                        failed = true;
+                        settled.push(kind);
+                        warn!("Failed to fetch lyrics from {}: {err}", kind.as_str());
+                    }
+                }
+            }
+        }
+
+        match unsynced {
+            Some((kind, found, step)) => Lookup::Found {
+                kind,
+                found,
+                matched_by: step,
+            },
+            None => Lookup::Missing {
+                timed_out,
+                rate_limited,
+                failed,
+            },
+        }
+    }
+
+    /// Flags the shown lyrics as wrong for the track and looks up the next best ones
+    pub async fn reject_lyrics(
+        &self,
+        mut req: LyricsRequestInfo,
+        source: LyricsSource,
+    ) -> Result<Messages, RuntimeError> {
+        info!("Lyrics {source:?} flagged as wrong for {req}");
+        if self.settings.read().await.caching_enabled
+            && let Err(cache_err) = self.reject_in_cache(&req, &source).await
+        {
+            error!("Failed updating cache entry: {:?}", cache_err);
+        }
+        req.rejected.push(source);
+        self.get_lyrics(req, true).await
+    }
+
+    /// Pins the lyrics for the track so they're used without searching, or unpins with `None`
+    pub async fn pin_lyrics(
+        &self,
+        req: LyricsRequestInfo,
+        pinned: Option<LyricsSource>,
+    ) -> Result<Messages, RuntimeError> {
+        info!("Pinning {pinned:?} for {req}");
+        if let Err(cache_err) = self.pin_in_cache(&req, pinned).await {
+            error!("Failed updating cache entry: {:?}", cache_err);
+        }
+        // Resend the lyrics so the overlay shows whether they're pinned
+        self.get_lyrics(req, false).await
+    }
+
+    /// The pinned lyrics straight from their provider, skipping the usual matching
+    async fn fetch_pinned(
+        &self,
+        req: &LyricsRequestInfo,
+        pinned: &LyricsSource,
+    ) -> Result<FoundLyrics, LyricsFetcherErr> {
+        debug!("Fetching pinned lyrics {pinned:?}");
+        if pinned.provider == LyricsProviderKind::Lrclib
+            && let Some(id) = pinned.id.as_deref().and_then(|id| id.parse().ok())
+        {
+            return lrc::response_lyrics(&lrc::get_lrclib_by_id(&self.client, id).await?, req);
+        }
+        // Other providers can't look lyrics up by id, accept the same result as before
+        let provider = self
+            .providers
+            .iter()
+            .find(|p| p.kind() == pinned.provider)
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        let found = provider.fetch(req).await?;
+        if found.provider_id == pinned.id {
+            Ok(found)
+        } else {
+            Err(LyricsFetcherErr::SongLyricsNotFound())
+        }
+    }
+
+    /// Lyrics for the overlay, marked if they're pinned
+    async fn got_lyrics(
+        &self,
+        mut lyrics: SongLyrics,
+        req: LyricsRequestInfo,
+        source: Option<LyricsSource>,
+    ) -> Messages {
+        let pinned = source.is_some() && self.pinned_lyrics(&req).await == source;
+        // Line lookups assume the lines are in order, not every provider sorts them
+        lyrics.synced_lyrics.sort_by_key(|line| line.time_ms);
+        let (break_sec, max_line_chars) = {
+            let settings = self.settings.read().await;
+            (settings.instrumental_break_sec, settings.max_line_chars)
+        };
+        if max_line_chars > 0 {
+            lyrics.split_long_lines(max_line_chars as usize);
+        }
+        if break_sec > 0 && !lyrics.unsynced {
+            lyrics.mark_instrumental_breaks(break_sec as usize * 1000);
+        }
+        let mut song = SongWithLyrics::new(lyrics, req).with_source(source);
+        song.pinned = pinned;
+        Messages::to_ui(MessageToUI::GotLyrics(song))
+            .with_status(StatusEvent::LyricsFetched { found: true })
+    }
+
+    /// Caches the lyrics, `matched_by` is kept in the cache meta to debug bad matches
+    async fn cache_found(
+        &self,
+        req: &LyricsRequestInfo,
+        kind: LyricsProviderKind,
+        found: &FoundLyrics,
+        matched_by: &str,
+    ) -> LyricsSource {
+        let source = LyricsSource {
+            provider: kind,
+            id: found.provider_id.clone(),
+        };
+        let cache_store_res = self
+            .store_in_cache(req, &source, matched_by, &found.lyrics)
+            .await;
+        if let Err(cache_err) = cache_store_res {
+            error!("Failed creating cache entry: {:?}", cache_err);
+        }
+        source
+    }
+
+    /// Caches and shows the lyrics
+    async fn found_lyrics(
+        &self,
+        req: LyricsRequestInfo,
+        kind: LyricsProviderKind,
+        found: FoundLyrics,
+        matched_by: &str,
+    ) -> Messages {
+        let source = self.cache_found(&req, kind, &found, matched_by).await;
+        self.got_lyrics(found.lyrics, req, Some(source)).await
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use crate::lyrics_parser::LyricPosition;
+
+    #[tokio::test]
+    async fn got_lyrics_with_lines_out_of_order() {
+        let mut settings = Settings::default();
+        settings.max_line_chars = 12;
+        settings.instrumental_break_sec = 1;
+        let fetcher = LyricsFetcher::new(Arc::new(TokioRwLock::new(settings.clone())), &settings);
+        let mut lyrics = SongLyrics::from_lrc_file(
+            "[00:05.00]Last\n[00:10.00]\n[00:20.00]First line, then a long second part",
+            30_000,
+        );
+        // Providers don't always sort their lines
+        lyrics.synced_lyrics.swap(0, 2);
+        let req = LyricsRequestInfo::new("Song", "Artist", "Album", 30.);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(2);
+        fetcher.got_lyrics(lyrics, req, None).await.send(tx).await;
+        let Some(MessageToUI::GotLyrics(song)) = rx.recv().await else {
+            panic!("expected lyrics");
+        };
+        let lines = &song.lyrics.synced_lyrics;
+        assert!(lines.len() > 3);
+        assert!(
+            lines
+                .windows(2)
+                .all(|pair| pair[0].time_ms <= pair[1].time_ms)
+        );
+        let LyricPosition::Line(index, progress) = song.lyrics.find_current_index(7_000) else {
+            panic!("expected a line");
+        };
+        assert_eq!(lines[index].text, "Last");
+        assert!((progress - 0.4).abs() < 0.01);
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::lyrics_parser::parse_lrc;
+use crate::settings::Settings;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::{debug, trace, warn};
+use url::Url;
+
+/// The desktop app's API, the public one only serves 30% of the lyrics
+const MUSIXMATCH_URL: &str = "https://apic-desktop.musixmatch.com/ws/1.1/macro.subtitles.get";
+const MUSIXMATCH_APP_ID: &str = "web-desktop-app-v1.0";
+
+/// Musixmatch, needs a user token from the desktop app
+pub(super) struct MusixmatchProvider {
+    pub client: reqwest::Client,
+    pub settings: Arc<TokioRwLock<Settings>>,
+}
+
+#[async_trait]
+impl LyricsProvider for MusixmatchProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::Musixmatch
+    }
+
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let token = self.settings.read().await.musixmatch_token.clone();
+        if token.is_empty() {
+            debug!("No musixmatch token, skipping");
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+
+        let duration = req.duration_sec.round().to_string();
+        let url = Url::parse_with_params(
+            MUSIXMATCH_URL,
+            [
+                ("format", "json"),
+                ("namespace", "lyrics_synched"),
+                ("subtitle_format", "lrc"),
+                ("app_id", MUSIXMATCH_APP_ID),
+                ("usertoken", &token),
+                ("q_track", &req.track_name),
+                ("q_artist", &req.artist_name),
+                ("q_album", &req.album_name),
+                ("q_duration", &duration),
+            ],
+        )
+        .expect("Musixmatch url should parse");
+        let body: serde_json::Value = self
+            .client
+            .get(url)
+            // Without it the API answers with a captcha
+            .header("Cookie", "x-mxm-token-guid=")
+            .send()
+            .await?
+            .json()
+            .await?;
+        trace!("Musixmatch response: {body:?}");
+
+        let message = &body["message"];
+        let status = message["header"]["status_code"].as_u64().unwrap_or(0);
+        if status == 401 {
+            warn!("Musixmatch refused the token, it may have expired");
+            // Not an answer about the song, nothing should be cached for it
+            return Err(LyricsFetcherErr::ProviderError(
+                401,
+                "the user token was refused, it may have expired".to_owned(),
+            ));
+        }
+
+        let calls = &message["body"]["macro_calls"];
+        let track = &calls["matcher.track.get"]["message"]["body"]["track"];
+        let subtitle =
+            &calls["track.subtitles.get"]["message"]["body"]["subtitle_list"][0]["subtitle"];
+        let lrc = subtitle["subtitle_body"]
+            .as_str()
+            .filter(|lrc| !lrc.trim().is_empty())
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+
+        Ok(FoundLyrics {
+            lyrics: parse_lrc(lrc, false).lyrics,
+            provider_id: track["track_id"].as_u64().map(|id| id.to_string()),
+        })
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use crate::lyrics_fetch::matching::{Candidate, best_match};
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::lyrics_parser::parse_lrc;
+use crate::settings::Settings;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::{debug, trace};
+use url::Url;
+
+const NETEASE_SEARCH_URL: &str = "https://music.163.com/api/search/get";
+const NETEASE_LYRICS_URL: &str = "https://music.163.com/api/song/lyric";
+/// The API refuses requests that don't look like they come from the website
+const NETEASE_REFERER: &str = "https://music.163.com/";
+
+#[derive(Deserialize, Debug)]
+struct SearchResponse {
+    result: Option<SearchResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResult {
+    #[serde(default)]
+    songs: Vec<NeteaseSong>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NeteaseSong {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    artists: Vec<NeteaseArtist>,
+    /// Milliseconds
+    duration: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct NeteaseArtist {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LyricsResponse {
+    lrc: Option<LyricsBody>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LyricsBody {
+    lyric: Option<String>,
+}
+
+/// Netease cloud music, strong on CJK releases
+pub(super) struct NeteaseProvider {
+    pub client: reqwest::Client,
+    pub settings: Arc<TokioRwLock<Settings>>,
+}
+
+#[async_trait]
+impl LyricsProvider for NeteaseProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::Netease
+    }
+
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let song = self.search(req).await?;
+        debug!("NetEase song id: {}", song.id);
+
+        let url = Url::parse_with_params(
+            NETEASE_LYRICS_URL,
+            [("id", song.id.to_string().as_str()), ("lv", "1")],
+        )
+        .expect("NetEase url should parse");
+        let lyrics: LyricsResponse = self
+            .client
+            .get(url)
+            .header("Referer", NETEASE_REFERER)
+            .send()
+            .await?
+            .json()
+            .await?;
+        trace!("NetEase lyrics: {lyrics:?}");
+
+        let lrc = lyrics
+            .lrc
+            .and_then(|body| body.lyric)
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        let parsed = parse_lrc(&lrc, false).lyrics;
+        // Plain lyrics come back as lrc without timestamps
+        if parsed.synced_lyrics.is_empty() {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+
+        Ok(FoundLyrics {
+            lyrics: parsed,
+            provider_id: Some(song.id.to_string()),
+        })
+    }
+}
+
+impl NeteaseProvider {
+    #[allow(clippy::cast_precision_loss)]
+    async fn search(&self, req: &LyricsRequestInfo) -> Result<NeteaseSong, LyricsFetcherErr> {
+        let query = format!("{} {}", req.track_name, req.artist_name);
+        let url = Url::parse_with_params(
+            NETEASE_SEARCH_URL,
+            [("s", query.as_str()), ("type", "1"), ("limit", "10")],
+        )
+        .expect("NetEase url should parse");
+        let search: SearchResponse = self
+            .client
+            .get(url)
+            .header("Referer", NETEASE_REFERER)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let tolerance_sec = self.settings.read().await.duration_tolerance_sec;
+        let songs = search.result.map(|result| result.songs).unwrap_or_default();
+        best_match(req, tolerance_sec, songs, |song| Candidate {
+            title: &song.name,
+            artist: song.artists.first().map_or("", |artist| &artist.name),
+            duration_sec: song.duration as f64 / 1000.,
+        })
+        .ok_or(LyricsFetcherErr::SongLyricsNotFound())
+    }
+}
@@ -0,0 +1,149 @@
+//! Cleaning up track info before looking it up, the originals are kept for display
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Decorations that don't change the lyrics, bracketed or after a " - ". Matched as whole
+/// words, so "Defeat" or "Monologue" aren't decorations
+const DECORATIONS: &[&str] = &[
+    "feat",
+    "featuring",
+    "ft",
+    "remaster",
+    "remastered",
+    "deluxe",
+    "edition",
+    "version",
+    "radio edit",
+    "single edit",
+    "mono",
+    "stereo",
+    "bonus",
+    "expanded",
+    "anniversary",
+    "explicit",
+];
+
+/// Title without decorations like "(feat. X)" or "- Remastered 2011", lowercased
+pub(super) fn clean_title(title: &str) -> String {
+    strip_decorations(&simplify(title))
+}
+
+/// Album without decorations like "(Deluxe)", lowercased
+pub(super) fn clean_album(album: &str) -> String {
+    strip_decorations(&simplify(album))
+}
+
+/// Artist with uniform punctuation, lowercased
+pub(super) fn clean_artist(artist: &str) -> String {
+    simplify(artist)
+}
+
+/// First of several credited artists, "A, B & C" gives "A"
+#[cfg(any(feature = "native", test))]
+pub(super) fn primary_artist(artist: &str) -> &str {
+    let lower = artist.to_ascii_lowercase();
+    let end = [",", "&", ";", " feat", " ft."]
+        .iter()
+        .filter_map(|separator| lower.find(separator))
+        .min()
+        .unwrap_or(artist.len());
+    artist[..end].trim()
+}
+
+/// Lowercase NFC with plain dashes and quotes and single spaces
+fn simplify(text: &str) -> String {
+    let text: String = text
+        .nfc()
+        .map(|c| match c {
+            '‐' | '‑' | '‒' | '–' | '—' | '―' => '-',
+            '‘' | '’' | '‚' | '′' => '\'',
+            '“' | '”' | '„' | '″' => '"',
+            _ => c,
+        })
+        .collect();
+    text.to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_decoration(text: &str) -> bool {
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    DECORATIONS.iter().any(|decoration| {
+        let decoration: Vec<&str> = decoration.split(' ').collect();
+        words
+            .windows(decoration.len())
+            .any(|window| window == decoration.as_slice())
+    })
+}
+
+fn strip_decorations(text: &str) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find(['(', '[']) {
+        let close = if rest[open..].starts_with('(') {
+            ')'
+        } else {
+            ']'
+        };
+        let Some(len) = rest[open..].find(close) else {
+            break;
+        };
+        let inner = &rest[open + 1..open + len];
+        cleaned.push_str(&rest[..open]);
+        if !is_decoration(inner) {
+            cleaned.push_str(&rest[open..=open + len]);
+        }
+        rest = &rest[open + len + 1..];
+    }
+    cleaned.push_str(rest);
+
+    // "Song - Remastered 2011", only when something is left before the dash
+    if let Some(dash) = cleaned.rfind(" - ")
+        && dash > 0
+        && is_decoration(&cleaned[dash + 3..])
+    {
+        cleaned.truncate(dash);
+    }
+
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_decorations() {
+        assert_eq!(
+            clean_title("Here Comes The Sun - Remastered 2009"),
+            "here comes the sun"
+        );
+        assert_eq!(clean_title("Stay (feat. Justin Bieber)"), "stay");
+        assert_eq!(clean_title("Don’t Stop Me Now"), "don't stop me now");
+        assert_eq!(clean_title("Song (Interlude)"), "song (interlude)");
+        assert_eq!(clean_title("Song (Radio Edit)"), "song");
+        assert_eq!(clean_title("Song - 2011 Remastered Version"), "song");
+        assert_eq!(clean_album("Abbey Road (Deluxe Edition)"), "abbey road");
+        assert_eq!(clean_artist("Beyoncé"), "beyoncé");
+        assert_eq!(primary_artist("Calvin Harris, Dua Lipa"), "Calvin Harris");
+        assert_eq!(primary_artist("Dua Lipa"), "Dua Lipa");
+    }
+
+    #[test]
+    fn decorations_are_whole_words() {
+        assert_eq!(
+            clean_title("Rise Up (Defeat the Night)"),
+            "rise up (defeat the night)"
+        );
+        assert_eq!(clean_title("Intro - Monologue"), "intro - monologue");
+        assert_eq!(clean_title("Monologue (Mono)"), "monologue");
+        assert_eq!(
+            clean_title("Defeat the Night - Edit"),
+            "defeat the night - edit"
+        );
+    }
+}
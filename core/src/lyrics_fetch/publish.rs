@@ -0,0 +1,113 @@
+//! Uploading lyrics to lrclib, gated behind a proof of work challenge
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::{
+    MessageToUI,
+    lyrics_fetch::{LyricsFetcher, LyricsFetcherErr, SongWithLyrics, lrc::LRC_USER_AGENT},
+    runtime::{Messages, RuntimeError},
+};
+
+const LRC_LIB_CHALLENGE_URL: &str = "https://lrclib.net/api/request-challenge";
+const LRC_LIB_PUBLISH_URL: &str = "https://lrclib.net/api/publish";
+
+/// Lyrics to upload, as shown in the overlay
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishRequest {
+    pub track_name: String,
+    pub artist_name: String,
+    pub album_name: String,
+    pub duration: f64,
+    pub plain_lyrics: String,
+    pub synced_lyrics: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Challenge {
+    prefix: String,
+    /// Hex encoded, the hash has to be at or below it
+    target: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PublishError {
+    message: String,
+}
+
+impl LyricsFetcher {
+    pub async fn publish(&self, req: PublishRequest) -> Result<Messages, RuntimeError> {
+        self.publish_to_lrclib(&req)
+            .await
+            .map_err(RuntimeError::PublishFailed)?;
+        info!(
+            "Published lyrics for {} - {} to lrclib",
+            req.artist_name, req.track_name
+        );
+        Ok(Messages::to_ui(MessageToUI::LyricsPublished))
+    }
+
+    async fn publish_to_lrclib(&self, req: &PublishRequest) -> Result<(), LyricsFetcherErr> {
+        let challenge: Challenge = self
+            .client
+            .post(LRC_LIB_CHALLENGE_URL)
+            .header("User-Agent", LRC_USER_AGENT)
+            .send()
+            .await?
+            .json()
+            .await?;
+        debug!("Solving publish challenge {challenge:?}");
+
+        let prefix = challenge.prefix.clone();
+        let nonce = tokio::task::spawn_blocking(move || solve_challenge(&challenge))
+            .await
+            .expect("challenge solver should not panic")
+            .ok_or_else(|| LyricsFetcherErr::PublishRejected("invalid challenge".to_owned()))?;
+
+        let response = self
+            .client
+            .post(LRC_LIB_PUBLISH_URL)
+            .header("User-Agent", LRC_USER_AGENT)
+            .header("X-Publish-Token", format!("{prefix}:{nonce}"))
+            .json(req)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+        let status = response.status();
+        let message = response
+            .json::<PublishError>()
+            .await
+            .map_or_else(|_| status.to_string(), |err| err.message);
+        Err(LyricsFetcherErr::PublishRejected(message))
+    }
+}
+
+/// Nonce whose hash with the prefix is at or below the target, None if the target isn't hex
+fn solve_challenge(challenge: &Challenge) -> Option<u64> {
+    let target = (0..challenge.target.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(challenge.target.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    (0..=u64::MAX).find(|nonce| {
+        let hash = Sha256::digest(format!("{}{nonce}", challenge.prefix));
+        hash.as_slice() <= target.as_slice()
+    })
+}
+
+impl SongWithLyrics {
+    /// Upload of these lyrics as they are now
+    pub fn publish_request(&self) -> PublishRequest {
+        PublishRequest {
+            track_name: self.track_name.clone(),
+            artist_name: self.artist_name.clone(),
+            album_name: self.album_name.clone(),
+            duration: self.duration_sec.round(),
+            plain_lyrics: self.lyrics.to_plain(),
+            synced_lyrics: self.lyrics.to_lrc(),
+        }
+    }
+}
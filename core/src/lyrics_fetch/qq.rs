@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use crate::lyrics_fetch::matching::{Candidate, best_match};
+use crate::lyrics_fetch::qrc::decrypt_qrc;
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::lyrics_parser::{SongLyrics, parse_lrc, parse_qrc};
+use crate::settings::Settings;
+
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::{debug, trace, warn};
+use url::Url;
+
+const QQ_SEARCH_URL: &str = "https://c.y.qq.com/soso/fcgi-bin/client_search_cp";
+/// Word timed QRC lyrics, see `qrc`
+const QQ_QRC_URL: &str = "https://c.y.qq.com/qqmusic/fcgi-bin/lyric_download.fcg";
+/// Line timed lyrics, for songs without QRC
+const QQ_LYRICS_URL: &str = "https://c.y.qq.com/lyric/fcgi-bin/fcg_query_lyric_new.fcg";
+/// The lyrics endpoint refuses requests without it
+const QQ_REFERER: &str = "https://y.qq.com/portal/player.html";
+
+#[derive(Deserialize, Debug)]
+struct SearchResponse {
+    data: SearchData,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchData {
+    song: SearchSongs,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchSongs {
+    #[serde(default)]
+    list: Vec<QqSong>,
+}
+
+#[derive(Deserialize, Debug)]
+struct QqSong {
+    /// Numeric id, the QRC endpoint wants it
+    #[serde(default)]
+    songid: u64,
+    songmid: String,
+    songname: String,
+    #[serde(default)]
+    singer: Vec<QqSinger>,
+    /// Seconds
+    #[serde(default)]
+    interval: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct QqSinger {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LyricsResponse {
+    /// Base64 encoded lrc
+    lyric: Option<String>,
+}
+
+/// QQ Music, for Chinese releases
+pub(super) struct QqMusicProvider {
+    pub client: reqwest::Client,
+    pub settings: Arc<TokioRwLock<Settings>>,
+}
+
+#[async_trait]
+impl LyricsProvider for QqMusicProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::QqMusic
+    }
+
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let song = self.search(req).await?;
+        debug!("QQ Music song mid: {}", song.songmid);
+
+        let lyrics = match self.fetch_qrc(&song).await {
+            Err(LyricsFetcherErr::SongLyricsNotFound()) => self.fetch_lrc(&song).await?,
+            result => result?,
+        };
+        Ok(FoundLyrics {
+            lyrics,
+            provider_id: Some(song.songmid),
+        })
+    }
+}
+
+impl QqMusicProvider {
+    async fn fetch_qrc(&self, song: &QqSong) -> Result<SongLyrics, LyricsFetcherErr> {
+        if song.songid == 0 {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+        let url = Url::parse_with_params(
+            QQ_QRC_URL,
+            [
+                ("version", "15"),
+                ("miniversion", "82"),
+                ("lrctype", "4"),
+                ("musicid", song.songid.to_string().as_str()),
+            ],
+        )
+        .expect("QQ Music url should parse");
+        let response = self
+            .client
+            .get(url)
+            .header("Referer", QQ_REFERER)
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        // The xml comes wrapped in a comment
+        let xml = response
+            .trim()
+            .trim_start_matches("<!--")
+            .trim_end_matches("-->");
+        let document = roxmltree::Document::parse(xml.trim()).map_err(|e| {
+            warn!("Failed to read the QQ Music QRC response: {e}");
+            LyricsFetcherErr::SongLyricsNotFound()
+        })?;
+        let qrc = document
+            .descendants()
+            .find(|node| node.has_tag_name("content"))
+            .and_then(|node| node.text())
+            .filter(|hex| !hex.trim().is_empty())
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        let qrc = decrypt_qrc(qrc).map_err(|e| {
+            warn!("Failed to decrypt QQ Music lyrics: {e}");
+            LyricsFetcherErr::SongLyricsNotFound()
+        })?;
+        trace!("QQ Music QRC: {qrc:?}");
+        let parsed = parse_qrc(&qrc);
+        if parsed.synced_lyrics.is_empty() {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+        Ok(parsed)
+    }
+
+    async fn fetch_lrc(&self, song: &QqSong) -> Result<SongLyrics, LyricsFetcherErr> {
+        let url = Url::parse_with_params(
+            QQ_LYRICS_URL,
+            [("songmid", song.songmid.as_str()), ("format", "json")],
+        )
+        .expect("QQ Music url should parse");
+        let response: LyricsResponse = self
+            .client
+            .get(url)
+            .header("Referer", QQ_REFERER)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let lrc = response
+            .lyric
+            .and_then(|lyric| BASE64.decode(lyric).ok())
+            .and_then(|lyric| String::from_utf8(lyric).ok())
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        trace!("QQ Music lyrics: {lrc:?}");
+        let parsed = parse_lrc(&lrc, false).lyrics;
+        if parsed.synced_lyrics.is_empty() {
+            return Err(LyricsFetcherErr::SongLyricsNotFound());
+        }
+        Ok(parsed)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    async fn search(&self, req: &LyricsRequestInfo) -> Result<QqSong, LyricsFetcherErr> {
+        let query = format!("{} {}", req.track_name, req.artist_name);
+        let url = Url::parse_with_params(
+            QQ_SEARCH_URL,
+            [
+                ("w", query.as_str()),
+                ("format", "json"),
+                ("p", "1"),
+                ("n", "10"),
+            ],
+        )
+        .expect("QQ Music url should parse");
+        let search: SearchResponse = self.client.get(url).send().await?.json().await?;
+
+        let tolerance_sec = self.settings.read().await.duration_tolerance_sec;
+        best_match(req, tolerance_sec, search.data.song.list, |song| {
+            Candidate {
+                title: &song.songname,
+                artist: song.singer.first().map_or("", |singer| &singer.name),
+                duration_sec: song.interval as f64,
+            }
+        })
+        .ok_or(LyricsFetcherErr::SongLyricsNotFound())
+    }
+}
@@ -0,0 +1,283 @@
+//! QQ Music's word timed QRC lyrics: hex of triple DES encrypted, zlib compressed xml. QQ's DES
+//! reads blocks and keys as little endian 32 bit words and has two altered S-box entries, so
+//! no DES crate decrypts it
+use std::io::{self, Read};
+
+use flate2::read::ZlibDecoder;
+
+const QRC_KEY: &[u8; 24] = b"!@#)(*$%123ZXC!@!@#)(NHL";
+
+const IP: [u8; 64] = [
+    58, 50, 42, 34, 26, 18, 10, 2, 60, 52, 44, 36, 28, 20, 12, 4, 62, 54, 46, 38, 30, 22, 14, 6,
+    64, 56, 48, 40, 32, 24, 16, 8, 57, 49, 41, 33, 25, 17, 9, 1, 59, 51, 43, 35, 27, 19, 11, 3, 61,
+    53, 45, 37, 29, 21, 13, 5, 63, 55, 47, 39, 31, 23, 15, 7,
+];
+const FP: [u8; 64] = [
+    40, 8, 48, 16, 56, 24, 64, 32, 39, 7, 47, 15, 55, 23, 63, 31, 38, 6, 46, 14, 54, 22, 62, 30,
+    37, 5, 45, 13, 53, 21, 61, 29, 36, 4, 44, 12, 52, 20, 60, 28, 35, 3, 43, 11, 51, 19, 59, 27,
+    34, 2, 42, 10, 50, 18, 58, 26, 33, 1, 41, 9, 49, 17, 57, 25,
+];
+const EXPANSION: [u8; 48] = [
+    32, 1, 2, 3, 4, 5, 4, 5, 6, 7, 8, 9, 8, 9, 10, 11, 12, 13, 12, 13, 14, 15, 16, 17, 16, 17, 18,
+    19, 20, 21, 20, 21, 22, 23, 24, 25, 24, 25, 26, 27, 28, 29, 28, 29, 30, 31, 32, 1,
+];
+const P: [u8; 32] = [
+    16, 7, 20, 21, 29, 12, 28, 17, 1, 15, 23, 26, 5, 18, 31, 10, 2, 8, 24, 14, 32, 27, 3, 9, 19,
+    13, 30, 6, 22, 11, 4, 25,
+];
+const PC1: [u8; 56] = [
+    57, 49, 41, 33, 25, 17, 9, 1, 58, 50, 42, 34, 26, 18, 10, 2, 59, 51, 43, 35, 27, 19, 11, 3, 60,
+    52, 44, 36, 63, 55, 47, 39, 31, 23, 15, 7, 62, 54, 46, 38, 30, 22, 14, 6, 61, 53, 45, 37, 29,
+    21, 13, 5, 28, 20, 12, 4,
+];
+const PC2: [u8; 48] = [
+    14, 17, 11, 24, 1, 5, 3, 28, 15, 6, 21, 10, 23, 19, 12, 4, 26, 8, 16, 7, 27, 20, 13, 2, 41, 52,
+    31, 37, 47, 55, 30, 40, 51, 45, 33, 48, 44, 49, 39, 56, 34, 53, 46, 42, 50, 36, 29, 32,
+];
+const KEY_SHIFTS: [u32; 16] = [1, 1, 2, 2, 2, 2, 2, 2, 1, 2, 2, 2, 2, 2, 2, 1];
+/// Rows of 16 after each other
+const DES_SBOXES: [[u8; 64]; 8] = [
+    [
+        14, 4, 13, 1, 2, 15, 11, 8, 3, 10, 6, 12, 5, 9, 0, 7, 0, 15, 7, 4, 14, 2, 13, 1, 10, 6, 12,
+        11, 9, 5, 3, 8, 4, 1, 14, 8, 13, 6, 2, 11, 15, 12, 9, 7, 3, 10, 5, 0, 15, 12, 8, 2, 4, 9,
+        1, 7, 5, 11, 3, 14, 10, 0, 6, 13,
+    ],
+    [
+        15, 1, 8, 14, 6, 11, 3, 4, 9, 7, 2, 13, 12, 0, 5, 10, 3, 13, 4, 7, 15, 2, 8, 14, 12, 0, 1,
+        10, 6, 9, 11, 5, 0, 14, 7, 11, 10, 4, 13, 1, 5, 8, 12, 6, 9, 3, 2, 15, 13, 8, 10, 1, 3, 15,
+        4, 2, 11, 6, 7, 12, 0, 5, 14, 9,
+    ],
+    [
+        10, 0, 9, 14, 6, 3, 15, 5, 1, 13, 12, 7, 11, 4, 2, 8, 13, 7, 0, 9, 3, 4, 6, 10, 2, 8, 5,
+        14, 12, 11, 15, 1, 13, 6, 4, 9, 8, 15, 3, 0, 11, 1, 2, 12, 5, 10, 14, 7, 1, 10, 13, 0, 6,
+        9, 8, 7, 4, 15, 14, 3, 11, 5, 2, 12,
+    ],
+    [
+        7, 13, 14, 3, 0, 6, 9, 10, 1, 2, 8, 5, 11, 12, 4, 15, 13, 8, 11, 5, 6, 15, 0, 3, 4, 7, 2,
+        12, 1, 10, 14, 9, 10, 6, 9, 0, 12, 11, 7, 13, 15, 1, 3, 14, 5, 2, 8, 4, 3, 15, 0, 6, 10, 1,
+        13, 8, 9, 4, 5, 11, 12, 7, 2, 14,
+    ],
+    [
+        2, 12, 4, 1, 7, 10, 11, 6, 8, 5, 3, 15, 13, 0, 14, 9, 14, 11, 2, 12, 4, 7, 13, 1, 5, 0, 15,
+        10, 3, 9, 8, 6, 4, 2, 1, 11, 10, 13, 7, 8, 15, 9, 12, 5, 6, 3, 0, 14, 11, 8, 12, 7, 1, 14,
+        2, 13, 6, 15, 0, 9, 10, 4, 5, 3,
+    ],
+    [
+        12, 1, 10, 15, 9, 2, 6, 8, 0, 13, 3, 4, 14, 7, 5, 11, 10, 15, 4, 2, 7, 12, 9, 5, 6, 1, 13,
+        14, 0, 11, 3, 8, 9, 14, 15, 5, 2, 8, 12, 3, 7, 0, 4, 10, 1, 13, 11, 6, 4, 3, 2, 12, 9, 5,
+        15, 10, 11, 14, 1, 7, 6, 0, 8, 13,
+    ],
+    [
+        4, 11, 2, 14, 15, 0, 8, 13, 3, 12, 9, 7, 5, 10, 6, 1, 13, 0, 11, 7, 4, 9, 1, 10, 14, 3, 5,
+        12, 2, 15, 8, 6, 1, 4, 11, 13, 12, 3, 7, 14, 10, 15, 6, 8, 0, 5, 9, 2, 6, 11, 13, 8, 1, 4,
+        10, 7, 9, 5, 0, 15, 14, 2, 3, 12,
+    ],
+    [
+        13, 2, 8, 4, 6, 15, 11, 1, 10, 9, 3, 14, 5, 0, 12, 7, 1, 15, 13, 8, 10, 3, 7, 4, 12, 5, 6,
+        11, 0, 14, 9, 2, 7, 11, 4, 1, 9, 12, 14, 2, 0, 6, 10, 13, 15, 3, 5, 8, 2, 1, 14, 7, 4, 10,
+        8, 13, 15, 12, 9, 0, 3, 5, 6, 11,
+    ],
+];
+/// QQ's have 15 instead of 14 in the second row of the second box and 10 instead of 1 in the
+/// last row of the fourth
+const QQ_SBOXES: [[u8; 64]; 8] = {
+    let mut sboxes = DES_SBOXES;
+    sboxes[1][16 + 7] = 15;
+    sboxes[3][48 + 5] = 10;
+    sboxes
+};
+
+/// The QRC xml's lines, `[start,duration]word(start,duration)...`
+pub(super) fn decrypt_qrc(hex: &str) -> io::Result<String> {
+    let encrypted = decode_hex(hex.trim())
+        .filter(|bytes| !bytes.is_empty() && bytes.len().is_multiple_of(8))
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "qrc isn't hex of whole blocks")
+        })?;
+    let keys = [
+        (&QRC_KEY[16..], true),
+        (&QRC_KEY[8..16], false),
+        (&QRC_KEY[..8], true),
+    ]
+    .map(|(key, decrypt)| {
+        let mut subkeys = subkeys(to_block(key));
+        if decrypt {
+            subkeys.reverse();
+        }
+        subkeys
+    });
+    let compressed: Vec<u8> = encrypted
+        .chunks_exact(8)
+        .flat_map(|chunk| {
+            let block = keys.iter().fold(to_block(chunk), |block, keys| {
+                crypt(block, keys, &QQ_SBOXES)
+            });
+            from_block(block)
+        })
+        .collect();
+
+    let mut xml = String::new();
+    ZlibDecoder::new(compressed.as_slice()).read_to_string(&mut xml)?;
+    lyric_content(&xml)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "qrc has no LyricContent"))
+}
+
+/// The `LyricContent` attribute, cut out by hand as xml parsers turn its line breaks into spaces
+fn lyric_content(xml: &str) -> Option<String> {
+    let start = xml.find("LyricContent=\"")? + "LyricContent=\"".len();
+    let end = start + xml[start..].find("\"/>")?;
+    Some(
+        xml[start..end]
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Eight bytes as two little endian words, the way QQ's DES reads them
+fn to_block(bytes: &[u8]) -> u64 {
+    let word =
+        |bytes: &[u8]| u64::from(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    (word(&bytes[..4]) << 32) | word(&bytes[4..8])
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn from_block(block: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&((block >> 32) as u32).to_le_bytes());
+    bytes[4..].copy_from_slice(&(block as u32).to_le_bytes());
+    bytes
+}
+
+/// Picks the bits of `input`, `in_bits` wide, numbered from 1 at the most significant
+fn permute(input: u64, in_bits: u32, table: &[u8]) -> u64 {
+    table.iter().fold(0, |out, &bit| {
+        (out << 1) | ((input >> (in_bits - u32::from(bit))) & 1)
+    })
+}
+
+/// Encryption round keys, reversed they decrypt
+fn subkeys(key: u64) -> [u64; 16] {
+    let halves = permute(key, 64, &PC1);
+    let (mut c, mut d) = (halves >> 28, halves & 0x0fff_ffff);
+    let rotate = |half: u64, n: u32| ((half << n) | (half >> (28 - n))) & 0x0fff_ffff;
+    KEY_SHIFTS.map(|n| {
+        c = rotate(c, n);
+        d = rotate(d, n);
+        permute((c << 28) | d, 56, &PC2)
+    })
+}
+
+fn feistel(half: u64, key: u64, sboxes: &[[u8; 64]; 8]) -> u64 {
+    let expanded = permute(half, 32, &EXPANSION) ^ key;
+    let substituted = sboxes.iter().enumerate().fold(0, |out, (i, sbox)| {
+        let six = (expanded >> (42 - 6 * i)) & 0x3f;
+        // Outer bits pick the row, inner ones the column
+        let index = (six & 0x20) | ((six & 1) << 4) | ((six >> 1) & 0xf);
+        (out << 4) | u64::from(sbox[usize::try_from(index).unwrap_or_default()])
+    });
+    permute(substituted, 32, &P)
+}
+
+fn crypt(block: u64, keys: &[u64; 16], sboxes: &[[u8; 64]; 8]) -> u64 {
+    let permuted = permute(block, 64, &IP);
+    let (mut left, mut right) = (permuted >> 32, permuted & 0xffff_ffff);
+    for &key in keys {
+        (left, right) = (right, left ^ feistel(right, key, sboxes));
+    }
+    permute((right << 32) | left, 64, &FP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lyrics_parser::parse_qrc;
+
+    #[test]
+    fn des_known_answers() {
+        // Standard DES test vectors, checking the tables and key schedule QQ's variant builds on
+        let vectors: [(u64, u64, u64); 4] = [
+            (
+                0x1334_5779_9BBC_DFF1,
+                0x0123_4567_89AB_CDEF,
+                0x85E8_1354_0F0A_B405,
+            ),
+            (
+                0x0E32_9232_EA6D_0D73,
+                0x8787_8787_8787_8787,
+                0x0000_0000_0000_0000,
+            ),
+            (
+                0x0101_0101_0101_0101,
+                0x8000_0000_0000_0000,
+                0x95F8_A5E5_DD31_D900,
+            ),
+            (
+                0x8001_0101_0101_0101,
+                0x0000_0000_0000_0000,
+                0x95A8_D728_13DA_A94D,
+            ),
+        ];
+        for (key, plain, cipher) in vectors {
+            let mut keys = subkeys(key);
+            assert_eq!(crypt(plain, &keys, &DES_SBOXES), cipher);
+            keys.reverse();
+            assert_eq!(crypt(cipher, &keys, &DES_SBOXES), plain);
+        }
+    }
+
+    #[test]
+    fn qq_variant() {
+        let changed: Vec<_> = (0..8)
+            .flat_map(|sbox| (0..64).map(move |i| (sbox, i)))
+            .filter(|&(sbox, i)| QQ_SBOXES[sbox][i] != DES_SBOXES[sbox][i])
+            .map(|(sbox, i)| (sbox, i, QQ_SBOXES[sbox][i]))
+            .collect();
+        assert_eq!(changed, [(1, 23, 15), (3, 53, 10)]);
+
+        let bytes = [0x67, 0x45, 0x23, 0x01, 0xEF, 0xCD, 0xAB, 0x89];
+        assert_eq!(to_block(&bytes), 0x0123_4567_89AB_CDEF);
+        assert_eq!(from_block(0x0123_4567_89AB_CDEF), bytes);
+    }
+
+    #[test]
+    fn decrypt_and_parse_qrc() {
+        let hex = [
+            "FC9F0D5040ABD165B060D705F42DACF08B42191450DE5244D12E30B0100B67466B4FA719B2BD58FE",
+            "67D671772294750224E5E430CFC5CF533D46C1117CC931FE85BF6D8F88EB8697CC5FC7FB625FC3C0",
+            "34527AC27E43CF4D870822FD531F4CA8B979D8F9255C481D12A5B0C08E1B0E3147EEFAFF2A64A17C",
+            "7FA788A6F8ED884D7CF21152EB4C29C55DEB85C0D77189D497D61702C4432294DA4BC076E7D4AF2D",
+            "DF01A244C9096C3ACA2553F79328897C1C8CDD19FBAD4EC1B816B37E743BDF07AAB1C8979EFAAC24",
+            "6A95A312C8FCB1C5F48459388C088BD65031850D3D3BCDC6B24CF72FFAE4281E5A6AFEA5A7EFC646",
+            "8CF535D35C3479CAA3AB0C74B7B16808245D454244799DFA",
+        ]
+        .concat();
+        let qrc = decrypt_qrc(&hex).unwrap();
+        assert!(qrc.starts_with("[ti:Never Gonna Give You Up]\n"));
+
+        let parsed = parse_qrc(&qrc);
+        assert_eq!(parsed.synced_lyrics.len(), 2);
+        let first = &parsed.synced_lyrics[0];
+        assert_eq!(first.time_ms, 18920);
+        assert_eq!(first.text, "We're no strangers");
+        assert_eq!(first.words.len(), 3);
+        assert_eq!(first.words[2].time_ms, 19620);
+        assert_eq!(first.words[2].duration_ms, 900);
+        // Parentheses that aren't timings stay in the text
+        assert_eq!(parsed.synced_lyrics[1].text, "You know the (ooh)");
+
+        assert!(decrypt_qrc("not hex").is_err());
+    }
+}
@@ -1,5 +1,12 @@
-use crate::lyrics_fetch::{LyricsFetcher, LyricsFetcherErr};
+use std::sync::Arc;
 
+use crate::lyrics_fetch::{
+    FoundLyrics, LyricsFetcherErr, LyricsProvider, LyricsProviderKind, LyricsRequestInfo,
+};
+use crate::settings::Settings;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock as TokioRwLock;
 use tracing::{debug, error};
 
 use serde::Deserialize;
@@ -35,7 +42,31 @@ struct SpotifyLyricsLine {
     words: String,
 }
 
-impl LyricsFetcher {
+/// Spotify's own lyrics, needs the `sp_dc` cookie and a spotify id for the track
+pub(super) struct SpotifyLyricsProvider {
+    pub client: reqwest::Client,
+    pub settings: Arc<TokioRwLock<Settings>>,
+}
+
+#[async_trait]
+impl LyricsProvider for SpotifyLyricsProvider {
+    fn kind(&self) -> LyricsProviderKind {
+        LyricsProviderKind::Spotify
+    }
+
+    async fn fetch(&self, req: &LyricsRequestInfo) -> Result<FoundLyrics, LyricsFetcherErr> {
+        let spotify_id = req
+            .spotify_id
+            .as_ref()
+            .ok_or(LyricsFetcherErr::SongLyricsNotFound())?;
+        Ok(FoundLyrics {
+            lyrics: self.request_track_spotify(spotify_id).await?,
+            provider_id: None,
+        })
+    }
+}
+
+impl SpotifyLyricsProvider {
     #[allow(clippy::cast_possible_truncation)]
     async fn get_secret_key(
         client: &reqwest::Client,
@@ -143,7 +174,7 @@ impl LyricsFetcher {
             })
     }
 
-    pub(super) async fn request_track_spotify(
+    async fn request_track_spotify(
         &self,
         spotify_id: &str,
     ) -> Result<SongLyrics, LyricsFetcherErr> {
@@ -208,6 +239,6 @@ impl LyricsFetcher {
             .collect::<Vec<_>>()
             .join("\n");
 
-        Ok(parse_lrc(&lrc_string, false))
+        Ok(parse_lrc(&lrc_string, false).lyrics)
     }
 }
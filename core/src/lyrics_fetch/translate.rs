@@ -0,0 +1,285 @@
+//! Machine translation of the lyrics, shown as a second line under each lyric line
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info, warn};
+
+use crate::{
+    MessageToUI,
+    lyrics_fetch::{LyricsFetcher, LyricsFetcherErr, LyricsRequestInfo},
+    runtime::{Messages, RuntimeError},
+};
+
+const DEEPL_URL: &str = "https://api.deepl.com/v2/translate";
+/// Keys of the free plan end in `:fx` and only work here
+const DEEPL_FREE_URL: &str = "https://api-free.deepl.com/v2/translate";
+const GOOGLE_TRANSLATE_URL: &str = "https://translation.googleapis.com/language/translate/v2";
+
+/// Services the lyrics can be translated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationBackendKind {
+    #[default]
+    Disabled,
+    /// A `LibreTranslate` instance, the API key is only needed by some
+    LibreTranslate,
+    DeepL,
+    /// Google Cloud Translation
+    Google,
+}
+
+impl TranslationBackendKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Disabled => "Off",
+            Self::LibreTranslate => "LibreTranslate",
+            Self::DeepL => "DeepL",
+            Self::Google => "Google",
+        }
+    }
+}
+
+/// Translated lines of a track, `None` where a line has no translation
+#[derive(Debug)]
+pub struct LyricsTranslation {
+    pub track_name: String,
+    pub artist_name: String,
+    pub lines: Vec<Option<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LibreTranslateResponse {
+    translated_text: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeepLResponse {
+    translations: Vec<DeepLTranslation>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeepLTranslation {
+    text: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GoogleResponse {
+    data: GoogleTranslations,
+}
+
+#[derive(Deserialize, Debug)]
+struct GoogleTranslations {
+    translations: Vec<GoogleTranslation>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GoogleTranslation {
+    translated_text: String,
+}
+
+impl LyricsFetcher {
+    /// Translates the lines of the shown lyrics, from the cache if they were translated before
+    pub async fn translate(
+        &self,
+        req: LyricsRequestInfo,
+        lines: Vec<String>,
+    ) -> Result<Messages, RuntimeError> {
+        // Choruses repeat, every line is translated once
+        let mut unique: Vec<String> = Vec::new();
+        for line in &lines {
+            let line = line.trim();
+            if !line.is_empty() && !unique.iter().any(|seen| seen == line) {
+                unique.push(line.to_owned());
+            }
+        }
+
+        let caching_enabled = self.settings.read().await.caching_enabled;
+        let cached = if caching_enabled {
+            self.cached_translation(&req, &unique).await
+        } else {
+            None
+        };
+        let translated = if let Some(cached) = cached {
+            debug!("Using the cached translation for {req}");
+            cached
+        } else {
+            let translated = self
+                .request_translation(&unique)
+                .await
+                .map_err(RuntimeError::TranslationFailed)?;
+            info!("Translated {} lines for {req}", unique.len());
+            if caching_enabled
+                && let Err(err) = self.store_translation(&req, &unique, &translated).await
+            {
+                warn!("Failed to cache the translation for {req}: {err}");
+            }
+            translated
+        };
+
+        let by_line: HashMap<&str, &str> = unique
+            .iter()
+            .map(String::as_str)
+            .zip(translated.iter().map(String::as_str))
+            .collect();
+        let lines = lines
+            .iter()
+            .map(|line| {
+                let line = line.trim();
+                // Lines already in the target language come back unchanged
+                by_line
+                    .get(line)
+                    .filter(|translation| !translation.trim().eq_ignore_ascii_case(line))
+                    .map(|translation| (*translation).to_owned())
+            })
+            .collect();
+        Ok(Messages::to_ui(MessageToUI::LyricsTranslated(
+            LyricsTranslation {
+                track_name: req.track_name,
+                artist_name: req.artist_name,
+                lines,
+            },
+        )))
+    }
+
+    /// Asks the configured backend, one translation per line in the same order
+    async fn request_translation(&self, lines: &[String]) -> Result<Vec<String>, LyricsFetcherErr> {
+        if lines.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (backend, url, key, target) = {
+            let settings = self.settings.read().await;
+            (
+                settings.translation_backend,
+                settings.translation_url.trim_end_matches('/').to_owned(),
+                settings.translation_api_key.clone(),
+                settings.translation_language.clone(),
+            )
+        };
+        if key.is_empty()
+            && matches!(
+                backend,
+                TranslationBackendKind::DeepL | TranslationBackendKind::Google
+            )
+        {
+            return Err(LyricsFetcherErr::TranslationFailed(format!(
+                "{} needs an API key",
+                backend.as_str()
+            )));
+        }
+
+        let translated = match backend {
+            TranslationBackendKind::Disabled => {
+                return Err(LyricsFetcherErr::TranslationFailed(
+                    "Translation is turned off".to_owned(),
+                ));
+            }
+            TranslationBackendKind::LibreTranslate => {
+                self.libre_translate(&url, &key, &target, lines).await?
+            }
+            TranslationBackendKind::DeepL => self.deepl(&key, &target, lines).await?,
+            TranslationBackendKind::Google => self.google_translate(&key, &target, lines).await?,
+        };
+
+        if translated.len() != lines.len() {
+            return Err(LyricsFetcherErr::TranslationFailed(format!(
+                "{} returned {} lines for {}",
+                backend.as_str(),
+                translated.len(),
+                lines.len()
+            )));
+        }
+        Ok(translated)
+    }
+
+    async fn libre_translate(
+        &self,
+        url: &str,
+        key: &str,
+        target: &str,
+        lines: &[String],
+    ) -> Result<Vec<String>, LyricsFetcherErr> {
+        let mut body = json!({
+            "q": lines,
+            "source": "auto",
+            "target": target,
+            "format": "text",
+        });
+        if !key.is_empty() {
+            body["api_key"] = json!(key);
+        }
+        let response: LibreTranslateResponse = self
+            .client
+            .post(format!("{url}/translate"))
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.translated_text)
+    }
+
+    async fn deepl(
+        &self,
+        key: &str,
+        target: &str,
+        lines: &[String],
+    ) -> Result<Vec<String>, LyricsFetcherErr> {
+        let url = if key.ends_with(":fx") {
+            DEEPL_FREE_URL
+        } else {
+            DEEPL_URL
+        };
+        let response: DeepLResponse = self
+            .client
+            .post(url)
+            .header("Authorization", format!("DeepL-Auth-Key {key}"))
+            .json(&json!({
+                "text": lines,
+                "target_lang": target.to_uppercase(),
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response
+            .translations
+            .into_iter()
+            .map(|translation| translation.text)
+            .collect())
+    }
+
+    async fn google_translate(
+        &self,
+        key: &str,
+        target: &str,
+        lines: &[String],
+    ) -> Result<Vec<String>, LyricsFetcherErr> {
+        let url = url::Url::parse_with_params(GOOGLE_TRANSLATE_URL, &[("key", key)])
+            .map_err(|err| LyricsFetcherErr::TranslationFailed(err.to_string()))?;
+        let response: GoogleResponse = self
+            .client
+            .post(url)
+            .json(&json!({
+                "q": lines,
+                "target": target,
+                "format": "text",
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response
+            .data
+            .translations
+            .into_iter()
+            .map(|translation| translation.translated_text)
+            .collect())
+    }
+}
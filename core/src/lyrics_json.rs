@@ -0,0 +1,216 @@
+//! Stable json form of `SongLyrics`, for other tools to read the cache and write lyrics files.
+//!
+//! Written as `lyrics.json` next to each cached `lyrics.lrc`, and read from `.json` files in the
+//! lrc folders. Within a schema version fields are only ever added, with defaults, anything else
+//! bumps `SCHEMA_VERSION`. Lrc text converts through `SongLyrics::from_lrc_file` and
+//! `SongLyrics::to_lrc_file`.
+//!
+//! ```json
+//! {
+//!   "schema": 1,
+//!   "unsynced": false,
+//!   "length_ms": 212000,
+//!   "title": "Never Gonna Give You Up",
+//!   "lines": [
+//!     { "start_ms": 18800, "text": "We're no strangers to love", "part": "male" },
+//!     { "start_ms": 22590, "text": "You know the rules",
+//!       "words": [{ "start_ms": 22590, "duration_ms": 400, "text": "You " }] }
+//!   ]
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::lyrics_parser::{LinePart, LrcMetadata, LyricLine, LyricWord, SongLyrics};
+
+/// Version written to `schema`, files with a newer one are refused
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum LyricsJsonErr {
+    #[error("Malformed lyrics json")]
+    Json(#[from] serde_json::Error),
+    #[error("Lyrics json schema {0} is newer than this version understands")]
+    UnsupportedSchema(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LyricsJson {
+    pub schema: u32,
+    /// The source had no timings, `start_ms` are estimates
+    #[serde(default)]
+    pub unsynced: bool,
+    /// Length of the recording the lyrics were timed to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length_ms: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    /// In order of `start_ms`
+    pub lines: Vec<LineJson>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LineJson {
+    pub start_ms: usize,
+    pub text: String,
+    /// Empty unless the source had word timings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<WordJson>,
+    /// Singer in a duet, `male`, `female`, `duet` or `v1`, `v2`... for numbered voices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WordJson {
+    pub start_ms: usize,
+    pub duration_ms: usize,
+    /// With the whitespace after it
+    pub text: String,
+}
+
+impl LyricsJson {
+    /// Reads lyrics json of this or an older schema
+    pub fn parse(json: &str) -> Result<SongLyrics, LyricsJsonErr> {
+        let lyrics: Self = serde_json::from_str(json)?;
+        if lyrics.schema > SCHEMA_VERSION {
+            return Err(LyricsJsonErr::UnsupportedSchema(lyrics.schema));
+        }
+        Ok(lyrics.into_lyrics())
+    }
+
+    /// Lines are sorted by `start_ms`, hand edited files can have them out of order
+    pub fn into_lyrics(self) -> SongLyrics {
+        let mut lines: Vec<_> = self.lines.into_iter().map(LineJson::into_line).collect();
+        lines.sort_by_key(|line| line.time_ms);
+        SongLyrics {
+            synced_lyrics: lines,
+            unsynced: self.unsynced,
+            length_ms: self.length_ms,
+            metadata: LrcMetadata {
+                title: self.title,
+                artist: self.artist,
+                album: self.album,
+            },
+        }
+    }
+}
+
+impl From<&SongLyrics> for LyricsJson {
+    fn from(lyrics: &SongLyrics) -> Self {
+        Self {
+            schema: SCHEMA_VERSION,
+            unsynced: lyrics.unsynced,
+            length_ms: lyrics.length_ms,
+            title: lyrics.metadata.title.clone(),
+            artist: lyrics.metadata.artist.clone(),
+            album: lyrics.metadata.album.clone(),
+            lines: lyrics
+                .synced_lyrics
+                .iter()
+                .map(|line| LineJson {
+                    start_ms: line.time_ms,
+                    text: line.text.clone(),
+                    words: line
+                        .words
+                        .iter()
+                        .map(|word| WordJson {
+                            start_ms: word.time_ms,
+                            duration_ms: word.duration_ms,
+                            text: word.text.clone(),
+                        })
+                        .collect(),
+                    part: line.part.map(part_name),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl LineJson {
+    fn into_line(self) -> LyricLine {
+        LyricLine {
+            time_ms: self.start_ms,
+            text: self.text,
+            words: self
+                .words
+                .into_iter()
+                .map(|word| LyricWord {
+                    time_ms: word.start_ms,
+                    duration_ms: word.duration_ms,
+                    text: word.text,
+                })
+                .collect(),
+            // Unknown parts from newer versions are dropped
+            part: self.part.as_deref().and_then(parse_part),
+            ..Default::default()
+        }
+    }
+}
+
+/// Spelled out here so renaming `LinePart` can't change the schema
+fn part_name(part: LinePart) -> String {
+    match part {
+        LinePart::Male => "male".to_owned(),
+        LinePart::Female => "female".to_owned(),
+        LinePart::Duet => "duet".to_owned(),
+        LinePart::Voice(number) => format!("v{number}"),
+    }
+}
+
+fn parse_part(name: &str) -> Option<LinePart> {
+    match name {
+        "male" => Some(LinePart::Male),
+        "female" => Some(LinePart::Female),
+        "duet" => Some(LinePart::Duet),
+        _ => name.strip_prefix('v')?.parse().ok().map(LinePart::Voice),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lyrics_parser::parse_lrc;
+
+    #[test]
+    fn json_roundtrip() {
+        let lyrics = parse_lrc(
+            "[ti:Song]\n[length:03:00]\n[00:01.00]M: <00:01.00>Hello <00:01.50>there<00:02.00>\n[00:03.00][v2]Bye",
+            false,
+        )
+        .lyrics;
+        let json = serde_json::to_string(&LyricsJson::from(&lyrics)).unwrap();
+        assert!(
+            json.starts_with(r#"{"schema":1,"unsynced":false,"length_ms":180000,"title":"Song""#)
+        );
+        let read = LyricsJson::parse(&json).unwrap();
+        assert_eq!(read.to_lrc_file(), lyrics.to_lrc_file());
+
+        let newer = json.replacen(r#""schema":1"#, r#""schema":2"#, 1);
+        assert!(matches!(
+            LyricsJson::parse(&newer),
+            Err(LyricsJsonErr::UnsupportedSchema(2))
+        ));
+    }
+
+    #[test]
+    fn lines_out_of_order() {
+        let json = r#"{"schema":1,"lines":[
+            {"start_ms":3000,"text":"Third"},
+            {"start_ms":1000,"text":"First"},
+            {"start_ms":2000,"text":"Second"}
+        ]}"#;
+        let lyrics = LyricsJson::parse(json).unwrap();
+        let texts: Vec<_> = lyrics
+            .synced_lyrics
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(texts, ["First", "Second", "Third"]);
+    }
+}
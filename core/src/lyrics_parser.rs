@@ -0,0 +1,1594 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use unicode_bidi::{Direction, Level, ParagraphBidiInfo, get_base_direction};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::romanize;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LyricLine {
+    pub time_ms: usize,
+    pub text: String,
+    /// Per word timings, empty unless the source has them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<LyricWord>,
+    /// Romanized text for CJK lines, see `SongLyrics::add_readings`
+    #[serde(skip)]
+    pub romanized: Option<String>,
+    /// The text split into parts with reading hints, empty when there are none
+    #[serde(skip)]
+    pub ruby: Vec<RubySegment>,
+    /// Machine translation, filled in once it arrives
+    #[serde(skip)]
+    pub translation: Option<String>,
+    /// Who sings the line in a duet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<LinePart>,
+    /// Nothing is sung from here to the next line, see `SongLyrics::mark_instrumental_breaks`
+    #[serde(skip)]
+    pub instrumental: bool,
+    /// Arabic or Hebrew text read right to left, see `SongLyrics::add_readings`
+    #[serde(skip)]
+    pub rtl: bool,
+}
+
+/// Singer of a line, from Walaoke `M:`, `F:` and `D:` markers or `[v1]` style voices.
+/// A marker holds for the following lines until the next one, `[-]` ends it
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinePart {
+    Male,
+    Female,
+    /// Both singers
+    Duet,
+    Voice(u8),
+}
+
+impl LinePart {
+    /// Marks the lines from here on as not belonging to any part
+    const NO_PART_MARKER: &str = "[-]";
+
+    /// The marker at the start of `text` and the text after it
+    fn strip_marker(text: &str) -> Option<(Self, &str)> {
+        let (part, rest) = if let Some(voice) = text.strip_prefix("[v") {
+            let (number, rest) = voice.split_once(']')?;
+            (Self::Voice(number.parse().ok()?), rest)
+        } else if let Some(voice) = text.strip_prefix('v') {
+            let (number, rest) = voice.split_once(':')?;
+            (Self::Voice(number.parse().ok()?), rest)
+        } else {
+            let (marker, rest) = text.split_once(':')?;
+            let part = match marker {
+                "M" => Self::Male,
+                "F" => Self::Female,
+                "D" => Self::Duet,
+                _ => return None,
+            };
+            (part, rest)
+        };
+        Some((part, rest.trim_start()))
+    }
+
+    fn marker(self) -> String {
+        match self {
+            Self::Male => "M: ".to_owned(),
+            Self::Female => "F: ".to_owned(),
+            Self::Duet => "D: ".to_owned(),
+            Self::Voice(number) => format!("[v{number}]"),
+        }
+    }
+}
+
+/// Part of a line, with the reading shown above it if it has one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RubySegment {
+    pub text: String,
+    pub reading: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LyricWord {
+    pub time_ms: usize,
+    pub duration_ms: usize,
+    pub text: String,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LyricPosition {
+    BeforeStart,
+    /// The line being sung and how far it is to the next one, 0 to 1
+    Line(usize, f32),
+    AfterEnd(usize),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SongLyrics {
+    pub synced_lyrics: Vec<LyricLine>,
+    /// The source had no timings, the line timings are estimated, see `from_plain`
+    #[serde(default)]
+    pub unsynced: bool,
+    /// Length of the recording the lyrics were timed to, if the source says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length_ms: Option<usize>,
+    /// Song the lyrics say they're for, from the `[ti:]`, `[ar:]` and `[al:]` tags
+    #[serde(default, skip_serializing_if = "LrcMetadata::is_empty")]
+    pub metadata: LrcMetadata,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LrcMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl LrcMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.artist.is_none() && self.album.is_none()
+    }
+}
+
+/// Lyrics read by `parse_lrc`, with what it had to skip
+#[derive(Debug)]
+pub struct ParsedLrc {
+    pub lyrics: SongLyrics,
+    pub warnings: Vec<LrcWarning>,
+}
+
+/// A line of lrc that was skipped or only partly understood
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrcWarning {
+    /// Starting at 1
+    pub line: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for LrcWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl SongLyrics {
+    /// Fills in the romanized text, reading hints and direction of every line.
+    /// Ruby markup like `｜漢字《かんじ》` is taken out of the text
+    pub fn add_readings(&mut self) {
+        for line in &mut self.synced_lyrics {
+            line.rtl = is_rtl(&line.text);
+            if let Some(ruby) = romanize::ruby_markup(&line.text) {
+                line.text = ruby.iter().map(|segment| segment.text.as_str()).collect();
+                let reading: String = ruby
+                    .iter()
+                    .map(|segment| segment.reading.as_deref().unwrap_or(&segment.text))
+                    .collect();
+                line.romanized = romanize::romanize(&reading);
+                line.ruby = ruby;
+            } else {
+                line.romanized = romanize::romanize(&line.text);
+                line.ruby = romanize::pinyin_ruby(&line.text).unwrap_or_default();
+            }
+        }
+    }
+
+    pub fn display_text_as_lyrics(text: String, duration_ms: usize) -> Self {
+        Self {
+            synced_lyrics: vec![
+                LyricLine {
+                    time_ms: 0,
+                    text,
+                    ..Default::default()
+                },
+                LyricLine {
+                    time_ms: duration_ms,
+                    text: " ".to_string(),
+                    ..Default::default()
+                },
+            ],
+            unsynced: false,
+            length_ms: None,
+            metadata: LrcMetadata::default(),
+        }
+    }
+
+    /// Marks silences of at least `min_gap_ms` between lines as instrumental breaks. Blank lines
+    /// starting one are marked, otherwise a blank line is added where the line before ends
+    pub fn mark_instrumental_breaks(&mut self, min_gap_ms: usize) {
+        let mut lines = Vec::with_capacity(self.synced_lyrics.len());
+        let mut rest = std::mem::take(&mut self.synced_lyrics)
+            .into_iter()
+            .peekable();
+        while let Some(mut line) = rest.next() {
+            let Some((next_ms, next_blank)) = rest
+                .peek()
+                .map(|next| (next.time_ms, next.text.trim().is_empty()))
+            else {
+                lines.push(line);
+                break;
+            };
+            if line.text.trim().is_empty() {
+                line.instrumental = next_ms.saturating_sub(line.time_ms) >= min_gap_ms;
+                lines.push(line);
+                continue;
+            }
+            let end_ms = line.time_ms + sung_ms(&line);
+            lines.push(line);
+            // A blank line after it is marked instead
+            if !next_blank && next_ms.saturating_sub(end_ms) >= min_gap_ms {
+                lines.push(LyricLine {
+                    time_ms: end_ms,
+                    text: String::new(),
+                    instrumental: true,
+                    ..Default::default()
+                });
+            }
+        }
+        self.synced_lyrics = lines;
+    }
+
+    /// Splits lines longer than `max_chars` at punctuation. The parts are timed by their words,
+    /// or spread by length over the time until the next line
+    pub fn split_long_lines(&mut self, max_chars: usize) {
+        let next_times: Vec<Option<usize>> = self
+            .synced_lyrics
+            .iter()
+            .skip(1)
+            .map(|line| Some(line.time_ms))
+            .chain([None])
+            .collect();
+        let mut lines = Vec::with_capacity(self.synced_lyrics.len());
+        for (line, next_ms) in std::mem::take(&mut self.synced_lyrics)
+            .into_iter()
+            .zip(next_times)
+        {
+            let parts = split_at_punctuation(&line.text, max_chars);
+            if parts.len() < 2 {
+                lines.push(line);
+                continue;
+            }
+            let duration_ms =
+                next_ms.map_or_else(|| sung_ms(&line), |next| next.saturating_sub(line.time_ms));
+            let total_chars = line.text.chars().count().max(1);
+            let mut words = line.words.iter().peekable();
+            let mut chars_before = 0;
+            for part in parts {
+                // Words go to the part their letters are in
+                let letters = part.chars().filter(|c| !c.is_whitespace()).count();
+                let mut part_words = Vec::new();
+                let mut word_letters = 0;
+                while let Some(word) = words.next_if(|_| word_letters < letters) {
+                    word_letters += word.text.chars().filter(|c| !c.is_whitespace()).count();
+                    part_words.push(word.clone());
+                }
+                let time_ms = part_words.first().map_or(
+                    line.time_ms + duration_ms * chars_before / total_chars,
+                    |word| word.time_ms,
+                );
+                chars_before += part.chars().count() + 1;
+                lines.push(LyricLine {
+                    time_ms,
+                    text: part.to_owned(),
+                    words: part_words,
+                    ..line.clone()
+                });
+            }
+        }
+        self.synced_lyrics = lines;
+    }
+
+    /// Lyrics without timings, with estimated timings spreading the lines over the track.
+    /// Longer lines get more time
+    pub fn from_plain(text: &str, duration_ms: usize) -> Self {
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+        // Short lines and stanza breaks still take a moment
+        let weight = |line: &str| line.chars().count() + 8;
+        let total_weight = lines.iter().map(|line| weight(line)).sum::<usize>().max(1);
+        let mut elapsed_weight = 0;
+        Self {
+            synced_lyrics: lines
+                .into_iter()
+                .map(|text| {
+                    let time_ms = duration_ms * elapsed_weight / total_weight;
+                    elapsed_weight += weight(text);
+                    (time_ms, text)
+                })
+                .map(|(time_ms, text)| LyricLine {
+                    time_ms,
+                    text: text.to_owned(),
+                    ..Default::default()
+                })
+                .collect(),
+            unsynced: true,
+            length_ms: None,
+            metadata: LrcMetadata::default(),
+        }
+    }
+
+    /// The last line started by `elapsed_ms`. Past the start of the last line it's `AfterEnd`
+    #[allow(clippy::cast_precision_loss)]
+    pub fn find_current_index(&self, elapsed_ms: usize) -> LyricPosition {
+        let lines = &self.synced_lyrics;
+        let started = lines.partition_point(|line| line.time_ms <= elapsed_ms);
+        if started == 0 {
+            return LyricPosition::BeforeStart;
+        }
+        let Some(next) = lines.get(started) else {
+            return LyricPosition::AfterEnd(lines.len());
+        };
+        // The next line starts after elapsed_ms, so after the current one
+        let start = lines[started - 1].time_ms;
+        let progress = (elapsed_ms - start) as f32 / (next.time_ms - start) as f32;
+        LyricPosition::Line(started - 1, progress)
+    }
+
+    /// The first line starting after `elapsed_ms`, with its index
+    pub fn next_line_after(&self, elapsed_ms: usize) -> Option<(usize, &LyricLine)> {
+        let index = self
+            .synced_lyrics
+            .partition_point(|line| line.time_ms <= elapsed_ms);
+        self.synced_lyrics.get(index).map(|line| (index, line))
+    }
+
+    /// Time from `elapsed_ms` until the next line starts, `None` once the last one started
+    pub fn time_until_next(&self, elapsed_ms: usize) -> Option<usize> {
+        self.next_line_after(elapsed_ms)
+            .map(|(_, line)| line.time_ms - elapsed_ms)
+    }
+
+    /// Every line with when it starts and ends, the last one ends at `song_end_ms`
+    pub fn timeline(&self, song_end_ms: usize) -> impl Iterator<Item = (usize, usize, &LyricLine)> {
+        let ends = self
+            .synced_lyrics
+            .iter()
+            .skip(1)
+            .map(|line| line.time_ms)
+            .chain([song_end_ms]);
+        self.synced_lyrics
+            .iter()
+            .zip(ends)
+            .map(|(line, end)| (line.time_ms, end.max(line.time_ms), line))
+    }
+
+    /// Lines as `[mm:ss.xx]` timed lrc
+    pub fn to_lrc(&self) -> String {
+        self.synced_lyrics
+            .iter()
+            .map(|line| format!("[{}]{}", format_time_tag(line.time_ms), line.text))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Everything we know as an lrc file: the metadata tags, word timings as enhanced lrc
+    /// `<mm:ss.xx>` tags, and plain lines for unsynced lyrics. Read back by `from_lrc_file`
+    pub fn to_lrc_file(&self) -> String {
+        if self.unsynced {
+            return self.to_plain();
+        }
+        let tags = [
+            ("ti", self.metadata.title.clone()),
+            ("ar", self.metadata.artist.clone()),
+            ("al", self.metadata.album.clone()),
+            ("length", self.length_ms.map(format_time_tag)),
+        ]
+        .into_iter()
+        .filter_map(|(tag, value)| Some(format!("[{tag}:{}]", value?)));
+        let mut part = None;
+        let lines = self.synced_lyrics.iter().map(|line| {
+            let mut lrc = format!("[{}]", format_time_tag(line.time_ms));
+            if line.part != part {
+                part = line.part;
+                lrc.push_str(
+                    &part.map_or_else(|| LinePart::NO_PART_MARKER.to_owned(), LinePart::marker),
+                );
+            }
+            if line.words.is_empty() {
+                lrc.push_str(&line.text);
+            }
+            for word in &line.words {
+                let _ = write!(lrc, "<{}>{}", format_time_tag(word.time_ms), word.text);
+            }
+            // Ends the last word
+            if let Some(last) = line.words.last() {
+                let _ = write!(
+                    lrc,
+                    "<{}>",
+                    format_time_tag(last.time_ms + last.duration_ms)
+                );
+            }
+            lrc
+        });
+        tags.chain(lines).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Reads lrc written by `to_lrc_file`, or any other lrc file.
+    /// Text without timings is spread over `duration_ms` as unsynced lyrics
+    pub fn from_lrc_file(content: &str, duration_ms: usize) -> Self {
+        let lyrics = parse_lrc(content, false).lyrics;
+        if lyrics.synced_lyrics.is_empty() {
+            Self::from_plain(content, duration_ms)
+        } else {
+            lyrics
+        }
+    }
+
+    /// Lines without timings
+    pub fn to_plain(&self) -> String {
+        self.synced_lyrics
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Timed lines of an lrc file. Lines that can't be read are skipped and listed in the warnings
+pub fn parse_lrc(content: &str, strip_empty_lines: bool) -> ParsedLrc {
+    let mut lyrics = SongLyrics {
+        synced_lyrics: Vec::new(),
+        unsynced: false,
+        length_ms: None,
+        metadata: LrcMetadata::default(),
+    };
+    let mut warnings = Vec::new();
+    let mut offset_ms: isize = 0;
+    let mut part = None;
+
+    for (number, raw) in clean_content(content).lines().enumerate() {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            continue;
+        }
+        let mut warn = |reason: String| {
+            warnings.push(LrcWarning {
+                line: number + 1,
+                reason,
+            });
+        };
+
+        // Every leading [mm:ss.xx] or [mm:ss:xx] timestamp, `[00:10.00][01:20.00]Chorus` repeats
+        // the line at both times
+        let mut times = Vec::new();
+        let mut rest = raw;
+        let mut malformed = false;
+        while let Some(inner) = rest.strip_prefix('[') {
+            let Some(close) = inner.find(']') else {
+                if times.is_empty() {
+                    warn("unclosed tag".to_owned());
+                    malformed = true;
+                }
+                break;
+            };
+            let tag = &inner[..close];
+            if let Some(ms) = parse_time_tag_to_ms(tag) {
+                times.push(ms);
+            } else if !times.is_empty() {
+                // Text that starts with brackets, like `[Chorus]`
+                break;
+            } else if let Err(reason) = read_lrc_tag(tag, &mut lyrics, &mut offset_ms) {
+                warn(reason);
+                malformed = true;
+            }
+            rest = inner[close + 1..].trim();
+        }
+        let Some(&first_ms) = times.first() else {
+            if !malformed && !rest.is_empty() {
+                warn("text without a timestamp".to_owned());
+            }
+            continue;
+        };
+
+        if let Some(text) = rest.strip_prefix(LinePart::NO_PART_MARKER) {
+            part = None;
+            rest = text.trim_start();
+        } else if let Some((marked, text)) = LinePart::strip_marker(rest) {
+            part = Some(marked);
+            rest = text;
+        }
+        let (text, words) = parse_enhanced_words(rest).unwrap_or_else(|| {
+            if rest.starts_with('<') {
+                warn("malformed word timings, kept as text".to_owned());
+            }
+            (
+                rest.split_whitespace().collect::<Vec<_>>().join(" "),
+                Vec::new(),
+            )
+        });
+        if strip_empty_lines && text.is_empty() {
+            continue;
+        }
+        for ms in times {
+            lyrics.synced_lyrics.push(LyricLine {
+                time_ms: ms,
+                text: text.clone(),
+                // Word timings are for the first time
+                words: words
+                    .iter()
+                    .map(|word| LyricWord {
+                        time_ms: (word.time_ms + ms).saturating_sub(first_ms),
+                        ..word.clone()
+                    })
+                    .collect(),
+                part,
+                ..Default::default()
+            });
+        }
+    }
+    // Repeated lines are out of order
+    lyrics.synced_lyrics.sort_by_key(|line| line.time_ms);
+
+    // A positive offset shows the lyrics sooner
+    if offset_ms != 0 {
+        let shift = |ms: usize| ms.saturating_add_signed(offset_ms.saturating_neg());
+        for line in &mut lyrics.synced_lyrics {
+            line.time_ms = shift(line.time_ms);
+            for word in &mut line.words {
+                word.time_ms = shift(word.time_ms);
+            }
+        }
+    }
+
+    for warning in &warnings {
+        debug!("Skipped lrc {warning}");
+    }
+    ParsedLrc { lyrics, warnings }
+}
+
+/// Reads a metadata tag like `[ti:Title]` into `lyrics`, why not if it's malformed
+fn read_lrc_tag(tag: &str, lyrics: &mut SongLyrics, offset_ms: &mut isize) -> Result<(), String> {
+    if tag.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(format!("malformed timestamp [{tag}]"));
+    }
+    let Some((key, value)) = tag.split_once(':') else {
+        return Err(format!("unknown tag [{tag}]"));
+    };
+    let value = value.trim();
+    let metadata = &mut lyrics.metadata;
+    match key.trim().to_lowercase().as_str() {
+        "length" => {
+            let length_ms =
+                parse_time_tag_to_ms(value).ok_or_else(|| format!("malformed length [{tag}]"))?;
+            lyrics.length_ms = Some(length_ms);
+        }
+        "offset" => {
+            *offset_ms = value
+                .trim_start_matches('+')
+                .parse()
+                .map_err(|_| format!("malformed offset [{tag}]"))?;
+        }
+        "ti" if !value.is_empty() => metadata.title = Some(value.to_owned()),
+        "ar" if !value.is_empty() => metadata.artist = Some(value.to_owned()),
+        "al" if !value.is_empty() => metadata.album = Some(value.to_owned()),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The first strongly directional letter of `text` is read right to left
+pub fn is_rtl(text: &str) -> bool {
+    get_base_direction(text) == Direction::Rtl
+}
+
+/// A row of text in the order it's drawn, as egui lays text out left to right without reordering.
+/// Right to left runs are reversed and their brackets mirrored
+pub fn visual_order(row: &str, rtl: bool) -> String {
+    let level = if rtl { Level::rtl() } else { Level::ltr() };
+    let bidi = ParagraphBidiInfo::new(row, Some(level));
+    if !bidi.has_rtl() {
+        return row.to_owned();
+    }
+    let (levels, runs) = bidi.visual_runs(0..row.len());
+    let mut visual = String::with_capacity(row.len());
+    for run in runs {
+        if levels[run.start].is_rtl() {
+            visual.extend(row[run].chars().rev().map(|c| match c {
+                '(' => ')',
+                ')' => '(',
+                '[' => ']',
+                ']' => '[',
+                '{' => '}',
+                '}' => '{',
+                '<' => '>',
+                '>' => '<',
+                '«' => '»',
+                '»' => '«',
+                _ => c,
+            }));
+        } else {
+            visual.push_str(&row[run]);
+        }
+    }
+    visual
+}
+
+/// `text` cut after punctuation into parts of at most `max_chars` where it can be
+fn split_at_punctuation(text: &str, max_chars: usize) -> Vec<&str> {
+    if text.chars().count() <= max_chars {
+        return vec![text];
+    }
+    let cuts = text
+        .char_indices()
+        .filter(|&(i, c)| {
+            matches!(c, ',' | ';' | ':' | '.' | '!' | '?' | '，' | '、' | '。')
+                && text[i + c.len_utf8()..].starts_with(char::is_whitespace)
+        })
+        .map(|(i, c)| i + c.len_utf8());
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut best = None;
+    for cut in cuts.chain([text.len()]) {
+        if text[start..cut].chars().count() > max_chars
+            && let Some(best_cut) = best
+        {
+            parts.push(text[start..best_cut].trim());
+            start = best_cut;
+        }
+        best = Some(cut);
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// How long `line` is sung for, by its word timings or a guess from its length
+fn sung_ms(line: &LyricLine) -> usize {
+    line.words.last().map_or_else(
+        || 1000 + 100 * line.text.chars().count(),
+        |word| (word.time_ms + word.duration_ms).saturating_sub(line.time_ms),
+    )
+}
+
+/// Part of the letters of `line` sung at `position_ms` by its word timings, letters of the word
+/// being sung filling up over its duration. `None` without word timings
+#[allow(clippy::cast_precision_loss)]
+pub fn sung_fraction(line: &LyricLine, position_ms: usize) -> Option<f32> {
+    let letters = |word: &LyricWord| word.text.chars().filter(|c| !c.is_whitespace()).count();
+    let total: usize = line.words.iter().map(letters).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut sung = 0.0;
+    for word in &line.words {
+        let count = letters(word) as f32;
+        if position_ms >= word.time_ms + word.duration_ms {
+            sung += count;
+        } else {
+            if position_ms > word.time_ms {
+                sung += count * (position_ms - word.time_ms) as f32 / word.duration_ms as f32;
+            }
+            break;
+        }
+    }
+    Some(sung / total as f32)
+}
+
+/// `content` as NFC with `\n` line endings, without BOMs and zero-width spaces
+fn clean_content(content: &str) -> String {
+    content
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .nfc()
+        .filter(|c| !matches!(c, '\u{feff}' | '\u{200b}' | '\u{2060}'))
+        .collect()
+}
+
+/// `text` split into parts, true for backing vocals in `(...)` and section markers in `[...]`
+pub fn split_adlibs(text: &str) -> Vec<(&str, bool)> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' => {
+                if depth == 0 && i > start {
+                    parts.push((&text[start..i], false));
+                    start = i;
+                }
+                depth += 1;
+            }
+            ')' | ']' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    parts.push((&text[start..=i], true));
+                    start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    // An adlib left open runs to the end of the line
+    if start < text.len() {
+        parts.push((&text[start..], depth > 0));
+    }
+    parts
+}
+
+/// `text` without backing vocals and section markers, see `split_adlibs`
+pub fn strip_adlibs(text: &str) -> String {
+    split_adlibs(text)
+        .into_iter()
+        .filter(|(_, adlib)| !adlib)
+        .map(|(part, _)| part)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses SRT or `WebVTT` subtitles, every cue is a line at its start time. Text over several
+/// lines is joined, formatting tags are dropped, and a gap after a cue gets an empty line
+pub fn parse_subtitles(content: &str) -> SongLyrics {
+    let mut cues: Vec<(usize, usize, String)> = Vec::new();
+    let content = content.replace("\r\n", "\n");
+    for block in content.split("\n\n") {
+        let mut block_lines = block.lines().map(str::trim);
+        // Cue numbers and VTT cue ids come before the timing, header and note blocks have none
+        let Some((start, end)) = block_lines.find_map(|line| line.split_once("-->")) else {
+            continue;
+        };
+        let cue_time = |time: &str| {
+            // VTT cue settings follow the end time
+            let time = time.split_whitespace().next().unwrap_or_default();
+            parse_time_tag_to_ms(&time.replace(',', "."))
+        };
+        let (Some(start_ms), Some(end_ms)) = (cue_time(start), cue_time(end)) else {
+            continue;
+        };
+        let text = block_lines
+            .map(strip_subtitle_tags)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        cues.push((start_ms, end_ms, text));
+    }
+    cues.sort_by_key(|(start_ms, ..)| *start_ms);
+
+    let mut lines = Vec::new();
+    for (i, (start_ms, end_ms, text)) in cues.iter().enumerate() {
+        lines.push(LyricLine {
+            time_ms: *start_ms,
+            text: text.clone(),
+            ..Default::default()
+        });
+        let next_ms = cues.get(i + 1).map_or(usize::MAX, |(next_ms, ..)| *next_ms);
+        if *end_ms < next_ms {
+            lines.push(LyricLine {
+                time_ms: *end_ms,
+                text: String::new(),
+                ..Default::default()
+            });
+        }
+    }
+
+    SongLyrics {
+        synced_lyrics: lines,
+        unsynced: false,
+        length_ms: None,
+        metadata: LrcMetadata::default(),
+    }
+}
+
+/// Cue text without `<i>` style tags and `{\an8}` style overrides
+fn strip_subtitle_tags(line: &str) -> String {
+    let mut text = String::new();
+    let mut closing = None;
+    for c in line.chars() {
+        match (closing, c) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (Some(close), c) if c == close => closing = None,
+            (None, c) => text.push(c),
+            _ => {}
+        }
+    }
+    text.trim().to_owned()
+}
+
+/// Parses TTML lyrics, like exported Apple Music lyrics. Timed `<span>`s are word timings and
+/// `ttm:agent` voices are parts. Background vocals are left out, they overlap the line
+pub fn parse_ttml(content: &str) -> SongLyrics {
+    let mut lyrics = SongLyrics {
+        synced_lyrics: Vec::new(),
+        unsynced: false,
+        length_ms: None,
+        metadata: LrcMetadata::default(),
+    };
+    let Ok(document) = roxmltree::Document::parse(content) else {
+        return lyrics;
+    };
+
+    for node in document.descendants() {
+        match node.tag_name().name() {
+            "body" => lyrics.length_ms = node.attribute("dur").and_then(parse_ttml_time),
+            "p" => {
+                let Some(time_ms) = node.attribute("begin").and_then(parse_ttml_time) else {
+                    continue;
+                };
+                let mut words = Vec::new();
+                ttml_words(node, &mut words);
+                let text = if words.is_empty() {
+                    ttml_text(node)
+                } else {
+                    words.iter().map(|word| word.text.as_str()).collect()
+                };
+                let part = ttml_attribute(node, "agent").and_then(|agent| {
+                    let number: u16 = agent.strip_prefix('v')?.parse().ok()?;
+                    // Apple's agent for the whole group
+                    Some(u8::try_from(number).map_or(LinePart::Duet, LinePart::Voice))
+                });
+                lyrics.synced_lyrics.push(LyricLine {
+                    time_ms,
+                    text: text.trim().to_owned(),
+                    words,
+                    part,
+                    ..Default::default()
+                });
+            }
+            _ => {}
+        }
+    }
+    lyrics.synced_lyrics.sort_by_key(|line| line.time_ms);
+    lyrics
+}
+
+/// Attribute by local name, whatever prefix the document gave its namespace
+fn ttml_attribute<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.value())
+}
+
+fn is_background_vocals(node: roxmltree::Node) -> bool {
+    ttml_attribute(node, "role") == Some("x-bg")
+}
+
+/// Timed spans under `node`, the text between them goes with the word before
+fn ttml_words(node: roxmltree::Node, words: &mut Vec<LyricWord>) {
+    for child in node.children() {
+        if child.is_text() {
+            if let Some(last) = words.last_mut() {
+                last.text
+                    .push_str(&collapse_whitespace(child.text().unwrap_or_default()));
+            }
+        } else if child.tag_name().name() == "span" && !is_background_vocals(child) {
+            let begin = child.attribute("begin").and_then(parse_ttml_time);
+            let end = child.attribute("end").and_then(parse_ttml_time);
+            if let (Some(time_ms), Some(end_ms)) = (begin, end) {
+                words.push(LyricWord {
+                    time_ms,
+                    duration_ms: end_ms.saturating_sub(time_ms),
+                    text: ttml_text(child),
+                });
+            } else {
+                ttml_words(child, words);
+            }
+        }
+    }
+}
+
+/// Text under `node` without background vocals
+fn ttml_text(node: roxmltree::Node) -> String {
+    node.children()
+        .map(|child| {
+            if child.is_text() {
+                collapse_whitespace(child.text().unwrap_or_default())
+            } else if is_background_vocals(child) {
+                String::new()
+            } else {
+                ttml_text(child)
+            }
+        })
+        .collect()
+}
+
+/// Runs of whitespace and line breaks from indenting as single spaces
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::new();
+    for (i, part) in text.split_whitespace().enumerate() {
+        if i > 0 {
+            collapsed.push(' ');
+        }
+        collapsed.push_str(part);
+    }
+    if text.starts_with(char::is_whitespace) {
+        collapsed.insert(0, ' ');
+    }
+    if text.ends_with(char::is_whitespace) && collapsed != " " {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+/// TTML times, `12.5s`, `1500ms`, `1:02.5`, `01:01:02.5` or plain seconds
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn parse_ttml_time(time: &str) -> Option<usize> {
+    let time = time.trim();
+    if let Some(millis) = time.strip_suffix("ms") {
+        return millis.parse::<f64>().ok().map(|ms| ms.max(0.) as usize);
+    }
+    if let Some(secs) = time.strip_suffix('s') {
+        return secs
+            .parse::<f64>()
+            .ok()
+            .map(|secs| (secs.max(0.) * 1000.).round() as usize);
+    }
+    match time.matches(':').count() {
+        0 => time
+            .parse::<f64>()
+            .ok()
+            .map(|secs| (secs.max(0.) * 1000.).round() as usize),
+        // `hh:mm:ss` would be read as lrc's `mm:ss:xx`
+        2 if !time.contains('.') => parse_time_tag_to_ms(&format!("{time}.0")),
+        _ => parse_time_tag_to_ms(time),
+    }
+}
+
+/// Parses Kugou's decoded KRC, lines look like `[start,duration]<offset,duration,0>word...`
+/// with word offsets relative to the line
+pub fn parse_krc(content: &str) -> SongLyrics {
+    let mut lines: Vec<LyricLine> = Vec::new();
+
+    for raw in content.lines() {
+        let raw = raw.trim();
+        let Some((tag, mut rest)) = raw.strip_prefix('[').and_then(|raw| raw.split_once(']'))
+        else {
+            continue;
+        };
+        // Metadata tags like [ar:...] don't have a numeric timing
+        let Some(line_ms) = tag
+            .split_once(',')
+            .and_then(|(start, _)| start.trim().parse::<usize>().ok())
+        else {
+            continue;
+        };
+
+        let mut words = Vec::new();
+        while let Some((timing, after)) = rest.strip_prefix('<').and_then(|r| r.split_once('>')) {
+            let text_end = after.find('<').unwrap_or(after.len());
+            let mut timing = timing.split(',').map(|n| n.trim().parse::<usize>().ok());
+            if let (Some(Some(offset)), Some(Some(duration_ms))) = (timing.next(), timing.next()) {
+                words.push(LyricWord {
+                    time_ms: line_ms + offset,
+                    duration_ms,
+                    text: after[..text_end].to_owned(),
+                });
+            }
+            rest = &after[text_end..];
+        }
+
+        let text = if words.is_empty() {
+            rest.to_owned()
+        } else {
+            words.iter().map(|word| word.text.as_str()).collect()
+        };
+        lines.push(LyricLine {
+            time_ms: line_ms,
+            text: text.trim().to_owned(),
+            words,
+            ..Default::default()
+        });
+    }
+    lines.sort_by_key(|line| line.time_ms);
+
+    SongLyrics {
+        synced_lyrics: lines,
+        unsynced: false,
+        length_ms: None,
+        metadata: LrcMetadata::default(),
+    }
+}
+
+/// Parses QQ Music's decrypted QRC, lines look like `[start,duration]word(start,duration)...`
+/// with word starts in song time
+pub fn parse_qrc(content: &str) -> SongLyrics {
+    let mut lines: Vec<LyricLine> = Vec::new();
+
+    for raw in content.lines() {
+        let raw = raw.trim();
+        let Some((tag, rest)) = raw.strip_prefix('[').and_then(|raw| raw.split_once(']')) else {
+            continue;
+        };
+        // Metadata tags like [ti:...] don't have a numeric timing
+        let Some(line_ms) = tag
+            .split_once(',')
+            .and_then(|(start, _)| start.trim().parse::<usize>().ok())
+        else {
+            continue;
+        };
+
+        // Parentheses in the lyrics themselves don't hold two numbers
+        let mut words = Vec::new();
+        let mut word_start = 0;
+        let mut search = 0;
+        while let Some(open) = rest[search..].find('(').map(|i| search + i) {
+            let Some(close) = rest[open..].find(')').map(|i| open + i) else {
+                break;
+            };
+            let timing = rest[open + 1..close]
+                .split_once(',')
+                .and_then(|(start, duration)| {
+                    Some((start.trim().parse().ok()?, duration.trim().parse().ok()?))
+                });
+            let Some((time_ms, duration_ms)) = timing else {
+                search = open + 1;
+                continue;
+            };
+            if open > word_start {
+                words.push(LyricWord {
+                    time_ms,
+                    duration_ms,
+                    text: rest[word_start..open].to_owned(),
+                });
+            }
+            word_start = close + 1;
+            search = close + 1;
+        }
+
+        let text = if words.is_empty() {
+            rest.to_owned()
+        } else {
+            words.iter().map(|word| word.text.as_str()).collect()
+        };
+        lines.push(LyricLine {
+            time_ms: line_ms,
+            text: text.trim().to_owned(),
+            words,
+            ..Default::default()
+        });
+    }
+    lines.sort_by_key(|line| line.time_ms);
+
+    SongLyrics {
+        synced_lyrics: lines,
+        unsynced: false,
+        length_ms: None,
+        metadata: LrcMetadata::default(),
+    }
+}
+
+/// Enhanced lrc word timings, `<mm:ss.xx>word <mm:ss.xx>word<mm:ss.xx>` where the last tag
+/// ends the last word. `None` if the line has none
+fn parse_enhanced_words(text: &str) -> Option<(String, Vec<LyricWord>)> {
+    let mut tags = Vec::new();
+    let mut rest = text.strip_prefix('<')?;
+    loop {
+        let (tag, after) = rest.split_once('>')?;
+        let time_ms = parse_time_tag_to_ms(tag)?;
+        let Some((word, next)) = after.split_once('<') else {
+            tags.push((time_ms, after));
+            break;
+        };
+        tags.push((time_ms, word));
+        rest = next;
+    }
+
+    let words: Vec<LyricWord> = tags
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, word))| !word.is_empty())
+        .map(|(i, &(time_ms, word))| LyricWord {
+            time_ms,
+            duration_ms: tags
+                .get(i + 1)
+                .map_or(0, |&(next_ms, _)| next_ms.saturating_sub(time_ms)),
+            text: word.to_owned(),
+        })
+        .collect();
+    let text = words
+        .iter()
+        .map(|word| word.text.as_str())
+        .collect::<String>();
+    Some((text.trim().to_owned(), words))
+}
+
+fn format_time_tag(ms: usize) -> String {
+    let centis = ms / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// `[mm:ss.xx]`, `[mm:ss:xx]` or `[hh:mm:ss.xxx]`, the fraction can have any number of digits
+fn parse_time_tag_to_ms(tag: &str) -> Option<usize> {
+    let parts: Vec<&str> = tag.split(':').map(str::trim).collect();
+    let (hours, minutes, seconds, fraction) = match parts.as_slice() {
+        [hours, minutes, seconds] if seconds.contains('.') => {
+            let (seconds, fraction) = seconds.split_once('.')?;
+            (*hours, *minutes, seconds, fraction)
+        }
+        [minutes, seconds, fraction] => ("0", *minutes, *seconds, *fraction),
+        [minutes, seconds] => {
+            let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+            ("0", *minutes, seconds, fraction)
+        }
+        _ => return None,
+    };
+
+    let hours: usize = hours.parse().ok()?;
+    let minutes: usize = minutes.parse().ok()?;
+    let secs: usize = seconds.parse().ok()?;
+    // Tenths, hundredths or thousandths, anything finer is dropped
+    let fraction = fraction.get(..3).unwrap_or(fraction);
+    let millis = format!("{fraction:0<3}").parse().unwrap_or(0);
+
+    Some(hours * 3_600_000 + minutes * 60_000 + secs * 1_000 + millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lrc_file_roundtrip() {
+        let krc = "[1000,2000]<0,500,0>Never <500,700,0>gonna<1200,800,0> give";
+        let mut lyrics = parse_krc(krc);
+        lyrics.length_ms = Some(212_000);
+        let file = lyrics.to_lrc_file();
+        assert_eq!(
+            file,
+            "[length:03:32.00]\n[00:01.00]<00:01.00>Never <00:01.50>gonna<00:02.20> give<00:03.00>"
+        );
+
+        let read = SongLyrics::from_lrc_file(&file, 0);
+        assert_eq!(read.length_ms, Some(212_000));
+        assert_eq!(read.synced_lyrics[0].text, "Never gonna give");
+        assert_eq!(read.synced_lyrics[0].words.len(), 3);
+        assert_eq!(read.synced_lyrics[0].words[1].duration_ms, 700);
+
+        let plain = SongLyrics::from_plain("One\nTwo", 10_000);
+        let read = SongLyrics::from_lrc_file(&plain.to_lrc_file(), 10_000);
+        assert!(read.unsynced);
+        assert_eq!(read.synced_lyrics[1].time_ms, 5_000);
+    }
+
+    #[test]
+    fn words_fill_up_while_sung() {
+        let lyrics = parse_krc("[1000,2000]<0,500,0>Never <500,700,0>gonna<1200,800,0> give");
+        let line = &lyrics.synced_lyrics[0];
+        assert_eq!(sung_fraction(line, 900), Some(0.0));
+        assert_eq!(sung_fraction(line, 1250), Some(2.5 / 14.0));
+        assert_eq!(sung_fraction(line, 2200), Some(10.0 / 14.0));
+        assert_eq!(sung_fraction(line, 5000), Some(1.0));
+
+        let plain = SongLyrics::from_plain("One", 10_000);
+        assert_eq!(sung_fraction(&plain.synced_lyrics[0], 500), None);
+    }
+
+    #[test]
+    fn estimate_plain_timings() {
+        let lyrics = SongLyrics::from_plain("Hi\nA much longer line than that\n\nBye", 65_000);
+        let times: Vec<usize> = lyrics
+            .synced_lyrics
+            .iter()
+            .map(|line| line.time_ms)
+            .collect();
+        assert_eq!(times, [0, 10_000, 46_000, 54_000]);
+    }
+
+    #[test]
+    fn parse_time_tags() {
+        assert_eq!(parse_time_tag_to_ms("01:02.34"), Some(62_340));
+        assert_eq!(parse_time_tag_to_ms("01:02:34"), Some(62_340));
+        assert_eq!(parse_time_tag_to_ms("01:02.345"), Some(62_345));
+        assert_eq!(parse_time_tag_to_ms("01:02.3"), Some(62_300));
+        assert_eq!(parse_time_tag_to_ms("01:02"), Some(62_000));
+        assert_eq!(parse_time_tag_to_ms("1:05:02.05"), Some(3_902_050));
+        assert_eq!(parse_time_tag_to_ms("ar:Someone"), None);
+    }
+
+    #[test]
+    fn parse_duet_parts() {
+        let lyrics = parse_lrc(
+            "[00:01.00]M: One\n[00:02.00]Two\n[00:03.00]F:Three\n[00:04.00][v2]Four",
+            false,
+        )
+        .lyrics;
+        let parts: Vec<(Option<LinePart>, &str)> = lyrics
+            .synced_lyrics
+            .iter()
+            .map(|line| (line.part, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (Some(LinePart::Male), "One"),
+                (Some(LinePart::Male), "Two"),
+                (Some(LinePart::Female), "Three"),
+                (Some(LinePart::Voice(2)), "Four")
+            ]
+        );
+        let read = parse_lrc(&lyrics.to_lrc_file(), false).lyrics;
+        assert_eq!(read.synced_lyrics[1].part, Some(LinePart::Male));
+        assert_eq!(read.synced_lyrics[3].part, Some(LinePart::Voice(2)));
+    }
+
+    #[test]
+    fn unassigned_lines_keep_no_part() {
+        let mut lyrics = parse_lrc("[00:01.00]One\n[00:02.00]Two\n[00:03.00]Three", false).lyrics;
+        lyrics.synced_lyrics[0].part = Some(LinePart::Voice(1));
+        lyrics.synced_lyrics[2].part = Some(LinePart::Voice(2));
+
+        let read = parse_lrc(&lyrics.to_lrc_file(), false).lyrics;
+        let parts: Vec<(Option<LinePart>, &str)> = read
+            .synced_lyrics
+            .iter()
+            .map(|line| (line.part, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (Some(LinePart::Voice(1)), "One"),
+                (None, "Two"),
+                (Some(LinePart::Voice(2)), "Three")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_subtitle_cues() {
+        let srt = "1\r\n00:00:01,500 --> 00:00:03,000\r\n<i>Hello</i>\r\nthere\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,250\r\n{\\an8}Again\r\n";
+        let vtt = "WEBVTT\n\nNOTE ripped\n\nintro\n00:01.500 --> 00:03.000 align:center\nHello\nthere\n\n00:03.000 --> 00:04.250\nAgain\n";
+        for subtitles in [srt, vtt] {
+            let lines: Vec<(usize, String)> = parse_subtitles(subtitles)
+                .synced_lyrics
+                .into_iter()
+                .map(|line| (line.time_ms, line.text))
+                .collect();
+            assert_eq!(
+                lines,
+                [
+                    (1_500, "Hello there".to_owned()),
+                    (3_000, "Again".to_owned()),
+                    (4_250, String::new())
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn parse_ttml_lyrics() {
+        let ttml = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata">
+  <body dur="3:32.000">
+    <div>
+      <p begin="1.000" end="3.000" ttm:agent="v1"><span begin="1.000" end="1.500">Nev</span><span begin="1.500" end="2.000">er</span> <span begin="2.000" end="2.800">gonna</span><span ttm:role="x-bg"><span begin="2.5" end="2.9">(ooh)</span></span></p>
+      <p begin="00:00:04" end="00:00:06" ttm:agent="v1000">
+        Give you up
+      </p>
+    </div>
+  </body>
+</tt>"#;
+        let lyrics = parse_ttml(ttml);
+        assert_eq!(lyrics.length_ms, Some(212_000));
+        assert_eq!(lyrics.synced_lyrics.len(), 2);
+
+        let first = &lyrics.synced_lyrics[0];
+        assert_eq!(first.text, "Never gonna");
+        assert_eq!(first.part, Some(LinePart::Voice(1)));
+        assert_eq!(first.words.len(), 3);
+        assert_eq!(first.words[1].text, "er ");
+        assert_eq!(first.words[2].duration_ms, 800);
+
+        let second = &lyrics.synced_lyrics[1];
+        assert_eq!(second.time_ms, 4_000);
+        assert_eq!(second.text, "Give you up");
+        assert_eq!(second.part, Some(LinePart::Duet));
+    }
+
+    #[test]
+    fn splits_adlibs() {
+        assert_eq!(
+            split_adlibs("Never gonna (ooh (yeah)) give"),
+            [
+                ("Never gonna ", false),
+                ("(ooh (yeah))", true),
+                (" give", false)
+            ]
+        );
+        assert_eq!(
+            strip_adlibs("Never gonna (ooh) give (you"),
+            "Never gonna give"
+        );
+        assert_eq!(strip_adlibs("[Chorus]"), "");
+        assert_eq!(strip_adlibs("No adlibs :)"), "No adlibs :)");
+    }
+
+    #[test]
+    fn parse_repeated_lines() {
+        let lyrics = parse_lrc(
+            "[00:05.00][00:30.00]Chorus\n[00:10.00]Verse\n[00:40.00][Bridge] part",
+            false,
+        )
+        .lyrics;
+        let lines: Vec<(usize, &str)> = lyrics
+            .synced_lyrics
+            .iter()
+            .map(|line| (line.time_ms, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (5_000, "Chorus"),
+                (10_000, "Verse"),
+                (30_000, "Chorus"),
+                (40_000, "[Bridge] part")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_metadata_tags() {
+        let lyrics = parse_lrc(
+            "[ar:Someone]\n[ti: A Song ]\n[length: 03:45]\n[offset:+500]\n[00:01.00]<00:01.20>Hello",
+            false,
+        ).lyrics;
+        assert_eq!(lyrics.length_ms, Some(225_000));
+        assert_eq!(lyrics.metadata.title.as_deref(), Some("A Song"));
+        assert_eq!(lyrics.metadata.artist.as_deref(), Some("Someone"));
+        assert_eq!(lyrics.metadata.album, None);
+        assert_eq!(lyrics.synced_lyrics.len(), 1);
+        assert_eq!(lyrics.synced_lyrics[0].time_ms, 500);
+        assert_eq!(lyrics.synced_lyrics[0].words[0].time_ms, 700);
+
+        let late = parse_lrc("[offset:-250]\n[00:01.00]Hello", false).lyrics;
+        assert_eq!(late.synced_lyrics[0].time_ms, 1250);
+    }
+
+    #[test]
+    fn find_line_progress() {
+        let lyrics = parse_lrc(
+            "[00:01.00]One\n[00:03.00]Two\n[00:03.00]Too\n[00:04.00]Three",
+            false,
+        )
+        .lyrics;
+        assert_eq!(lyrics.find_current_index(2000), LyricPosition::Line(0, 0.5));
+        assert_eq!(lyrics.find_current_index(3000), LyricPosition::Line(2, 0.0));
+        assert_eq!(lyrics.find_current_index(4000), LyricPosition::AfterEnd(4));
+    }
+
+    #[test]
+    fn timeline_windows() {
+        let lyrics = parse_lrc("[00:01.00]One\n[00:03.00]Two", false).lyrics;
+        assert_eq!(lyrics.next_line_after(0).map(|(i, _)| i), Some(0));
+        assert_eq!(lyrics.next_line_after(1000).map(|(i, _)| i), Some(1));
+        assert_eq!(lyrics.time_until_next(2500), Some(500));
+        assert_eq!(lyrics.time_until_next(3000), None);
+        let windows: Vec<_> = lyrics
+            .timeline(5000)
+            .map(|(start, end, line)| (start, end, line.text.as_str()))
+            .collect();
+        assert_eq!(windows, [(1000, 3000, "One"), (3000, 5000, "Two")]);
+    }
+
+    #[test]
+    fn marks_instrumental_breaks() {
+        let mut lyrics = parse_lrc(
+            "[00:01.00]Short\n[00:20.00]\n[01:00.00]Verse\n[01:05.00]Chorus\n[01:30.00]End",
+            false,
+        )
+        .lyrics;
+        lyrics.mark_instrumental_breaks(10_000);
+        let breaks: Vec<_> = lyrics
+            .synced_lyrics
+            .iter()
+            .map(|line| (line.time_ms, line.text.as_str(), line.instrumental))
+            .collect();
+        assert_eq!(
+            breaks,
+            [
+                (1000, "Short", false),
+                (20_000, "", true),
+                (60_000, "Verse", false),
+                (65_000, "Chorus", false),
+                (66_600, "", true),
+                (90_000, "End", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn reorders_rtl_text() {
+        assert!(is_rtl("שלום world"));
+        assert!(!is_rtl("hello עולם"));
+        assert_eq!(visual_order("hello world", false), "hello world");
+        assert_eq!(visual_order("שלום (עולם)", true), "(םלוע) םולש");
+        assert_eq!(visual_order("אבג abc!", true), "!abc גבא");
+    }
+
+    #[test]
+    fn splits_long_lines() {
+        let mut lyrics = parse_lrc(
+            "[00:10.00]Never gonna give you up, never gonna let you down\n\
+             [00:20.00]<00:20.00>Short <00:21.00>words, <00:22.00>timed <00:23.00>too<00:24.00>\n\
+             [00:30.00]No punctuation in this rather long line at all",
+            false,
+        )
+        .lyrics;
+        lyrics.split_long_lines(20);
+        let lines: Vec<_> = lyrics
+            .synced_lyrics
+            .iter()
+            .map(|line| (line.time_ms, line.text.as_str(), line.words.len()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (10_000, "Never gonna give you up,", 0),
+                (15_102, "never gonna let you down", 0),
+                (20_000, "Short words,", 2),
+                (22_000, "timed too", 2),
+                (30_000, "No punctuation in this rather long line at all", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_skipped_lines() {
+        let parsed = parse_lrc(
+            "[ar:Someone]\n[00:01.00]Fine\n[0:x1.00]Broken\nNo time\n[offset:soon]\n[00:02.00]<00:0x>Word",
+            false,
+        );
+        assert_eq!(parsed.lyrics.synced_lyrics.len(), 2);
+        let warnings: Vec<_> = parsed
+            .warnings
+            .iter()
+            .map(|w| (w.line, w.reason.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (3, "malformed timestamp [0:x1.00]"),
+                (4, "text without a timestamp"),
+                (5, "malformed offset [offset:soon]"),
+                (6, "malformed word timings, kept as text"),
+            ]
+        );
+    }
+
+    #[test]
+    fn cleans_up_text() {
+        let lyrics = parse_lrc(
+            "\u{feff}[00:01.00]Cafe\u{301}  \u{200b}au\tlait \r\n[00:02.00]Second\r[00:03.00]Third",
+            false,
+        )
+        .lyrics;
+        let texts: Vec<_> = lyrics
+            .synced_lyrics
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(texts, ["Caf\u{e9} au lait", "Second", "Third"]);
+    }
+
+    #[test]
+    fn parse_rick() {
+        let rick: String = "[00:18.92] We're no strangers to love
+[00:22.59] You know the rules and so do I (do I)
+[00:26.93] A full commitment's what I'm thinking of
+[00:31.35] You wouldn't get this from any other guy
+[00:35.14] I just wanna tell you how I'm feeling
+[00:40.28] Gotta make you understand
+[00:42.83] Never gonna give you up
+[00:45.22] Never gonna let you down
+[00:47.14] Never gonna run around and desert you
+[00:51.40] Never gonna make you cry
+[00:53.88] Never gonna say goodbye
+[00:55.67] Never gonna tell a lie and hurt you
+[01:00.52] We've known each other for so long
+[01:05.04] Your heart's been aching, but you're too shy to say it (say it)
+[01:09.42] Inside, we both know what's been going on (going on)
+[01:13.11] We know the game and we're gonna play it
+[01:17.29] And if you ask me how I'm feeling
+[01:22.51] Don't tell me you're too blind to see
+[01:25.33] Never gonna give you up
+[01:27.47] Never gonna let you down
+[01:29.65] Never gonna run around and desert you
+[01:33.42] Never gonna make you cry
+[01:35.82] Never gonna say goodbye
+[01:37.78] Never gonna tell a lie and hurt you
+[01:41.99] Never gonna give you up
+[01:44.10] Never gonna let you down
+[01:46.43] Never gonna run around and desert you
+[01:50.26] Never gonna make you cry
+[01:52.56] Never gonna say goodbye
+[01:54.79] Never gonna tell a lie and hurt you
+[01:59.22] (Ooh, give you up)
+[02:02.98] (Ooh, give you up)
+[02:07.08] (Ooh) Never gonna give, never gonna give (give you up)
+[02:11.26] (Ooh) Never gonna give, never gonna give (give you up)
+[02:16.13] We've known each other for so long
+[02:20.53] Your heart's been aching, but you're too shy to say it (to say it)
+[02:24.65] Inside, we both know what's been going on (going on)
+[02:28.87] We know the game and we're gonna play it
+[02:32.55] I just wanna tell you how I'm feeling
+[02:37.79] Gotta make you understand
+[02:40.88] Never gonna give you up
+[02:42.94] Never gonna let you down
+[02:45.16] Never gonna run around and desert you
+[02:49.00] Never gonna make you cry
+[02:51.17] Never gonna say goodbye
+[02:53.78] Never gonna tell a lie and hurt you
+[02:57.61] Never gonna give you up
+[02:59.47] Never gonna let you down
+[03:02.00] Never gonna run around and desert you
+[03:05.95] Never gonna make you cry
+[03:08.34] Never gonna say goodbye
+[03:10.45] Never gonna tell a lie and hurt you
+[03:14.37] Never gonna give you up
+[03:16.37] Never gonna let you down
+[03:18.84] Never gonna run around and desert you
+[03:23.07] Never gonna make you cry
+[03:25.17] Never gonna say goodbye
+[03:27.38] Never gonna tell a lie and hurt you
+[03:30.57]"
+            .into();
+
+        let rick_parsed = parse_lrc(&rick, false).lyrics;
+        assert_eq!(rick_parsed.synced_lyrics.len(), 59);
+
+        let rick_parsed_strip = parse_lrc(&rick, true).lyrics;
+        assert_eq!(rick_parsed_strip.synced_lyrics.len(), 58);
+
+        assert!(matches!(
+            rick_parsed.find_current_index(19111),
+            LyricPosition::Line(0, progress) if progress > 0.0 && progress < 1.0
+        ));
+
+        assert_eq!(
+            rick_parsed.find_current_index(1),
+            LyricPosition::BeforeStart
+        );
+
+        assert_eq!(
+            rick_parsed.find_current_index(1_111_111_111),
+            LyricPosition::AfterEnd(rick_parsed.synced_lyrics.len())
+        );
+    }
+
+    #[test]
+    fn to_lrc_roundtrip() {
+        let lrc = "[00:18.92] We're no strangers to love
+[00:22.59] You know the rules and so do I (do I)
+[01:00.52] We've known each other for so long
+[03:30.57]";
+        let parsed = parse_lrc(lrc, false).lyrics;
+        let roundtrip = parse_lrc(&parsed.to_lrc(), false).lyrics;
+        assert_eq!(roundtrip.synced_lyrics.len(), 4);
+        assert_eq!(roundtrip.synced_lyrics[1].time_ms, 22590);
+        assert_eq!(roundtrip.synced_lyrics[2].time_ms, 60520);
+        assert_eq!(roundtrip.to_lrc(), parsed.to_lrc());
+    }
+
+    #[test]
+    fn parse_krc_words() {
+        let krc = "[ar:Rick Astley]
+[ti:Never Gonna Give You Up]
+[18920,3670]<0,400,0>We're <400,300,0>no <700,900,0>strangers
+[22590,4340]<0,350,0>You <350,300,0>know";
+
+        let parsed = parse_krc(krc);
+        assert_eq!(parsed.synced_lyrics.len(), 2);
+
+        let first = &parsed.synced_lyrics[0];
+        assert_eq!(first.time_ms, 18920);
+        assert_eq!(first.text, "We're no strangers");
+        assert_eq!(first.words.len(), 3);
+        assert_eq!(first.words[2].time_ms, 19620);
+        assert_eq!(first.words[2].duration_ms, 900);
+    }
+
+    #[test]
+    fn krc_lines_out_of_order() {
+        let krc = "[22590,4340]<0,350,0>You <350,300,0>know
+[9000,0]
+[18920,3670]<0,400,0>We're <400,300,0>no <700,900,0>strangers";
+
+        let mut parsed = parse_krc(krc);
+        parsed.mark_instrumental_breaks(5000);
+        let times: Vec<usize> = parsed.synced_lyrics.iter().map(|l| l.time_ms).collect();
+        assert_eq!(times, [9000, 18920, 22590]);
+        assert!(parsed.synced_lyrics[0].instrumental);
+    }
+}
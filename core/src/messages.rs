@@ -0,0 +1,107 @@
+//! What the runtime and a UI send each other
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::album_art::Palette;
+use crate::lyrics_fetch::CacheListing;
+use crate::lyrics_fetch::LyricsRequestInfo;
+use crate::lyrics_fetch::LyricsSearchQuery;
+use crate::lyrics_fetch::LyricsSearchResult;
+use crate::lyrics_fetch::LyricsSource;
+use crate::lyrics_fetch::LyricsTranslation;
+use crate::lyrics_fetch::PublishRequest;
+use crate::lyrics_fetch::SongWithLyrics;
+use crate::player::{NowPlaying, PlaybackControl};
+use crate::spotify::{AudioAnalysis, AudioFeatures, UpcomingTrack};
+
+#[derive(Debug)]
+pub enum MessageToUI {
+    AuthenticationStateUpdate(bool),
+    AwaitingAuthCallback(String),
+    AuthenticationFailed(String),
+    RateLimitsExceeded,
+    CurrentlyPlaying(NowPlaying),
+    NotCurrentlyPlaying(String),
+    DisplayError(String),
+    GotLyrics(SongWithLyrics),
+    /// lrclib accepted the lyrics
+    LyricsPublished,
+    /// lrclib entries for the manual search dialog
+    LyricsSearchResults(Vec<LyricsSearchResult>),
+    /// Translation of the shown lyrics
+    LyricsTranslated(LyricsTranslation),
+    /// Cached tracks, for the cache tab in the settings
+    CacheListing(CacheListing),
+    /// The cache was exported or imported, with what happened
+    CacheArchived(String),
+    /// Playlist prefetch progress, `done == total` once finished
+    PrefetchProgress {
+        done: usize,
+        total: usize,
+        found: usize,
+    },
+    UpcomingTrack(Option<UpcomingTrack>),
+    AudioFeatures(AudioFeatures),
+    AudioAnalysis(Arc<AudioAnalysis>),
+    /// Colors of the album art at this url
+    AlbumPalette(String, Palette),
+    Status(StatusEvent),
+}
+
+/// Structured session updates shown in the status panel
+#[derive(Debug, Clone, Copy)]
+pub enum StatusEvent {
+    /// The access token expires at this unix time, `None` if we have no token
+    TokenExpiry(Option<u64>),
+    /// A spotify API call succeeded
+    ApiCallSucceeded,
+    /// A lyrics fetch finished
+    LyricsFetched { found: bool },
+}
+
+#[derive(Debug)]
+pub enum MessageToRT {
+    Authenticate,
+    AddAccount,
+    SwitchAccount(usize),
+    RemoveAccount(usize),
+    CancelAuth,
+    GetCurrentTrack,
+    PlayerControl(PlaybackControl),
+    /// Log in to the service of the selected player source
+    PlayerLogin,
+    GetQueue,
+    GetAudioFeatures(String),
+    GetAudioAnalysis(String),
+    /// Extract the colors of the album art at this url
+    GetAlbumPalette(String),
+    GetLyrics(LyricsRequestInfo),
+    /// Look up lyrics again, ignoring what's cached
+    RefetchLyrics(LyricsRequestInfo),
+    /// Upload the shown lyrics to lrclib
+    PublishLyrics(PublishRequest),
+    /// Search lrclib with a query edited by the user
+    SearchLyrics(LyricsSearchQuery),
+    /// Use and cache the picked lrclib entry for the track
+    ChooseLyrics(LyricsRequestInfo, usize),
+    /// The shown lyrics are for another song, skip them and look up the next best
+    RejectLyrics(LyricsRequestInfo, LyricsSource),
+    /// Always use these lyrics for the track, `None` unpins
+    PinLyrics(LyricsRequestInfo, Option<LyricsSource>),
+    /// Cache the lyrics of every track in a spotify playlist, by url or id
+    PrefetchPlaylist(String),
+    /// Translate the shown lyrics, one line each
+    TranslateLyrics(LyricsRequestInfo, Vec<String>),
+    /// List the cached tracks
+    ListCache,
+    /// Remove these track folders from the cache, every track if `None`
+    ClearCache(Option<Vec<String>>),
+    /// Write the cache to a `.tar.gz` archive
+    ExportCache(PathBuf),
+    /// Add the tracks of a cache archive
+    ImportCache(PathBuf),
+    InvalidateToken,
+    /// The system woke up from sleep, refresh what went stale
+    Resync,
+}
@@ -0,0 +1,154 @@
+//! OAuth login flow shared by the services we authenticate with, serves the redirect and captures its code
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::Notify;
+use tracing::{debug, trace};
+use warp::Filter;
+
+#[cfg(feature = "webview-auth")]
+mod webview;
+
+/// Code and state from the redirect
+pub type Callback = (Option<String>, Option<String>);
+
+#[derive(Error, Debug)]
+/// Error enum for waiting on the OAuth redirect
+pub enum CallbackError {
+    #[error("Callback port {0} is in use, pick another port or allow fallback ports")]
+    PortUnavailable(u16),
+    #[cfg(feature = "webview-auth")]
+    #[error("Could not open the login window: {0}")]
+    Webview(String),
+    #[error("Authentication cancelled")]
+    Cancelled,
+    #[error("Timed out waiting for the login")]
+    CallbackTimeout,
+    #[error("IO error")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Bind the callback server to a free address. Port 0 lets the OS pick any free port,
+/// otherwise the configured port and up to `fallback_range` ports after it are tried.
+/// The listener is kept and served by `wait_for_login`, so no other program can take the port
+pub fn bind_callback(
+    ip: IpAddr,
+    port: u16,
+    fallback_range: u16,
+) -> Result<(TcpListener, SocketAddr), CallbackError> {
+    for candidate in port..=port.saturating_add(fallback_range) {
+        match TcpListener::bind(SocketAddr::new(ip, candidate)) {
+            Ok(listener) => {
+                let addr = listener.local_addr()?;
+                return Ok((listener, addr));
+            }
+            Err(err) => debug!("Callback port {candidate} unavailable: {err}"),
+        }
+    }
+    Err(CallbackError::PortUnavailable(port))
+}
+
+/// Let the user log in to `service` and return the code and state it redirected with
+pub async fn wait_for_login(
+    service: &str,
+    auth_url: &str,
+    listener: TcpListener,
+    redirect_uri: String,
+    embedded_auth: bool,
+    timeout: Duration,
+    cancel: Arc<Notify>,
+) -> Result<Callback, CallbackError> {
+    if embedded_auth {
+        #[cfg(feature = "webview-auth")]
+        return webview::wait_for_callback(
+            service.to_owned(),
+            auth_url.to_owned(),
+            redirect_uri,
+            timeout,
+            cancel,
+        )
+        .await;
+        #[cfg(not(feature = "webview-auth"))]
+        tracing::warn!(
+            "Built without the webview-auth feature, logging in with the browser instead"
+        );
+    }
+
+    debug!("Opening browser to log in to {service}");
+    webbrowser::open(auth_url)?;
+    trace!("Waiting for a redirect to {redirect_uri}");
+
+    // Spawn the warp server on a blocking thread with its own single-threaded runtime
+    tokio::task::spawn_blocking(move || wait_for_callback(listener, timeout, cancel))
+        .await
+        .unwrap()
+}
+
+/// Serve the OAuth callback until the service redirects to it, the timeout passes or the flow is cancelled
+fn wait_for_callback(
+    listener: TcpListener,
+    timeout: Duration,
+    cancel: Arc<Notify>,
+) -> Result<Callback, CallbackError> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async move {
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        let (tx_content, rx_content) = oneshot::channel::<(Option<String>, Option<String>)>();
+        let tx_content_mutex = Arc::new(Mutex::new(Some(tx_content)));
+        let (tx_shutdown, rx_shutdown) = oneshot::channel();
+        let tx_shutdown_mutex = Arc::new(Mutex::new(Some(tx_shutdown)));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let callback_route = warp::path("callback")
+            .and(warp::query::<std::collections::HashMap<String, String>>())
+            .map(move |params: std::collections::HashMap<String, String>| {
+                let code = params.get("code").cloned();
+                let state = params.get("state").cloned();
+                if let Some(tx_inner) = tx_content_mutex.lock().unwrap().take() {
+                    trace!("Sending code and state");
+                    tx_inner.send((code, state)).unwrap();
+                }
+                if let Some(tx_shutdown_inner) = tx_shutdown_mutex.lock().unwrap().take() {
+                    trace!("Sending shutdown!");
+                    tx_shutdown_inner.send(()).unwrap();
+                }
+                warp::reply::html(
+                    "<html><body><h1>Authentication successful!</h1><p>You can close this window.</p></body></html>".to_string()
+                )
+            });
+
+        let shutdown = {
+            let cancelled = cancelled.clone();
+            async move {
+                tokio::select! {
+                    _ = rx_shutdown => trace!("Server shutdown received"),
+                    () = tokio::time::sleep(timeout) => debug!("Timed out waiting for the auth callback"),
+                    () = cancel.notified() => {
+                        debug!("Authentication cancelled");
+                        cancelled.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        };
+
+        warp::serve(callback_route)
+            .incoming(listener)
+            .graceful(shutdown)
+            .run()
+            .await;
+
+        // The sender is dropped with the server if the callback was never called
+        match rx_content.await {
+            Ok(content) => Ok(content),
+            Err(_) if cancelled.load(Ordering::Relaxed) => Err(CallbackError::Cancelled),
+            Err(_) => Err(CallbackError::CallbackTimeout),
+        }
+    })
+}
@@ -0,0 +1,130 @@
+//! Embedded login window, captures the OAuth redirect itself instead of running the warp callback server
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tao::event::{Event, WindowEvent};
+use tao::event_loop::{ControlFlow, EventLoopBuilder};
+use tao::platform::run_return::EventLoopExtRunReturn;
+use tao::window::WindowBuilder;
+use tokio::sync::Notify;
+use tracing::{debug, trace};
+use url::Url;
+use wry::WebViewBuilder;
+
+use super::{Callback, CallbackError};
+
+/// How often the event loop checks for cancellation and the timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Show the login page in a window until it redirects to `redirect_uri`,
+/// the timeout passes, the window is closed or the flow is cancelled
+pub async fn wait_for_callback(
+    service: String,
+    auth_url: String,
+    redirect_uri: String,
+    timeout: Duration,
+    cancel: Arc<Notify>,
+) -> Result<Callback, CallbackError> {
+    // The event loop can't await the notify, so forward it to a flag it can poll
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let watcher = tokio::spawn({
+        let cancelled = cancelled.clone();
+        async move {
+            cancel.notified().await;
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    });
+
+    let res = tokio::task::spawn_blocking(move || {
+        run_login_window(&service, &auth_url, &redirect_uri, timeout, &cancelled)
+    })
+    .await
+    .unwrap();
+
+    watcher.abort();
+    res
+}
+
+fn run_login_window(
+    service: &str,
+    auth_url: &str,
+    redirect_uri: &str,
+    timeout: Duration,
+    cancelled: &AtomicBool,
+) -> Result<Callback, CallbackError> {
+    let mut builder = EventLoopBuilder::new();
+    // We're on a tokio blocking thread, eframe owns the main thread
+    #[cfg(target_os = "linux")]
+    tao::platform::unix::EventLoopBuilderExtUnix::with_any_thread(&mut builder, true);
+    #[cfg(target_os = "windows")]
+    tao::platform::windows::EventLoopBuilderExtWindows::with_any_thread(&mut builder, true);
+    let mut event_loop = builder.build();
+
+    let window = WindowBuilder::new()
+        .with_title(format!("Log in to {service}"))
+        .build(&event_loop)
+        .map_err(|e| CallbackError::Webview(e.to_string()))?;
+
+    let callback: Arc<Mutex<Option<Callback>>> = Arc::new(Mutex::new(None));
+    let webview_builder = WebViewBuilder::new()
+        .with_url(auth_url)
+        .with_navigation_handler({
+            let callback = callback.clone();
+            let redirect_uri = redirect_uri.to_owned();
+            move |url| {
+                if !url.starts_with(&redirect_uri) {
+                    return true;
+                }
+                trace!("Captured redirect");
+                // Nothing listens on the redirect uri, so stop the navigation here
+                if let Ok(url) = Url::parse(&url) {
+                    let param = |key: &str| {
+                        url.query_pairs()
+                            .find(|(k, _)| k == key)
+                            .map(|(_, v)| v.into_owned())
+                    };
+                    *callback.lock().unwrap() = Some((param("code"), param("state")));
+                }
+                false
+            }
+        });
+
+    #[cfg(not(target_os = "linux"))]
+    let webview = webview_builder.build(&window);
+    #[cfg(target_os = "linux")]
+    let webview = {
+        use tao::platform::unix::WindowExtUnix;
+        use wry::WebViewBuilderExtUnix;
+        webview_builder.build_gtk(window.default_vbox().unwrap())
+    };
+    let _webview = webview.map_err(|e| CallbackError::Webview(e.to_string()))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut closed = false;
+    event_loop.run_return(|event, _, control_flow| {
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + POLL_INTERVAL);
+
+        if let Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } = event
+        {
+            debug!("Login window closed");
+            closed = true;
+        }
+        if closed
+            || cancelled.load(Ordering::Relaxed)
+            || callback.lock().unwrap().is_some()
+            || Instant::now() >= deadline
+        {
+            *control_flow = ControlFlow::Exit;
+        }
+    });
+
+    match callback.lock().unwrap().take() {
+        Some(content) => Ok(content),
+        None if closed || cancelled.load(Ordering::Relaxed) => Err(CallbackError::Cancelled),
+        None => Err(CallbackError::CallbackTimeout),
+    }
+}
@@ -0,0 +1,52 @@
+//! Where the config, cache and logs live, the platform's folders unless told otherwise
+
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+
+/// Env var pointing at a config file to use instead of the platform's
+const CONFIG_ENV: &str = "APP_CONFIG";
+/// Config next to the working directory, as older versions kept it, wins over the platform's
+const LEGACY_CONFIG: &str = "config.toml";
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("com", "ATantalizedLion", "LyricsOverlay")
+}
+
+/// Config file, from `APP_CONFIG`, the working directory if one is there, else the platform's
+/// config folder
+pub fn config_file() -> PathBuf {
+    if let Some(path) = std::env::var_os(CONFIG_ENV) {
+        return path.into();
+    }
+    if Path::new(LEGACY_CONFIG).exists() {
+        return LEGACY_CONFIG.into();
+    }
+    project_dirs().map_or_else(
+        || LEGACY_CONFIG.into(),
+        |dirs| dirs.config_dir().join(LEGACY_CONFIG),
+    )
+}
+
+/// Folder relative paths in the config are resolved against
+pub fn config_dir() -> PathBuf {
+    config_file()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// Folder of the `.toml` themes to pick from, next to the config
+pub fn theme_folder() -> PathBuf {
+    config_dir().join("themes")
+}
+
+/// Default folder for cached lyrics
+pub fn default_cache_folder() -> PathBuf {
+    project_dirs().map_or_else(|| "cache".into(), |dirs| dirs.cache_dir().join("lyrics"))
+}
+
+/// Folder the logs are written to
+pub fn log_folder() -> PathBuf {
+    project_dirs().map_or_else(|| "logs".into(), |dirs| dirs.data_local_dir().join("logs"))
+}
@@ -0,0 +1,129 @@
+//! Local estimate of the playback position between spotify polls
+use std::time::Instant;
+
+/// Differences larger than this are seeks or skips and jump straight to the new position
+const SNAP_THRESHOLD_MS: f64 = 1000.0;
+/// Time constant of the drift correction. Must be at least `SNAP_THRESHOLD_MS`, otherwise
+/// correcting a position that ran ahead would move it backwards
+const CORRECTION_TAU_MS: f64 = 1000.0;
+const _: () = assert!(CORRECTION_TAU_MS >= SNAP_THRESHOLD_MS);
+
+/// Playback position driven by the monotonic clock. Samples from the API correct drift
+/// gradually instead of making the lyrics snap every time a poll lands.
+#[derive(Debug, Clone)]
+pub struct PlaybackClock {
+    /// Position according to the latest sample at `anchor_at`
+    anchor_ms: f64,
+    anchor_at: Instant,
+    playing: bool,
+    /// Difference between what we displayed and the latest sample, decays towards 0
+    offset_ms: f64,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self {
+            anchor_ms: 0.0,
+            anchor_at: Instant::now(),
+            playing: false,
+            offset_ms: 0.0,
+        }
+    }
+}
+
+impl PlaybackClock {
+    /// Feed a progress sample, blending it in if it's close to what we expected
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sync(&mut self, progress_ms: usize, sampled_at: Instant, playing: bool) {
+        let now = Instant::now();
+        let displayed_ms = self.position_at(now);
+        let target_ms = progress_ms as f64 + Self::elapsed_ms(sampled_at, now, playing);
+
+        let offset_ms = displayed_ms - target_ms;
+        // Only smooth while playing continuously, pausing and resuming should be immediate
+        let smooth = playing && self.playing && offset_ms.abs() < SNAP_THRESHOLD_MS;
+
+        self.anchor_ms = target_ms;
+        self.anchor_at = now;
+        self.playing = playing;
+        self.offset_ms = if smooth { offset_ms } else { 0.0 };
+    }
+
+    /// Jump to a position without smoothing, for track changes
+    #[allow(clippy::cast_precision_loss)]
+    pub fn reset(&mut self, progress_ms: usize, sampled_at: Instant, playing: bool) {
+        let now = Instant::now();
+        self.anchor_ms = progress_ms as f64 + Self::elapsed_ms(sampled_at, now, playing);
+        self.anchor_at = now;
+        self.playing = playing;
+        self.offset_ms = 0.0;
+    }
+
+    /// Stop advancing, keeping the position we currently display
+    pub fn pause(&mut self) {
+        let now = Instant::now();
+        self.anchor_ms = self.position_at(now);
+        self.anchor_at = now;
+        self.playing = false;
+        self.offset_ms = 0.0;
+    }
+
+    /// Current playback position in ms
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn position_ms(&self) -> u128 {
+        self.position_at(Instant::now()).max(0.0) as u128
+    }
+
+    fn position_at(&self, now: Instant) -> f64 {
+        let since_anchor = Self::elapsed_ms(self.anchor_at, now, true);
+        let advanced = if self.playing { since_anchor } else { 0.0 };
+        let correction = self.offset_ms * (-since_anchor / CORRECTION_TAU_MS).exp();
+        self.anchor_ms + advanced + correction
+    }
+
+    fn elapsed_ms(since: Instant, now: Instant, playing: bool) -> f64 {
+        if playing {
+            now.saturating_duration_since(since).as_secs_f64() * 1000.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn small_drift_is_blended_in() {
+        let mut clock = PlaybackClock::default();
+        let start = Instant::now();
+        clock.reset(10_000, start, true);
+
+        // Spotify says we're 300 ms further than we thought
+        clock.sync(10_300, Instant::now(), true);
+        let now = clock.anchor_at;
+        let shown = clock.position_at(now);
+        assert!(shown < clock.anchor_ms - 200.0);
+
+        // After a few time constants the correction is gone
+        let later = now + Duration::from_secs(5);
+        let caught_up = clock.position_at(later) - (clock.anchor_ms + 5000.0);
+        assert!(caught_up.abs() < 5.0);
+    }
+
+    #[test]
+    fn seeks_and_pauses_jump() {
+        let mut clock = PlaybackClock::default();
+        clock.reset(10_000, Instant::now(), true);
+
+        clock.sync(60_000, Instant::now(), true);
+        assert!((clock.position_at(clock.anchor_at) - clock.anchor_ms).abs() < f64::EPSILON);
+
+        clock.sync(61_000, Instant::now(), false);
+        let frozen = clock.position_at(clock.anchor_at + Duration::from_secs(3));
+        assert!((frozen - 61_000.0).abs() < f64::EPSILON);
+    }
+}
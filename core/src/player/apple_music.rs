@@ -0,0 +1,92 @@
+//! Apple Music on macOS, asked through AppleScript
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+
+/// osascript takes a moment to start, but shouldn't take this long
+const CALL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// One field per line, checking first so we don't launch Music ourselves
+const NOW_PLAYING_SCRIPT: &str = r#"
+if application "Music" is not running then return "stopped"
+tell application "Music"
+    set state to player state as string
+    if state is "stopped" then return "stopped"
+    set t to current track
+    return state & linefeed & (persistent ID of t) & linefeed & (name of t) & linefeed & (artist of t) & linefeed & (album of t) & linefeed & (duration of t) & linefeed & player position
+end tell
+"#;
+
+pub struct AppleMusicSource;
+
+impl AppleMusicSource {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn query(&self) -> Result<NowPlaying, PlayerError> {
+        let sent_at = Instant::now();
+        let output = osascript(NOW_PLAYING_SCRIPT).await?;
+        let received_at = Instant::now();
+
+        let mut lines = output.lines();
+        let is_playing = match lines.next() {
+            Some("playing") => true,
+            Some("paused") => false,
+            _ => return Err(PlayerError::NothingPlaying),
+        };
+        let mut field = || lines.next().unwrap_or_default().to_owned();
+        let (track_id, title, artist, album) = (field(), field(), field(), field());
+        // Reals are formatted with the user's decimal separator
+        let mut seconds = || field().replace(',', ".").parse::<f64>().unwrap_or(0.0);
+        let (duration_sec, position_sec) = (seconds(), seconds());
+
+        Ok(NowPlaying {
+            track_id,
+            spotify_id: None,
+            file_path: None,
+            title,
+            artist,
+            album,
+            album_art_url: None,
+            duration_sec,
+            is_playing,
+            progress_ms: (position_sec * 1000.0).max(0.0) as usize,
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+}
+
+#[async_trait]
+impl PlayerSource for AppleMusicSource {
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        tokio::time::timeout(CALL_TIMEOUT, self.query())
+            .await
+            .map_err(|_| PlayerError::Timeout)?
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let script = match command {
+            PlaybackControl::PlayPause => r#"tell application "Music" to playpause"#,
+            PlaybackControl::Next => r#"tell application "Music" to next track"#,
+            PlaybackControl::Previous => r#"tell application "Music" to previous track"#,
+        };
+        osascript(script).await.map(|_| ())
+    }
+}
+
+async fn osascript(script: &str) -> Result<String, PlayerError> {
+    let output = Command::new("osascript")
+        .args(["-e", script])
+        .output()
+        .await
+        .map_err(|err| PlayerError::Other(format!("Failed to run osascript: {err}")))?;
+    if !output.status.success() {
+        return Err(PlayerError::Other(format!(
+            "Apple Music: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,225 @@
+//! Deezer player source. Deezer has no endpoint for the current playback, so we derive it
+//! from the listening history: the last track is playing if it hasn't had time to end yet.
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::{Notify, RwLock as TokioRwLock};
+use tracing::debug;
+use url::Url;
+
+use crate::http;
+use crate::oauth::{bind_callback, wait_for_login};
+use crate::player::{NowPlaying, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+const DEEZER_AUTH_URL: &str = "https://connect.deezer.com/oauth/auth.php";
+const DEEZER_TOKEN_URL: &str = "https://connect.deezer.com/oauth/access_token.php";
+const DEEZER_HISTORY_URL: &str = "https://api.deezer.com/user/me/history";
+/// `offline_access` gives us a token that doesn't expire
+const DEEZER_PERMISSIONS: &str = "basic_access,listening_history,offline_access";
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct HistoryResponse {
+    #[serde(default)]
+    data: Vec<HistoryTrack>,
+    /// Deezer reports errors with a 200 status
+    error: Option<DeezerError>,
+}
+
+#[derive(Deserialize)]
+struct DeezerError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct HistoryTrack {
+    id: u64,
+    title: String,
+    duration: u64,
+    /// When the track was started, unix time
+    timestamp: u64,
+    artist: DeezerName,
+    album: DeezerAlbum,
+}
+
+#[derive(Deserialize)]
+struct DeezerName {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct DeezerAlbum {
+    title: String,
+    cover_medium: Option<String>,
+}
+
+pub struct DeezerSource {
+    client: Client,
+    settings: Arc<TokioRwLock<Settings>>,
+}
+
+impl DeezerSource {
+    pub fn new(settings: Arc<TokioRwLock<Settings>>, settings_snapshot: &Settings) -> Self {
+        Self {
+            client: http::client_builder(settings_snapshot).build().unwrap(),
+            settings,
+        }
+    }
+}
+
+#[async_trait]
+impl PlayerSource for DeezerSource {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        let token = self.settings.read().await.deezer_token.clone();
+        if token.is_empty() {
+            return Err(PlayerError::Other(
+                "Log in to Deezer in the settings".to_owned(),
+            ));
+        }
+
+        let sent_at = Instant::now();
+        let history: HistoryResponse = self
+            .client
+            .get(with_params(
+                DEEZER_HISTORY_URL,
+                &[("access_token", &token), ("limit", "1")],
+            ))
+            .send()
+            .await
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(http_error)?;
+        let received_at = Instant::now();
+
+        if let Some(err) = history.error {
+            return Err(PlayerError::Other(format!("Deezer: {}", err.message)));
+        }
+        let track = history
+            .data
+            .into_iter()
+            .next()
+            .ok_or(PlayerError::NothingPlaying)?;
+        let elapsed_sec = get_unix_time().saturating_sub(track.timestamp);
+        // Pauses aren't in the history, a paused track looks like it ended
+        if elapsed_sec >= track.duration {
+            return Err(PlayerError::NothingPlaying);
+        }
+
+        Ok(NowPlaying {
+            track_id: track.id.to_string(),
+            spotify_id: None,
+            file_path: None,
+            title: track.title,
+            artist: track.artist.name,
+            album: track.album.title,
+            album_art_url: track.album.cover_medium,
+            duration_sec: track.duration as f64,
+            is_playing: true,
+            progress_ms: elapsed_sec as usize * 1000,
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+
+    async fn login(&self, cancel: Arc<Notify>) -> Result<(), PlayerError> {
+        let settings = self.settings.read().await.clone();
+        if settings.deezer_app_id.is_empty() || settings.deezer_secret.is_empty() {
+            return Err(PlayerError::Other(
+                "Add the Deezer app id and secret first".to_owned(),
+            ));
+        }
+        let ip: IpAddr = settings.host.parse().map_err(|_| {
+            PlayerError::Other(format!(
+                "Redirect host {:?} is not an IP address",
+                settings.host
+            ))
+        })?;
+        let (listener, addr) =
+            bind_callback(ip, settings.port, settings.port_fallback_range).map_err(login_error)?;
+        let redirect_uri = format!("http://{addr}/callback");
+
+        let auth_url = with_params(
+            DEEZER_AUTH_URL,
+            &[
+                ("app_id", &settings.deezer_app_id),
+                ("redirect_uri", &redirect_uri),
+                ("perms", DEEZER_PERMISSIONS),
+            ],
+        );
+
+        // Deezer doesn't return a state to check
+        let (code, _) = wait_for_login(
+            "Deezer",
+            auth_url.as_str(),
+            listener,
+            redirect_uri,
+            settings.embedded_auth,
+            Duration::from_secs(settings.auth_timeout_sec),
+            cancel,
+        )
+        .await
+        .map_err(login_error)?;
+        let code = code.ok_or_else(|| PlayerError::Other("Deezer login was refused".to_owned()))?;
+
+        let token: TokenResponse = self
+            .client
+            .get(with_params(
+                DEEZER_TOKEN_URL,
+                &[
+                    ("app_id", &settings.deezer_app_id),
+                    ("secret", &settings.deezer_secret),
+                    ("code", &code),
+                    ("output", "json"),
+                ],
+            ))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(|_| PlayerError::Other("Deezer did not return a token".to_owned()))?;
+
+        let mut settings = self.settings.write().await;
+        settings.deezer_token = token.access_token;
+        settings.save().map_err(PlayerError::Other)?;
+        debug!("Logged in to Deezer");
+        Ok(())
+    }
+}
+
+fn with_params(url: &str, params: &[(&str, &str)]) -> Url {
+    Url::parse_with_params(url, params).expect("Deezer urls should parse")
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn http_error(err: reqwest::Error) -> PlayerError {
+    if err.is_timeout() {
+        PlayerError::Timeout
+    } else {
+        PlayerError::Other(format!("Deezer: {err}"))
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn login_error(err: crate::oauth::CallbackError) -> PlayerError {
+    PlayerError::Other(format!("Deezer login: {err}"))
+}
+
+fn get_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,366 @@
+//! Identifies whatever is audible by fingerprinting the default input device with Chromaprint's
+//! `fpcalc` and looking the fingerprint up on `AcoustID`. Neither tells how far into the track we
+//! are, so the position is estimated from when we first recognised it.
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::process::Command;
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::http;
+use crate::player::{NowPlaying, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+const ACOUSTID_LOOKUP_URL: &str = "https://api.acoustid.org/v2/lookup";
+/// Audio captured per lookup, chromaprint needs a few seconds to be reliable
+const CAPTURE_LENGTH: Duration = Duration::from_secs(12);
+/// Pause between lookups, `AcoustID` allows 3 requests a second but there's no need to hurry
+const LOOKUP_INTERVAL: Duration = Duration::from_secs(10);
+/// Matches below this are too likely to be wrong
+const MIN_SCORE: f64 = 0.5;
+
+#[derive(Deserialize)]
+struct FpcalcOutput {
+    duration: f64,
+    fingerprint: String,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    status: String,
+    error: Option<LookupError>,
+    #[serde(default)]
+    results: Vec<LookupResult>,
+}
+
+#[derive(Deserialize)]
+struct LookupError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct LookupResult {
+    score: f64,
+    #[serde(default)]
+    recordings: Vec<Recording>,
+}
+
+#[derive(Deserialize)]
+struct Recording {
+    id: String,
+    title: Option<String>,
+    duration: Option<f64>,
+    #[serde(default)]
+    artists: Vec<Named>,
+    #[serde(default)]
+    releasegroups: Vec<ReleaseGroup>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ReleaseGroup {
+    title: String,
+}
+
+/// Last recognised track
+struct Identified {
+    recording_id: String,
+    title: String,
+    artist: String,
+    album: String,
+    duration_sec: f64,
+    /// Estimated from the start of the capture that first recognised it
+    started_at: Instant,
+    /// When a capture last matched it
+    last_match: Instant,
+}
+
+#[derive(Default)]
+struct Shared {
+    identified: Mutex<Option<Identified>>,
+    /// Why the last lookup failed, if it did
+    error: Mutex<Option<String>>,
+    changed: Arc<Notify>,
+}
+
+pub struct FingerprintSource {
+    shared: Arc<Shared>,
+}
+
+impl FingerprintSource {
+    /// Also starts listening, so must be called within the runtime
+    pub fn new(settings: &Settings) -> Self {
+        let shared = Arc::new(Shared::default());
+        let listener = Listener {
+            client: http::client_builder(settings).build().unwrap(),
+            api_key: settings.acoustid_key.clone(),
+            fpcalc: settings.fpcalc_path.clone(),
+            shared: shared.clone(),
+        };
+        tokio::spawn(listener.run());
+        Self { shared }
+    }
+}
+
+#[async_trait]
+impl PlayerSource for FingerprintSource {
+    #[allow(clippy::cast_possible_truncation)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        let identified = self.shared.identified.lock().unwrap();
+        let Some(track) = identified.as_ref() else {
+            return Err(match self.shared.error.lock().unwrap().clone() {
+                Some(err) => PlayerError::Other(err),
+                None => PlayerError::NothingPlaying,
+            });
+        };
+        let now = Instant::now();
+        let progress = now - track.started_at;
+        // Without a recent match the music has stopped or changed to something unknown
+        let stale = now - track.last_match > CAPTURE_LENGTH + LOOKUP_INTERVAL * 2;
+        if stale || (track.duration_sec > 0.0 && progress.as_secs_f64() > track.duration_sec) {
+            return Err(PlayerError::NothingPlaying);
+        }
+
+        Ok(NowPlaying {
+            track_id: track.recording_id.clone(),
+            spotify_id: None,
+            file_path: None,
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            album_art_url: None,
+            duration_sec: track.duration_sec,
+            is_playing: true,
+            progress_ms: progress.as_millis() as usize,
+            received_at: now,
+            round_trip: Duration::ZERO,
+        })
+    }
+
+    fn changes(&self) -> Option<Arc<Notify>> {
+        Some(self.shared.changed.clone())
+    }
+}
+
+struct Listener {
+    client: Client,
+    api_key: String,
+    fpcalc: String,
+    shared: Arc<Shared>,
+}
+
+impl Listener {
+    async fn run(self) {
+        let wav = std::env::temp_dir().join(format!("lyrics-overlay-{}.wav", std::process::id()));
+        loop {
+            let result = self.identify(&wav).await;
+            let changed = match result {
+                Ok(Some((recording, started_at))) => self.update(recording, started_at),
+                Ok(None) => false,
+                Err(err) => {
+                    warn!("Fingerprint lookup failed: {err}");
+                    *self.shared.error.lock().unwrap() = Some(err);
+                    true
+                }
+            };
+            if changed {
+                self.shared.changed.notify_one();
+            }
+            tokio::time::sleep(LOOKUP_INTERVAL).await;
+        }
+    }
+
+    /// Capture, fingerprint and look up a sample, returning the best match and when it started
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn identify(&self, wav: &Path) -> Result<Option<(Recording, Instant)>, String> {
+        if self.api_key.is_empty() {
+            return Err("Add an AcoustID api key in the settings".to_owned());
+        }
+
+        let capture_started = Instant::now();
+        let path = wav.to_owned();
+        tokio::task::spawn_blocking(move || capture(&path))
+            .await
+            .map_err(|err| err.to_string())??;
+        let fingerprint = self.fingerprint(wav).await?;
+        let _ = std::fs::remove_file(wav);
+
+        let duration = (fingerprint.duration.round() as u64).to_string();
+        let url = Url::parse_with_params(
+            ACOUSTID_LOOKUP_URL,
+            [
+                ("client", self.api_key.as_str()),
+                ("meta", "recordings releasegroups"),
+                ("duration", &duration),
+                ("fingerprint", &fingerprint.fingerprint),
+            ],
+        )
+        .expect("AcoustID url should parse");
+        let response: LookupResponse = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|err| format!("AcoustID: {err}"))?
+            .json()
+            .await
+            .map_err(|err| format!("AcoustID: {err}"))?;
+        if response.status != "ok" {
+            let message = response.error.map(|err| err.message).unwrap_or_default();
+            return Err(format!("AcoustID: {message}"));
+        }
+
+        let best = response
+            .results
+            .into_iter()
+            .filter(|result| result.score >= MIN_SCORE)
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+            .and_then(|result| {
+                result
+                    .recordings
+                    .into_iter()
+                    .find(|recording| recording.title.is_some())
+            });
+        debug!(
+            "Fingerprint matched {:?}",
+            best.as_ref().and_then(|recording| recording.title.as_ref())
+        );
+        Ok(best.map(|recording| (recording, capture_started)))
+    }
+
+    async fn fingerprint(&self, wav: &Path) -> Result<FpcalcOutput, String> {
+        let fpcalc = if self.fpcalc.is_empty() {
+            "fpcalc"
+        } else {
+            &self.fpcalc
+        };
+        let output = Command::new(fpcalc)
+            .arg("-json")
+            .arg(wav)
+            .output()
+            .await
+            .map_err(|err| format!("Failed to run fpcalc, is chromaprint installed? {err}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "fpcalc: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        serde_json::from_slice(&output.stdout).map_err(|err| format!("fpcalc: {err}"))
+    }
+
+    /// Store a match, returns whether it is a different track
+    fn update(&self, recording: Recording, capture_started: Instant) -> bool {
+        *self.shared.error.lock().unwrap() = None;
+        let mut identified = self.shared.identified.lock().unwrap();
+        if let Some(track) = identified.as_mut()
+            && track.recording_id == recording.id
+        {
+            track.last_match = Instant::now();
+            return false;
+        }
+        *identified = Some(Identified {
+            title: recording.title.unwrap_or_default(),
+            artist: recording
+                .artists
+                .into_iter()
+                .map(|artist| artist.name)
+                .collect::<Vec<_>>()
+                .join(", "),
+            album: recording
+                .releasegroups
+                .into_iter()
+                .next()
+                .map(|group| group.title)
+                .unwrap_or_default(),
+            duration_sec: recording.duration.unwrap_or(0.0),
+            recording_id: recording.id,
+            // We can't tell where in the track the sample was, assume it had just started
+            started_at: capture_started,
+            last_match: Instant::now(),
+        });
+        true
+    }
+}
+
+/// Record `CAPTURE_LENGTH` of the default input device into a 16 bit wav file
+fn capture(path: &Path) -> Result<(), String> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or("No audio input device")?;
+    let config = device
+        .default_input_config()
+        .map_err(|err| format!("Audio input: {err}"))?;
+    let samples = Arc::new(Mutex::new(Vec::<i16>::new()));
+    let on_error = |err| warn!("Audio input: {err}");
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => {
+            let samples = samples.clone();
+            device.build_input_stream(
+                &config.config(),
+                move |data: &[f32], _: &_| {
+                    #[allow(clippy::cast_possible_truncation)]
+                    samples.lock().unwrap().extend(
+                        data.iter()
+                            .map(|sample| (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16),
+                    );
+                },
+                on_error,
+                None,
+            )
+        }
+        cpal::SampleFormat::I16 => {
+            let samples = samples.clone();
+            device.build_input_stream(
+                &config.config(),
+                move |data: &[i16], _: &_| samples.lock().unwrap().extend_from_slice(data),
+                on_error,
+                None,
+            )
+        }
+        format => return Err(format!("Unsupported audio input format {format}")),
+    }
+    .map_err(|err| format!("Audio input: {err}"))?;
+    stream.play().map_err(|err| format!("Audio input: {err}"))?;
+    std::thread::sleep(CAPTURE_LENGTH);
+    drop(stream);
+
+    let samples = samples.lock().unwrap();
+    write_wav(path, &samples, config.channels(), config.sample_rate().0)
+        .map_err(|err| format!("Failed to write the audio sample: {err}"))
+}
+
+fn write_wav(path: &Path, samples: &[i16], channels: u16, sample_rate: u32) -> std::io::Result<()> {
+    let data_len = u32::try_from(samples.len() * 2).unwrap_or(u32::MAX);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_len).to_le_bytes())?;
+    file.write_all(b"WAVEfmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    // PCM
+    file.write_all(&1u16.to_le_bytes())?;
+    file.write_all(&channels.to_le_bytes())?;
+    file.write_all(&sample_rate.to_le_bytes())?;
+    file.write_all(&(sample_rate * u32::from(channels) * 2).to_le_bytes())?;
+    file.write_all(&(channels * 2).to_le_bytes())?;
+    file.write_all(&16u16.to_le_bytes())?;
+    file.write_all(b"data")?;
+    file.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+    file.flush()
+}
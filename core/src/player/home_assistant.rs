@@ -0,0 +1,152 @@
+//! Home Assistant player source, follows one `media_player` entity through the REST API
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::http;
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+#[derive(Deserialize)]
+struct EntityState {
+    /// playing, paused, idle, off, ...
+    state: String,
+    attributes: MediaAttributes,
+}
+
+/// Named as Home Assistant's attributes
+#[derive(Deserialize)]
+#[allow(clippy::struct_field_names)]
+struct MediaAttributes {
+    media_content_id: Option<String>,
+    media_title: Option<String>,
+    #[serde(default)]
+    media_artist: String,
+    #[serde(default)]
+    media_album_name: String,
+    /// Seconds
+    #[serde(default)]
+    media_duration: f64,
+    /// Seconds, as of `media_position_updated_at`
+    #[serde(default)]
+    media_position: f64,
+    media_position_updated_at: Option<DateTime<Utc>>,
+}
+
+pub struct HomeAssistantSource {
+    client: Client,
+    url: String,
+    token: String,
+    entity_id: String,
+}
+
+impl HomeAssistantSource {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            client: http::client_builder(settings).build().unwrap(),
+            url: settings.home_assistant_url.trim_end_matches('/').to_owned(),
+            token: settings.home_assistant_token.clone(),
+            entity_id: settings.home_assistant_entity.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl PlayerSource for HomeAssistantSource {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        if self.token.is_empty() || self.entity_id.is_empty() {
+            return Err(PlayerError::Other(
+                "Add your Home Assistant token and media player in the settings".to_owned(),
+            ));
+        }
+
+        let sent_at = Instant::now();
+        let response = self
+            .client
+            .get(format!("{}/api/states/{}", self.url, self.entity_id))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(http_error)?;
+        let received_at = Instant::now();
+
+        match response.status() {
+            StatusCode::UNAUTHORIZED => return Err(PlayerError::NotAuthenticated),
+            StatusCode::NOT_FOUND => {
+                return Err(PlayerError::Other(format!(
+                    "Home Assistant has no entity {}",
+                    self.entity_id
+                )));
+            }
+            _ => {}
+        }
+        let entity: EntityState = response
+            .error_for_status()
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(http_error)?;
+
+        let is_playing = match entity.state.as_str() {
+            "playing" => true,
+            "paused" => false,
+            _ => return Err(PlayerError::NothingPlaying),
+        };
+        let media = entity.attributes;
+        let title = media.media_title.ok_or(PlayerError::NotATrack)?;
+
+        // The position is only updated on state changes, extrapolate from when it was
+        let mut position_sec = media.media_position;
+        if is_playing && let Some(updated_at) = media.media_position_updated_at {
+            position_sec += (Utc::now() - updated_at).as_seconds_f64().max(0.0);
+        }
+
+        Ok(NowPlaying {
+            track_id: media
+                .media_content_id
+                .unwrap_or_else(|| format!("{} - {title}", media.media_artist)),
+            spotify_id: None,
+            file_path: None,
+            title,
+            artist: media.media_artist,
+            album: media.media_album_name,
+            album_art_url: None,
+            duration_sec: media.media_duration,
+            is_playing,
+            progress_ms: (position_sec * 1000.0).max(0.0) as usize,
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let service = match command {
+            PlaybackControl::PlayPause => "media_play_pause",
+            PlaybackControl::Next => "media_next_track",
+            PlaybackControl::Previous => "media_previous_track",
+        };
+        self.client
+            .post(format!("{}/api/services/media_player/{service}", self.url))
+            .bearer_auth(&self.token)
+            .json(&json!({ "entity_id": self.entity_id }))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn http_error(err: reqwest::Error) -> PlayerError {
+    if err.is_timeout() {
+        PlayerError::Timeout
+    } else {
+        PlayerError::Other(format!("Home Assistant: {err}"))
+    }
+}
@@ -0,0 +1,200 @@
+//! macOS Now Playing source, read from the private `MediaRemote` framework.
+//! Newer macOS versions only answer entitled processes, then polling times out.
+use std::ffi::c_void;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use block2::{Block, RcBlock};
+use core_foundation::{
+    base::{CFType, TCFType},
+    date::CFDate,
+    dictionary::{CFDictionary, CFDictionaryRef},
+    number::CFNumber,
+    string::CFString,
+};
+use libloading::Library;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+
+const FRAMEWORK: &str = "/System/Library/PrivateFrameworks/MediaRemote.framework/MediaRemote";
+/// The framework never answers if there is no now playing app
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `MRMediaRemoteCommand` values
+const TOGGLE_PLAY_PAUSE: u32 = 2;
+const NEXT_TRACK: u32 = 4;
+const PREVIOUS_TRACK: u32 = 5;
+
+type GetNowPlayingInfo = unsafe extern "C" fn(*mut c_void, &Block<dyn Fn(*const c_void)>);
+type SendCommand = unsafe extern "C" fn(u32, CFDictionaryRef) -> bool;
+
+unsafe extern "C" {
+    fn dispatch_get_global_queue(identifier: isize, flags: usize) -> *mut c_void;
+}
+
+/// Functions we use from the framework, valid as long as `_library` is loaded
+struct MediaRemote {
+    _library: Library,
+    get_now_playing_info: GetNowPlayingInfo,
+    send_command: SendCommand,
+}
+
+impl MediaRemote {
+    fn load() -> Result<Self, libloading::Error> {
+        // SAFETY: Loading a system framework, the signatures match its headers
+        unsafe {
+            let library = Library::new(FRAMEWORK)?;
+            Ok(Self {
+                get_now_playing_info: *library
+                    .get::<GetNowPlayingInfo>(b"MRMediaRemoteGetNowPlayingInfo\0")?,
+                send_command: *library.get::<SendCommand>(b"MRMediaRemoteSendCommand\0")?,
+                _library: library,
+            })
+        }
+    }
+
+    /// Ask for the now playing info, the answer arrives on a dispatch queue
+    fn request_info(&self) -> oneshot::Receiver<Option<NowPlayingInfo>> {
+        let (tx, rx) = oneshot::channel();
+        let tx = Mutex::new(Some(tx));
+        let block = RcBlock::new(move |info: *const c_void| {
+            // SAFETY: The framework passes a valid dictionary or null
+            let info = unsafe { NowPlayingInfo::parse(info.cast()) };
+            if let Some(tx) = tx.lock().unwrap().take() {
+                let _ = tx.send(info);
+            }
+        });
+        // SAFETY: The framework copies the block before returning
+        unsafe {
+            (self.get_now_playing_info)(dispatch_get_global_queue(0, 0), &block);
+        }
+        rx
+    }
+}
+
+/// Fields we use from the now playing dictionary
+struct NowPlayingInfo {
+    id: Option<String>,
+    title: String,
+    artist: String,
+    album: String,
+    duration_sec: f64,
+    elapsed_sec: f64,
+    /// When `elapsed_sec` was sampled, as `CFAbsoluteTime`
+    timestamp: Option<f64>,
+    playback_rate: f64,
+}
+
+impl NowPlayingInfo {
+    unsafe fn parse(info: CFDictionaryRef) -> Option<Self> {
+        if info.is_null() {
+            return None;
+        }
+        // SAFETY: Non-null, and retained for as long as we use it
+        let info: CFDictionary<CFString, CFType> =
+            unsafe { CFDictionary::wrap_under_get_rule(info) };
+        let value = |key: &'static str| {
+            info.find(CFString::from_static_string(key))
+                .map(|value| (*value).clone())
+        };
+        let string = |key| {
+            value(key)
+                .and_then(|v| v.downcast::<CFString>())
+                .map(|s| s.to_string())
+        };
+        let number = |key| {
+            value(key)
+                .and_then(|v| v.downcast::<CFNumber>())
+                .and_then(|n| n.to_f64())
+        };
+
+        Some(Self {
+            id: string("kMRMediaRemoteNowPlayingInfoContentItemIdentifier"),
+            title: string("kMRMediaRemoteNowPlayingInfoTitle")?,
+            artist: string("kMRMediaRemoteNowPlayingInfoArtist").unwrap_or_default(),
+            album: string("kMRMediaRemoteNowPlayingInfoAlbum").unwrap_or_default(),
+            duration_sec: number("kMRMediaRemoteNowPlayingInfoDuration").unwrap_or(0.0),
+            elapsed_sec: number("kMRMediaRemoteNowPlayingInfoElapsedTime").unwrap_or(0.0),
+            timestamp: value("kMRMediaRemoteNowPlayingInfoTimestamp")
+                .and_then(|v| v.downcast::<CFDate>())
+                .map(|date| date.abs_time()),
+            playback_rate: number("kMRMediaRemoteNowPlayingInfoPlaybackRate").unwrap_or(0.0),
+        })
+    }
+}
+
+pub struct MediaRemoteSource {
+    media_remote: Result<MediaRemote, String>,
+}
+
+impl MediaRemoteSource {
+    pub fn new() -> Self {
+        let media_remote = MediaRemote::load().map_err(|err| {
+            warn!("Failed to load MediaRemote: {err}");
+            format!("MediaRemote unavailable: {err}")
+        });
+        Self { media_remote }
+    }
+
+    fn media_remote(&self) -> Result<&MediaRemote, PlayerError> {
+        self.media_remote
+            .as_ref()
+            .map_err(|err| PlayerError::Other(err.clone()))
+    }
+}
+
+#[async_trait]
+impl PlayerSource for MediaRemoteSource {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        let rx = self.media_remote()?.request_info();
+        let info = tokio::time::timeout(CALL_TIMEOUT, rx)
+            .await
+            .map_err(|_| PlayerError::Timeout)?
+            .map_err(|_| PlayerError::Timeout)?
+            .ok_or(PlayerError::NothingPlaying)?;
+
+        // Elapsed time is only updated on state changes, extrapolate from when it was
+        let is_playing = info.playback_rate > 0.0;
+        let mut elapsed_sec = info.elapsed_sec;
+        if let Some(timestamp) = info.timestamp.filter(|_| is_playing) {
+            elapsed_sec += (CFDate::now().abs_time() - timestamp).max(0.0) * info.playback_rate;
+        }
+
+        Ok(NowPlaying {
+            track_id: info
+                .id
+                .unwrap_or_else(|| format!("{} - {}", info.artist, info.title)),
+            spotify_id: None,
+            file_path: None,
+            title: info.title,
+            artist: info.artist,
+            album: info.album,
+            album_art_url: None,
+            duration_sec: info.duration_sec,
+            is_playing,
+            progress_ms: (elapsed_sec * 1000.0).max(0.0) as usize,
+            // Already extrapolated to now
+            received_at: Instant::now(),
+            round_trip: Duration::ZERO,
+        })
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let command = match command {
+            PlaybackControl::PlayPause => TOGGLE_PLAY_PAUSE,
+            PlaybackControl::Next => NEXT_TRACK,
+            PlaybackControl::Previous => PREVIOUS_TRACK,
+        };
+        // SAFETY: No options dictionary is a valid argument
+        let sent = unsafe { (self.media_remote()?.send_command)(command, std::ptr::null()) };
+        if sent {
+            Ok(())
+        } else {
+            Err(PlayerError::Unsupported)
+        }
+    }
+}
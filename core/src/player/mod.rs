@@ -0,0 +1,227 @@
+//! Sources of what is currently playing, the rest of the app only sees `NowPlaying`
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{Notify, RwLock as TokioRwLock};
+
+use crate::settings::Settings;
+use crate::spotify::SpotifyClient;
+
+#[cfg(target_os = "macos")]
+mod apple_music;
+mod deezer;
+#[cfg(feature = "fingerprint")]
+mod fingerprint;
+mod home_assistant;
+#[cfg(target_os = "macos")]
+mod media_remote;
+mod mpd;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod plex;
+pub mod poller;
+#[cfg(windows)]
+mod smtc;
+mod spotify;
+mod subsonic;
+mod web_now_playing;
+mod youtube_music;
+
+#[derive(Error, Debug)]
+/// Error enum for player sources
+pub enum PlayerError {
+    #[error("Not authenticated")]
+    NotAuthenticated,
+    #[error("Not playing a track")]
+    NotATrack,
+    #[error("Not playing anything")]
+    NothingPlaying,
+    #[error("Exceeded rate limits")]
+    RateLimitsExceeded,
+    #[error("Player did not respond in time")]
+    Timeout,
+    #[error("Not supported by this player")]
+    Unsupported,
+    #[error("{0}")]
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+/// Track reported by a player source
+pub struct NowPlaying {
+    /// Identifies the track within its source, used to detect track changes
+    pub track_id: String,
+    /// Spotify id if the source knows it, enables spotify lyrics and audio features
+    pub spotify_id: Option<String>,
+    /// Local file being played, if the source knows it
+    pub file_path: Option<PathBuf>,
+    pub title: String,
+    pub artist: String,
+    /// Empty if the source doesn't report one
+    pub album: String,
+    /// Cover of the album, `http(s)` or `file` url
+    pub album_art_url: Option<String>,
+    /// 0.0 if the source doesn't report one
+    pub duration_sec: f64,
+    /// Are we currently playing this track?
+    pub is_playing: bool,
+    /// Playback progress
+    pub progress_ms: usize,
+    /// When the progress was received
+    pub received_at: Instant,
+    /// Round trip time of the request, `progress_ms` was sampled somewhere within it
+    pub round_trip: Duration,
+}
+
+/// Commands for players that can be controlled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackControl {
+    PlayPause,
+    Next,
+    Previous,
+}
+
+#[async_trait]
+pub trait PlayerSource: Send + Sync {
+    /// Poll the player for the current track
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError>;
+
+    /// Control playback, players without controls keep the default
+    async fn control(&self, _command: PlaybackControl) -> Result<(), PlayerError> {
+        Err(PlayerError::Unsupported)
+    }
+
+    /// Log in to the player's service, sources without a login keep the default
+    async fn login(&self, _cancel: Arc<Notify>) -> Result<(), PlayerError> {
+        Err(PlayerError::Unsupported)
+    }
+
+    /// Notified when the player reports a change, so it can be polled right away
+    fn changes(&self) -> Option<Arc<Notify>> {
+        None
+    }
+}
+
+/// Where we get the currently playing track from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerSourceKind {
+    #[default]
+    Spotify,
+    /// Music Player Daemon
+    Mpd,
+    /// Youtube music desktop app with its API server plugin
+    YoutubeMusic,
+    /// Deezer, from the listening history
+    Deezer,
+    /// Plex media server, for Plexamp and other Plex players
+    Plex,
+    /// Subsonic API servers, like Navidrome
+    Subsonic,
+    /// Browser tabs, pushed by the `WebNowPlaying` extension
+    WebNowPlaying,
+    /// A media player entity in Home Assistant
+    HomeAssistant,
+    /// Whatever the microphone hears, identified by its audio fingerprint
+    #[cfg(feature = "fingerprint")]
+    Fingerprint,
+    /// Any MPRIS player on the session bus
+    #[cfg(target_os = "linux")]
+    Mpris,
+    /// Whatever the OS media controls show
+    #[cfg(any(windows, target_os = "macos"))]
+    System,
+    /// The Apple Music app
+    #[cfg(any(windows, target_os = "macos"))]
+    AppleMusic,
+}
+
+impl PlayerSourceKind {
+    pub const ALL: &[Self] = &[
+        Self::Spotify,
+        Self::Mpd,
+        Self::YoutubeMusic,
+        Self::Deezer,
+        Self::Plex,
+        Self::Subsonic,
+        Self::WebNowPlaying,
+        Self::HomeAssistant,
+        #[cfg(feature = "fingerprint")]
+        Self::Fingerprint,
+        #[cfg(target_os = "linux")]
+        Self::Mpris,
+        #[cfg(any(windows, target_os = "macos"))]
+        Self::System,
+        #[cfg(any(windows, target_os = "macos"))]
+        Self::AppleMusic,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Spotify => "Spotify",
+            Self::Mpd => "MPD",
+            Self::YoutubeMusic => "YouTube Music",
+            Self::Deezer => "Deezer",
+            Self::Plex => "Plex",
+            Self::Subsonic => "Subsonic",
+            Self::WebNowPlaying => "Browser (WebNowPlaying)",
+            Self::HomeAssistant => "Home Assistant",
+            #[cfg(feature = "fingerprint")]
+            Self::Fingerprint => "Audio fingerprint",
+            #[cfg(target_os = "linux")]
+            Self::Mpris => "MPRIS",
+            #[cfg(any(windows, target_os = "macos"))]
+            Self::System => "System media",
+            #[cfg(any(windows, target_os = "macos"))]
+            Self::AppleMusic => "Apple Music",
+        }
+    }
+
+    /// Does the source need a spotify login to tell what's playing
+    pub fn needs_spotify_auth(self) -> bool {
+        matches!(self, Self::Spotify)
+    }
+}
+
+/// Create the source selected in the settings
+pub async fn create_source(
+    shared_settings: &Arc<TokioRwLock<Settings>>,
+    spotify: Arc<SpotifyClient>,
+) -> Arc<dyn PlayerSource> {
+    let settings = &*shared_settings.read().await;
+    match settings.player_source {
+        PlayerSourceKind::Spotify => spotify,
+        PlayerSourceKind::Mpd => Arc::new(mpd::MpdSource::new(settings)),
+        PlayerSourceKind::YoutubeMusic => {
+            Arc::new(youtube_music::YoutubeMusicSource::new(settings))
+        }
+        PlayerSourceKind::Deezer => {
+            Arc::new(deezer::DeezerSource::new(shared_settings.clone(), settings))
+        }
+        PlayerSourceKind::Plex => Arc::new(plex::PlexSource::new(settings)),
+        PlayerSourceKind::Subsonic => Arc::new(subsonic::SubsonicSource::new(settings)),
+        PlayerSourceKind::WebNowPlaying => {
+            Arc::new(web_now_playing::WebNowPlayingSource::new(settings))
+        }
+        PlayerSourceKind::HomeAssistant => {
+            Arc::new(home_assistant::HomeAssistantSource::new(settings))
+        }
+        #[cfg(feature = "fingerprint")]
+        PlayerSourceKind::Fingerprint => Arc::new(fingerprint::FingerprintSource::new(settings)),
+        #[cfg(target_os = "linux")]
+        PlayerSourceKind::Mpris => Arc::new(mpris::MprisSource::new(&settings.mpris_player)),
+        #[cfg(windows)]
+        PlayerSourceKind::System => Arc::new(smtc::SmtcSource::default()),
+        #[cfg(target_os = "macos")]
+        PlayerSourceKind::System => Arc::new(media_remote::MediaRemoteSource::new()),
+        // The Windows app only shows up as a media session
+        #[cfg(windows)]
+        PlayerSourceKind::AppleMusic => Arc::new(smtc::SmtcSource::for_app("AppleMusic")),
+        #[cfg(target_os = "macos")]
+        PlayerSourceKind::AppleMusic => Arc::new(apple_music::AppleMusicSource),
+    }
+}
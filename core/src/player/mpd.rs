@@ -0,0 +1,226 @@
+//! MPD player source, speaks the plain text protocol directly
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+use tracing::debug;
+
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+/// MPD is usually on the local network, anything slower than this is hung
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+/// Wait before reconnecting the idle connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+struct MpdAddress {
+    host: String,
+    port: u16,
+    password: String,
+}
+
+pub struct MpdSource {
+    address: MpdAddress,
+    /// MPD's `music_directory`, song files are relative to it
+    music_dir: String,
+    changed: Arc<Notify>,
+}
+
+impl MpdSource {
+    /// Also starts listening for player events, so must be called within the runtime
+    pub fn new(settings: &Settings) -> Self {
+        let address = MpdAddress {
+            host: settings.mpd_host.clone(),
+            port: settings.mpd_port,
+            password: settings.mpd_password.clone(),
+        };
+        let changed = Arc::new(Notify::new());
+        tokio::spawn(watch_changes(address.clone(), changed.clone()));
+        Self {
+            address,
+            music_dir: settings.mpd_music_dir.clone(),
+            changed,
+        }
+    }
+
+    async fn query(&self) -> Result<NowPlaying, PlayerError> {
+        let mut mpd = MpdConnection::open(&self.address).await?;
+        let song = mpd.command("currentsong").await?;
+
+        let sent_at = Instant::now();
+        let status = mpd.command("status").await?;
+        let received_at = Instant::now();
+
+        let is_playing = match status.get("state").map(String::as_str) {
+            Some("play") => true,
+            Some("pause") => false,
+            _ => return Err(PlayerError::NothingPlaying),
+        };
+        let file = song.get("file").ok_or(PlayerError::NothingPlaying)?;
+        // Untagged files are common in local libraries, fall back to the file name
+        let title = song.get("Title").cloned().unwrap_or_else(|| {
+            let name = file.rsplit('/').next().unwrap_or(file);
+            name.rsplit_once('.')
+                .map_or(name, |(stem, _)| stem)
+                .to_owned()
+        });
+        let seconds = |map: &HashMap<String, String>, key: &str| {
+            map.get(key).and_then(|value| value.parse::<f64>().ok())
+        };
+        let duration_sec = seconds(&status, "duration")
+            .or_else(|| seconds(&song, "duration"))
+            .or_else(|| seconds(&song, "Time"))
+            .unwrap_or(0.0);
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let progress_ms = (seconds(&status, "elapsed").unwrap_or(0.0) * 1000.0).max(0.0) as usize;
+
+        Ok(NowPlaying {
+            track_id: song.get("Id").unwrap_or(file).clone(),
+            spotify_id: None,
+            file_path: (!self.music_dir.is_empty()).then(|| Path::new(&self.music_dir).join(file)),
+            title,
+            artist: song.get("Artist").cloned().unwrap_or_default(),
+            album: song.get("Album").cloned().unwrap_or_default(),
+            album_art_url: None,
+            duration_sec,
+            is_playing,
+            progress_ms,
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+
+    async fn send_control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let mut mpd = MpdConnection::open(&self.address).await?;
+        let command = match command {
+            PlaybackControl::PlayPause => {
+                let status = mpd.command("status").await?;
+                match status.get("state").map(String::as_str) {
+                    Some("play") => "pause 1",
+                    Some("pause") => "pause 0",
+                    _ => "play",
+                }
+            }
+            PlaybackControl::Next => "next",
+            PlaybackControl::Previous => "previous",
+        };
+        mpd.command(command).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PlayerSource for MpdSource {
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        tokio::time::timeout(CALL_TIMEOUT, self.query())
+            .await
+            .map_err(|_| PlayerError::Timeout)?
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        tokio::time::timeout(CALL_TIMEOUT, self.send_control(command))
+            .await
+            .map_err(|_| PlayerError::Timeout)?
+    }
+
+    fn changes(&self) -> Option<Arc<Notify>> {
+        Some(self.changed.clone())
+    }
+}
+
+struct MpdConnection {
+    stream: BufStream<TcpStream>,
+}
+
+impl MpdConnection {
+    async fn open(address: &MpdAddress) -> Result<Self, PlayerError> {
+        let stream = TcpStream::connect((address.host.as_str(), address.port))
+            .await
+            .map_err(io_error)?;
+        let mut mpd = Self {
+            stream: BufStream::new(stream),
+        };
+        if !mpd.read_line().await?.starts_with("OK MPD ") {
+            return Err(PlayerError::Other(format!(
+                "{}:{} is not an MPD server",
+                address.host, address.port
+            )));
+        }
+        if !address.password.is_empty() {
+            mpd.command(&format!("password {}", quote(&address.password)))
+                .await?;
+        }
+        Ok(mpd)
+    }
+
+    /// Send a command and collect the `key: value` pairs it responds with, keeping the first of repeated keys
+    async fn command(&mut self, command: &str) -> Result<HashMap<String, String>, PlayerError> {
+        self.stream
+            .write_all(format!("{command}\n").as_bytes())
+            .await
+            .map_err(io_error)?;
+        self.stream.flush().await.map_err(io_error)?;
+
+        let mut pairs = HashMap::new();
+        loop {
+            let line = self.read_line().await?;
+            if line == "OK" {
+                return Ok(pairs);
+            }
+            if let Some(err) = line.strip_prefix("ACK ") {
+                return Err(PlayerError::Other(format!("MPD: {err}")));
+            }
+            if let Some((key, value)) = line.split_once(": ") {
+                pairs
+                    .entry(key.to_owned())
+                    .or_insert_with(|| value.to_owned());
+            }
+        }
+    }
+
+    async fn read_line(&mut self) -> Result<String, PlayerError> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await.map_err(io_error)? == 0 {
+            return Err(PlayerError::Other("MPD closed the connection".to_owned()));
+        }
+        line.truncate(line.trim_end_matches('\n').len());
+        Ok(line)
+    }
+}
+
+/// Keep an `idle` connection open and wake the poller whenever the player changes
+async fn watch_changes(address: MpdAddress, changed: Arc<Notify>) {
+    loop {
+        if let Err(err) = idle(&address, &changed).await {
+            debug!("MPD idle connection lost: {err}");
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn idle(address: &MpdAddress, changed: &Notify) -> Result<(), PlayerError> {
+    let mut mpd = MpdConnection::open(address).await?;
+    loop {
+        mpd.command("idle player").await?;
+        changed.notify_one();
+    }
+}
+
+fn quote(argument: &str) -> String {
+    format!(
+        "\"{}\"",
+        argument.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn io_error(err: std::io::Error) -> PlayerError {
+    PlayerError::Other(format!("MPD: {err}"))
+}
@@ -0,0 +1,239 @@
+//! MPRIS player source, any linux player exposing itself on the session bus
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use tokio::sync::{Notify, OnceCell};
+use tracing::warn;
+use url::Url;
+use zbus::{
+    Connection, MatchRule, MessageStream,
+    fdo::DBusProxy,
+    message,
+    proxy::CacheProperties,
+    zvariant::{OwnedValue, Value},
+};
+
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+
+const BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+/// Track id players report when they have nothing loaded
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+/// Players are local, anything slower than this is hung
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[zbus::proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait MprisPlayer {
+    fn play_pause(&self) -> zbus::Result<()>;
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+    /// Position in µs, players don't signal changes to it
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> zbus::Result<i64>;
+}
+
+impl From<zbus::Error> for PlayerError {
+    fn from(err: zbus::Error) -> Self {
+        PlayerError::Other(format!("D-Bus: {err}"))
+    }
+}
+
+impl From<zbus::fdo::Error> for PlayerError {
+    fn from(err: zbus::fdo::Error) -> Self {
+        PlayerError::Other(format!("D-Bus: {err}"))
+    }
+}
+
+pub struct MprisSource {
+    /// Only follow players whose bus name contains this, lowercase
+    filter: String,
+    /// Connected on first use, so a missing session bus shows up as a poll error
+    connection: OnceCell<Connection>,
+    changed: Arc<Notify>,
+}
+
+impl MprisSource {
+    pub fn new(filter: &str) -> Self {
+        Self {
+            filter: filter.to_lowercase(),
+            connection: OnceCell::new(),
+            changed: Arc::new(Notify::new()),
+        }
+    }
+
+    async fn connection(&self) -> Result<&Connection, PlayerError> {
+        self.connection
+            .get_or_try_init(|| async {
+                let connection = Connection::session().await?;
+                let watched = connection.clone();
+                let changed = self.changed.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = watch_changes(watched, changed).await {
+                        warn!("Not watching MPRIS players for changes: {err}");
+                    }
+                });
+                Ok::<_, PlayerError>(connection)
+            })
+            .await
+    }
+
+    /// The player to follow, preferring one that is playing
+    async fn find_player(&self) -> Result<MprisPlayerProxy<'static>, PlayerError> {
+        let connection = self.connection().await?;
+        let names = DBusProxy::new(connection).await?.list_names().await?;
+
+        let mut fallback = None;
+        for name in names {
+            let Some(player_name) = name.as_str().strip_prefix(BUS_PREFIX) else {
+                continue;
+            };
+            if !player_name.to_lowercase().contains(&self.filter) {
+                continue;
+            }
+            let player = MprisPlayerProxy::builder(connection)
+                .destination(name)?
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await?;
+            if player
+                .playback_status()
+                .await
+                .is_ok_and(|status| status == "Playing")
+            {
+                return Ok(player);
+            }
+            fallback.get_or_insert(player);
+        }
+        fallback.ok_or(PlayerError::NothingPlaying)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    async fn query(&self) -> Result<NowPlaying, PlayerError> {
+        let player = self.find_player().await?;
+        let is_playing = match player.playback_status().await?.as_str() {
+            "Playing" => true,
+            "Paused" => false,
+            _ => return Err(PlayerError::NothingPlaying),
+        };
+        let metadata = player.metadata().await?;
+
+        let sent_at = Instant::now();
+        // Streams and some browsers don't implement seeking and may not report a position
+        let position_us = player.position().await.unwrap_or(0);
+        let received_at = Instant::now();
+
+        let title = string(&metadata, "xesam:title")
+            .filter(|title| !title.is_empty())
+            .ok_or(PlayerError::NotATrack)?;
+        let artist = strings(&metadata, "xesam:artist").join(", ");
+        let mpris_id = string(&metadata, "mpris:trackid").filter(|id| id != NO_TRACK);
+        let url = string(&metadata, "xesam:url");
+
+        Ok(NowPlaying {
+            spotify_id: spotify_id(mpris_id.as_deref(), url.as_deref()),
+            file_path: url
+                .and_then(|url| Url::parse(&url).ok())
+                .filter(|url| url.scheme() == "file")
+                .and_then(|url| url.to_file_path().ok()),
+            track_id: mpris_id.unwrap_or_else(|| format!("{artist} - {title}")),
+            album: string(&metadata, "xesam:album").unwrap_or_default(),
+            album_art_url: string(&metadata, "mpris:artUrl").filter(|url| !url.is_empty()),
+            duration_sec: length_us(&metadata).map_or(0.0, |us| us as f64 / 1_000_000.0),
+            title,
+            artist,
+            is_playing,
+            progress_ms: usize::try_from(position_us / 1000).unwrap_or(0),
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+}
+
+#[async_trait]
+impl PlayerSource for MprisSource {
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        tokio::time::timeout(CALL_TIMEOUT, self.query())
+            .await
+            .map_err(|_| PlayerError::Timeout)?
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let player = self.find_player().await?;
+        match command {
+            PlaybackControl::PlayPause => player.play_pause().await?,
+            PlaybackControl::Next => player.next().await?,
+            PlaybackControl::Previous => player.previous().await?,
+        }
+        Ok(())
+    }
+
+    fn changes(&self) -> Option<Arc<Notify>> {
+        Some(self.changed.clone())
+    }
+}
+
+/// Wake the poller on `PropertiesChanged` and `Seeked` from any player, instead of waiting for the interval
+async fn watch_changes(connection: Connection, changed: Arc<Notify>) -> zbus::Result<()> {
+    let rule = MatchRule::builder()
+        .msg_type(message::Type::Signal)
+        .path(OBJECT_PATH)?
+        .build();
+    let mut signals = MessageStream::for_match_rule(rule, &connection, None).await?;
+    while signals.next().await.is_some() {
+        changed.notify_one();
+    }
+    Ok(())
+}
+
+fn string(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    match metadata.get(key).map(|value| &**value) {
+        Some(Value::Str(s)) => Some(s.to_string()),
+        Some(Value::ObjectPath(path)) => Some(path.to_string()),
+        _ => None,
+    }
+}
+
+/// `xesam:artist` is a list, some players send a single string anyway
+fn strings(metadata: &HashMap<String, OwnedValue>, key: &str) -> Vec<String> {
+    match metadata.get(key).map(|value| &**value) {
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|value| match value {
+                Value::Str(s) => Some(s.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::Str(s)) => vec![s.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// `mpris:length` should be an i64, but players disagree on the integer type
+fn length_us(metadata: &HashMap<String, OwnedValue>) -> Option<i64> {
+    match metadata.get("mpris:length").map(|value| &**value) {
+        Some(Value::I64(us)) => Some(*us),
+        Some(Value::U64(us)) => i64::try_from(*us).ok(),
+        Some(Value::I32(us)) => Some(i64::from(*us)),
+        Some(Value::U32(us)) => Some(i64::from(*us)),
+        _ => None,
+    }
+}
+
+/// The spotify client reports its track ids, which lets us use spotify lyrics and audio features
+fn spotify_id(mpris_id: Option<&str>, url: Option<&str>) -> Option<String> {
+    mpris_id
+        .and_then(|id| id.strip_prefix("/com/spotify/track/"))
+        .or_else(|| url.and_then(|url| url.strip_prefix("https://open.spotify.com/track/")))
+        .map(str::to_owned)
+}
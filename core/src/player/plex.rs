@@ -0,0 +1,139 @@
+//! Plex player source, reads the server's sessions for the music track being played
+use std::time::Instant;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::http;
+use crate::player::{NowPlaying, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SessionsResponse {
+    media_container: MediaContainer,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MediaContainer {
+    /// Missing when nothing is playing
+    #[serde(default)]
+    metadata: Vec<Session>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    #[serde(rename = "type")]
+    kind: String,
+    rating_key: String,
+    title: String,
+    /// Track artist, only set when it differs from the album artist
+    original_title: Option<String>,
+    #[serde(default)]
+    grandparent_title: String,
+    #[serde(default)]
+    parent_title: String,
+    /// Milliseconds
+    #[serde(default)]
+    duration: u64,
+    /// Playback position in milliseconds
+    #[serde(default)]
+    view_offset: u64,
+    #[serde(rename = "Player")]
+    player: SessionPlayer,
+}
+
+#[derive(Deserialize)]
+struct SessionPlayer {
+    /// playing, paused or buffering
+    state: String,
+}
+
+pub struct PlexSource {
+    client: Client,
+    url: String,
+    token: String,
+}
+
+impl PlexSource {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            client: http::client_builder(settings).build().unwrap(),
+            url: settings.plex_url.trim_end_matches('/').to_owned(),
+            token: settings.plex_token.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl PlayerSource for PlexSource {
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        if self.token.is_empty() {
+            return Err(PlayerError::Other(
+                "Add your Plex token in the settings".to_owned(),
+            ));
+        }
+
+        let sent_at = Instant::now();
+        let response = self
+            .client
+            .get(format!("{}/status/sessions", self.url))
+            .header("X-Plex-Token", &self.token)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .send()
+            .await
+            .map_err(http_error)?;
+        let received_at = Instant::now();
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(PlayerError::NotAuthenticated);
+        }
+        let sessions: SessionsResponse = response
+            .error_for_status()
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(http_error)?;
+
+        // Other users may be streaming too, prefer a track that is playing over a paused one
+        let mut tracks: Vec<Session> = sessions
+            .media_container
+            .metadata
+            .into_iter()
+            .filter(|session| session.kind == "track")
+            .collect();
+        tracks.sort_by_key(|session| session.player.state != "playing");
+        let track = tracks
+            .into_iter()
+            .next()
+            .ok_or(PlayerError::NothingPlaying)?;
+
+        Ok(NowPlaying {
+            track_id: track.rating_key,
+            spotify_id: None,
+            file_path: None,
+            title: track.title,
+            artist: track.original_title.unwrap_or(track.grandparent_title),
+            album: track.parent_title,
+            album_art_url: None,
+            duration_sec: track.duration as f64 / 1000.0,
+            is_playing: track.player.state == "playing",
+            progress_ms: track.view_offset as usize,
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn http_error(err: reqwest::Error) -> PlayerError {
+    if err.is_timeout() {
+        PlayerError::Timeout
+    } else {
+        PlayerError::Other(format!("Plex: {err}"))
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::info;
+
+use crate::{
+    MessageToRT, MessageToUI, StatusEvent,
+    player::{NowPlaying, PlayerError, PlayerSource, PlayerSourceKind},
+    runtime::Messages,
+    settings::Settings,
+    spotify::{SpotifyClient, SpotifyClientTrackError, UpcomingTrack},
+};
+use tokio::sync::{Notify, mpsc};
+
+pub struct PlayerPoller {
+    source: Arc<dyn PlayerSource>,
+    /// Used for the queue, which only spotify has
+    spotify: Arc<SpotifyClient>,
+    settings: Arc<TokioRwLock<Settings>>,
+}
+
+impl PlayerPoller {
+    pub fn new(
+        source: Arc<dyn PlayerSource>,
+        spotify: Arc<SpotifyClient>,
+        settings: Arc<TokioRwLock<Settings>>,
+    ) -> Self {
+        Self {
+            source,
+            spotify,
+            settings,
+        }
+    }
+
+    pub async fn run(self, tx_ui: mpsc::Sender<MessageToUI>, tx_rt: mpsc::Sender<MessageToRT>) {
+        let (mut poll_interval, mut queue_poll_interval) = self.intervals().await;
+        let mut interval = tokio::time::interval(poll_interval);
+        let mut queue_interval = tokio::time::interval(queue_poll_interval);
+        let mut resume_detector = ResumeDetector::new(poll_interval);
+        let changes = self.source.changes();
+        loop {
+            let queue_tick = tokio::select! {
+                _ = interval.tick() => false,
+                _ = queue_interval.tick() => true,
+                () = notified(changes.as_deref()) => false,
+            };
+
+            if resume_detector.woke_from_sleep() {
+                info!("System resumed from sleep, resyncing");
+                tx_rt.send(MessageToRT::Resync).await.unwrap();
+                continue;
+            }
+
+            // Changed in the settings window, start over at the new pace
+            let (new_poll_interval, new_queue_poll_interval) = self.intervals().await;
+            if new_poll_interval != poll_interval {
+                poll_interval = new_poll_interval;
+                interval = tokio::time::interval_at(
+                    tokio::time::Instant::now() + poll_interval,
+                    poll_interval,
+                );
+                resume_detector = ResumeDetector::new(poll_interval);
+            }
+            if new_queue_poll_interval != queue_poll_interval {
+                queue_poll_interval = new_queue_poll_interval;
+                queue_interval = tokio::time::interval_at(
+                    tokio::time::Instant::now() + queue_poll_interval,
+                    queue_poll_interval,
+                );
+            }
+
+            let msg = if queue_tick {
+                let settings = self.settings.read().await;
+                if settings.player_source != PlayerSourceKind::Spotify
+                    || !settings.show_upcoming_track
+                    || !settings.oauth_scopes.can_read_playback_state()
+                {
+                    continue;
+                }
+                drop(settings);
+                self.poll_queue().await
+            } else {
+                self.poll().await
+            };
+            msg.send(tx_ui.clone()).await;
+        }
+    }
+
+    /// Playback and queue poll intervals from the settings
+    async fn intervals(&self) -> (Duration, Duration) {
+        let settings = self.settings.read().await;
+        (
+            Duration::from_millis(settings.poll_interval_ms),
+            Duration::from_millis(settings.queue_poll_interval_ms),
+        )
+    }
+
+    pub async fn poll(&self) -> Messages {
+        process_now_playing(self.source.now_playing().await)
+    }
+
+    pub async fn poll_queue(&self) -> Messages {
+        process_queue_response(self.spotify.get_queue().await)
+    }
+}
+
+/// Wait for the source to report a change, forever if it can't
+async fn notified(changes: Option<&Notify>) {
+    match changes {
+        Some(changes) => changes.notified().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Detects the system waking up from sleep. The monotonic clock tokio's timers run on stops while
+/// suspended, the wall clock doesn't, so a wakeup long after the previous one means we slept.
+struct ResumeDetector {
+    last_wake: SystemTime,
+    /// Largest gap between wakeups we expect while awake, slow requests included
+    max_gap: Duration,
+}
+
+impl ResumeDetector {
+    fn new(poll_interval: Duration) -> Self {
+        Self {
+            last_wake: SystemTime::now(),
+            max_gap: poll_interval + Duration::from_mins(1),
+        }
+    }
+
+    fn woke_from_sleep(&mut self) -> bool {
+        let now = SystemTime::now();
+        let gap = now.duration_since(self.last_wake).unwrap_or_default();
+        self.last_wake = now;
+        gap > self.max_gap
+    }
+}
+
+pub fn process_now_playing(res: Result<NowPlaying, PlayerError>) -> Messages {
+    match res {
+        Ok(playing) => Messages::to_ui(MessageToUI::CurrentlyPlaying(playing))
+            .with_status(StatusEvent::ApiCallSucceeded),
+        Err(PlayerError::NotATrack) => Messages::to_ui(MessageToUI::NotCurrentlyPlaying(
+            "Not playing a song".to_owned(),
+        ))
+        .with_status(StatusEvent::ApiCallSucceeded),
+        Err(PlayerError::NothingPlaying) => Messages::to_ui(MessageToUI::NotCurrentlyPlaying(
+            "Not playing anything".to_owned(),
+        ))
+        .with_status(StatusEvent::ApiCallSucceeded),
+        Err(PlayerError::NotAuthenticated) => {
+            Messages::to_ui(MessageToUI::AuthenticationStateUpdate(false))
+        }
+        Err(PlayerError::RateLimitsExceeded) => Messages::to_ui(MessageToUI::RateLimitsExceeded),
+        Err(err) => Messages::to_ui(MessageToUI::DisplayError(err.to_string())),
+    }
+}
+
+pub fn process_queue_response(
+    res: Result<Option<UpcomingTrack>, SpotifyClientTrackError>,
+) -> Messages {
+    match res {
+        Ok(upcoming) => Messages::to_ui(MessageToUI::UpcomingTrack(upcoming))
+            .with_status(StatusEvent::ApiCallSucceeded),
+        // Queue failures are not worth bothering the user with, the current track poll reports auth issues
+        Err(_) => Messages::to_ui(MessageToUI::UpcomingTrack(None)),
+    }
+}
@@ -0,0 +1,198 @@
+//! Windows media session source, whatever the OS shows in its media flyout
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use tokio::sync::{Notify, OnceCell};
+use windows::{
+    Foundation::TypedEventHandler,
+    Media::Control::{
+        GlobalSystemMediaTransportControlsSession as Session,
+        GlobalSystemMediaTransportControlsSessionManager as SessionManager,
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus as PlaybackStatus,
+    },
+    core::RuntimeType,
+};
+
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+
+/// 100 ns ticks between 1601-01-01, where windows time starts, and the unix epoch
+const UNIX_EPOCH_TICKS: i64 = 116_444_736_000_000_000;
+
+impl From<windows::core::Error> for PlayerError {
+    fn from(err: windows::core::Error) -> Self {
+        PlayerError::Other(format!("Media session: {err}"))
+    }
+}
+
+#[derive(Default)]
+pub struct SmtcSource {
+    /// Only follow sessions of apps whose id contains this, instead of the current one
+    app: Option<&'static str>,
+    /// Requested on first use
+    manager: OnceCell<SessionManager>,
+    /// Session we receive change events from
+    watched: Mutex<Option<WatchedSession>>,
+    changed: Arc<Notify>,
+}
+
+impl SmtcSource {
+    pub fn for_app(app: &'static str) -> Self {
+        Self {
+            app: Some(app),
+            ..Self::default()
+        }
+    }
+
+    async fn manager(&self) -> Result<&SessionManager, PlayerError> {
+        self.manager
+            .get_or_try_init(|| async {
+                let manager = SessionManager::RequestAsync()?.await?;
+                manager.CurrentSessionChanged(&notify_handler(&self.changed))?;
+                manager.SessionsChanged(&notify_handler(&self.changed))?;
+                Ok::<_, PlayerError>(manager)
+            })
+            .await
+    }
+
+    /// The session of our app, or the one the OS considers current and its media controls act on
+    async fn current_session(&self) -> Result<Session, PlayerError> {
+        let manager = self.manager().await?;
+        let session = match self.app {
+            Some(app) => manager
+                .GetSessions()?
+                .into_iter()
+                .find(|session| {
+                    session
+                        .SourceAppUserModelId()
+                        .is_ok_and(|id| id.to_string_lossy().contains(app))
+                })
+                .ok_or(PlayerError::NothingPlaying)?,
+            None => manager
+                .GetCurrentSession()
+                .map_err(|_| PlayerError::NothingPlaying)?,
+        };
+
+        let mut watched = self.watched.lock().unwrap();
+        if watched.as_ref().is_none_or(|w| w.session != session) {
+            if let Some(previous) = watched.take() {
+                previous.unregister();
+            }
+            *watched = Some(WatchedSession::register(session.clone(), &self.changed)?);
+        }
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl PlayerSource for SmtcSource {
+    #[allow(clippy::cast_precision_loss)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        let session = self.current_session().await?;
+        let is_playing = match session.GetPlaybackInfo()?.PlaybackStatus()? {
+            PlaybackStatus::Playing => true,
+            PlaybackStatus::Paused => false,
+            _ => return Err(PlayerError::NothingPlaying),
+        };
+
+        let properties = session.TryGetMediaPropertiesAsync()?.await?;
+        let title = properties.Title()?.to_string_lossy();
+        if title.is_empty() {
+            return Err(PlayerError::NotATrack);
+        }
+        let artist = properties.Artist()?.to_string_lossy();
+        let app = session.SourceAppUserModelId()?.to_string_lossy();
+
+        // Players only update the timeline every few seconds, extrapolate from when they did
+        let timeline = session.GetTimelineProperties()?;
+        let start = timeline.StartTime()?.Duration;
+        let end = timeline.EndTime()?.Duration;
+        let mut position = timeline.Position()?.Duration - start;
+        if is_playing {
+            position += (now_ticks() - timeline.LastUpdatedTime()?.UniversalTime).max(0);
+        }
+
+        Ok(NowPlaying {
+            track_id: format!("{app}: {artist} - {title}"),
+            spotify_id: None,
+            file_path: None,
+            album: properties.AlbumTitle()?.to_string_lossy(),
+            album_art_url: None,
+            duration_sec: (end - start).max(0) as f64 / 10_000_000.0,
+            title,
+            artist,
+            is_playing,
+            progress_ms: usize::try_from(position / 10_000).unwrap_or(0),
+            received_at: Instant::now(),
+            round_trip: Duration::ZERO,
+        })
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let session = self.current_session().await?;
+        let accepted = match command {
+            PlaybackControl::PlayPause => session.TryTogglePlayPauseAsync()?.await?,
+            PlaybackControl::Next => session.TrySkipNextAsync()?.await?,
+            PlaybackControl::Previous => session.TrySkipPreviousAsync()?.await?,
+        };
+        if accepted {
+            Ok(())
+        } else {
+            Err(PlayerError::Unsupported)
+        }
+    }
+
+    fn changes(&self) -> Option<Arc<Notify>> {
+        Some(self.changed.clone())
+    }
+}
+
+/// Event registrations on a session, removed when another one becomes current
+struct WatchedSession {
+    session: Session,
+    media_properties: i64,
+    playback_info: i64,
+    timeline_properties: i64,
+}
+
+impl WatchedSession {
+    fn register(session: Session, changed: &Arc<Notify>) -> windows::core::Result<Self> {
+        Ok(Self {
+            media_properties: session.MediaPropertiesChanged(&notify_handler(changed))?,
+            playback_info: session.PlaybackInfoChanged(&notify_handler(changed))?,
+            timeline_properties: session.TimelinePropertiesChanged(&notify_handler(changed))?,
+            session,
+        })
+    }
+
+    fn unregister(self) {
+        // The session may already be gone, then so are its handlers
+        let _ = self
+            .session
+            .RemoveMediaPropertiesChanged(self.media_properties);
+        let _ = self.session.RemovePlaybackInfoChanged(self.playback_info);
+        let _ = self
+            .session
+            .RemoveTimelinePropertiesChanged(self.timeline_properties);
+    }
+}
+
+fn notify_handler<S, A>(changed: &Arc<Notify>) -> TypedEventHandler<S, A>
+where
+    S: RuntimeType + 'static,
+    A: RuntimeType + 'static,
+{
+    let changed = changed.clone();
+    TypedEventHandler::new(move |_, _| {
+        changed.notify_one();
+        Ok(())
+    })
+}
+
+/// Current time in the windows `DateTime` format
+fn now_ticks() -> i64 {
+    let since_unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    UNIX_EPOCH_TICKS + i64::try_from(since_unix.as_nanos() / 100).unwrap_or(0)
+}
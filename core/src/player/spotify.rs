@@ -0,0 +1,36 @@
+//! Spotify Web API as a player source
+use async_trait::async_trait;
+
+use super::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+use crate::spotify::{SpotifyClient, SpotifyClientTrackError};
+
+impl From<SpotifyClientTrackError> for PlayerError {
+    fn from(err: SpotifyClientTrackError) -> Self {
+        match err {
+            SpotifyClientTrackError::NotAuthenticated | SpotifyClientTrackError::TokenError => {
+                Self::NotAuthenticated
+            }
+            SpotifyClientTrackError::NotATrack => Self::NotATrack,
+            SpotifyClientTrackError::NoContentResponse => Self::NothingPlaying,
+            SpotifyClientTrackError::RateLimitsExceeded => Self::RateLimitsExceeded,
+            SpotifyClientTrackError::Timeout => Self::Timeout,
+            SpotifyClientTrackError::BadRequest
+            | SpotifyClientTrackError::NotFound
+            | SpotifyClientTrackError::ReqwestError(_) => Self::Other(err.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl PlayerSource for SpotifyClient {
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        self.get_current_track()
+            .await?
+            .into_now_playing()
+            .ok_or(PlayerError::NotATrack)
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        Ok(self.control_playback(command).await?)
+    }
+}
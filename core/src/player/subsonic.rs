@@ -0,0 +1,201 @@
+//! Subsonic API player source (Navidrome, Gonic, Airsonic). The API only tells what each user
+//! is playing and since when, so we estimate the position from when the track was started.
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use md5::{Digest, Md5};
+use reqwest::Client;
+use serde::Deserialize;
+use url::Url;
+
+use crate::http;
+use crate::player::{NowPlaying, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+/// Oldest API version with token authentication
+const API_VERSION: &str = "1.13.0";
+const CLIENT_NAME: &str = "lyrics-overlay";
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(rename = "subsonic-response")]
+    response: SubsonicResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubsonicResponse {
+    error: Option<SubsonicError>,
+    now_playing: Option<NowPlayingList>,
+}
+
+#[derive(Deserialize)]
+struct SubsonicError {
+    code: u32,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct NowPlayingList {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    id: String,
+    title: String,
+    #[serde(default)]
+    artist: String,
+    #[serde(default)]
+    album: String,
+    /// Seconds
+    #[serde(default)]
+    duration: u64,
+    username: String,
+    /// Minutes since the player reported the track as started
+    minutes_ago: u64,
+}
+
+/// Track we're following and our best guess of when it started
+struct Started {
+    id: String,
+    at: Instant,
+}
+
+pub struct SubsonicSource {
+    client: Client,
+    url: String,
+    username: String,
+    password: String,
+    started: Mutex<Option<Started>>,
+}
+
+impl SubsonicSource {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            client: http::client_builder(settings).build().unwrap(),
+            url: settings.subsonic_url.trim_end_matches('/').to_owned(),
+            username: settings.subsonic_username.clone(),
+            password: settings.subsonic_password.clone(),
+            started: Mutex::new(None),
+        }
+    }
+
+    /// Url of an API method, authenticated with a fresh salt each time
+    fn endpoint(&self, method: &str) -> Result<Url, PlayerError> {
+        let mut salt = [0u8; 8];
+        getrandom::fill(&mut salt)
+            .map_err(|err| PlayerError::Other(format!("Failed to generate a salt: {err}")))?;
+        let salt = to_hex(&salt);
+        let token = to_hex(&Md5::digest(format!("{}{salt}", self.password)));
+        Url::parse_with_params(
+            &format!("{}/rest/{method}", self.url),
+            [
+                ("u", self.username.as_str()),
+                ("t", &token),
+                ("s", &salt),
+                ("v", API_VERSION),
+                ("c", CLIENT_NAME),
+                ("f", "json"),
+            ],
+        )
+        .map_err(|err| PlayerError::Other(format!("Invalid Subsonic server url: {err}")))
+    }
+}
+
+#[async_trait]
+impl PlayerSource for SubsonicSource {
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        if self.username.is_empty() {
+            return Err(PlayerError::Other(
+                "Add your Subsonic login in the settings".to_owned(),
+            ));
+        }
+
+        let sent_at = Instant::now();
+        let envelope: Envelope = self
+            .client
+            .get(self.endpoint("getNowPlaying")?)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(http_error)?;
+        let received_at = Instant::now();
+
+        let response = envelope.response;
+        if let Some(err) = response.error {
+            // 40 is wrong credentials, 41 a server that doesn't support tokens
+            return Err(match err.code {
+                40 | 41 => PlayerError::NotAuthenticated,
+                _ => PlayerError::Other(format!("Subsonic: {}", err.message)),
+            });
+        }
+        // Lists what every user of the server plays, keep the latest of ours
+        let entry = response
+            .now_playing
+            .map(|list| list.entry)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| entry.username.eq_ignore_ascii_case(&self.username))
+            .min_by_key(|entry| entry.minutes_ago)
+            .ok_or(PlayerError::NothingPlaying)?;
+
+        let mut started = self.started.lock().unwrap();
+        if started
+            .as_ref()
+            .is_none_or(|started| started.id != entry.id)
+        {
+            // Only whole minutes, the first poll after a track change gets it right
+            let ago = Duration::from_secs(entry.minutes_ago * 60);
+            *started = Some(Started {
+                id: entry.id.clone(),
+                at: received_at.checked_sub(ago).unwrap_or(received_at),
+            });
+        }
+        let elapsed = received_at - started.as_ref().unwrap().at;
+        // Entries outlive the track, until the server times them out
+        if entry.duration > 0 && elapsed.as_secs() >= entry.duration {
+            return Err(PlayerError::NothingPlaying);
+        }
+
+        Ok(NowPlaying {
+            track_id: entry.id,
+            spotify_id: None,
+            file_path: None,
+            title: entry.title,
+            artist: entry.artist,
+            album: entry.album,
+            album_art_url: None,
+            duration_sec: entry.duration as f64,
+            // Pauses aren't reported
+            is_playing: true,
+            progress_ms: elapsed.as_millis() as usize,
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn http_error(err: reqwest::Error) -> PlayerError {
+    if err.is_timeout() {
+        PlayerError::Timeout
+    } else {
+        PlayerError::Other(format!("Subsonic: {err}"))
+    }
+}
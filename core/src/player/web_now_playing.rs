@@ -0,0 +1,220 @@
+//! `WebNowPlaying` source. The browser extension connects to our WebSocket server and pushes
+//! whatever the supported sites in the browser are playing, one field per message.
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Notify, mpsc};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error};
+
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+/// Extension state values
+const STATE_PLAYING: &str = "1";
+const STATE_PAUSED: &str = "2";
+
+/// What one connected browser tab reports
+#[derive(Default)]
+struct TabState {
+    player: String,
+    state: String,
+    title: String,
+    artist: String,
+    album: String,
+    cover: String,
+    duration_sec: f64,
+    position_sec: f64,
+    /// When `position_sec` was last reported
+    position_at: Option<Instant>,
+}
+
+struct Tab {
+    state: TabState,
+    commands: mpsc::UnboundedSender<&'static str>,
+}
+
+#[derive(Default)]
+struct Shared {
+    tabs: Mutex<HashMap<u64, Tab>>,
+    changed: Arc<Notify>,
+    /// Set if the server couldn't start
+    error: Mutex<Option<String>>,
+}
+
+pub struct WebNowPlayingSource {
+    shared: Arc<Shared>,
+}
+
+impl WebNowPlayingSource {
+    /// Also starts the server, so must be called within the runtime
+    pub fn new(settings: &Settings) -> Self {
+        let shared = Arc::new(Shared::default());
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, settings.web_now_playing_port));
+        tokio::spawn(serve(addr, shared.clone()));
+        Self { shared }
+    }
+
+    /// The playing tab that reported last, or a paused one if none is playing
+    fn with_active_tab<T>(&self, f: impl FnOnce(&Tab) -> T) -> Result<T, PlayerError> {
+        if let Some(err) = self.shared.error.lock().unwrap().as_ref() {
+            return Err(PlayerError::Other(err.clone()));
+        }
+        let tabs = self.shared.tabs.lock().unwrap();
+        tabs.values()
+            .filter(|tab| {
+                !tab.state.title.is_empty()
+                    && [STATE_PLAYING, STATE_PAUSED].contains(&tab.state.state.as_str())
+            })
+            .max_by_key(|tab| (tab.state.state == STATE_PLAYING, tab.state.position_at))
+            .map(f)
+            .ok_or(PlayerError::NothingPlaying)
+    }
+}
+
+#[async_trait]
+impl PlayerSource for WebNowPlayingSource {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        self.with_active_tab(|tab| {
+            let state = &tab.state;
+            let received_at = state.position_at.unwrap_or_else(Instant::now);
+            NowPlaying {
+                track_id: format!("{}: {} - {}", state.player, state.artist, state.title),
+                spotify_id: None,
+                file_path: None,
+                title: state.title.clone(),
+                artist: state.artist.clone(),
+                album: state.album.clone(),
+                album_art_url: Some(state.cover.clone()).filter(|url| !url.is_empty()),
+                duration_sec: state.duration_sec,
+                is_playing: state.state == STATE_PLAYING,
+                progress_ms: (state.position_sec * 1000.0).max(0.0) as usize,
+                received_at,
+                round_trip: Duration::ZERO,
+            }
+        })
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let command = match command {
+            PlaybackControl::PlayPause => "playpause",
+            PlaybackControl::Next => "next",
+            PlaybackControl::Previous => "previous",
+        };
+        self.with_active_tab(|tab| tab.commands.send(command))?
+            .map_err(|_| PlayerError::NothingPlaying)
+    }
+
+    fn changes(&self) -> Option<Arc<Notify>> {
+        Some(self.shared.changed.clone())
+    }
+}
+
+async fn serve(addr: SocketAddr, shared: Arc<Shared>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Failed to start the WebNowPlaying server on {addr}: {err}");
+            *shared.error.lock().unwrap() = Some(format!("Port {} is in use", addr.port()));
+            return;
+        }
+    };
+    let mut next_id = 0;
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                next_id += 1;
+                tokio::spawn(handle_tab(stream, next_id, shared.clone()));
+            }
+            Err(err) => debug!("WebNowPlaying connection failed: {err}"),
+        }
+    }
+}
+
+async fn handle_tab(stream: TcpStream, id: u64, shared: Arc<Shared>) {
+    let socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            debug!("WebNowPlaying handshake failed: {err}");
+            return;
+        }
+    };
+    let (mut sink, mut messages) = socket.split();
+    let (commands, mut pending) = mpsc::unbounded_channel();
+    shared.tabs.lock().unwrap().insert(
+        id,
+        Tab {
+            state: TabState::default(),
+            commands,
+        },
+    );
+
+    loop {
+        tokio::select! {
+            Some(command) = pending.recv() => {
+                if sink.send(Message::text(command)).await.is_err() {
+                    break;
+                }
+            }
+            message = messages.next() => {
+                let Some(Ok(message)) = message else { break };
+                let Ok(text) = message.to_text() else { continue };
+                let changed = {
+                    let mut tabs = shared.tabs.lock().unwrap();
+                    tabs.get_mut(&id)
+                        .is_some_and(|tab| apply_message(&mut tab.state, text))
+                };
+                if changed {
+                    shared.changed.notify_one();
+                }
+            }
+        }
+    }
+
+    shared.tabs.lock().unwrap().remove(&id);
+    shared.changed.notify_one();
+}
+
+/// Update the tab with a `KEY:value` message, returns whether the track or state changed
+fn apply_message(state: &mut TabState, message: &str) -> bool {
+    let Some((key, value)) = message.split_once(':') else {
+        return false;
+    };
+    let field = match key.to_ascii_uppercase().as_str() {
+        "PLAYER" => &mut state.player,
+        "STATE" => &mut state.state,
+        "TITLE" => &mut state.title,
+        "ARTIST" => &mut state.artist,
+        "ALBUM" => &mut state.album,
+        "COVER" => &mut state.cover,
+        "DURATION" => {
+            state.duration_sec = parse_time(value);
+            return false;
+        }
+        "POSITION" => {
+            state.position_sec = parse_time(value);
+            state.position_at = Some(Instant::now());
+            return false;
+        }
+        _ => return false,
+    };
+    if field == value {
+        return false;
+    }
+    value.clone_into(field);
+    true
+}
+
+/// Times are sent as `m:ss` or `h:mm:ss`
+fn parse_time(value: &str) -> f64 {
+    value
+        .split(':')
+        .map(|part| part.trim().parse::<f64>().unwrap_or(0.0))
+        .fold(0.0, |total, part| total * 60.0 + part)
+}
@@ -0,0 +1,160 @@
+//! Youtube music desktop app (th-ch/youtube-music), through its API server plugin
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::player::{NowPlaying, PlaybackControl, PlayerError, PlayerSource};
+use crate::settings::Settings;
+
+/// The app runs locally, anything slower than this is hung
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+/// Time the user has to approve our token request in the app
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(30);
+/// Name we request a token under, shown in the app's approval prompt
+const CLIENT_ID: &str = "lyrics-overlay";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SongInfo {
+    title: String,
+    artist: String,
+    album: Option<String>,
+    image_src: Option<String>,
+    video_id: String,
+    is_paused: bool,
+    /// Whole seconds, the playback clock smooths out the steps
+    elapsed_seconds: f64,
+    song_duration: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthResponse {
+    access_token: String,
+}
+
+pub struct YoutubeMusicSource {
+    client: Client,
+    url: String,
+    /// From the settings, or requested when the server first refuses us
+    token: Mutex<Option<String>>,
+}
+
+impl YoutubeMusicSource {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            // Local server, skip the proxy from the settings
+            client: Client::builder()
+                .timeout(CALL_TIMEOUT)
+                .no_proxy()
+                .build()
+                .expect("Client should build"),
+            url: settings.youtube_music_url.trim_end_matches('/').to_owned(),
+            token: Mutex::new(
+                Some(settings.youtube_music_token.clone()).filter(|token| !token.is_empty()),
+            ),
+        }
+    }
+
+    /// Send a request, getting a token first if the server requires one
+    async fn send(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<reqwest::Response, PlayerError> {
+        let response = self.authorize(request()).send().await.map_err(http_error)?;
+        if !matches!(
+            response.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+            return Ok(response);
+        }
+
+        info!("Requesting a YouTube Music API token, approve it in the app");
+        let auth: AuthResponse = self
+            .client
+            .post(format!("{}/auth/{CLIENT_ID}", self.url))
+            .timeout(APPROVAL_TIMEOUT)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| {
+                PlayerError::Other(format!("YouTube Music refused the API token: {err}"))
+            })?
+            .json()
+            .await
+            .map_err(http_error)?;
+        *self.token.lock().unwrap() = Some(auth.access_token);
+
+        self.authorize(request()).send().await.map_err(http_error)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.token.lock().unwrap().as_ref() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl PlayerSource for YoutubeMusicSource {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    async fn now_playing(&self) -> Result<NowPlaying, PlayerError> {
+        let sent_at = Instant::now();
+        let response = self
+            .send(|| self.client.get(format!("{}/api/v1/song", self.url)))
+            .await?;
+        let received_at = Instant::now();
+
+        if response.status() == StatusCode::NO_CONTENT {
+            return Err(PlayerError::NothingPlaying);
+        }
+        let song: SongInfo = response
+            .error_for_status()
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(http_error)?;
+
+        Ok(NowPlaying {
+            track_id: song.video_id,
+            spotify_id: None,
+            file_path: None,
+            title: song.title,
+            artist: song.artist,
+            album: song.album.unwrap_or_default(),
+            album_art_url: song.image_src,
+            duration_sec: song.song_duration,
+            is_playing: !song.is_paused,
+            progress_ms: (song.elapsed_seconds * 1000.0).max(0.0) as usize,
+            received_at,
+            round_trip: received_at - sent_at,
+        })
+    }
+
+    async fn control(&self, command: PlaybackControl) -> Result<(), PlayerError> {
+        let endpoint = match command {
+            PlaybackControl::PlayPause => "toggle-play",
+            PlaybackControl::Next => "next",
+            PlaybackControl::Previous => "previous",
+        };
+        self.send(|| self.client.post(format!("{}/api/v1/{endpoint}", self.url)))
+            .await?
+            .error_for_status()
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn http_error(err: reqwest::Error) -> PlayerError {
+    if err.is_timeout() {
+        PlayerError::Timeout
+    } else {
+        PlayerError::Other(format!("YouTube Music: {err}"))
+    }
+}
@@ -0,0 +1,85 @@
+//! Masking of profanity in the shown lyrics, the cached lyrics are left as they are
+
+use std::borrow::Cow;
+
+/// Default word list, a trailing `*` also matches longer words
+pub const DEFAULT_PROFANITY: [&str; 12] = [
+    "fuck*",
+    "motherfuck*",
+    "shit*",
+    "bitch*",
+    "cunt*",
+    "asshole*",
+    "dick",
+    "dicks",
+    "pussy",
+    "whore*",
+    "nigga*",
+    "bastard*",
+];
+
+/// `text` with every listed word masked as `f**k`
+pub fn mask_profanity<'a>(text: &'a str, words: &[String]) -> Cow<'a, str> {
+    let mut masked = String::new();
+    let mut copied = 0;
+    let mut word_start = None;
+    // A trailing space ends the last word
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (word_start, c.is_alphanumeric()) {
+            (None, true) => word_start = Some(i),
+            (Some(start), false) => {
+                word_start = None;
+                let word = &text[start..i];
+                if is_listed(word, words) {
+                    masked.push_str(&text[copied..start]);
+                    masked.push_str(&mask(word));
+                    copied = i;
+                }
+            }
+            _ => {}
+        }
+    }
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    masked.push_str(&text[copied..]);
+    Cow::Owned(masked)
+}
+
+fn is_listed(word: &str, words: &[String]) -> bool {
+    let word = word.to_lowercase();
+    words.iter().any(|listed| {
+        let listed = listed.trim().to_lowercase();
+        match listed.strip_suffix('*') {
+            Some(prefix) => !prefix.is_empty() && word.starts_with(prefix),
+            None => !listed.is_empty() && word == listed,
+        }
+    })
+}
+
+/// Keeps the first and last letter
+fn mask(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    match chars.as_slice() {
+        [first, middle @ .., last] if !middle.is_empty() => {
+            format!("{first}{}{last}", "*".repeat(middle.len()))
+        }
+        _ => "*".repeat(chars.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mask_profanity;
+
+    #[test]
+    fn masks_listed_words() {
+        let words = vec!["fuck*".to_owned(), "damn".to_owned()];
+        assert_eq!(mask_profanity("Fuck it, damn!", &words), "F**k it, d**n!");
+        assert_eq!(
+            mask_profanity("so fucking damned", &words),
+            "so f*****g damned"
+        );
+        assert_eq!(mask_profanity("nothing here", &words), "nothing here");
+    }
+}
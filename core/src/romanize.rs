@@ -0,0 +1,420 @@
+//! Romanization of japanese kana, chinese hanzi and korean hangul, shown under the original lines,
+//! and the reading hints shown above them
+
+use pinyin::ToPinyin;
+
+use crate::lyrics_parser::RubySegment;
+
+/// Hangul initials in unicode order
+const HANGUL_INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+/// Index of the silent initial ㅇ
+const SILENT_INITIAL: usize = 11;
+/// Index of the final ㄹ
+const FINAL_RIEUL: usize = 8;
+const HANGUL_MEDIALS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+/// Hangul finals as pronounced at the end of a syllable, and as the next syllable's initial
+/// when that starts with a vowel. Clusters stay put
+const HANGUL_FINALS: [(&str, Option<&str>); 28] = [
+    ("", None),
+    ("k", Some("g")),
+    ("k", Some("kk")),
+    ("k", None),
+    ("n", Some("n")),
+    ("n", None),
+    ("n", None),
+    ("t", Some("d")),
+    ("l", Some("r")),
+    ("k", None),
+    ("m", None),
+    ("l", None),
+    ("l", None),
+    ("l", None),
+    ("p", None),
+    ("l", None),
+    ("m", Some("m")),
+    ("p", Some("b")),
+    ("p", None),
+    ("t", Some("s")),
+    ("t", Some("ss")),
+    ("ng", None),
+    ("t", Some("j")),
+    ("t", Some("ch")),
+    ("k", Some("k")),
+    ("t", Some("t")),
+    ("p", Some("p")),
+    ("t", Some("")),
+];
+
+/// Romanized `text`, `None` if it has nothing to romanize.
+/// Kanji in japanese lines are kept, reading them needs a dictionary
+pub fn romanize(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if !chars
+        .iter()
+        .any(|&c| is_kana(c) || is_hangul(c) || is_han(c))
+    {
+        return None;
+    }
+    // Kanji shouldn't get chinese readings
+    let japanese = chars.iter().any(|&c| is_kana(c));
+
+    let mut out = String::new();
+    // Final consonant of the previous hangul syllable, carried over to a vowel
+    let mut carried: Option<&str> = None;
+    let mut prev_final = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if is_hangul(c) {
+            let (initial, medial, final_) = decompose_hangul(c);
+            match carried.take() {
+                Some(consonant) if initial == SILENT_INITIAL => out.push_str(consonant),
+                // ㄹㄹ is pronounced l-l
+                _ if initial == 5 && prev_final == FINAL_RIEUL => out.push('l'),
+                _ => out.push_str(HANGUL_INITIALS[initial]),
+            }
+            out.push_str(HANGUL_MEDIALS[medial]);
+            let next_is_vowel = chars
+                .get(i + 1)
+                .is_some_and(|&next| is_hangul(next) && decompose_hangul(next).0 == SILENT_INITIAL);
+            match HANGUL_FINALS[final_] {
+                (_, Some(as_initial)) if next_is_vowel => carried = Some(as_initial),
+                (at_end, _) => out.push_str(at_end),
+            }
+            prev_final = final_;
+            i += 1;
+            continue;
+        }
+        carried = None;
+        prev_final = 0;
+
+        if is_kana(c) {
+            i += push_kana(&chars[i..], &mut out);
+            continue;
+        }
+        match c.to_pinyin() {
+            Some(pinyin) if !japanese => {
+                out.push(' ');
+                out.push_str(pinyin.with_tone());
+                out.push(' ');
+            }
+            _ => match punctuation(c) {
+                Some(mapped) => out.push_str(mapped),
+                None => out.push(c),
+            },
+        }
+        i += 1;
+    }
+    Some(tidy(&out))
+}
+
+/// Splits aozora style ruby markup, `｜漢字《かんじ》` or `漢字《かんじ》` where the reading belongs
+/// to the kanji right before it. `None` if there is no markup
+pub fn ruby_markup(text: &str) -> Option<Vec<RubySegment>> {
+    if !text.contains('《') {
+        return None;
+    }
+    let mut segments = Vec::new();
+    let mut plain = String::new();
+    let mut base_start = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '｜' => base_start = Some(plain.len()),
+            '《' => {
+                let reading: String = chars.by_ref().take_while(|&c| c != '》').collect();
+                let start = base_start.take().unwrap_or_else(|| {
+                    plain
+                        .char_indices()
+                        .rev()
+                        .take_while(|&(_, c)| is_han(c))
+                        .last()
+                        .map_or(plain.len(), |(i, _)| i)
+                });
+                let base = plain.split_off(start);
+                if base.is_empty() {
+                    plain.push_str(&reading);
+                    continue;
+                }
+                push_plain(&mut segments, &mut plain);
+                segments.push(RubySegment {
+                    text: base,
+                    reading: Some(reading),
+                });
+            }
+            _ => plain.push(c),
+        }
+    }
+    push_plain(&mut segments, &mut plain);
+    Some(segments)
+}
+
+/// Pinyin over every hanzi of a chinese line, `None` for anything else
+pub fn pinyin_ruby(text: &str) -> Option<Vec<RubySegment>> {
+    if text.chars().any(is_kana) || !text.chars().any(is_han) {
+        return None;
+    }
+    let mut segments = Vec::new();
+    let mut plain = String::new();
+    for c in text.chars() {
+        match c.to_pinyin() {
+            Some(pinyin) => {
+                push_plain(&mut segments, &mut plain);
+                segments.push(RubySegment {
+                    text: c.to_string(),
+                    reading: Some(pinyin.with_tone().to_owned()),
+                });
+            }
+            None => plain.push(c),
+        }
+    }
+    push_plain(&mut segments, &mut plain);
+    Some(segments)
+}
+
+/// Moves text without a reading into its own segment
+fn push_plain(segments: &mut Vec<RubySegment>, plain: &mut String) {
+    if !plain.is_empty() {
+        segments.push(RubySegment {
+            text: std::mem::take(plain),
+            reading: None,
+        });
+    }
+}
+
+fn is_hangul(c: char) -> bool {
+    ('\u{AC00}'..='\u{D7A3}').contains(&c)
+}
+
+fn is_kana(c: char) -> bool {
+    ('\u{3041}'..='\u{3096}').contains(&c) || ('\u{30A1}'..='\u{30F6}').contains(&c) || c == 'ー'
+}
+
+fn is_han(c: char) -> bool {
+    ('\u{4E00}'..='\u{9FFF}').contains(&c)
+        || ('\u{3400}'..='\u{4DBF}').contains(&c)
+        || ('\u{F900}'..='\u{FAFF}').contains(&c)
+}
+
+/// Initial, medial and final indices of a hangul syllable
+fn decompose_hangul(c: char) -> (usize, usize, usize) {
+    let index = c as usize - 0xAC00;
+    (index / (21 * 28), index % (21 * 28) / 28, index % 28)
+}
+
+fn to_hiragana(c: char) -> char {
+    if ('\u{30A1}'..='\u{30F6}').contains(&c) {
+        char::from_u32(c as u32 - 0x60).unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+/// Romanizes the kana at the start of `chars`, returns how many were used
+fn push_kana(chars: &[char], out: &mut String) -> usize {
+    match to_hiragana(chars[0]) {
+        // Small tsu doubles the next consonant
+        'っ' => {
+            let mut next = String::new();
+            let used = match chars.get(1) {
+                Some(&c) if is_kana(c) => push_kana(&chars[1..], &mut next),
+                _ => 0,
+            };
+            if next.starts_with("ch") {
+                out.push('t');
+            } else if let Some(consonant) = next.chars().next().filter(|c| !"aeiou".contains(*c)) {
+                out.push(consonant);
+            }
+            out.push_str(&next);
+            return 1 + used;
+        }
+        // Long vowel mark repeats the vowel
+        'ー' => {
+            if let Some(vowel) = out.chars().rev().find(|c| "aeiou".contains(*c)) {
+                out.push(vowel);
+            }
+            return 1;
+        }
+        _ => {}
+    }
+
+    let Some(base) = kana_romaji(to_hiragana(chars[0])) else {
+        out.push(chars[0]);
+        return 1;
+    };
+    let small = chars.get(1).copied().map(to_hiragana);
+    match small {
+        // きゃ is kya, しゃ is sha
+        Some(small @ ('ゃ' | 'ゅ' | 'ょ')) if base.len() > 1 && base.ends_with('i') => {
+            let stem = &base[..base.len() - 1];
+            out.push_str(stem);
+            if !(stem.ends_with("sh") || stem.ends_with("ch") || stem == "j") {
+                out.push('y');
+            }
+            out.push_str(&kana_romaji(small).unwrap_or_default()[1..]);
+            2
+        }
+        // ファ is fa, ウィ is wi
+        Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')) if base.len() > 1 || base == "u" => {
+            out.push_str(if base == "u" {
+                "w"
+            } else {
+                &base[..base.len() - 1]
+            });
+            out.push_str(kana_romaji(small).unwrap_or_default());
+            2
+        }
+        _ => {
+            out.push_str(base);
+            1
+        }
+    }
+}
+
+/// Hepburn romanization of a single hiragana
+fn kana_romaji(c: char) -> Option<&'static str> {
+    Some(match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' | 'ゐ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' | 'ゑ' => "e",
+        'お' | 'ぉ' | 'を' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    })
+}
+
+/// ASCII stand-in for CJK punctuation
+fn punctuation(c: char) -> Option<&'static str> {
+    Some(match c {
+        '、' | '，' => ", ",
+        '。' => ". ",
+        '！' => "! ",
+        '？' => "? ",
+        '「' | '」' | '『' | '』' => "\"",
+        '（' => "(",
+        '）' => ")",
+        '～' => "~",
+        '　' | '・' => " ",
+        _ => return None,
+    })
+}
+
+/// Single spaces, none before punctuation
+fn tidy(romanized: &str) -> String {
+    let mut tidy = String::new();
+    for word in romanized.split_whitespace() {
+        if !tidy.is_empty() && !word.starts_with([',', '.', '!', '?', ')']) {
+            tidy.push(' ');
+        }
+        tidy.push_str(word);
+    }
+    tidy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{romanize, ruby_markup};
+    use crate::lyrics_parser::RubySegment;
+
+    #[test]
+    fn romanizes_each_script() {
+        assert_eq!(romanize("Never gonna give you up"), None);
+        assert_eq!(romanize("きっと ちょっと").as_deref(), Some("kitto chotto"));
+        assert_eq!(romanize("コーヒー、ファン").as_deref(), Some("koohii, fan"));
+        assert_eq!(
+            romanize("사랑해 한국어").as_deref(),
+            Some("saranghae hangugeo")
+        );
+        assert_eq!(romanize("我爱你。").as_deref(), Some("wǒ ài nǐ."));
+        // Kanji are kept in japanese lines
+        assert_eq!(romanize("君の名は").as_deref(), Some("君no名ha"));
+    }
+
+    #[test]
+    fn splits_ruby_markup() {
+        let segment = |text: &str, reading: Option<&str>| RubySegment {
+            text: text.to_owned(),
+            reading: reading.map(str::to_owned),
+        };
+        assert_eq!(ruby_markup("君の名は"), None);
+        assert_eq!(
+            ruby_markup("君《きみ》の｜名前《なまえ》は"),
+            Some(vec![
+                segment("君", Some("きみ")),
+                segment("の", None),
+                segment("名前", Some("なまえ")),
+                segment("は", None),
+            ])
+        );
+    }
+}
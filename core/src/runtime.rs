@@ -0,0 +1,419 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex as TokioMutex;
+use tokio::sync::Notify;
+use tokio::sync::RwLock as TokioRwLock;
+use tokio::sync::mpsc;
+
+use tracing::info;
+use tracing::{debug, trace, warn};
+
+use crate::MessageToRT;
+use crate::MessageToUI;
+use crate::StatusEvent;
+use crate::album_art::fetch_palette;
+use crate::http;
+use crate::lyrics_fetch::LyricsFetcher;
+use crate::lyrics_fetch::LyricsFetcherErr;
+use crate::lyrics_fetch::LyricsRequestInfo;
+use crate::oauth::CallbackError;
+use crate::player::PlaybackControl;
+use crate::player::PlayerError;
+use crate::player::PlayerSource;
+use crate::player::create_source;
+use crate::player::poller::PlayerPoller;
+use crate::player::poller::process_now_playing;
+use crate::player::poller::process_queue_response;
+use crate::settings::Settings;
+use crate::spotify::SpotifyClient;
+use crate::spotify::SpotifyClientTrackError;
+use crate::spotify::auth::SpotifyAuthClient;
+use crate::spotify::auth::SpotifyClientAuthError;
+use crate::spotify::parse_playlist_id;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RuntimeError {
+    #[error("Authentication failed: {0}")]
+    AuthenticationFailed(#[from] SpotifyClientAuthError),
+    #[error("Getting lyrics failed: {0}")]
+    GetFailed(#[from] LyricsFetcherErr),
+    #[error("Playback control failed: {0}")]
+    PlayerControl(#[from] PlayerError),
+    #[error("Player login failed: {0}")]
+    PlayerLogin(PlayerError),
+    #[error("Publishing lyrics failed: {0}")]
+    PublishFailed(LyricsFetcherErr),
+    #[error("Not a spotify playlist: {0}")]
+    InvalidPlaylist(String),
+    #[error("Getting the playlist failed: {0}")]
+    PlaylistFailed(SpotifyClientTrackError),
+    #[error("Translating the lyrics failed: {0}")]
+    TranslationFailed(LyricsFetcherErr),
+    #[error("Cache archive failed: {0}")]
+    CacheArchive(std::io::Error),
+}
+
+/// Struct to possibly allow handling different types of messages in a send or receive loop
+#[derive(Debug)]
+pub struct Messages {
+    to_ui: Option<MessageToUI>,
+    /// Sent after `to_ui`, for the status panel
+    status: Option<StatusEvent>,
+}
+impl Messages {
+    pub fn to_ui(to_ui: MessageToUI) -> Self {
+        Self {
+            to_ui: Some(to_ui),
+            status: None,
+        }
+    }
+    pub fn none() -> Self {
+        Self {
+            to_ui: None,
+            status: None,
+        }
+    }
+    #[must_use]
+    pub fn with_status(mut self, status: StatusEvent) -> Self {
+        self.status = Some(status);
+        self
+    }
+    pub async fn send(self, tx_to_ui: mpsc::Sender<MessageToUI>) {
+        if let Some(message_ui) = self.to_ui {
+            tx_to_ui.send(message_ui).await.unwrap();
+        }
+        if let Some(status) = self.status {
+            tx_to_ui.send(MessageToUI::Status(status)).await.unwrap();
+        }
+    }
+}
+
+pub async fn start_runtime(
+    tx_to_ui: mpsc::Sender<MessageToUI>,
+    tx_to_rt: mpsc::Sender<MessageToRT>,
+    mut rx: mpsc::Receiver<MessageToRT>,
+    settings: Arc<TokioRwLock<Settings>>,
+) {
+    info!("Runtime started");
+    let spotify_auth_client = Arc::new(TokioMutex::new(SpotifyAuthClient::new(settings.clone())));
+
+    let (token_handle, cancel_auth) = {
+        let auth_lock = spotify_auth_client.lock().await;
+        (
+            auth_lock.retreive_token_handle().clone(),
+            auth_lock.retreive_cancel_handle(),
+        )
+    };
+    // Network settings and the player source are only applied on startup
+    let settings_snapshot = settings.read().await.clone();
+    let spotify_client = Arc::new(SpotifyClient::new(token_handle, &settings_snapshot));
+    let lyrics_fetcher = Arc::new(LyricsFetcher::new(settings.clone(), &settings_snapshot));
+    let player_source = create_source(&settings, spotify_client.clone()).await;
+    let art_client = http::client_builder(&settings_snapshot).build().unwrap();
+
+    // Spawn a thread for our player poller
+    let poller = PlayerPoller::new(
+        player_source.clone(),
+        spotify_client.clone(),
+        settings.clone(),
+    );
+    tokio::spawn(poller.run(tx_to_ui.clone(), tx_to_rt.clone()));
+    tokio::spawn(lyrics_fetcher.clone().maintain_cache());
+
+    if settings.read().await.auto_auth
+        && !settings.read().await.client_id.is_empty()
+        && !settings.read().await.client_secret.is_empty()
+    {
+        tx_to_rt.send(MessageToRT::Authenticate).await.unwrap();
+    }
+
+    while let Some(msg) = rx.recv().await {
+        let tx_ui = tx_to_ui.clone();
+        let auth = spotify_auth_client.clone();
+        let client = spotify_client.clone();
+        let lyrics = lyrics_fetcher.clone();
+        let cancel = cancel_auth.clone();
+        let source = player_source.clone();
+        let art_client = art_client.clone();
+
+        // Start a new thread which handles our message, and the required response.
+        // A message returns a (MessageToUI, and a MessageToRT), so an action can
+        // trigger an update of the UI, or trigger a new action.
+        tokio::spawn(async move {
+            let res = match msg {
+                MessageToRT::Authenticate => authenticate(auth, client, &tx_ui, false).await,
+                MessageToRT::AddAccount => authenticate(auth, client, &tx_ui, true).await,
+                MessageToRT::SwitchAccount(index) => {
+                    switch_account(auth, client, &tx_ui, index).await
+                }
+                MessageToRT::CancelAuth => Ok(cancel_authentication(&cancel)),
+                MessageToRT::RemoveAccount(index) => remove_account(auth, index).await,
+                MessageToRT::InvalidateToken => invalidate(auth).await,
+                MessageToRT::Resync => resync(auth, source).await,
+                MessageToRT::GetCurrentTrack => Ok(get_current_track(source).await),
+                MessageToRT::PlayerControl(command) => player_control(source, command).await,
+                MessageToRT::PlayerLogin => player_login(source, cancel).await,
+                MessageToRT::GetQueue => get_queue(client).await,
+                MessageToRT::GetAudioFeatures(id) => get_audio_features(client, &id).await,
+                MessageToRT::GetAudioAnalysis(id) => get_audio_analysis(client, &id).await,
+                MessageToRT::GetAlbumPalette(url) => get_album_palette(&art_client, url).await,
+                MessageToRT::GetLyrics(request) => lyrics.get_lyrics(request, false).await,
+                MessageToRT::RefetchLyrics(request) => lyrics.get_lyrics(request, true).await,
+                MessageToRT::PublishLyrics(request) => lyrics.publish(request).await,
+                MessageToRT::SearchLyrics(query) => lyrics.search_lrclib(query).await,
+                MessageToRT::ChooseLyrics(request, id) => lyrics.choose_lrclib(request, id).await,
+                MessageToRT::RejectLyrics(request, source) => {
+                    lyrics.reject_lyrics(request, source).await
+                }
+                MessageToRT::PinLyrics(request, pinned) => lyrics.pin_lyrics(request, pinned).await,
+                MessageToRT::TranslateLyrics(request, lines) => {
+                    lyrics.translate(request, lines).await
+                }
+                MessageToRT::ListCache => lyrics.list_cache().await,
+                MessageToRT::ClearCache(folders) => lyrics.clear_cache(folders).await,
+                MessageToRT::ExportCache(path) => lyrics.export_cache(path).await,
+                MessageToRT::ImportCache(path) => lyrics.import_cache(path).await,
+                MessageToRT::PrefetchPlaylist(playlist) => {
+                    prefetch_playlist(client, lyrics, &tx_ui, &playlist).await
+                }
+            };
+
+            match res {
+                Ok(msg) => {
+                    msg.send(tx_ui).await;
+                }
+                Err(x) => {
+                    tx_ui
+                        .send(MessageToUI::DisplayError(format!("{x}")))
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+    }
+    trace!("Reached end of runtime");
+}
+
+async fn get_current_track(source: Arc<dyn PlayerSource>) -> Messages {
+    process_now_playing(source.now_playing().await)
+}
+
+async fn player_control(
+    source: Arc<dyn PlayerSource>,
+    command: PlaybackControl,
+) -> Result<Messages, RuntimeError> {
+    debug!("Sending {command:?} to the player");
+    source.control(command).await?;
+    // Show the new state right away instead of waiting for the next poll
+    Ok(get_current_track(source).await)
+}
+
+async fn player_login(
+    source: Arc<dyn PlayerSource>,
+    cancel: Arc<Notify>,
+) -> Result<Messages, RuntimeError> {
+    source
+        .login(cancel)
+        .await
+        .map_err(RuntimeError::PlayerLogin)?;
+    Ok(get_current_track(source).await)
+}
+
+async fn get_queue(spotify_client: Arc<SpotifyClient>) -> Result<Messages, RuntimeError> {
+    Ok(process_queue_response(spotify_client.get_queue().await))
+}
+
+/// Caches the lyrics of every track in the playlist, reporting progress as it goes
+async fn prefetch_playlist(
+    spotify_client: Arc<SpotifyClient>,
+    lyrics_fetcher: Arc<LyricsFetcher>,
+    tx_ui: &mpsc::Sender<MessageToUI>,
+    playlist: &str,
+) -> Result<Messages, RuntimeError> {
+    let playlist_id = parse_playlist_id(playlist)
+        .ok_or_else(|| RuntimeError::InvalidPlaylist(playlist.to_owned()))?;
+    let tracks = spotify_client
+        .get_playlist_tracks(playlist_id)
+        .await
+        .map_err(RuntimeError::PlaylistFailed)?;
+    info!("Prefetching lyrics for {} tracks", tracks.len());
+
+    let total = tracks.len();
+    let mut found = 0;
+    for (done, track) in tracks.iter().enumerate() {
+        tx_ui
+            .send(MessageToUI::PrefetchProgress { done, total, found })
+            .await
+            .unwrap();
+        if lyrics_fetcher
+            .prefetch(LyricsRequestInfo::from_playlist_track(track))
+            .await
+        {
+            found += 1;
+        }
+    }
+    info!("Prefetched lyrics for {found} of {total} tracks");
+    Ok(Messages::to_ui(MessageToUI::PrefetchProgress {
+        done: total,
+        total,
+        found,
+    }))
+}
+
+async fn get_audio_features(
+    spotify_client: Arc<SpotifyClient>,
+    spotify_id: &str,
+) -> Result<Messages, RuntimeError> {
+    match spotify_client.get_audio_features(spotify_id).await {
+        Ok(features) => Ok(Messages::to_ui(MessageToUI::AudioFeatures(features))
+            .with_status(StatusEvent::ApiCallSucceeded)),
+        Err(err) => {
+            // Not all apps have access to this endpoint, fall back to the static theme
+            warn!("Failed to get audio features for {spotify_id}: {err}");
+            Ok(Messages::none())
+        }
+    }
+}
+
+async fn get_album_palette(
+    client: &reqwest::Client,
+    url: String,
+) -> Result<Messages, RuntimeError> {
+    match fetch_palette(client, &url).await {
+        Ok(palette) => Ok(Messages::to_ui(MessageToUI::AlbumPalette(url, palette))),
+        Err(err) => {
+            // Keep the theme's colors
+            warn!("Failed to get the album art colors from {url}: {err}");
+            Ok(Messages::none())
+        }
+    }
+}
+
+async fn get_audio_analysis(
+    spotify_client: Arc<SpotifyClient>,
+    spotify_id: &str,
+) -> Result<Messages, RuntimeError> {
+    match spotify_client.get_audio_analysis(spotify_id).await {
+        Ok(analysis) => Ok(Messages::to_ui(MessageToUI::AudioAnalysis(analysis))
+            .with_status(StatusEvent::ApiCallSucceeded)),
+        Err(err) => {
+            // Not all apps have access to this endpoint, just don't pulse
+            warn!("Failed to get audio analysis for {spotify_id}: {err}");
+            Ok(Messages::none())
+        }
+    }
+}
+
+async fn authenticate(
+    spotify_auth_client: Arc<TokioMutex<SpotifyAuthClient>>,
+    spotify_client: Arc<SpotifyClient>,
+    tx_ui: &mpsc::Sender<MessageToUI>,
+    new_account: bool,
+) -> Result<Messages, RuntimeError> {
+    debug!("Starting authentication");
+    let res = spotify_auth_client
+        .lock()
+        .await
+        .authenticate(new_account, Some(tx_ui))
+        .await;
+    match res {
+        Ok(()) => {
+            // Name the account for the account switcher
+            match spotify_client.get_current_user().await {
+                Ok(user) => {
+                    let name = user.display_name.unwrap_or_else(|| user.id.clone());
+                    spotify_auth_client
+                        .lock()
+                        .await
+                        .set_active_profile(user.id, name)
+                        .await?;
+                }
+                Err(err) => warn!("Failed to get the profile of the authenticated user: {err}"),
+            }
+            let expiry = spotify_auth_client.lock().await.token_expiry_unix().await;
+            Ok(
+                Messages::to_ui(MessageToUI::AuthenticationStateUpdate(true))
+                    .with_status(StatusEvent::TokenExpiry(expiry)),
+            )
+        }
+        Err(SpotifyClientAuthError::Callback(CallbackError::Cancelled)) => Ok(Messages::to_ui(
+            MessageToUI::AuthenticationFailed(String::new()),
+        )),
+        Err(err) => Ok(Messages::to_ui(MessageToUI::AuthenticationFailed(
+            RuntimeError::AuthenticationFailed(err).to_string(),
+        ))),
+    }
+}
+
+/// Cancel a running authentication, this can't wait for the auth client lock as authenticating holds it
+fn cancel_authentication(cancel_auth: &Notify) -> Messages {
+    debug!("Cancelling authentication");
+    cancel_auth.notify_waiters();
+    Messages::none()
+}
+
+async fn switch_account(
+    spotify_auth_client: Arc<TokioMutex<SpotifyAuthClient>>,
+    spotify_client: Arc<SpotifyClient>,
+    tx_ui: &mpsc::Sender<MessageToUI>,
+    index: usize,
+) -> Result<Messages, RuntimeError> {
+    debug!("Switching to account {index}");
+    spotify_auth_client
+        .lock()
+        .await
+        .switch_account(index)
+        .await?;
+    authenticate(spotify_auth_client, spotify_client, tx_ui, false).await
+}
+
+async fn remove_account(
+    spotify_auth_client: Arc<TokioMutex<SpotifyAuthClient>>,
+    index: usize,
+) -> Result<Messages, RuntimeError> {
+    debug!("Removing account {index}");
+    if spotify_auth_client
+        .lock()
+        .await
+        .remove_account(index)
+        .await?
+    {
+        Ok(
+            Messages::to_ui(MessageToUI::AuthenticationStateUpdate(false))
+                .with_status(StatusEvent::TokenExpiry(None)),
+        )
+    } else {
+        Ok(Messages::none())
+    }
+}
+
+/// Refresh the token if it expired while we slept, then poll right away instead of
+/// extrapolating the progress from before the sleep
+async fn resync(
+    spotify_auth_client: Arc<TokioMutex<SpotifyAuthClient>>,
+    source: Arc<dyn PlayerSource>,
+) -> Result<Messages, RuntimeError> {
+    let auth = spotify_auth_client.lock().await;
+    if auth.token_expired().await {
+        debug!("Access token expired during sleep, refreshing");
+        // Sources other than Spotify don't need the token, poll them anyway
+        if let Err(err) = auth.refresh_access_token().await {
+            warn!("Failed refreshing the access token after sleep: {err:?}");
+        }
+    }
+    drop(auth);
+    Ok(get_current_track(source).await)
+}
+
+async fn invalidate(
+    spotify_auth_client: Arc<TokioMutex<SpotifyAuthClient>>,
+) -> Result<Messages, RuntimeError> {
+    debug!("Invalidating authentication");
+    spotify_auth_client.lock().await.invalidate_token().await;
+    Ok(
+        Messages::to_ui(MessageToUI::AuthenticationStateUpdate(false))
+            .with_status(StatusEvent::TokenExpiry(None)),
+    )
+}
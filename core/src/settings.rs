@@ -0,0 +1,542 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use config::{Config, ConfigError, Environment, File};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::lyrics_fetch::{LyricsProviderKind, TranslationBackendKind};
+use crate::paths;
+use crate::player::PlayerSourceKind;
+use crate::profanity::DEFAULT_PROFANITY;
+
+//TODO Split settings into multiple sub-structs
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Host for the OAuth server
+    pub host: String,
+    /// Port for the OAuth server, 0 for any free port
+    pub port: u16,
+    /// Number of ports after `port` to try if it is in use
+    pub port_fallback_range: u16,
+    /// Log in to spotify in an embedded window instead of the browser, needs the `webview-auth` feature
+    pub embedded_auth: bool,
+    /// sp dc, do not use!
+    pub sp_dc: String,
+    /// Spotify client id
+    pub client_id: String,
+    /// Spotify client secret
+    pub client_secret: String,
+    /// Authenticated spotify accounts
+    pub accounts: Vec<SpotifyAccount>,
+    /// Index of the account currently in use
+    pub active_account: usize,
+    /// Scopes requested when authenticating
+    pub oauth_scopes: OAuthScopes,
+    /// Where we get the currently playing track from
+    pub player_source: PlayerSourceKind,
+    /// MPD server to follow
+    pub mpd_host: String,
+    pub mpd_port: u16,
+    /// Empty if the server needs none
+    pub mpd_password: String,
+    /// MPD's music directory, lets us read lyrics from the playing file's tags
+    pub mpd_music_dir: String,
+    /// API server of the youtube music desktop app
+    pub youtube_music_url: String,
+    /// Token for the API server, requested from the app if empty
+    pub youtube_music_token: String,
+    /// Deezer app credentials, from the deezer developer portal
+    pub deezer_app_id: String,
+    pub deezer_secret: String,
+    /// Deezer access token, doesn't expire
+    pub deezer_token: String,
+    /// Plex media server to follow
+    pub plex_url: String,
+    pub plex_token: String,
+    /// Subsonic server to follow, and the account whose playback we show
+    pub subsonic_url: String,
+    pub subsonic_username: String,
+    pub subsonic_password: String,
+    /// Port the `WebNowPlaying` extension connects to
+    pub web_now_playing_port: u16,
+    /// Home Assistant instance, long-lived access token and the `media_player` entity to follow
+    pub home_assistant_url: String,
+    pub home_assistant_token: String,
+    pub home_assistant_entity: String,
+    /// `AcoustID` application key for the fingerprint source
+    pub acoustid_key: String,
+    /// Chromaprint's fpcalc, empty to find it on the path
+    pub fpcalc_path: String,
+    /// Only follow MPRIS players whose name contains this, empty for any
+    pub mpris_player: String,
+    /// Legacy spotify refresh token, moved into `accounts`
+    #[serde(skip_serializing)]
+    refresh_token: Option<String>,
+    /// Legacy spotify access token, moved into `accounts`
+    #[serde(skip_serializing)]
+    access_token: Option<String>,
+    /// Legacy spotify token expiry date/time, moved into `accounts`
+    #[serde(skip_serializing)]
+    expiry_time_as_unix: Option<u64>,
+    /// Proxy for all requests (http, https or socks5 url), empty for none
+    pub proxy_url: String,
+    /// Proxy username, empty for none
+    pub proxy_username: String,
+    /// Proxy password
+    pub proxy_password: String,
+    /// Time (ms) to wait for a connection to be established
+    pub connect_timeout_ms: u64,
+    /// Time (ms) to wait for a complete response
+    pub request_timeout_ms: u64,
+    /// Time (seconds) to wait for the user to log in to spotify in the browser
+    pub auth_timeout_sec: u64,
+    /// Authenticate on startup
+    pub auto_auth: bool,
+    /// Log level for all logs
+    pub log_level: String,
+    /// Layout of the lyrics
+    pub display_mode: DisplayMode,
+    /// Scroll lines too long for the ticker sideways
+    pub ticker_marquee: bool,
+    /// Background opacity 0.0–1.0
+    pub opacity: f32,
+    /// Font size for the active lyric line (px)
+    pub font_size: f32,
+    /// Font files tried before the built-in fonts, in this order
+    pub font_files: Vec<String>,
+    /// Line spacing
+    pub line_spacing: f32,
+    /// Window position and size as `[x, y, width, height]`, by the size of the monitor it's on
+    pub window_placements: BTreeMap<String, [f32; 4]>,
+    /// Monitor the window was last on, its placement is restored on startup
+    pub last_monitor: String,
+    /// Lyrics providers, tried in this order until one has synced lyrics. Unsynced ones are kept as a fallback
+    pub lyrics_providers: Vec<LyricsProviderKind>,
+    /// Folders searched for lrc files by the local provider, including subfolders
+    pub lrc_folders: Vec<String>,
+    /// Musixmatch desktop app user token
+    pub musixmatch_token: String,
+    /// How far off a search result's duration may be, alternate masters differ by a few seconds
+    pub duration_tolerance_sec: f64,
+    /// Do we cache found lyrics
+    pub caching_enabled: bool,
+    /// Folder in which we store cached lyrics
+    pub cache_folder: String,
+    /// How long tracks without lyrics are remembered before the providers are asked again
+    pub not_found_ttl_hours: u64,
+    /// Cached lrclib lyrics older than this are refetched in the background, 0 never refreshes
+    pub cache_refresh_days: u64,
+    /// Size the cache is kept under by dropping the least recently used tracks, 0 for no limit
+    pub cache_max_mb: u64,
+    /// Dim lines that are far from the current line
+    pub dim_distant_lines: bool,
+    /// Fill in the current line word by word when the lyrics have word timings
+    pub word_highlight: bool,
+    /// Sweep across the current line by the time to the next one when it has no word timings
+    pub line_sweep: bool,
+    /// Tint duet lines by who sings them
+    pub color_duet_parts: bool,
+    /// How often (seconds) to poll Spotify for the current track
+    pub poll_interval_ms: u64,
+    /// Fraction of the request round trip added to spotify's progress, 0.5 assumes symmetric latency
+    pub latency_compensation: f32,
+    /// Show the next track in the queue near the end of the current one
+    pub show_upcoming_track: bool,
+    /// How often (ms) to poll Spotify for the queue
+    pub queue_poll_interval_ms: u64,
+    /// How long (seconds) before the end of a track the upcoming track is shown
+    pub upcoming_track_lead_sec: u64,
+    /// Scroll smoothly or jump per line
+    pub scroll_smoothly: bool,
+    /// Time between line transitions
+    pub line_transition_ms: u64,
+    /// File name of the theme in the themes folder, empty for the built-in one
+    pub theme: String,
+    /// Tint the lines and background with the colors of the album art
+    pub album_art_theme: bool,
+    /// Derive line colors and transition speed from the track's audio features
+    pub dynamic_theme: bool,
+    /// Pulse the current line on the beat of the track
+    pub beat_pulse: bool,
+    /// Show which provider the lyrics came from next to the track
+    pub show_lyrics_source: bool,
+    /// Global hotkey hiding the window and back, like `CmdOrCtrl+Shift+H`, empty disables it
+    pub visibility_hotkey: String,
+    /// Global hotkey making the window ignore the mouse and back
+    pub click_through_hotkey: String,
+    /// Global hotkey showing the lyrics 100ms sooner
+    pub offset_earlier_hotkey: String,
+    /// Global hotkey showing the lyrics 100ms later
+    pub offset_later_hotkey: String,
+    /// Global hotkey playing or pausing the player
+    pub play_pause_hotkey: String,
+    /// Scroll through lyrics without timings at estimated times, instead of showing them all
+    pub scroll_unsynced_lyrics: bool,
+    /// Show identical consecutive lines once with a repeat counter
+    pub fold_repeated_lines: bool,
+    /// Silences between lines at least this long are marked as instrumental breaks, 0 to never
+    pub instrumental_break_sec: u32,
+    /// Lines longer than this many characters are split at punctuation, 0 to never
+    pub max_line_chars: u32,
+    /// Show a romanized line under japanese, chinese and korean lyrics
+    pub romanize_lyrics: bool,
+    /// How backing vocals like `(ooh)` and section markers like `[Chorus]` are shown
+    pub adlibs: AdlibDisplay,
+    /// Show readings above kanji with ruby markup and above hanzi
+    pub ruby_annotations: bool,
+    /// Service translating the lyrics, shown under each line
+    pub translation_backend: TranslationBackendKind,
+    /// `LibreTranslate` instance
+    pub translation_url: String,
+    /// API key for the translation service
+    pub translation_api_key: String,
+    /// Language code to translate to, like "en" or "de"
+    pub translation_language: String,
+    /// Mask the words in `profanity_words` in the shown lyrics
+    pub profanity_filter: bool,
+    /// Words masked by the profanity filter, a trailing `*` also matches longer words
+    pub profanity_words: Vec<String>,
+    /// Do we show debug draws or not.
+    pub draw_debug_stuff: bool,
+    /// progress bar position
+    pub line_progress_bar_position: ProgressBarPosition,
+    /// song bar position
+    pub song_progress_bar_position: ProgressBarPosition,
+    /// easing of the position while playing
+    pub ease_position: EasingModes,
+    /// easing of the color while playing
+    pub ease_color: EasingModes,
+}
+
+impl Default for Settings {
+    #[allow(clippy::too_many_lines)]
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".into(),
+            port: 8123,
+            port_fallback_range: 0,
+            embedded_auth: false,
+            sp_dc: String::new(),
+            client_id: String::new(),
+            client_secret: String::new(),
+            accounts: Vec::new(),
+            active_account: 0,
+            oauth_scopes: OAuthScopes::Standard,
+            player_source: PlayerSourceKind::Spotify,
+            mpd_host: "127.0.0.1".to_owned(),
+            mpd_port: 6600,
+            mpd_password: String::new(),
+            mpd_music_dir: String::new(),
+            youtube_music_url: "http://127.0.0.1:26538".to_owned(),
+            youtube_music_token: String::new(),
+            deezer_app_id: String::new(),
+            deezer_secret: String::new(),
+            deezer_token: String::new(),
+            plex_url: "http://127.0.0.1:32400".to_owned(),
+            plex_token: String::new(),
+            subsonic_url: String::new(),
+            subsonic_username: String::new(),
+            subsonic_password: String::new(),
+            web_now_playing_port: 8974,
+            home_assistant_url: "http://homeassistant.local:8123".to_owned(),
+            home_assistant_token: String::new(),
+            home_assistant_entity: String::new(),
+            acoustid_key: String::new(),
+            fpcalc_path: String::new(),
+            mpris_player: String::new(),
+            refresh_token: None,
+            access_token: None,
+            expiry_time_as_unix: None,
+            proxy_url: String::new(),
+            proxy_username: String::new(),
+            proxy_password: String::new(),
+            connect_timeout_ms: 5000,
+            request_timeout_ms: 10000,
+            auth_timeout_sec: 180,
+            auto_auth: true,
+            log_level: "debug".into(),
+            display_mode: DisplayMode::Scrolling,
+            ticker_marquee: true,
+            opacity: 0.7,
+            font_size: 26.0,
+            font_files: vec![],
+            line_spacing: 42.0,
+            window_placements: BTreeMap::new(),
+            last_monitor: String::new(),
+            lyrics_providers: vec![
+                LyricsProviderKind::Embedded,
+                LyricsProviderKind::LocalLrc,
+                LyricsProviderKind::Spotify,
+                LyricsProviderKind::Lrclib,
+                LyricsProviderKind::Genius,
+            ],
+            lrc_folders: Vec::new(),
+            musixmatch_token: String::new(),
+            duration_tolerance_sec: 3.0,
+            caching_enabled: true,
+            cache_folder: paths::default_cache_folder().to_string_lossy().into_owned(),
+            not_found_ttl_hours: 24,
+            cache_refresh_days: 30,
+            cache_max_mb: 200,
+            dim_distant_lines: true,
+            word_highlight: true,
+            line_sweep: true,
+            color_duet_parts: true,
+            poll_interval_ms: 4000,
+            latency_compensation: 0.5,
+            show_upcoming_track: true,
+            queue_poll_interval_ms: 15000,
+            upcoming_track_lead_sec: 15,
+            scroll_smoothly: true,
+            line_transition_ms: 400,
+            theme: String::new(),
+            album_art_theme: false,
+            dynamic_theme: false,
+            beat_pulse: false,
+            visibility_hotkey: "CmdOrCtrl+Shift+H".to_owned(),
+            click_through_hotkey: "CmdOrCtrl+Shift+L".to_owned(),
+            offset_earlier_hotkey: "CmdOrCtrl+Shift+Right".to_owned(),
+            offset_later_hotkey: "CmdOrCtrl+Shift+Left".to_owned(),
+            play_pause_hotkey: "CmdOrCtrl+Shift+Space".to_owned(),
+            show_lyrics_source: true,
+            scroll_unsynced_lyrics: true,
+            fold_repeated_lines: false,
+            instrumental_break_sec: 10,
+            max_line_chars: 0,
+            romanize_lyrics: false,
+            adlibs: AdlibDisplay::Show,
+            ruby_annotations: false,
+            translation_backend: TranslationBackendKind::Disabled,
+            translation_url: "https://libretranslate.com".to_owned(),
+            translation_api_key: String::new(),
+            translation_language: "en".to_owned(),
+            profanity_filter: false,
+            profanity_words: DEFAULT_PROFANITY.map(str::to_owned).to_vec(),
+            draw_debug_stuff: false,
+            line_progress_bar_position: ProgressBarPosition::Hidden,
+            song_progress_bar_position: ProgressBarPosition::Hidden,
+            ease_position: EasingModes::Linear,
+            ease_color: EasingModes::Cubic,
+        }
+    }
+}
+
+impl Settings {
+    pub fn new() -> Result<Self, ConfigError> {
+        let mut settings: Self = Config::builder()
+            .add_source(File::from(paths::config_file()))
+            .add_source(Environment::with_prefix("APP"))
+            .build()?
+            .try_deserialize()?;
+        settings.migrate_legacy_tokens();
+        // A relative cache folder is next to the config, not wherever we were started from
+        if Path::new(&settings.cache_folder).is_relative() {
+            settings.cache_folder = paths::config_dir()
+                .join(&settings.cache_folder)
+                .to_string_lossy()
+                .into_owned();
+        }
+        Ok(settings)
+    }
+
+    /// Move tokens stored by older versions into an account
+    fn migrate_legacy_tokens(&mut self) {
+        if self.refresh_token.is_none() && self.access_token.is_none() {
+            return;
+        }
+        if self.accounts.is_empty() {
+            self.accounts.push(SpotifyAccount {
+                name: "Default".into(),
+                refresh_token: self.refresh_token.take(),
+                access_token: self.access_token.take(),
+                expiry_time_as_unix: self.expiry_time_as_unix.take(),
+                ..SpotifyAccount::default()
+            });
+            self.active_account = 0;
+        }
+        self.refresh_token = None;
+        self.access_token = None;
+        self.expiry_time_as_unix = None;
+    }
+
+    pub fn active_account(&self) -> Option<&SpotifyAccount> {
+        self.accounts.get(self.active_account)
+    }
+
+    pub fn active_account_mut(&mut self) -> Option<&mut SpotifyAccount> {
+        self.accounts.get_mut(self.active_account)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self { ..Self::default() };
+    }
+
+    /// Redirect uris that have to be registered in the spotify dashboard
+    pub fn redirect_uris(&self) -> Vec<String> {
+        if self.port == 0 {
+            return vec![format!("http://{}:<any port>/callback", self.host)];
+        }
+        (self.port..=self.port.saturating_add(self.port_fallback_range))
+            .map(|port| format!("http://{}:{port}/callback", self.host))
+            .collect()
+    }
+
+    /// Serialize the current state back to the config file.
+    pub fn save(&self) -> Result<(), String> {
+        debug!("Starting save!");
+        let toml = toml::ser::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialise settings: {e}"))?;
+        let config_file = paths::config_file();
+        let res = fs::write(&config_file, toml)
+            .map_err(|e| format!("Failed to write {}: {e}", config_file.display()));
+        if res.is_err() {
+            error!("{}", res.clone().err().unwrap());
+        }
+        res
+    }
+}
+
+/// Tokens and profile of a single authenticated spotify account
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct SpotifyAccount {
+    /// Display name, shown in the account switcher
+    pub name: String,
+    /// Spotify user id
+    pub user_id: Option<String>,
+    /// Spotify refresh token
+    pub refresh_token: Option<String>,
+    /// Spotify access token
+    pub access_token: Option<String>,
+    /// Spotify token expiry date/time
+    pub expiry_time_as_unix: Option<u64>,
+    /// Scopes granted to the tokens, empty for accounts authorized before scopes were configurable
+    pub scopes: Vec<String>,
+}
+
+impl SpotifyAccount {
+    /// Were all the given scopes granted to this account's tokens
+    pub fn has_scopes(&self, scopes: &[&str]) -> bool {
+        let granted: Vec<&str> = if self.scopes.is_empty() {
+            OAuthScopes::Standard.scopes().to_vec()
+        } else {
+            self.scopes.iter().map(String::as_str).collect()
+        };
+        scopes.iter().all(|scope| granted.contains(scope))
+    }
+}
+
+/// Sets of OAuth scopes the user can choose from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum OAuthScopes {
+    /// Only the currently playing track
+    Minimal,
+    /// Currently playing track and playback state, including the queue
+    #[default]
+    Standard,
+    /// Standard, plus controlling playback
+    PlaybackControl,
+}
+impl OAuthScopes {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Minimal => "Lyrics only",
+            Self::Standard => "Lyrics and queue",
+            Self::PlaybackControl => "Playback control",
+        }
+    }
+    pub fn scopes(self) -> &'static [&'static str] {
+        match self {
+            Self::Minimal => &["user-read-currently-playing"],
+            Self::Standard => &["user-read-currently-playing", "user-read-playback-state"],
+            Self::PlaybackControl => &[
+                "user-read-currently-playing",
+                "user-read-playback-state",
+                "user-modify-playback-state",
+            ],
+        }
+    }
+    pub fn can_read_playback_state(self) -> bool {
+        self != Self::Minimal
+    }
+    pub fn can_control_playback(self) -> bool {
+        self == Self::PlaybackControl
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum ProgressBarPosition {
+    Hidden,
+    BelowCurrentLine,
+    #[default]
+    Bottom,
+}
+impl ProgressBarPosition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::BelowCurrentLine => "Below line",
+            Self::Bottom => "Bottom",
+            Self::Hidden => "Hidden",
+        }
+    }
+}
+
+/// How the lyrics are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DisplayMode {
+    /// Every line, scrolling along with the song
+    #[default]
+    Scrolling,
+    /// Only the current line, for a slim bar above the taskbar or under a stream cam
+    Ticker,
+    /// The previous line, the current one and the next two, in a window sized by the font
+    Compact,
+    /// The current line large and the next one beneath it, for a strip at the bottom of the screen
+    Karaoke,
+}
+impl DisplayMode {
+    pub const ALL: [Self; 4] = [Self::Scrolling, Self::Ticker, Self::Compact, Self::Karaoke];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Scrolling => "Scrolling",
+            Self::Ticker => "Ticker",
+            Self::Compact => "Compact",
+            Self::Karaoke => "Karaoke",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum AdlibDisplay {
+    #[default]
+    Show,
+    Dim,
+    Hide,
+}
+impl AdlibDisplay {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Show => "Show",
+            Self::Dim => "Dim",
+            Self::Hide => "Hide",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum EasingModes {
+    Cubic,
+    #[default]
+    Linear,
+}
+impl EasingModes {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cubic => "Cubic",
+            Self::Linear => "Linear",
+        }
+    }
+}
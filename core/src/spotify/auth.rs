@@ -0,0 +1,471 @@
+//! Module for talking with spotify, implements only the parts of the API needed for this app
+use oauth2::basic::{BasicClient, BasicErrorResponseType};
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, HttpClientError,
+    PkceCodeChallenge, RedirectUrl, RequestTokenError, Scope, StandardErrorResponse, TokenResponse,
+    TokenUrl,
+};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::RwLock as TokioRwLock;
+use tokio::sync::{Notify, mpsc};
+use tracing::{debug, info};
+
+use crate::MessageToUI;
+use crate::http;
+use crate::oauth::{CallbackError, bind_callback, wait_for_login};
+use crate::settings::{Settings, SpotifyAccount};
+
+const SPOTIFY_AUTH_URL: &str = "https://accounts.spotify.com/authorize";
+const SPOTIFY_TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+
+type TokenError = RequestTokenError<
+    HttpClientError<oauth2::reqwest::Error>,
+    StandardErrorResponse<BasicErrorResponseType>,
+>;
+
+#[derive(Error, Debug)]
+/// Error enum for spotify authentication requests
+pub enum SpotifyClientAuthError {
+    #[error("Missing client id")]
+    MissingClientId,
+    #[error("Missing client secret")]
+    MissingClientSecret,
+    #[error("Client id should be the 32 character id from the spotify dashboard")]
+    InvalidClientId,
+    #[error("Client secret should be the 32 character secret from the spotify dashboard")]
+    InvalidClientSecret,
+    #[error(
+        "Redirect host {0:?} is not an IP address, use 127.0.0.1 (spotify does not allow localhost)"
+    )]
+    InvalidHost(String),
+    #[error("Missing code in auth callback URL")]
+    MissingCodeAuthError,
+    #[error("Missing state in auth callback URL")]
+    MissingStateAuthError,
+    #[error("Missing refresh token")]
+    MissingRefreshToken,
+    #[error(transparent)]
+    Callback(#[from] CallbackError),
+    #[error("CRSF token mismatch")]
+    CrsfMismatch,
+    #[error("Url Error")]
+    UrlParse(#[from] url::ParseError),
+    #[error("IO error")]
+    IoError(#[from] std::io::Error), // RequestTokenError
+    #[error("Spotify did not respond in time")]
+    Timeout,
+    #[error("OAuth token request failed: {0}")]
+    TokenRequest(TokenError),
+    #[error("Request failed: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("Saving the account failed: {0}")]
+    SaveSettings(String),
+}
+
+impl From<TokenError> for SpotifyClientAuthError {
+    fn from(err: TokenError) -> Self {
+        match err {
+            RequestTokenError::Request(HttpClientError::Reqwest(ref inner))
+                if inner.is_timeout() =>
+            {
+                Self::Timeout
+            }
+            _ => Self::TokenRequest(err),
+        }
+    }
+}
+
+/// Spotify client state
+pub struct SpotifyAuthClient {
+    /// Our very important amazing access token
+    access_token: Arc<TokioRwLock<Option<String>>>,
+    /// Settings!
+    settings: Arc<TokioRwLock<Settings>>,
+    refresh_token: Arc<TokioRwLock<Option<String>>>,
+    token_expiry: Arc<TokioRwLock<Option<std::time::Instant>>>,
+    /// Notified to stop waiting for the OAuth callback
+    cancel_auth: Arc<Notify>,
+}
+
+impl SpotifyAuthClient {
+    pub fn new(settings: Arc<TokioRwLock<Settings>>) -> Self {
+        Self {
+            access_token: Arc::new(TokioRwLock::new(None)),
+            settings,
+            refresh_token: Arc::new(TokioRwLock::new(None)),
+            token_expiry: Arc::new(TokioRwLock::new(None)),
+            cancel_auth: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Authenticate the active account, or go through the full OAuth flow for a new account if `new_account` is set
+    pub async fn authenticate(
+        &mut self,
+        new_account: bool,
+        tx_ui: Option<&mpsc::Sender<MessageToUI>>,
+    ) -> Result<(), SpotifyClientAuthError> {
+        let (
+            client_id,
+            client_secret,
+            host,
+            port,
+            port_fallback_range,
+            embedded_auth,
+            auth_timeout_sec,
+            scopes,
+            saved_refresh,
+            stored_access_token,
+            stored_expiry_time,
+        ) = {
+            let settings_lock = self.settings.read().await;
+            let scopes = settings_lock.oauth_scopes.scopes();
+            // Tokens without the requested scopes need the user's consent again
+            let account = settings_lock
+                .active_account()
+                .filter(|account| !new_account && account.has_scopes(scopes));
+            (
+                settings_lock.client_id.clone(),
+                settings_lock.client_secret.clone(),
+                settings_lock.host.clone(),
+                settings_lock.port,
+                settings_lock.port_fallback_range,
+                settings_lock.embedded_auth,
+                settings_lock.auth_timeout_sec,
+                scopes,
+                account.and_then(|a| a.refresh_token.clone()),
+                account.and_then(|a| a.access_token.clone()),
+                account.and_then(|a| a.expiry_time_as_unix),
+            )
+        };
+
+        if let Some(res) = self
+            .authenticate_stored(stored_access_token, stored_expiry_time, saved_refresh)
+            .await
+        {
+            return res;
+        }
+
+        // Catch config mistakes before the user ends up on a spotify error page
+        let ip = validate_auth_settings(&client_id, &client_secret, &host)?;
+
+        // The redirect uri has to match the address we actually listen on
+        let (listener, addr) = bind_callback(ip, port, port_fallback_range)?;
+        let redirect_uri = format!("http://{addr}/callback");
+        debug!("Using redirect uri {redirect_uri}");
+
+        let client = BasicClient::new(ClientId::new(client_id))
+            .set_client_secret(ClientSecret::new(client_secret))
+            .set_auth_uri(AuthUrl::new(SPOTIFY_AUTH_URL.to_string())?)
+            .set_token_uri(TokenUrl::new(SPOTIFY_TOKEN_URL.to_string())?)
+            .set_redirect_uri(RedirectUrl::new(redirect_uri.clone())?);
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let mut auth_request = client
+            .authorize_url(CsrfToken::new_random)
+            .add_scopes(scopes.iter().map(|scope| Scope::new((*scope).to_string())))
+            .set_pkce_challenge(pkce_challenge);
+        if new_account {
+            // Otherwise spotify silently approves with whichever account is logged in to the browser
+            auth_request = auth_request.add_extra_param("show_dialog", "true");
+        }
+        let (auth_url, csrf_token) = auth_request.url();
+
+        if let Some(tx_ui) = tx_ui {
+            tx_ui
+                .send(MessageToUI::AwaitingAuthCallback(redirect_uri.clone()))
+                .await
+                .unwrap();
+        }
+
+        let (code, state) = wait_for_login(
+            "Spotify",
+            auth_url.as_str(),
+            listener,
+            redirect_uri,
+            embedded_auth,
+            Duration::from_secs(auth_timeout_sec),
+            self.cancel_auth.clone(),
+        )
+        .await?;
+
+        let Some(code) = code else {
+            return Err(SpotifyClientAuthError::MissingCodeAuthError);
+        };
+        let Some(state) = state else {
+            return Err(SpotifyClientAuthError::MissingStateAuthError);
+        };
+
+        if state != *csrf_token.secret() {
+            return Err(SpotifyClientAuthError::CrsfMismatch);
+        }
+
+        let http_client = http::oauth_client(&*self.settings.read().await);
+
+        let token_result = client
+            .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(pkce_verifier)
+            .request_async(&http_client)
+            .await?;
+
+        self.process_token_result(token_result, new_account).await?;
+
+        debug!("Successfully authenticated!");
+        Ok(())
+    }
+
+    /// Use the stored access token if it is still valid, otherwise the stored refresh token.
+    /// The refresh token is kept either way, for when the access token expires later.
+    /// Returns `None` if neither is usable
+    async fn authenticate_stored(
+        &self,
+        access_token: Option<String>,
+        expiry_time: Option<u64>,
+        refresh_token: Option<String>,
+    ) -> Option<Result<(), SpotifyClientAuthError>> {
+        let refresh_token = refresh_token.filter(|token| !token.is_empty());
+        *self.refresh_token.write().await = refresh_token.clone();
+
+        if let Some(a_token) = access_token
+            && let Some(exp) = expiry_time
+        {
+            if exp > get_unix_time() {
+                info!(
+                    "Using stored access token expiring in {} secs",
+                    exp - get_unix_time()
+                );
+                let mut token_guard = self.access_token.write().await;
+                *token_guard = Some(a_token);
+                return Some(Ok(()));
+            }
+            debug!(
+                "Stored access token expired {} secs ago",
+                get_unix_time() - exp
+            );
+        }
+
+        if refresh_token.is_some() {
+            info!("Getting access token from stored refresh token",);
+            return Some(self.refresh_access_token().await);
+        }
+
+        None
+    }
+
+    pub async fn refresh_access_token(&self) -> Result<(), SpotifyClientAuthError> {
+        let refresh_token = {
+            let guard = self.refresh_token.read().await;
+            guard
+                .clone()
+                .ok_or(SpotifyClientAuthError::MissingRefreshToken)?
+        };
+
+        let (client_id, client_secret) = {
+            let s = self.settings.read().await;
+            (s.client_id.clone(), s.client_secret.clone())
+        };
+
+        let client = BasicClient::new(ClientId::new(client_id))
+            .set_client_secret(ClientSecret::new(client_secret))
+            .set_auth_uri(AuthUrl::new(SPOTIFY_AUTH_URL.to_string())?)
+            .set_token_uri(TokenUrl::new(SPOTIFY_TOKEN_URL.to_string())?);
+
+        let http_client = http::oauth_client(&*self.settings.read().await);
+
+        let token_result = client
+            .exchange_refresh_token(&oauth2::RefreshToken::new(refresh_token))
+            .request_async(&http_client)
+            .await?;
+
+        self.process_token_result(token_result, false).await?;
+
+        Ok(())
+    }
+
+    /// Expiry of the active account's access token as unix time
+    pub async fn token_expiry_unix(&self) -> Option<u64> {
+        self.settings
+            .read()
+            .await
+            .active_account()
+            .and_then(|account| account.expiry_time_as_unix)
+    }
+
+    /// Do we have a token that has expired
+    pub async fn token_expired(&self) -> bool {
+        self.token_expiry_unix()
+            .await
+            .is_some_and(|expiry| expiry <= get_unix_time())
+    }
+
+    pub async fn invalidate_token(&self) {
+        let mut token_opt = self.access_token.write().await;
+        *token_opt = None;
+    }
+
+    /// Make another stored account the active one, dropping the in-memory tokens of the current one
+    pub async fn switch_account(&self, index: usize) -> Result<(), SpotifyClientAuthError> {
+        {
+            let mut rw_settings = self.settings.write().await;
+            if index >= rw_settings.accounts.len() {
+                return Ok(());
+            }
+            rw_settings.active_account = index;
+            rw_settings
+                .save()
+                .map_err(SpotifyClientAuthError::SaveSettings)?;
+        }
+        self.clear_tokens().await;
+        Ok(())
+    }
+
+    /// Forget a stored account, if it was the active one we are no longer authenticated
+    pub async fn remove_account(&self, index: usize) -> Result<bool, SpotifyClientAuthError> {
+        let was_active = {
+            let mut rw_settings = self.settings.write().await;
+            if index >= rw_settings.accounts.len() {
+                return Ok(false);
+            }
+            rw_settings.accounts.remove(index);
+            let was_active = rw_settings.active_account == index;
+            if rw_settings.active_account > index
+                || rw_settings.active_account >= rw_settings.accounts.len()
+            {
+                rw_settings.active_account = rw_settings.active_account.saturating_sub(1);
+            }
+            rw_settings
+                .save()
+                .map_err(SpotifyClientAuthError::SaveSettings)?;
+            was_active
+        };
+        if was_active {
+            self.clear_tokens().await;
+        }
+        Ok(was_active)
+    }
+
+    /// Store the profile of the active account, merging it with an older entry of the same user
+    pub async fn set_active_profile(
+        &self,
+        user_id: String,
+        name: String,
+    ) -> Result<(), SpotifyClientAuthError> {
+        let mut rw_settings = self.settings.write().await;
+        let active = rw_settings.active_account;
+        if let Some(duplicate) = rw_settings
+            .accounts
+            .iter()
+            .enumerate()
+            .position(|(i, a)| i != active && a.user_id.as_ref() == Some(&user_id))
+        {
+            rw_settings.accounts.remove(duplicate);
+            if duplicate < active {
+                rw_settings.active_account -= 1;
+            }
+        }
+        if let Some(account) = rw_settings.active_account_mut() {
+            account.user_id = Some(user_id);
+            account.name = name;
+        }
+        rw_settings
+            .save()
+            .map_err(SpotifyClientAuthError::SaveSettings)
+    }
+
+    async fn clear_tokens(&self) {
+        *self.access_token.write().await = None;
+        *self.refresh_token.write().await = None;
+        *self.token_expiry.write().await = None;
+    }
+
+    pub fn retreive_token_handle(&self) -> Arc<TokioRwLock<Option<String>>> {
+        self.access_token.clone()
+    }
+
+    /// Handle to cancel a running authentication, usable without locking the client
+    pub fn retreive_cancel_handle(&self) -> Arc<Notify> {
+        self.cancel_auth.clone()
+    }
+
+    /// Process the token result,
+    /// Grab the access token, refresh tokens, and store the expiry times in the active (or a new) account
+    pub async fn process_token_result(
+        &self,
+        token_result: oauth2::StandardTokenResponse<
+            oauth2::EmptyExtraTokenFields,
+            oauth2::basic::BasicTokenType,
+        >,
+        new_account: bool,
+    ) -> Result<(), SpotifyClientAuthError> {
+        let mut rw_settings = self.settings.write().await;
+        if new_account || rw_settings.active_account().is_none() {
+            rw_settings.accounts.push(SpotifyAccount::default());
+            rw_settings.active_account = rw_settings.accounts.len() - 1;
+        }
+        let requested_scopes = rw_settings.oauth_scopes.scopes();
+        let account = rw_settings.active_account_mut().unwrap();
+
+        // The scope field may be left out if it matches the requested scopes
+        account.scopes = token_result.scopes().map_or_else(
+            || requested_scopes.iter().map(ToString::to_string).collect(),
+            |granted| granted.iter().map(|scope| scope.to_string()).collect(),
+        );
+
+        let mut token_guard = self.access_token.write().await;
+        *token_guard = Some(token_result.access_token().secret().clone());
+        account.access_token.clone_from(&token_guard);
+
+        if let Some(new_refresh) = token_result.refresh_token() {
+            let mut refresh_guard = self.refresh_token.write().await;
+            *refresh_guard = Some(new_refresh.secret().clone());
+            account.refresh_token = Some(new_refresh.secret().clone());
+        }
+
+        if let Some(duration) = token_result.expires_in() {
+            let mut expiry_guard = self.token_expiry.write().await;
+            *expiry_guard = Some(std::time::Instant::now() + duration);
+            account.expiry_time_as_unix = Some(get_unix_time() + duration.as_secs());
+        }
+
+        rw_settings
+            .save()
+            .map_err(SpotifyClientAuthError::SaveSettings)
+    }
+}
+
+/// Spotify client ids and secrets are 32 hex characters
+pub fn is_spotify_credential(value: &str) -> bool {
+    value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Check the settings needed for the login flow, returns the address to listen on
+fn validate_auth_settings(
+    client_id: &str,
+    client_secret: &str,
+    host: &str,
+) -> Result<IpAddr, SpotifyClientAuthError> {
+    if client_id.is_empty() {
+        return Err(SpotifyClientAuthError::MissingClientId);
+    }
+    if client_secret.is_empty() {
+        return Err(SpotifyClientAuthError::MissingClientSecret);
+    }
+    if !is_spotify_credential(client_id) {
+        return Err(SpotifyClientAuthError::InvalidClientId);
+    }
+    if !is_spotify_credential(client_secret) {
+        return Err(SpotifyClientAuthError::InvalidClientSecret);
+    }
+    host.parse()
+        .map_err(|_| SpotifyClientAuthError::InvalidHost(host.to_owned()))
+}
+
+fn get_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,593 @@
+//! Module for talking with spotify, implements only the parts of the API needed for this app
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::Mutex as TokioMutex;
+use tokio::sync::RwLock as TokioRwLock;
+use tracing::trace;
+
+use crate::{
+    http,
+    player::{NowPlaying, PlaybackControl},
+    settings::Settings,
+};
+
+pub mod auth;
+
+#[derive(Error, Debug)]
+/// Error enum for spotify requests
+pub enum SpotifyClientTrackError {
+    #[error("Not authenticated")]
+    NotAuthenticated,
+    #[error("Not playing a track")]
+    NotATrack,
+    #[error("Not playing anything")]
+    NoContentResponse,
+    #[error("Not found on spotify, private playlists can't be read")]
+    NotFound,
+    #[error("OAuthError, try reauthenticating")]
+    TokenError,
+    #[error("BadRequest, reauthentication won't help you, I don't know what will")]
+    BadRequest,
+    #[error("Exceeded spotify rate limits")]
+    RateLimitsExceeded,
+    #[error("Spotify did not respond in time")]
+    Timeout,
+    #[error("Reqwest error: {0}")]
+    ReqwestError(reqwest::Error),
+}
+
+impl From<reqwest::Error> for SpotifyClientTrackError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::ReqwestError(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Response of the spotify currently playing song endpoint
+pub struct CurrentlyPlayingResponse {
+    /// Type of the included item, we only care if this matches "track"
+    currently_playing_type: String,
+    /// Item, can also be a podcast ep, but we only care about track
+    item: Option<Track>,
+    /// Are we currently playing this song?
+    pub is_playing: bool,
+    /// Playback progress
+    pub progress_ms: usize,
+    /// When the response arrived
+    #[serde(skip, default = "Instant::now")]
+    pub received_at: Instant,
+    /// Round trip time of the request, `progress_ms` was sampled somewhere within it
+    #[serde(skip)]
+    pub round_trip: Duration,
+}
+
+impl CurrentlyPlayingResponse {
+    pub fn is_track(&self) -> bool {
+        self.currently_playing_type == "track" && self.item.is_some()
+    }
+
+    /// Convert into the source independent `NowPlaying`, `None` if this isn't a track
+    pub fn into_now_playing(self) -> Option<NowPlaying> {
+        if !self.is_track() {
+            return None;
+        }
+        let track = self.item?;
+        Some(NowPlaying {
+            track_id: track.id.clone(),
+            spotify_id: Some(track.id.clone()),
+            file_path: None,
+            title: track.name.clone(),
+            artist: track.get_artist(),
+            album: track.get_album(),
+            album_art_url: track.album.images.last().map(|image| image.url.clone()),
+            duration_sec: track.get_duration_sec(),
+            is_playing: self.is_playing,
+            progress_ms: self.progress_ms,
+            received_at: self.received_at,
+            round_trip: self.round_trip,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Contents of the track item of the spotify API
+struct Track {
+    /// Song title
+    name: String,
+    /// Spotify song id
+    id: String,
+    /// Duration in ms of the song
+    duration_ms: usize,
+    /// Artists listed for this song
+    artists: Vec<Artist>,
+    /// Song's album
+    album: Album,
+}
+impl Track {
+    fn get_artist(&self) -> String {
+        self.artists.first().unwrap().name.clone()
+    }
+    fn get_album(&self) -> String {
+        self.album.name.clone()
+    }
+    #[allow(clippy::cast_precision_loss)]
+    fn get_duration_sec(&self) -> f64 {
+        self.duration_ms as f64 / 1000.0
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Response of the spotify queue endpoint
+struct QueueResponse {
+    /// Upcoming items, first one plays next
+    queue: Vec<QueueItem>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Item in the queue, this can be a track or an episode so everything but the name is optional
+struct QueueItem {
+    /// Track or episode name
+    name: String,
+    /// Artists, missing for episodes
+    #[serde(default)]
+    artists: Vec<Artist>,
+}
+
+#[derive(Debug, Deserialize)]
+/// (Partial) Page of the playlist items endpoint
+struct PlaylistItemsPage {
+    items: Vec<PlaylistItem>,
+    /// Url of the next page
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+/// (Partial) Playlist item, kept as json as episodes and removed tracks don't parse as a `Track`
+struct PlaylistItem {
+    track: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+/// Track in a playlist
+pub struct PlaylistTrack {
+    pub spotify_id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration_sec: f64,
+}
+
+/// Playlist id from a share url, a `spotify:playlist:` uri or the bare id
+pub fn parse_playlist_id(input: &str) -> Option<&str> {
+    let input = input.trim();
+    let id = if let Some(uri) = input.strip_prefix("spotify:playlist:") {
+        uri
+    } else if let Some((_, path)) = input.split_once("/playlist/") {
+        path.split(['?', '/', '#']).next()?
+    } else {
+        input
+    };
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())).then_some(id)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Track that will be played after the current one
+pub struct UpcomingTrack {
+    pub title: String,
+    pub artist: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+/// (Partial) Response of the spotify audio features endpoint
+pub struct AudioFeatures {
+    /// Perceptual intensity and activity, 0.0–1.0
+    pub energy: f32,
+    /// Musical positiveness, 0.0–1.0
+    pub valence: f32,
+    /// Estimated tempo in BPM
+    pub tempo: f32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Response of the spotify audio analysis endpoint
+pub struct AudioAnalysis {
+    /// Beats, sorted by start time
+    pub beats: Vec<TimeInterval>,
+    /// Bars, sorted by start time
+    pub bars: Vec<TimeInterval>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+/// Time interval of a beat or bar in the audio analysis
+pub struct TimeInterval {
+    /// Start in seconds
+    pub start: f32,
+    /// Duration in seconds
+    pub duration: f32,
+    /// Confidence in the interval, 0.0–1.0
+    pub confidence: f32,
+}
+
+impl AudioAnalysis {
+    /// Intensity of the beat pulse at the given position, 1.0 on a beat decaying to 0.0 before the next one.
+    /// Beats starting a bar pulse at full strength, others at most at 60%.
+    pub fn beat_pulse(&self, position_sec: f32) -> f32 {
+        let idx = self.beats.partition_point(|b| b.start <= position_sec);
+        let Some(beat) = idx.checked_sub(1).map(|i| self.beats[i]) else {
+            return 0.0;
+        };
+        if beat.duration <= 0.0 {
+            return 0.0;
+        }
+        let since_beat = position_sec - beat.start;
+        let decay = (1.0 - since_beat / (beat.duration * 0.5)).clamp(0.0, 1.0);
+
+        let on_bar = self
+            .bars
+            .iter()
+            .any(|bar| (bar.start - beat.start).abs() < 0.05);
+        let strength = if on_bar { 1.0 } else { 0.6 };
+
+        decay * decay * strength * beat.confidence.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Response of the spotify current user profile endpoint
+pub struct CurrentUser {
+    /// Spotify user id
+    pub id: String,
+    /// Name shown on the profile, not every account has one
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Contents of the artist item of the spotify API
+struct Artist {
+    /// Artist name
+    name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Contents of the album item of the spotify API
+struct Album {
+    /// Album name
+    name: String,
+    /// Covers, widest first
+    #[serde(default)]
+    images: Vec<Image>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+/// (Partial) Image object of the spotify API
+struct Image {
+    url: String,
+}
+
+/// Spotify client state
+pub struct SpotifyClient {
+    /// Our very important amazing access token
+    access_token: Arc<TokioRwLock<Option<String>>>,
+    /// Client used for requests (not used in oauth request)
+    client: reqwest::Client,
+    /// Audio analyses of the last tracks, these are large and never change
+    analysis_cache: TokioMutex<AnalysisCache>,
+}
+
+/// Audio analyses kept, enough for the current track and the next one
+const ANALYSIS_CACHE_SIZE: usize = 2;
+
+/// The most recently used audio analyses by spotify id, least recently used first
+#[derive(Default)]
+struct AnalysisCache(VecDeque<(String, Arc<AudioAnalysis>)>);
+
+impl AnalysisCache {
+    fn get(&mut self, spotify_id: &str) -> Option<Arc<AudioAnalysis>> {
+        let index = self.0.iter().position(|(id, _)| id == spotify_id)?;
+        let entry = self.0.remove(index)?;
+        let analysis = entry.1.clone();
+        self.0.push_back(entry);
+        Some(analysis)
+    }
+
+    fn insert(&mut self, spotify_id: String, analysis: Arc<AudioAnalysis>) {
+        self.0.retain(|(id, _)| *id != spotify_id);
+        self.0.push_back((spotify_id, analysis));
+        while self.0.len() > ANALYSIS_CACHE_SIZE {
+            self.0.pop_front();
+        }
+    }
+}
+
+impl SpotifyClient {
+    pub fn new(access_token: Arc<TokioRwLock<Option<String>>>, settings: &Settings) -> Self {
+        Self {
+            access_token,
+            client: http::client_builder(settings).build().unwrap(),
+            analysis_cache: TokioMutex::new(AnalysisCache::default()),
+        }
+    }
+
+    pub async fn get_current_track(
+        &self,
+    ) -> Result<CurrentlyPlayingResponse, SpotifyClientTrackError> {
+        let token_opt = self.access_token.read().await.clone();
+
+        let Some(token) = token_opt else {
+            return Err(SpotifyClientTrackError::NotAuthenticated);
+        };
+
+        let sent_at = Instant::now();
+        let response: reqwest::Response = self
+            .client
+            .get("https://api.spotify.com/v1/me/player/currently-playing")
+            .bearer_auth(token)
+            .send()
+            .await?;
+        let received_at = Instant::now();
+
+        if response.status().as_u16() == 204 {
+            // No content - nothing playing
+            return Err(SpotifyClientTrackError::NoContentResponse);
+        }
+        check_response_status(&response)?;
+
+        let mut playing: CurrentlyPlayingResponse = response.json().await?;
+        playing.received_at = received_at;
+        playing.round_trip = received_at - sent_at;
+
+        trace!("CurrentlyPlayingResponse {playing:?}");
+
+        if playing.currently_playing_type != "track" {
+            return Err(SpotifyClientTrackError::NotATrack);
+        }
+
+        Ok(playing)
+    }
+
+    pub async fn control_playback(
+        &self,
+        command: PlaybackControl,
+    ) -> Result<(), SpotifyClientTrackError> {
+        let token_opt = self.access_token.read().await.clone();
+
+        let Some(token) = token_opt else {
+            return Err(SpotifyClientTrackError::NotAuthenticated);
+        };
+
+        let request = match command {
+            PlaybackControl::PlayPause => {
+                let playing = self
+                    .get_current_track()
+                    .await
+                    .is_ok_and(|playing| playing.is_playing);
+                let action = if playing { "pause" } else { "play" };
+                self.client
+                    .put(format!("https://api.spotify.com/v1/me/player/{action}"))
+            }
+            PlaybackControl::Next => self
+                .client
+                .post("https://api.spotify.com/v1/me/player/next"),
+            PlaybackControl::Previous => self
+                .client
+                .post("https://api.spotify.com/v1/me/player/previous"),
+        };
+
+        // Spotify rejects bodyless PUT and POST requests without a content length
+        let response: reqwest::Response = request
+            .bearer_auth(token)
+            .header(reqwest::header::CONTENT_LENGTH, 0)
+            .send()
+            .await?;
+
+        check_response_status(&response)?;
+
+        Ok(())
+    }
+
+    pub async fn get_current_user(&self) -> Result<CurrentUser, SpotifyClientTrackError> {
+        let token_opt = self.access_token.read().await.clone();
+
+        let Some(token) = token_opt else {
+            return Err(SpotifyClientTrackError::NotAuthenticated);
+        };
+
+        let response: reqwest::Response = self
+            .client
+            .get("https://api.spotify.com/v1/me")
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        check_response_status(&response)?;
+
+        let user: CurrentUser = response.json().await?;
+        trace!("CurrentUser {user:?}");
+
+        Ok(user)
+    }
+
+    pub async fn get_queue(&self) -> Result<Option<UpcomingTrack>, SpotifyClientTrackError> {
+        let token_opt = self.access_token.read().await.clone();
+
+        let Some(token) = token_opt else {
+            return Err(SpotifyClientTrackError::NotAuthenticated);
+        };
+
+        let response: reqwest::Response = self
+            .client
+            .get("https://api.spotify.com/v1/me/player/queue")
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if response.status().as_u16() == 204 {
+            return Err(SpotifyClientTrackError::NoContentResponse);
+        }
+        check_response_status(&response)?;
+
+        let queue: QueueResponse = response.json().await?;
+        trace!("QueueResponse {queue:?}");
+
+        Ok(queue.queue.into_iter().next().map(|item| UpcomingTrack {
+            title: item.name,
+            artist: item.artists.into_iter().next().map(|a| a.name),
+        }))
+    }
+
+    pub async fn get_audio_features(
+        &self,
+        spotify_id: &str,
+    ) -> Result<AudioFeatures, SpotifyClientTrackError> {
+        let token_opt = self.access_token.read().await.clone();
+
+        let Some(token) = token_opt else {
+            return Err(SpotifyClientTrackError::NotAuthenticated);
+        };
+
+        let response: reqwest::Response = self
+            .client
+            .get(format!(
+                "https://api.spotify.com/v1/audio-features/{spotify_id}"
+            ))
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        check_response_status(&response)?;
+
+        let features: AudioFeatures = response.json().await?;
+        trace!("AudioFeatures {features:?}");
+
+        Ok(features)
+    }
+
+    /// Every track in the playlist, skipping episodes and local files
+    pub async fn get_playlist_tracks(
+        &self,
+        playlist_id: &str,
+    ) -> Result<Vec<PlaylistTrack>, SpotifyClientTrackError> {
+        let token_opt = self.access_token.read().await.clone();
+
+        let Some(token) = token_opt else {
+            return Err(SpotifyClientTrackError::NotAuthenticated);
+        };
+
+        let mut tracks = Vec::new();
+        let mut url = Some(format!(
+            "https://api.spotify.com/v1/playlists/{playlist_id}/tracks?limit=100"
+        ));
+        while let Some(page_url) = url {
+            let response: reqwest::Response =
+                self.client.get(page_url).bearer_auth(&token).send().await?;
+            if response.status().as_u16() == 404 {
+                return Err(SpotifyClientTrackError::NotFound);
+            }
+            check_response_status(&response)?;
+
+            let page: PlaylistItemsPage = response.json().await?;
+            tracks.extend(
+                page.items
+                    .into_iter()
+                    .filter_map(|item| serde_json::from_value::<Track>(item.track?).ok())
+                    .filter(|track| !track.artists.is_empty())
+                    .map(|track| PlaylistTrack {
+                        title: track.name.clone(),
+                        artist: track.get_artist(),
+                        album: track.get_album(),
+                        duration_sec: track.get_duration_sec(),
+                        spotify_id: track.id,
+                    }),
+            );
+            url = page.next;
+        }
+        trace!("Playlist {playlist_id} has {} tracks", tracks.len());
+        Ok(tracks)
+    }
+
+    pub async fn get_audio_analysis(
+        &self,
+        spotify_id: &str,
+    ) -> Result<Arc<AudioAnalysis>, SpotifyClientTrackError> {
+        if let Some(analysis) = self.analysis_cache.lock().await.get(spotify_id) {
+            return Ok(analysis);
+        }
+
+        let token_opt = self.access_token.read().await.clone();
+
+        let Some(token) = token_opt else {
+            return Err(SpotifyClientTrackError::NotAuthenticated);
+        };
+
+        let response: reqwest::Response = self
+            .client
+            .get(format!(
+                "https://api.spotify.com/v1/audio-analysis/{spotify_id}"
+            ))
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        check_response_status(&response)?;
+
+        let analysis = Arc::new(response.json::<AudioAnalysis>().await?);
+        trace!(
+            "AudioAnalysis with {} beats and {} bars",
+            analysis.beats.len(),
+            analysis.bars.len()
+        );
+
+        self.analysis_cache
+            .lock()
+            .await
+            .insert(spotify_id.to_owned(), analysis.clone());
+
+        Ok(analysis)
+    }
+}
+
+/// Map the error status codes shared by all player endpoints
+fn check_response_status(response: &reqwest::Response) -> Result<(), SpotifyClientTrackError> {
+    match response.status().as_u16() {
+        // Bad or expired token. This can happen if the user revoked a token or the access token has expired. You should re-authenticate the user.
+        401 => Err(SpotifyClientTrackError::TokenError),
+        // Bad OAuth request (wrong consumer key, bad nonce, expired timestamp...). Unfortunately, re-authenticating the user won't help here.
+        403 => Err(SpotifyClientTrackError::BadRequest),
+        // The app has exceeded its rate limits.
+        // According to the internet, "100 requests per hour for each user token and 25 requests per second for each application token."
+        // But spotify is vague about this
+        429 => Err(SpotifyClientTrackError::RateLimitsExceeded),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analysis_cache_drops_the_least_recently_used() {
+        let analysis = || {
+            Arc::new(AudioAnalysis {
+                beats: vec![],
+                bars: vec![],
+            })
+        };
+        let mut cache = AnalysisCache::default();
+        cache.insert("current".to_owned(), analysis());
+        cache.insert("next".to_owned(), analysis());
+        assert!(cache.get("current").is_some());
+        cache.insert("after".to_owned(), analysis());
+
+        assert!(cache.get("next").is_none());
+        assert!(cache.get("current").is_some());
+        assert!(cache.get("after").is_some());
+    }
+}
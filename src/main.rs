@@ -8,7 +8,7 @@
 //TODO: allow offsetting lyrics, using arrow keys?
 //TODO: Handle unsynced lyrics, show scrollbar?
 
-use std::fs::{File, exists};
+use std::fs::{self, File};
 use std::io::Write;
 use std::sync::Arc;
 
@@ -19,45 +19,28 @@ use tracing::{debug, info};
 use tracing_appender::{non_blocking, rolling};
 use tracing_subscriber::EnvFilter;
 
-use crate::lyrics_fetch::LyricsRequestInfo;
-use crate::lyrics_fetch::SongWithLyrics;
+use lyrics_overlay_core::paths;
+use lyrics_overlay_core::runtime::start_runtime;
+use lyrics_overlay_core::settings::Settings;
+
 use crate::overlay::LyricsAppUI;
-use crate::runtime::start_runtime;
-use crate::settings::Settings;
-use crate::spotify::CurrentlyPlayingResponse;
 
-mod lyrics_fetch;
-mod lyrics_parser;
 mod overlay;
-mod runtime;
-mod settings;
-mod spotify;
-
-#[derive(Debug)]
-pub enum MessageToUI {
-    AuthenticationStateUpdate(bool),
-    RateLimitsExceeded,
-    CurrentlyPlaying(CurrentlyPlayingResponse),
-    NotCurrentlyPlaying(String),
-    DisplayError(String),
-    GotLyrics(SongWithLyrics),
-}
-
-#[derive(Debug)]
-pub enum MessageToRT {
-    Authenticate,
-    GetCurrentTrack,
-    GetLyrics(LyricsRequestInfo),
-    InvalidateToken,
-}
 
 fn main() {
     // Generate config file if no config is found
-    if !exists("config.toml").unwrap() {
+    let config_file = paths::config_file();
+    if !config_file.exists() {
+        if let Some(config_dir) = config_file.parent() {
+            fs::create_dir_all(config_dir).unwrap();
+        }
         let str = toml::ser::to_string_pretty(&Settings::default()).unwrap();
-        let mut output = File::create("config.toml").unwrap();
+        let mut output = File::create(&config_file).unwrap();
         write!(output, "{str}").unwrap();
-        println!("Created config, please add client_id and client_secret");
+        println!(
+            "Created config at {}, please add client_id and client_secret",
+            config_file.display()
+        );
     }
 
     // Load settings file
@@ -71,7 +54,7 @@ fn main() {
     let rw_settings = Arc::new(TokioRwLock::new(settings));
     let settings_read = rw_settings.blocking_read();
     // Logging
-    let file_appender = rolling::daily("logs", "app.log");
+    let file_appender = rolling::daily(paths::log_folder(), "app.log");
     let (non_blocking, _writer_guard) = non_blocking(file_appender);
     let filter = EnvFilter::try_new(&settings_read.log_level).unwrap();
     let subscriber = tracing_subscriber::FmtSubscriber::builder()
@@ -100,10 +83,23 @@ fn main() {
         }
     });
 
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_title("Lyrics Overlay")
+        .with_inner_size([680.0, 340.0]);
+    // Where it was left on the last monitor it was on
+    let settings_read = rw_settings.blocking_read();
+    if let Some(&[x, y, width, height]) = settings_read
+        .window_placements
+        .get(&settings_read.last_monitor)
+    {
+        viewport = viewport
+            .with_position([x, y])
+            .with_inner_size([width, height]);
+    }
+    std::mem::drop(settings_read);
+
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_title("Lyrics Overlay")
-            .with_inner_size([680.0, 340.0]) // TODO: Restore size when starting
+        viewport: viewport
             .with_min_inner_size([320.0, 160.0])
             .with_decorations(false) // no window chrome
             .with_transparent(true) // transparent background
@@ -1,6 +1,8 @@
 use egui::{Color32, RichText, Ui};
 
-use crate::{MessageToRT, overlay::LyricsAppUI};
+use lyrics_overlay_core::{MessageToRT, spotify::auth::is_spotify_credential};
+
+use crate::overlay::{AuthFlow, LyricsAppUI};
 
 impl LyricsAppUI {
     pub fn authentication_ui(&mut self, ui: &mut Ui) {
@@ -51,8 +53,13 @@ impl LyricsAppUI {
             ui.add_space(12.0);
             let has_credentials = !self.settings_cache.client_id.is_empty()
                 && !self.settings_cache.client_secret.is_empty();
+            let connect_label = if self.auth_flow == AuthFlow::Failed {
+                "Retry connecting"
+            } else {
+                "Connect Spotify"
+            };
             ui.add_enabled_ui(has_credentials, |ui| {
-                if ui.button("Connect Spotify").clicked() {
+                if ui.button(connect_label).clicked() {
                     self.tx.try_send(MessageToRT::Authenticate).unwrap();
                 }
             });
@@ -62,6 +69,40 @@ impl LyricsAppUI {
                         .size(11.0)
                         .color(Color32::from_gray(100)),
                 );
+            } else if !is_spotify_credential(&self.settings_cache.client_id)
+                || !is_spotify_credential(&self.settings_cache.client_secret)
+            {
+                ui.label(
+                    RichText::new("Client ID and secret should be 32 characters, check for typos")
+                        .size(11.0)
+                        .color(Color32::from_rgb(255, 160, 80)),
+                );
+            }
+        });
+    }
+
+    pub fn awaiting_auth_ui(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 2.0 - 22.0);
+            ui.label(
+                RichText::new("Waiting for Spotify login…")
+                    .size(18.0)
+                    .color(Color32::from_gray(180)),
+            );
+            ui.label(
+                RichText::new("Finish logging in in your browser")
+                    .size(11.0)
+                    .color(Color32::from_gray(100)),
+            );
+            // Spotify rejects the login if this exact uri isn't registered for the app
+            ui.label(
+                RichText::new(format!("Redirect URI: {}", self.auth_redirect_uri))
+                    .size(11.0)
+                    .color(Color32::from_gray(100)),
+            );
+            ui.add_space(12.0);
+            if ui.button("Cancel").clicked() {
+                self.tx.try_send(MessageToRT::CancelAuth).unwrap();
             }
         });
     }
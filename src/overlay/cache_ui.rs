@@ -0,0 +1,223 @@
+//! Cache tab of the settings window, listing the cached tracks
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+use egui::{Color32, RichText, Ui};
+
+use lyrics_overlay_core::{
+    MessageToRT,
+    lyrics_fetch::{CacheListing, CachedTrack},
+};
+
+use crate::overlay::LyricsAppUI;
+
+/// Pages of the settings window
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsTab {
+    #[default]
+    Settings,
+    Cache,
+}
+
+/// State of the cache tab
+#[derive(Debug, Default)]
+pub struct CacheBrowser {
+    /// `None` until the runtime listed the cache
+    listing: Option<CacheListing>,
+    /// Only tracks with this in their title or artist are listed
+    filter: String,
+    loading: bool,
+    /// Clear all was clicked once, waiting for the confirmation
+    confirm_clear: bool,
+    /// Where the cache is exported to and imported from
+    archive_path: String,
+    /// Outcome of the last export or import
+    pub archive_status: Option<String>,
+}
+
+impl CacheBrowser {
+    pub fn set_listing(&mut self, listing: CacheListing) {
+        self.listing = Some(listing);
+        self.loading = false;
+    }
+
+    pub fn failed(&mut self) {
+        self.loading = false;
+    }
+}
+
+impl LyricsAppUI {
+    /// Tab picker at the top of the settings window
+    pub(super) fn settings_tabs(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.settings_tab, SettingsTab::Settings, "Settings");
+            if ui
+                .selectable_value(&mut self.settings_tab, SettingsTab::Cache, "Cache")
+                .clicked()
+            {
+                self.send_cache_request(MessageToRT::ListCache);
+            }
+        });
+        ui.separator();
+    }
+
+    fn send_cache_request(&mut self, message: MessageToRT) {
+        self.tx.try_send(message).unwrap();
+        self.cache_browser.loading = true;
+    }
+
+    pub(super) fn cache_tab(&mut self, ui: &mut Ui) {
+        let mut request = None;
+        let browser = &mut self.cache_browser;
+
+        ui.horizontal(|ui| {
+            if let Some(listing) = &browser.listing {
+                ui.label(
+                    RichText::new(format!(
+                        "{} tracks, {} · {} hits, {} misses this session",
+                        listing.tracks.len(),
+                        format_size(listing.total_bytes),
+                        listing.hits,
+                        listing.misses
+                    ))
+                    .size(11.0)
+                    .color(Color32::from_gray(160)),
+                );
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("⟳").on_hover_text("Refresh").clicked() {
+                    request = Some(MessageToRT::ListCache);
+                }
+                if browser.loading {
+                    ui.spinner();
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut browser.filter)
+                    .hint_text("Filter")
+                    .desired_width(160.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if browser.confirm_clear {
+                    if ui.button("Cancel").clicked() {
+                        browser.confirm_clear = false;
+                    }
+                    if ui.button("Clear everything").clicked() {
+                        request = Some(MessageToRT::ClearCache(None));
+                        browser.confirm_clear = false;
+                    }
+                } else if ui.button("Clear all").clicked() {
+                    browser.confirm_clear = true;
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            if browser.archive_path.is_empty() {
+                browser.archive_path = default_archive_path();
+            }
+            ui.add(
+                egui::TextEdit::singleline(&mut browser.archive_path)
+                    .hint_text("Archive path")
+                    .desired_width(160.0)
+                    .text_color(Color32::from_gray(200)),
+            )
+            .on_hover_text("A .tar.gz archive of the cache, for moving it to another machine");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let path = PathBuf::from(browser.archive_path.trim());
+                if ui.button("Import").clicked() {
+                    request = Some(MessageToRT::ImportCache(path.clone()));
+                }
+                if ui.button("Export").clicked() {
+                    request = Some(MessageToRT::ExportCache(path));
+                }
+            });
+        });
+        if let Some(status) = &browser.archive_status {
+            ui.label(
+                RichText::new(status)
+                    .size(11.0)
+                    .color(Color32::from_gray(160)),
+            );
+        }
+        ui.add_space(4.0);
+
+        if let Some(listing) = &browser.listing {
+            let filter = browser.filter.to_lowercase();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for track in listing.tracks.iter().filter(|track| {
+                    filter.is_empty()
+                        || track.track_name.to_lowercase().contains(&filter)
+                        || track.artist_name.to_lowercase().contains(&filter)
+                }) {
+                    if cached_track_row(ui, track) {
+                        request = Some(MessageToRT::ClearCache(Some(vec![track.folder.clone()])));
+                    }
+                }
+            });
+        }
+
+        if let Some(request) = request {
+            self.send_cache_request(request);
+        }
+    }
+}
+
+/// One cached track, true if its delete button was clicked
+fn cached_track_row(ui: &mut Ui, track: &CachedTrack) -> bool {
+    let mut delete = false;
+    ui.horizontal(|ui| {
+        ui.vertical(|ui| {
+            ui.label(
+                RichText::new(format!("{} - {}", track.artist_name, track.track_name))
+                    .size(12.0)
+                    .color(Color32::from_gray(200)),
+            );
+            let provider = track
+                .provider
+                .map_or("No lyrics", |provider| provider.as_str());
+            let last_used = track
+                .last_used
+                .and_then(|secs| DateTime::from_timestamp(i64::try_from(secs).ok()?, 0))
+                .map_or_else(String::new, |time| {
+                    format!(" · {}", time.with_timezone(&Local).format("%Y-%m-%d %H:%M"))
+                });
+            let pinned = if track.pinned { " · pinned" } else { "" };
+            ui.label(
+                RichText::new(format!(
+                    "{provider} · {}{last_used}{pinned}",
+                    format_size(track.size_bytes)
+                ))
+                .size(10.0)
+                .color(Color32::from_gray(130)),
+            );
+        });
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            delete = ui
+                .button("🗑")
+                .on_hover_text("Remove from the cache")
+                .clicked();
+        });
+    });
+    delete
+}
+
+/// Archive in the home folder, or the working directory without one
+fn default_archive_path() -> String {
+    directories::UserDirs::new()
+        .map_or_else(PathBuf::new, |dirs| dirs.home_dir().to_path_buf())
+        .join("lyrics-cache.tar.gz")
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{:.0} KB", bytes as f64 / 1024.)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1024. / 1024.)
+    }
+}
@@ -0,0 +1,97 @@
+//! Fonts of the overlay, egui's own only cover latin, greek, cyrillic and some emoji
+use std::{fs, sync::Arc};
+
+use egui::{FontData, FontDefinitions, FontFamily};
+use tracing::{info, warn};
+
+use crate::overlay::LyricsAppUI;
+
+/// System fonts covering Chinese, Japanese and Korean, the first one found is the fallback
+const CJK_FONTS: &[&str] = &[
+    // Linux
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    // Windows
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\malgun.ttf",
+    // macOS
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/System/Library/Fonts/AppleSDGothicNeo.ttc",
+];
+
+/// egui's fonts with `files` tried first and a system CJK font last, so the user's fonts
+/// win and egui's emoji still back them up
+fn font_definitions(files: &[String]) -> FontDefinitions {
+    let mut fonts = FontDefinitions::default();
+    let custom: Vec<String> = files
+        .iter()
+        .map(|path| path.trim())
+        .filter(|path| !path.is_empty())
+        .filter_map(|path| {
+            load_font(&mut fonts, path)
+                .inspect_err(|err| warn!("Failed loading the font {path}: {err}"))
+                .ok()
+        })
+        .collect();
+    let cjk = CJK_FONTS
+        .iter()
+        .find_map(|path| load_font(&mut fonts, path).ok());
+    if let Some(cjk) = &cjk {
+        info!("Using {cjk} for CJK lyrics");
+    }
+
+    for (family, custom_first) in [
+        (FontFamily::Proportional, true),
+        (FontFamily::Monospace, false),
+    ] {
+        let chain = fonts.families.entry(family).or_default();
+        if custom_first {
+            chain.splice(0..0, custom.iter().cloned());
+        } else {
+            chain.extend(custom.iter().cloned());
+        }
+        chain.extend(cjk.clone());
+    }
+    fonts
+}
+
+/// Adds the font at `path` under its path as name
+fn load_font(fonts: &mut FontDefinitions, path: &str) -> std::io::Result<String> {
+    let data = fs::read(path)?;
+    fonts
+        .font_data
+        .insert(path.to_owned(), Arc::new(FontData::from_owned(data)));
+    Ok(path.to_owned())
+}
+
+impl LyricsAppUI {
+    /// Installs the fonts from the settings, again whenever they change
+    pub(super) fn update_fonts(&mut self, ctx: &egui::Context) {
+        if self.font_files.as_ref() == Some(&self.settings_cache.font_files) {
+            return;
+        }
+        ctx.set_fonts(font_definitions(&self.settings_cache.font_files));
+        self.font_files = Some(self.settings_cache.font_files.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_fonts_keep_the_defaults() {
+        let defaults = FontDefinitions::default();
+        let fonts = font_definitions(&["/does/not/exist.ttf".to_owned(), "  ".to_owned()]);
+        let chain = &fonts.families[&FontFamily::Proportional];
+        assert_eq!(
+            chain[..defaults.families[&FontFamily::Proportional].len()],
+            defaults.families[&FontFamily::Proportional][..]
+        );
+        assert!(!fonts.font_data.contains_key("/does/not/exist.ttf"));
+    }
+}
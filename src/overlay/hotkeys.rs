@@ -0,0 +1,413 @@
+//! System-wide hotkeys, which still reach us while the window is hidden, can't be clicked or
+//! sits behind a fullscreen app. With the `tray` feature the tray menu does the same
+use std::str::FromStr;
+
+use egui::{Color32, Key, KeyboardShortcut, Modifiers, RichText, ViewportCommand};
+use global_hotkey::{
+    GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState,
+    hotkey::{self, HotKey},
+};
+use tracing::{info, warn};
+
+#[cfg(feature = "tray")]
+use lyrics_overlay_core::settings::DisplayMode;
+use lyrics_overlay_core::{MessageToRT, player::PlaybackControl, settings::Settings};
+
+use crate::overlay::LyricsAppUI;
+
+/// Each nudge of the lyrics offset
+const OFFSET_STEP_MS: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayAction {
+    ToggleVisibility,
+    /// The window ignores the mouse, so it can sit over a game or a video
+    ToggleClickThrough,
+    /// Show the lyrics sooner
+    OffsetEarlier,
+    OffsetLater,
+    PlayPause,
+    /// Only from the tray menu
+    #[cfg(feature = "tray")]
+    Quit,
+    #[cfg(feature = "tray")]
+    SetDisplayMode(DisplayMode),
+}
+
+struct Binding {
+    action: OverlayAction,
+    hotkey: HotKey,
+    /// The hotkey while the window has focus, for when registering it globally failed
+    shortcut: Option<KeyboardShortcut>,
+}
+
+pub struct Hotkeys {
+    /// Is the window ignoring the mouse
+    pub click_through: bool,
+    /// Hidden by the visibility hotkey, the window stays open so the hotkeys keep working
+    pub hidden: bool,
+    /// Unregisters the hotkeys when dropped
+    _manager: Option<GlobalHotKeyManager>,
+    bindings: Vec<Binding>,
+    #[cfg(feature = "tray")]
+    _tray: Option<tray::Tray>,
+}
+
+impl Hotkeys {
+    /// Registers the hotkeys in the settings, empty ones are left out
+    pub fn new(settings: &Settings) -> Self {
+        let bindings: Vec<Binding> = [
+            (OverlayAction::ToggleVisibility, &settings.visibility_hotkey),
+            (
+                OverlayAction::ToggleClickThrough,
+                &settings.click_through_hotkey,
+            ),
+            (
+                OverlayAction::OffsetEarlier,
+                &settings.offset_earlier_hotkey,
+            ),
+            (OverlayAction::OffsetLater, &settings.offset_later_hotkey),
+            (OverlayAction::PlayPause, &settings.play_pause_hotkey),
+        ]
+        .into_iter()
+        .filter(|(_, hotkey)| !hotkey.trim().is_empty())
+        .filter_map(|(action, hotkey)| {
+            let hotkey = HotKey::from_str(hotkey)
+                .inspect_err(|err| warn!("Invalid hotkey {hotkey:?} for {action:?}: {err}"))
+                .ok()?;
+            Some(Binding {
+                action,
+                shortcut: egui_shortcut(&hotkey),
+                hotkey,
+            })
+        })
+        .collect();
+
+        let manager = GlobalHotKeyManager::new()
+            .inspect_err(|err| warn!("Global hotkeys are unavailable: {err}"))
+            .ok();
+        if let Some(manager) = &manager {
+            for binding in &bindings {
+                if let Err(err) = manager.register(binding.hotkey) {
+                    warn!(
+                        "Failed registering the hotkey for {:?}: {err}",
+                        binding.action
+                    );
+                }
+            }
+        }
+        Self {
+            click_through: false,
+            hidden: false,
+            _manager: manager,
+            bindings,
+            #[cfg(feature = "tray")]
+            _tray: tray::Tray::new(),
+        }
+    }
+
+    /// Hotkeys pressed and tray entries clicked since the last frame
+    pub fn poll(&self, ctx: &egui::Context) -> Vec<OverlayAction> {
+        let mut actions = Vec::new();
+        while let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
+            if event.state() != HotKeyState::Pressed {
+                continue;
+            }
+            actions.extend(
+                self.bindings
+                    .iter()
+                    .filter(|binding| binding.hotkey.id() == event.id())
+                    .map(|binding| binding.action),
+            );
+        }
+        for binding in &self.bindings {
+            if let Some(shortcut) = &binding.shortcut
+                && ctx.input_mut(|i| i.consume_shortcut(shortcut))
+            {
+                actions.push(binding.action);
+            }
+        }
+        #[cfg(feature = "tray")]
+        actions.extend(std::iter::from_fn(tray::Tray::poll));
+        actions
+    }
+
+    /// How to trigger the action, for hover texts
+    pub fn hint(&self, action: OverlayAction, ctx: &egui::Context) -> Option<String> {
+        let binding = self
+            .bindings
+            .iter()
+            .find(|binding| binding.action == action)?;
+        Some(binding.shortcut.as_ref().map_or_else(
+            || binding.hotkey.to_string(),
+            |shortcut| ctx.format_shortcut(shortcut),
+        ))
+    }
+}
+
+impl LyricsAppUI {
+    pub(super) fn handle_hotkeys(&mut self, ctx: &egui::Context) {
+        for action in self.hotkeys.poll(ctx) {
+            match action {
+                OverlayAction::ToggleVisibility => {
+                    self.hotkeys.hidden = !self.hotkeys.hidden;
+                    info!(
+                        "Overlay {}",
+                        if self.hotkeys.hidden {
+                            "hidden"
+                        } else {
+                            "shown"
+                        }
+                    );
+                    self.apply_passthrough(ctx);
+                }
+                OverlayAction::ToggleClickThrough => {
+                    self.set_click_through(ctx, !self.hotkeys.click_through);
+                }
+                OverlayAction::OffsetEarlier => self.lyrics_offset_ms += OFFSET_STEP_MS,
+                OverlayAction::OffsetLater => self.lyrics_offset_ms -= OFFSET_STEP_MS,
+                OverlayAction::PlayPause => {
+                    self.tx
+                        .try_send(MessageToRT::PlayerControl(PlaybackControl::PlayPause))
+                        .unwrap();
+                }
+                #[cfg(feature = "tray")]
+                OverlayAction::Quit => ctx.send_viewport_cmd(ViewportCommand::Close),
+                #[cfg(feature = "tray")]
+                OverlayAction::SetDisplayMode(mode) => {
+                    self.update_settings(|settings| settings.display_mode = mode);
+                }
+            }
+        }
+    }
+
+    fn set_click_through(&mut self, ctx: &egui::Context, enabled: bool) {
+        info!("Click-through {}", if enabled { "on" } else { "off" });
+        self.hotkeys.click_through = enabled;
+        self.apply_passthrough(ctx);
+    }
+
+    /// A hidden window lets clicks through too
+    fn apply_passthrough(&self, ctx: &egui::Context) {
+        ctx.send_viewport_cmd(ViewportCommand::MousePassthrough(
+            self.hotkeys.click_through || self.hotkeys.hidden,
+        ));
+    }
+
+    /// Button in the corner turning click-through on
+    pub(super) fn click_through_button(&mut self, ctx: &egui::Context, full_width: f32) {
+        let undo = match (
+            self.hotkeys.hint(OverlayAction::ToggleClickThrough, ctx),
+            cfg!(feature = "tray"),
+        ) {
+            (Some(hotkey), true) => format!("{hotkey} or the tray menu"),
+            (Some(hotkey), false) => hotkey,
+            (None, true) => "the tray menu".to_owned(),
+            (None, false) => "a click-through hotkey".to_owned(),
+        };
+        let clicked = egui::Area::new("click_through_overlay".into())
+            .fixed_pos(egui::pos2(full_width - 85., 10.))
+            .show(ctx, |ui| {
+                ui.add(
+                    egui::Button::new(RichText::new("🖱").size(12.0).color(Color32::from_gray(160)))
+                        .frame(false),
+                )
+                .on_hover_text(format!(
+                    "Let clicks through to the windows below, {undo} to undo"
+                ))
+                .clicked()
+            })
+            .inner;
+        if clicked {
+            self.set_click_through(ctx, true);
+        }
+    }
+
+    /// The lyrics offset while it's nudged away from 0
+    pub(super) fn offset_label(&self, ctx: &egui::Context, full_height: f32) {
+        if self.lyrics_offset_ms == 0 {
+            return;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let offset_sec = self.lyrics_offset_ms as f32 / 1000.;
+        egui::Area::new("lyrics offset".into())
+            .fixed_pos(egui::pos2(10., full_height - 40.))
+            .show(ctx, |ui| {
+                ui.label(
+                    RichText::new(format!("Lyrics offset {offset_sec:+.1}s"))
+                        .size(11.0)
+                        .color(Color32::from_gray(160)),
+                );
+            });
+    }
+}
+
+/// The same keys as seen by egui, `None` for keys egui has no name for
+fn egui_shortcut(hotkey: &HotKey) -> Option<KeyboardShortcut> {
+    let code = hotkey.key.to_string();
+    let name = code
+        .strip_prefix("Key")
+        .or_else(|| code.strip_prefix("Digit"))
+        .unwrap_or(&code);
+    let key = Key::from_name(name)?;
+    let ctrl = hotkey.mods.contains(hotkey::Modifiers::CONTROL);
+    let super_key = hotkey.mods.contains(hotkey::Modifiers::SUPER);
+    let mac = cfg!(target_os = "macos");
+    // `command` is egui's CmdOrCtrl
+    let modifiers = Modifiers {
+        alt: hotkey.mods.contains(hotkey::Modifiers::ALT),
+        ctrl: mac && ctrl,
+        shift: hotkey.mods.contains(hotkey::Modifiers::SHIFT),
+        mac_cmd: false,
+        command: if mac { super_key } else { ctrl },
+    };
+    Some(KeyboardShortcut::new(modifiers, key))
+}
+
+#[cfg(feature = "tray")]
+mod tray {
+    use tracing::warn;
+    use tray_icon::{
+        Icon, TrayIconBuilder,
+        menu::{IsMenuItem, Menu, MenuEvent, MenuItem, Submenu},
+    };
+
+    use lyrics_overlay_core::settings::DisplayMode;
+
+    use super::OverlayAction;
+
+    const VISIBILITY_ID: &str = "visibility";
+    const CLICK_THROUGH_ID: &str = "click-through";
+    const QUIT_ID: &str = "quit";
+    /// Followed by the mode's name
+    const MODE_ID_PREFIX: &str = "mode-";
+    const ICON_SIZE: u32 = 32;
+
+    /// Keeps the tray icon alive, on linux it lives on its own gtk thread instead
+    pub struct Tray {
+        #[cfg(not(target_os = "linux"))]
+        _icon: tray_icon::TrayIcon,
+    }
+
+    impl Tray {
+        #[cfg(not(target_os = "linux"))]
+        pub fn new() -> Option<Self> {
+            let icon = build_tray()
+                .inspect_err(|err| warn!("Failed creating the tray icon: {err}"))
+                .ok()?;
+            Some(Self { _icon: icon })
+        }
+
+        /// Tray icons need a running gtk loop on their thread
+        #[cfg(target_os = "linux")]
+        pub fn new() -> Option<Self> {
+            std::thread::Builder::new()
+                .name("tray".to_owned())
+                .spawn(|| {
+                    if let Err(err) = gtk::init() {
+                        warn!("Failed initializing gtk for the tray icon: {err}");
+                        return;
+                    }
+                    match build_tray() {
+                        Ok(_icon) => gtk::main(),
+                        Err(err) => warn!("Failed creating the tray icon: {err}"),
+                    }
+                })
+                .inspect_err(|err| warn!("Failed starting the tray thread: {err}"))
+                .ok()?;
+            Some(Self {})
+        }
+
+        /// Next clicked menu entry
+        pub fn poll() -> Option<OverlayAction> {
+            while let Ok(event) = MenuEvent::receiver().try_recv() {
+                if event.id == VISIBILITY_ID {
+                    return Some(OverlayAction::ToggleVisibility);
+                }
+                if event.id == CLICK_THROUGH_ID {
+                    return Some(OverlayAction::ToggleClickThrough);
+                }
+                if event.id == QUIT_ID {
+                    return Some(OverlayAction::Quit);
+                }
+                if let Some(name) = event.id.0.strip_prefix(MODE_ID_PREFIX)
+                    && let Some(mode) = DisplayMode::ALL.into_iter().find(|m| m.as_str() == name)
+                {
+                    return Some(OverlayAction::SetDisplayMode(mode));
+                }
+            }
+            None
+        }
+    }
+
+    fn build_tray() -> Result<tray_icon::TrayIcon, Box<dyn std::error::Error>> {
+        let menu = Menu::new();
+        menu.append(&MenuItem::with_id(
+            VISIBILITY_ID,
+            "Show or hide",
+            true,
+            None,
+        ))?;
+        menu.append(&MenuItem::with_id(
+            CLICK_THROUGH_ID,
+            "Toggle click-through",
+            true,
+            None,
+        ))?;
+        let modes: Vec<MenuItem> = DisplayMode::ALL
+            .into_iter()
+            .map(|mode| {
+                MenuItem::with_id(
+                    format!("{MODE_ID_PREFIX}{}", mode.as_str()),
+                    mode.as_str(),
+                    true,
+                    None,
+                )
+            })
+            .collect();
+        let modes: Vec<&dyn IsMenuItem> = modes.iter().map(|m| m as &dyn IsMenuItem).collect();
+        menu.append(&Submenu::with_items("Display mode", true, &modes)?)?;
+        menu.append(&MenuItem::with_id(QUIT_ID, "Quit", true, None))?;
+        Ok(TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("Lyrics Overlay")
+            .with_icon(icon()?)
+            .build()?)
+    }
+
+    /// A filled circle, there are no image files to load one from
+    #[allow(clippy::cast_precision_loss)]
+    fn icon() -> Result<Icon, tray_icon::BadIcon> {
+        let center = (ICON_SIZE as f32 - 1.) / 2.;
+        let rgba = (0..ICON_SIZE * ICON_SIZE)
+            .flat_map(|i| {
+                let (x, y) = ((i % ICON_SIZE) as f32, (i / ICON_SIZE) as f32);
+                let inside = (x - center).hypot(y - center) <= center;
+                [80, 200, 120, if inside { 255 } else { 0 }]
+            })
+            .collect();
+        Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotkey_as_egui_shortcut() {
+        let hotkey = HotKey::from_str("CmdOrCtrl+Shift+L").unwrap();
+        assert_eq!(
+            egui_shortcut(&hotkey),
+            Some(KeyboardShortcut::new(
+                Modifiers::COMMAND | Modifiers::SHIFT,
+                Key::L
+            ))
+        );
+        let hotkey = HotKey::from_str("Alt+F9").unwrap();
+        assert_eq!(
+            egui_shortcut(&hotkey),
+            Some(KeyboardShortcut::new(Modifiers::ALT, Key::F9))
+        );
+    }
+}
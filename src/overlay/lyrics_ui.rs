@@ -1,11 +1,98 @@
-use egui::{Align, Color32, Layout, Rect, RichText, ScrollArea, Sense, Ui, Vec2};
+use egui::{
+    Align, Align2, Color32, FontId, Layout, Rect, Response, RichText, ScrollArea, Sense,
+    TextFormat, TextStyle, TextWrapMode, Ui, Vec2, WidgetText, text::LayoutJob,
+};
+
+use std::borrow::Cow;
+
+use lyrics_overlay_core::{
+    lyrics_fetch::{LyricsSource, SongWithLyrics, TranslationBackendKind},
+    lyrics_parser::{
+        LinePart, LyricLine, LyricPosition, RubySegment, SongLyrics, is_rtl, split_adlibs,
+        strip_adlibs, sung_fraction, visual_order,
+    },
+    profanity::mask_profanity,
+    settings::{AdlibDisplay, DisplayMode, EasingModes, ProgressBarPosition},
+};
 
-use crate::{
-    lyrics_parser::LyricPosition,
-    overlay::LyricsAppUI,
-    settings::{EasingModes, ProgressBarPosition},
-    spotify::CurrentlyPlayingResponse,
+use crate::overlay::{
+    LyricsAppUI, REFRESH_SHORTCUT,
+    search_ui::format_duration,
+    theme::{LineColors, transition_scale},
 };
+
+/// Rows of the compact mode as the line relative to the current one and its size relative to
+/// the font size
+const COMPACT_ROWS: &[(isize, f32)] = &[(-1, 1.0), (0, 1.0), (1, 1.0), (2, 1.0)];
+/// The current line large and the next one smaller beneath it, like on a karaoke machine
+const KARAOKE_ROWS: &[(isize, f32)] = &[(0, 1.25), (1, 0.7)];
+
+/// Rows of the modes that show a fixed number of lines, none for the others
+pub(super) fn fixed_rows(mode: DisplayMode) -> &'static [(isize, f32)] {
+    match mode {
+        DisplayMode::Compact => COMPACT_ROWS,
+        DisplayMode::Karaoke => KARAOKE_ROWS,
+        DisplayMode::Scrolling | DisplayMode::Ticker => &[],
+    }
+}
+
+/// Height of a fixed row for lines of `size`, with room for the beat pulse
+pub(super) fn row_height(size: f32) -> f32 {
+    size * 1.6
+}
+
+/// How far the current line is filled in
+#[derive(Debug, Clone, Copy)]
+pub(super) struct LineFill {
+    /// Part of the letters sung, 0.0–1.0
+    sung: f32,
+    /// Brightness of the letters yet to be sung
+    dim: f32,
+    /// Letters the edge of the fill blends over
+    edge: f32,
+}
+
+impl LineFill {
+    /// Filled in as the words are sung
+    fn words(sung: f32) -> Self {
+        Self {
+            sung,
+            dim: 0.5,
+            edge: 1.0,
+        }
+    }
+
+    /// A softer sweep over the time to the next line, without word timings to go by
+    fn sweep(progress: f32) -> Self {
+        Self {
+            sung: progress,
+            dim: 0.75,
+            edge: 6.0,
+        }
+    }
+}
+
+/// How quickly the scroll catches up with the current line, the time constant of its easing
+const SCROLL_FOLLOW_SEC: f32 = 0.12;
+
+/// Position within the lyrics for a single frame
+struct LineTiming {
+    /// Index of the line being sung
+    current_index: usize,
+    /// Progress through the current line, 0.0–1.0
+    raw_progress: f32,
+    /// Line to scroll to, fractional while transitioning between lines
+    target_line: f32,
+}
+
+/// Picked from the track header's context menu
+enum HeaderAction {
+    Refresh,
+    Reject,
+    Pin,
+    Unpin,
+}
+
 fn ease_in_out(t: f32, mode: EasingModes) -> f32 {
     match mode {
         EasingModes::Cubic => t * t * (3.0 - 2.0 * t),
@@ -18,7 +105,142 @@ fn ease_in_out(t: f32, mode: EasingModes) -> f32 {
 #[allow(clippy::cast_precision_loss)]
 #[allow(clippy::cast_sign_loss)]
 impl LyricsAppUI {
-    // TODO: Split into smaller functions
+    /// Scroll offset that puts `target_line` just above the center
+    fn scroll_offset(&self, target_line: f32, center_bias: f32) -> f32 {
+        let line_floor = target_line.floor() as usize;
+        let line_frac = target_line.fract();
+        let y_floor = self
+            .line_top_offsets
+            .get(line_floor)
+            .copied()
+            .unwrap_or_else(|| self.line_top_offsets.last().copied().unwrap_or(0.0));
+        let y_ceil = self
+            .line_top_offsets
+            .get(line_floor + 1)
+            .copied()
+            .unwrap_or(y_floor);
+
+        // Interpolate between the two neighbouring line positions.
+        let y_exact = y_floor + (y_ceil - y_floor) * line_frac;
+        (y_exact - center_bias).max(0.0)
+    }
+
+    /// Moves the shown scroll offset part of the way to `target_y` when scrolling smoothly, so
+    /// seeks and lines changing size glide instead of snapping
+    fn eased_scroll(&self, target_y: f32, dt: f32) -> f32 {
+        match self.scroll_y {
+            Some(shown) if self.settings_cache.scroll_smoothly => {
+                shown + (target_y - shown) * (1.0 - (-dt / SCROLL_FOLLOW_SEC).exp())
+            }
+            _ => target_y,
+        }
+    }
+
+    /// Track header, with lookup actions when nothing was found. False if the lyrics were cleared
+    fn lyrics_header(&mut self, ui: &mut Ui) -> bool {
+        let Some(song) = &self.current_song_with_lyrics else {
+            return true;
+        };
+        let header = track_header(
+            ui,
+            song,
+            self.settings_cache.show_lyrics_source,
+            self.settings_cache.scroll_unsynced_lyrics,
+        );
+        // Our placeholder text and lyrics without an id can't be skipped or pinned
+        let source = song.source.clone().filter(|source| source.id.is_some());
+        let pinned = song.pinned;
+        // Pins live in the cache
+        let can_pin = self.settings_cache.caching_enabled;
+        let mut action = None;
+        header.context_menu(|ui| {
+            let refresh = egui::Button::new("Refresh lyrics")
+                .shortcut_text(ui.ctx().format_shortcut(&REFRESH_SHORTCUT));
+            if ui.add(refresh).clicked() {
+                action = Some(HeaderAction::Refresh);
+            }
+            if source.is_some() {
+                if ui.button("Wrong lyrics").clicked() {
+                    action = Some(HeaderAction::Reject);
+                }
+                if can_pin && !pinned && ui.button("Pin these lyrics").clicked() {
+                    action = Some(HeaderAction::Pin);
+                }
+                if pinned && ui.button("Unpin lyrics").clicked() {
+                    action = Some(HeaderAction::Unpin);
+                }
+            }
+            if action.is_some() {
+                ui.close();
+            }
+        });
+        match (action, source) {
+            (Some(HeaderAction::Refresh), _) => {
+                self.refetch_lyrics();
+                return false;
+            }
+            (Some(HeaderAction::Reject), Some(source)) => {
+                self.reject_lyrics(source);
+                return false;
+            }
+            (Some(HeaderAction::Pin), source) => self.pin_lyrics(source),
+            (Some(HeaderAction::Unpin), _) => self.pin_lyrics(None),
+            _ => {}
+        }
+        if let Some(lyrics_sec) = self
+            .current_song_with_lyrics
+            .as_ref()
+            .and_then(|song| song.duration_mismatch(self.settings_cache.duration_tolerance_sec))
+        {
+            self.duration_warning(ui, lyrics_sec);
+        }
+        if self.session_status.lyrics_found() != Some(false) {
+            return true;
+        }
+        let (retry, search) = ui
+            .horizontal(|ui| {
+                (
+                    ui.small_button("Retry lookup").clicked(),
+                    ui.small_button("Search manually").clicked(),
+                )
+            })
+            .inner;
+        if search {
+            self.open_search_dialog();
+        }
+        if retry {
+            self.refetch_lyrics();
+            return false;
+        }
+        true
+    }
+
+    /// The lyrics are for a recording of a different length, offers finding better ones
+    fn duration_warning(&mut self, ui: &mut Ui, lyrics_sec: f64) {
+        let Some(song) = &self.current_song_with_lyrics else {
+            return;
+        };
+        let details = format!(
+            "The lyrics are timed to a {} version, the track is {}",
+            format_duration(lyrics_sec),
+            format_duration(song.duration_sec)
+        );
+        let search = ui
+            .horizontal(|ui| {
+                ui.label(
+                    RichText::new("⚠ timings may be off")
+                        .size(11.0)
+                        .color(Color32::from_rgb(230, 180, 80)),
+                )
+                .on_hover_text(details);
+                ui.small_button("Search manually").clicked()
+            })
+            .inner;
+        if search {
+            self.open_search_dialog();
+        }
+    }
+
     pub(super) fn display_lyrics(&mut self, ui: &mut Ui) {
         // Do we have lyrics
         let Some(song) = &self.current_song_with_lyrics else {
@@ -27,108 +249,90 @@ impl LyricsAppUI {
         };
 
         // Make sure it's not the previous song's lyrics
-        if Some(song.track_name.clone())
-            != self
-                .currently_playing
-                .as_ref()
-                .and_then(CurrentlyPlayingResponse::get_track_title)
-        {
+        if Some(&song.track_name) != self.currently_playing.as_ref().map(|p| &p.title) {
             self.waiting_for_lyrics(ui);
             return;
         }
 
-        ui.label(
-            RichText::new(format!("♫ {1} - {0}", song.track_name, song.artist_name))
-                .size(11.0)
-                .color(Color32::from_gray(180)),
-        );
+        // The other modes have no room for the header
+        let scrolling = self.settings_cache.display_mode == DisplayMode::Scrolling;
+        if scrolling && !self.lyrics_header(ui) {
+            return;
+        }
+        let Some(song) = &self.current_song_with_lyrics else {
+            return;
+        };
+        if song.lyrics.unsynced && !self.settings_cache.scroll_unsynced_lyrics {
+            self.plain_lyrics(ui, song);
+            return;
+        }
 
-        let progress_ms = self.currently_playing.as_ref().map_or(0, |p| p.progress_ms);
-        let current_ms = progress_ms as u128
-            + self.currently_playing.as_ref().map_or(0, |c| {
-                if c.is_playing {
-                    self.time_of_last_req.elapsed().as_millis()
-                } else {
-                    0
-                }
-            });
-        let synced_lyrics = &song.lyrics.synced_lyrics;
+        let current_ms = self.lyrics_position_ms();
         let song_end_ms = (song.duration_sec * 1000.) as i64;
         let song_progress = current_ms as f32 / song_end_ms as f32;
 
-        let (t0, t1, current_index) = match song
-            .lyrics
-            .find_current_index(current_ms.try_into().unwrap())
-        {
-            LyricPosition::BeforeStart => (
-                0,
-                synced_lyrics
-                    .first()
-                    .map_or(song_end_ms, |l| l.time_ms as i64),
-                0,
-            ),
-            LyricPosition::Line(n) => (
-                synced_lyrics[n].time_ms as i64,
-                synced_lyrics
-                    .get(n + 1)
-                    .map_or(song_end_ms, |l| l.time_ms as i64),
-                n,
-            ),
-            LyricPosition::AfterEnd(n) => (synced_lyrics[n - 1].time_ms as i64, song_end_ms, n),
-        };
-
-        let raw_progress = if t1 - t0 > 0 {
-            ((current_ms as i64 - t0) as f32 / (t1 - t0) as f32).clamp(0.0, 1.0)
-        } else {
-            0.0
-        };
-
-        let target_line = if self.settings_cache.scroll_smoothly {
-            match song
-                .lyrics
-                .find_current_index(current_ms.try_into().unwrap())
-            {
-                LyricPosition::BeforeStart => {
-                    -1.0 + ease_in_out(raw_progress, self.settings_cache.ease_position)
-                }
-                _ => {
-                    current_index as f32
-                        + ease_in_out(raw_progress, self.settings_cache.ease_position)
+        let timing = self.line_timing(&song.lyrics, current_ms, song_end_ms);
+        let raw_progress = timing.raw_progress;
+        let colors = self.line_colors();
+        match self.settings_cache.display_mode {
+            DisplayMode::Scrolling => {
+                let (offsets, scroll_y) =
+                    self.scrolling_lines(ui, song, &timing, &colors, current_ms, song_progress);
+                self.line_top_offsets = offsets;
+                self.scroll_y = Some(scroll_y);
+            }
+            DisplayMode::Ticker => {
+                let lines = &song.lyrics.synced_lyrics;
+                if let Some(line) = lines.get(timing.current_index.min(lines.len() - 1)) {
+                    let signed = timing.current_index as f32 - timing.target_line;
+                    let color = self.line_color(&colors, signed);
+                    let fill = self.line_fill(line, current_ms, raw_progress);
+                    self.ticker(ui, line, raw_progress, color, fill);
                 }
             }
-        } else {
-            current_index as f32
-        };
+            mode @ (DisplayMode::Compact | DisplayMode::Karaoke) => {
+                let lines = &song.lyrics.synced_lyrics;
+                self.fixed_lines(ui, lines, &timing, &colors, current_ms, fixed_rows(mode));
+            }
+        }
+
+        if self.settings_cache.line_progress_bar_position == ProgressBarPosition::Bottom {
+            draw_progress_bar(ui, raw_progress, ui.available_width());
+        }
+        if self.settings_cache.song_progress_bar_position == ProgressBarPosition::Bottom {
+            draw_progress_bar(ui, song_progress, ui.available_width());
+        }
+    }
 
+    /// Every line, scrolled to keep the current one in place. Returns where each line starts and
+    /// the scroll offset
+    fn scrolling_lines(
+        &self,
+        ui: &mut Ui,
+        song: &SongWithLyrics,
+        timing: &LineTiming,
+        colors: &LineColors,
+        current_ms: u128,
+        song_progress: f32,
+    ) -> (Vec<f32>, f32) {
+        let &LineTiming {
+            current_index,
+            raw_progress,
+            target_line,
+        } = timing;
         let available_height = ui.available_height();
-        let center_bias = available_height * 0.25 * 0.5;
-        // 0 is bottom, 0.25 is almost off screen, 0.25*0.5 is just above center.
-
-        let scroll_y = {
-            let line_floor = target_line.floor() as usize;
-            let line_frac = target_line.fract();
-            let y_floor = self
-                .line_top_offsets
-                .get(line_floor)
-                .copied()
-                .unwrap_or_else(|| self.line_top_offsets.last().copied().unwrap_or(0.0));
-            let y_ceil = self
-                .line_top_offsets
-                .get(line_floor + 1)
-                .copied()
-                .unwrap_or(y_floor);
-
-            // Interpolate between the two neighbouring line positions.
-            let y_exact = y_floor + (y_ceil - y_floor) * line_frac;
-            (y_exact - center_bias).max(0.0)
-        };
+        let center_bias = available_height * self.theme().current_line_height;
+
+        let target_y = self.scroll_offset(target_line, center_bias);
+        let scroll_y = self.eased_scroll(target_y, ui.input(|i| i.stable_dt));
 
         if self.settings_cache.draw_debug_stuff {
-            ui.label(format!("target_line: {target_line:.3}"));
-            ui.label(format!("scroll_y: {scroll_y:.1}"));
-            ui.label(format!("current_ms: {current_ms}"));
+            debug_info(ui, &song.lyrics, current_ms, target_line, scroll_y);
         }
 
+        let synced_lyrics = &song.lyrics.synced_lyrics;
+        let beat_pulse = self.beat_pulse(current_ms);
+        let runs = self.repeat_runs(synced_lyrics);
         let mut new_offsets: Vec<f32> = Vec::with_capacity(synced_lyrics.len());
         ScrollArea::vertical()
             .id_salt("lyrics_scroll")
@@ -140,50 +344,59 @@ impl LyricsAppUI {
 
                 ui.with_layout(Layout::top_down(Align::Center), |ui| {
                     for (i, line) in synced_lyrics.iter().enumerate() {
-                        let top_y = ui.cursor().top() - ui.min_rect().top() - center_bias;
+                        let run = runs[i];
+                        // Folded lines scroll to the line shown for them
+                        let top_y = match new_offsets.last() {
+                            Some(&top_y) if run == 0 => top_y,
+                            _ => ui.cursor().top() - ui.min_rect().top() - center_bias,
+                        };
                         new_offsets.push(top_y);
+                        if run == 0 {
+                            continue;
+                        }
 
-                        let dist = (i as f32 - target_line).abs();
-                        let alpha_f = 0.20 + 0.80 * (1.0 - (dist / 3.5).clamp(0.0, 1.0)).powi(2);
-                        let alpha = (alpha_f * 255.0) as u8;
-
-                        let signed = i as f32 - target_line;
-                        // TODO: Add to settings
-                        let past_color = [200u8, 180, 255];
-                        let current_color = [255u8, 255, 255];
-                        let future_color = [180u8, 210, 255];
-
-                        let (r, g, b) = if signed < 0.0 {
-                            let t = ease_in_out((-signed).min(1.0), self.settings_cache.ease_color);
-                            lerp_color(current_color, past_color, t)
+                        // Distance to the run of repeats, any of them being sung counts
+                        let last = (i + run - 1) as f32;
+                        let signed = if target_line > last {
+                            last - target_line
                         } else {
-                            let t = ease_in_out(signed.min(1.0), self.settings_cache.ease_color);
-                            lerp_color(current_color, future_color, t)
+                            (i as f32 - target_line).max(0.0)
                         };
+                        let color = self.line_color(colors, signed);
+                        let is_current = (i..i + run).contains(&current_index);
+                        // Grows while it's scrolled to, and pulses while it's sung
+                        let emphasis = 1.0 - signed.abs().min(1.0);
+                        let pulse = if is_current {
+                            self.theme().beat_pulse_scale * beat_pulse
+                        } else {
+                            0.0
+                        };
+                        let size = self.settings_cache.font_size
+                            * (1.0 + self.theme().current_line_growth * emphasis + pulse);
+                        // Folded lines are filled in by the repeat being sung
+                        let fill = synced_lyrics
+                            .get(current_index)
+                            .filter(|_| is_current)
+                            .and_then(|current| self.line_fill(current, current_ms, raw_progress));
+                        let label_resp = self.line_label(ui, line, size, color, fill);
+                        if run > 1 {
+                            repeat_counter(ui, label_resp.rect, run, size, color);
+                        }
 
-                        let color = Color32::from_rgba_unmultiplied(r, g, b, alpha);
-                        let label_resp = ui.label(
-                            RichText::new(&line.text)
-                                .size(self.settings_cache.font_size)
-                                .color(color)
-                                .strong(),
-                        );
-
-                        if i == current_index {
+                        if is_current {
                             let bar_width = label_resp.rect.width();
-                            if self.settings_cache.line_progress_bar_position
-                                == ProgressBarPosition::BelowCurrentLine
-                            {
-                                ui.add_space(2.0);
-                                draw_progress_bar(ui, raw_progress, bar_width);
-                                ui.add_space(2.0);
-                            }
-                            if self.settings_cache.song_progress_bar_position
-                                == ProgressBarPosition::BelowCurrentLine
-                            {
-                                ui.add_space(2.0);
-                                draw_progress_bar(ui, song_progress, bar_width);
-                                ui.add_space(2.0);
+                            for (position, progress) in [
+                                (self.settings_cache.line_progress_bar_position, raw_progress),
+                                (
+                                    self.settings_cache.song_progress_bar_position,
+                                    song_progress,
+                                ),
+                            ] {
+                                if position == ProgressBarPosition::BelowCurrentLine {
+                                    ui.add_space(2.0);
+                                    draw_progress_bar(ui, progress, bar_width);
+                                    ui.add_space(2.0);
+                                }
                             }
                         }
 
@@ -192,36 +405,564 @@ impl LyricsAppUI {
                 });
             });
 
-        self.line_top_offsets = new_offsets;
+        (new_offsets, scroll_y)
+    }
 
-        if self.settings_cache.line_progress_bar_position == ProgressBarPosition::Bottom {
-            draw_progress_bar(ui, raw_progress, ui.available_width());
+    /// The lines around the current one in fixed rows, cut off instead of wrapped and without
+    /// translations so the rows keep their height
+    fn fixed_lines(
+        &self,
+        ui: &mut Ui,
+        lines: &[LyricLine],
+        timing: &LineTiming,
+        colors: &LineColors,
+        current_ms: u128,
+        rows: &[(isize, f32)],
+    ) {
+        let beat_pulse = self.beat_pulse(current_ms);
+        for &(offset, scale) in rows {
+            let font_size = self.settings_cache.font_size * scale;
+            let (rect, _) = ui.allocate_exact_size(
+                Vec2::new(ui.available_width(), row_height(font_size)),
+                Sense::hover(),
+            );
+            // Rows before the first line and after the last stay empty
+            let Some(line) = timing
+                .current_index
+                .checked_add_signed(offset)
+                .and_then(|i| lines.get(i))
+            else {
+                continue;
+            };
+            let size = if offset == 0 {
+                font_size * (1.0 + self.theme().beat_pulse_scale * beat_pulse)
+            } else {
+                font_size
+            };
+            let signed = timing.current_index as f32 + offset as f32 - timing.target_line;
+            let color = self.part_color(line.part, self.line_color(colors, signed));
+            let text = if line.instrumental {
+                RichText::new("♪").size(size).color(color).into()
+            } else {
+                let fill = self
+                    .line_fill(line, current_ms, timing.raw_progress)
+                    .filter(|_| offset == 0);
+                self.line_text(ui, line, size, color, fill)
+            };
+            let galley = text.into_galley(
+                ui,
+                Some(TextWrapMode::Truncate),
+                rect.width(),
+                TextStyle::Body,
+            );
+            ui.painter_at(rect)
+                .galley(rect.center() - galley.size() / 2.0, galley, color);
         }
-        if self.settings_cache.song_progress_bar_position == ProgressBarPosition::Bottom {
-            draw_progress_bar(ui, song_progress, ui.available_width());
+    }
+
+    /// Lyrics without timings, scrolled by the user instead of following playback
+    fn plain_lyrics(&self, ui: &mut Ui, song: &SongWithLyrics) {
+        let [r, g, b] = self.line_colors().current;
+        let size = self.settings_cache.font_size * self.theme().plain_scale;
+        ScrollArea::vertical()
+            .id_salt("plain_lyrics_scroll")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.with_layout(Layout::top_down(Align::Center), |ui| {
+                    for line in &song.lyrics.synced_lyrics {
+                        ui.label(self.line_text(
+                            ui,
+                            line,
+                            size,
+                            self.part_color(line.part, Color32::from_rgb(r, g, b)),
+                            None,
+                        ));
+                        self.secondary_lines(ui, line, size, Color32::from_rgb(r, g, b));
+                        ui.add_space(self.settings_cache.line_spacing * 0.25);
+                    }
+                });
+            });
+    }
+
+    /// A synced line, with its reading hints and romanization if enabled.
+    /// Returns the response of the line itself
+    fn line_label(
+        &self,
+        ui: &mut Ui,
+        line: &LyricLine,
+        size: f32,
+        color: Color32,
+        fill: Option<LineFill>,
+    ) -> Response {
+        let color = self.part_color(line.part, color);
+        let response = if line.instrumental {
+            ui.label(RichText::new("♪").size(size).color(color))
+        } else if self.settings_cache.ruby_annotations && !line.ruby.is_empty() {
+            ruby_label(ui, &self.filtered_ruby(&line.ruby), size, color)
+        } else {
+            ui.label(self.line_text(ui, line, size, color, fill))
+        };
+        self.secondary_lines(ui, line, size, color);
+        response
+    }
+
+    /// Color of a line `signed` lines after the one being sung, faded with the distance if enabled
+    fn line_color(&self, colors: &LineColors, signed: f32) -> Color32 {
+        let alpha = if self.settings_cache.dim_distant_lines {
+            let theme = self.theme();
+            let fade = 1.0 - (signed.abs() / theme.fade_lines).clamp(0.0, 1.0);
+            ((theme.min_alpha + (1.0 - theme.min_alpha) * fade.powi(2)) * 255.0) as u8
+        } else {
+            255
+        };
+
+        let (r, g, b) = if signed < 0.0 {
+            let t = ease_in_out((-signed).min(1.0), self.settings_cache.ease_color);
+            lerp_color(colors.current, colors.past, t)
+        } else {
+            let t = ease_in_out(signed.min(1.0), self.settings_cache.ease_color);
+            lerp_color(colors.current, colors.future, t)
+        };
+        Color32::from_rgba_unmultiplied(r, g, b, alpha)
+    }
+
+    /// Length of each run of identical lines at its first line, 0 for the lines folded into it.
+    /// All 1 unless folding repeated lines is enabled
+    fn repeat_runs(&self, lines: &[LyricLine]) -> Vec<usize> {
+        let mut runs = vec![1; lines.len()];
+        if !self.settings_cache.fold_repeated_lines {
+            return runs;
+        }
+        let mut first = 0;
+        for (i, line) in lines.iter().enumerate().skip(1) {
+            if !line.text.trim().is_empty() && line.text == lines[first].text {
+                runs[first] += 1;
+                runs[i] = 0;
+            } else {
+                first = i;
+            }
+        }
+        runs
+    }
+
+    /// Text of a line with profanity masked, and backing vocals dimmed or hidden if set.
+    /// Right to left lines are put in the order they are drawn in
+    pub(super) fn line_text(
+        &self,
+        ui: &Ui,
+        line: &LyricLine,
+        size: f32,
+        color: Color32,
+        fill: Option<LineFill>,
+    ) -> WidgetText {
+        let mut text = self.filtered(&line.text);
+        if self.settings_cache.adlibs == AdlibDisplay::Hide {
+            text = Cow::Owned(strip_adlibs(&text));
+        }
+        if line.rtl {
+            text = Cow::Owned(rtl_rows(ui, &text, size));
+        }
+        // The rows of right to left lines are reordered, a fill would jump around
+        let fill = fill.filter(|_| !line.rtl);
+        let dim_adlibs = self.settings_cache.adlibs == AdlibDisplay::Dim;
+        if !dim_adlibs && fill.is_none() {
+            return RichText::new(text).size(size).color(color).strong().into();
+        }
+        let parts = if dim_adlibs {
+            split_adlibs(&text)
+        } else {
+            vec![(&*text, false)]
+        };
+        // Every letter is fully sung at 1.0, however wide the edge
+        let letters = text.chars().filter(|c| !c.is_whitespace()).count();
+        let fill = fill.unwrap_or(LineFill::words(1.0));
+        let sung = fill.sung * (letters as f32 + fill.edge - 1.0);
+        let mut letter = 0.0;
+        let mut job = LayoutJob::default();
+        for (part, adlib) in parts {
+            let format = TextFormat {
+                font_id: FontId::proportional(size),
+                color: if adlib {
+                    color.gamma_multiply(0.45)
+                } else {
+                    color
+                },
+                ..TextFormat::default()
+            };
+            append_filled(&mut job, part, &format, fill, sung, &mut letter);
+        }
+        job.into()
+    }
+
+    /// `color` tinted by the singer of a duet line, if enabled
+    pub(super) fn part_color(&self, part: Option<LinePart>, color: Color32) -> Color32 {
+        let Some(part) = part.filter(|_| self.settings_cache.color_duet_parts) else {
+            return color;
+        };
+        let tint = match part {
+            LinePart::Male | LinePart::Voice(1) => [110, 170, 255],
+            LinePart::Female | LinePart::Voice(2) => [255, 130, 190],
+            LinePart::Duet => [200, 150, 255],
+            LinePart::Voice(_) => [130, 230, 160],
+        };
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let (r, g, b) = lerp_color([r, g, b], tint, 0.5);
+        Color32::from_rgba_unmultiplied(r, g, b, a)
+    }
+
+    /// Smaller, dimmer romanization and translation under a line, if enabled
+    fn secondary_lines(&self, ui: &mut Ui, line: &LyricLine, size: f32, color: Color32) {
+        let romanized = line
+            .romanized
+            .as_ref()
+            .filter(|_| self.settings_cache.romanize_lyrics);
+        let translation = line.translation.as_ref().filter(|_| {
+            self.settings_cache.translation_backend != TranslationBackendKind::Disabled
+        });
+        let size = size * self.theme().secondary_scale;
+        for text in [romanized, translation].into_iter().flatten() {
+            let mut text = self.filtered(text);
+            if is_rtl(&text) {
+                text = Cow::Owned(rtl_rows(ui, &text, size));
+            }
+            ui.label(
+                RichText::new(text)
+                    .size(size)
+                    .color(color.gamma_multiply(0.7)),
+            );
+        }
+    }
+
+    /// `text` with profanity masked, if the filter is on
+    fn filtered<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.settings_cache.profanity_filter {
+            mask_profanity(text, &self.settings_cache.profanity_words)
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    fn filtered_ruby<'a>(&self, ruby: &'a [RubySegment]) -> Cow<'a, [RubySegment]> {
+        if !self.settings_cache.profanity_filter {
+            return Cow::Borrowed(ruby);
         }
+        ruby.iter()
+            .map(|segment| RubySegment {
+                text: self.filtered(&segment.text).into_owned(),
+                reading: segment.reading.clone(),
+            })
+            .collect()
+    }
+
+    /// Find the current line, the progress through it and the (eased) line to scroll to
+    fn line_timing(&self, lyrics: &SongLyrics, current_ms: u128, song_end_ms: i64) -> LineTiming {
+        let position = lyrics.find_current_index(current_ms.try_into().unwrap());
+        let (current_index, window) = match position {
+            LyricPosition::BeforeStart => (0, None),
+            LyricPosition::Line(n, _) => (n, Some(n)),
+            LyricPosition::AfterEnd(n) => (n, Some(n - 1)),
+        };
+        let (t0, t1) = match window.and_then(|n| lyrics.timeline(song_end_ms as usize).nth(n)) {
+            Some((start, end, _)) => (start as i64, end as i64),
+            // Waiting for the first line
+            None => (
+                0,
+                lyrics
+                    .next_line_after(current_ms as usize)
+                    .map_or(song_end_ms, |(_, line)| line.time_ms as i64),
+            ),
+        };
+
+        let raw_progress = if let LyricPosition::Line(_, progress) = position {
+            progress
+        } else if t1 - t0 > 0 {
+            ((current_ms as i64 - t0) as f32 / (t1 - t0) as f32).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        // The dynamic theme paces the scroll by the track, only moving to the next line once
+        // within the transition time. Otherwise it eases over the whole line
+        let transition_ms = self.transition_ms();
+        let transition_progress = if !self.settings_cache.dynamic_theme {
+            raw_progress
+        } else if transition_ms > 0.0 {
+            ((current_ms as f32 - (t1 as f32 - transition_ms)) / transition_ms).clamp(0.0, 1.0)
+        } else {
+            raw_progress.floor()
+        };
+
+        let target_line = if self.settings_cache.scroll_smoothly {
+            let eased = ease_in_out(transition_progress, self.settings_cache.ease_position);
+            if position == LyricPosition::BeforeStart {
+                -1.0 + eased
+            } else {
+                current_index as f32 + eased
+            }
+        } else {
+            current_index as f32
+        };
+
+        LineTiming {
+            current_index,
+            raw_progress,
+            target_line,
+        }
+    }
+
+    /// How far the line being sung is filled in, by its word timings if highlighting words is on
+    /// and else by `progress` to the next line if sweeping lines is on
+    fn line_fill(&self, line: &LyricLine, current_ms: u128, progress: f32) -> Option<LineFill> {
+        let words = sung_fraction(line, current_ms.try_into().unwrap_or(usize::MAX));
+        match words {
+            Some(sung) if self.settings_cache.word_highlight => Some(LineFill::words(sung)),
+            None if self.settings_cache.line_sweep => Some(LineFill::sweep(progress)),
+            _ => None,
+        }
+    }
+
+    /// Strength of the beat pulse at the given position, 0.0 if disabled or no analysis is available
+    fn beat_pulse(&self, current_ms: u128) -> f32 {
+        if !self.settings_cache.beat_pulse {
+            return 0.0;
+        }
+        self.audio_analysis.as_ref().map_or(0.0, |analysis| {
+            analysis.beat_pulse(current_ms as f32 / 1000.0)
+        })
+    }
+
+    /// Line colors, from the album art or the track's audio features if those themes are enabled
+    fn line_colors(&self) -> LineColors {
+        if self.settings_cache.album_art_theme {
+            return self.album_tint.current(self.theme_tint()).colors;
+        }
+        match &self.audio_features {
+            Some(features) if self.settings_cache.dynamic_theme => {
+                LineColors::from_audio_features(features)
+            }
+            _ => self.theme().colors,
+        }
+    }
+
+    /// Time spent scrolling from one line to the next, scaled by the track's audio features if the dynamic theme is enabled
+    fn transition_ms(&self) -> f32 {
+        let base = self.settings_cache.line_transition_ms as f32;
+        match &self.audio_features {
+            Some(features) if self.settings_cache.dynamic_theme => {
+                base * transition_scale(features)
+            }
+            _ => base,
+        }
+    }
+
+    /// Show the next track in the queue in the bottom right corner, during the last seconds of the current one
+    pub(super) fn upcoming_track_ui(&self, ctx: &egui::Context, full_width: f32, full_height: f32) {
+        if !self.is_auth || !self.settings_cache.show_upcoming_track {
+            return;
+        }
+        let Some(upcoming) = &self.upcoming_track else {
+            return;
+        };
+        let Some(duration_sec) = self.currently_playing.as_ref().map(|p| p.duration_sec) else {
+            return;
+        };
+        let remaining_ms = (duration_sec * 1000.) as i64 - self.current_progress_ms() as i64;
+        let lead_ms = (self.settings_cache.upcoming_track_lead_sec * 1000) as i64;
+        if remaining_ms < 0 || remaining_ms > lead_ms {
+            return;
+        }
+
+        let text = match &upcoming.artist {
+            Some(artist) => format!("Up next: {} - {artist}", upcoming.title),
+            None => format!("Up next: {}", upcoming.title),
+        };
+        egui::Area::new("upcoming_track".into())
+            .fixed_pos(egui::pos2(0., full_height - 40.))
+            .show(ctx, |ui| {
+                ui.set_min_width(full_width - 12.);
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.label(
+                        RichText::new(text)
+                            .size(11.0)
+                            .color(Color32::from_gray(180)),
+                    );
+                });
+            });
     }
 
     fn waiting_for_lyrics(&mut self, ui: &mut Ui) {
         ui.vertical_centered(|ui| {
-            if let Some(playing) = &self.currently_playing
-                && let Some(title) = playing.get_track_title()
-            {
+            if let Some(playing) = &self.currently_playing {
                 ui.label(
-                    RichText::new(format!("♫  {title}"))
-                        .size(18.0)
+                    RichText::new(format!("♫  {}", playing.title))
+                        .size(self.settings_cache.font_size * 0.7)
                         .color(Color32::from_gray(180)),
                 );
             }
             ui.label(
                 RichText::new("Loading lyrics…")
-                    .size(14.0)
+                    .size(self.settings_cache.font_size * 0.55)
                     .color(Color32::from_gray(100)),
             );
         });
     }
 }
 
+/// Small line naming the track the lyrics are for, with a badge if they aren't synced
+/// Right clicking it opens the actions for the shown lyrics, hovering it names their source
+fn track_header(
+    ui: &mut Ui,
+    song: &SongWithLyrics,
+    show_source: bool,
+    scroll_unsynced: bool,
+) -> egui::Response {
+    let source = song.source.as_ref().filter(|_| show_source);
+    let header = ui
+        .horizontal(|ui| {
+            ui.label(
+                RichText::new(format!("♫ {} - {}", song.artist_name, song.track_name))
+                    .size(11.0)
+                    .color(Color32::from_gray(180)),
+            );
+            if song.lyrics.unsynced && scroll_unsynced {
+                badge(ui, "estimated timing")
+                    .on_hover_text("The lyrics have no timings, they scroll at a guessed pace");
+            } else if song.lyrics.unsynced {
+                badge(ui, "not synced");
+            }
+            if song.pinned {
+                badge(ui, "pinned");
+            }
+            if let Some(source) = source {
+                badge(ui, source.provider.as_str());
+            }
+        })
+        .response
+        .interact(Sense::click());
+    match source {
+        Some(LyricsSource {
+            provider,
+            id: Some(id),
+        }) => header.on_hover_text(format!("Lyrics from {}, id {id}", provider.as_str())),
+        Some(LyricsSource { provider, id: None }) => {
+            header.on_hover_text(format!("Lyrics from {}", provider.as_str()))
+        }
+        None => header,
+    }
+}
+
+fn badge(ui: &mut Ui, text: &str) -> egui::Response {
+    egui::Frame::new()
+        .fill(Color32::from_gray(60))
+        .corner_radius(3.0)
+        .inner_margin(egui::Margin::symmetric(4, 1))
+        .show(ui, |ui| {
+            ui.label(
+                RichText::new(text)
+                    .size(10.0)
+                    .color(Color32::from_gray(200)),
+            );
+        })
+        .response
+}
+
+/// Lays a line out segment by segment, with each reading centered above its segment
+fn ruby_label(ui: &mut Ui, ruby: &[RubySegment], size: f32, color: Color32) -> Response {
+    let layout = |text: &str, size: f32, color: Color32| {
+        WidgetText::from(RichText::new(text).size(size).color(color).strong()).into_galley(
+            ui,
+            Some(TextWrapMode::Extend),
+            f32::INFINITY,
+            TextStyle::Body,
+        )
+    };
+    let segments: Vec<_> = ruby
+        .iter()
+        .map(|segment| {
+            let reading = segment
+                .reading
+                .as_deref()
+                .map(|reading| layout(reading, size * 0.45, color.gamma_multiply(0.8)));
+            let base = layout(&segment.text, size, color);
+            let width = base
+                .size()
+                .x
+                .max(reading.as_ref().map_or(0.0, |reading| reading.size().x));
+            (base, reading, width)
+        })
+        .collect();
+    let reading_height = size * 0.45 * 1.2;
+    let width = segments.iter().map(|(_, _, width)| width).sum();
+    let height = reading_height
+        + segments
+            .iter()
+            .map(|(base, _, _)| base.size().y)
+            .fold(0.0, f32::max);
+
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(width, height), Sense::hover());
+    let mut x = rect.left();
+    for (base, reading, segment_width) in segments {
+        if let Some(reading) = reading {
+            let reading_x = x + (segment_width - reading.size().x) / 2.0;
+            ui.painter()
+                .galley(egui::pos2(reading_x, rect.top()), reading, color);
+        }
+        let base_x = x + (segment_width - base.size().x) / 2.0;
+        ui.painter()
+            .galley(egui::pos2(base_x, rect.top() + reading_height), base, color);
+        x += segment_width;
+    }
+    response
+}
+
+/// Appends `text` in `format` with the letters counted from `letter` on past `sung` dimmed,
+/// blending over the `fill`'s edge
+fn append_filled(
+    job: &mut LayoutJob,
+    text: &str,
+    format: &TextFormat,
+    fill: LineFill,
+    sung: f32,
+    letter: &mut f32,
+) {
+    let mut start = 0;
+    let mut run_color = None;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let t = ((sung - *letter) / fill.edge).clamp(0.0, 1.0);
+        *letter += 1.0;
+        let color = format
+            .color
+            .gamma_multiply(fill.dim)
+            .lerp_to_gamma(format.color, t);
+        if let Some(run_color) = run_color
+            && run_color != color
+        {
+            job.append(
+                &text[start..i],
+                0.0,
+                TextFormat {
+                    color: run_color,
+                    ..format.clone()
+                },
+            );
+            start = i;
+        }
+        run_color = Some(color);
+    }
+    let color = run_color.unwrap_or(format.color);
+    job.append(
+        &text[start..],
+        0.0,
+        TextFormat {
+            color,
+            ..format.clone()
+        },
+    );
+}
+
 /// Helper for nearly lerping between two colors
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::cast_sign_loss)]
@@ -230,6 +971,59 @@ fn lerp_color(a: [u8; 3], b: [u8; 3], t: f32) -> (u8, u8, u8) {
     (l(a[0], b[0]), l(a[1], b[1]), l(a[2], b[2]))
 }
 
+/// Where the scrolling is at, under the debug setting
+fn debug_info(ui: &mut Ui, lyrics: &SongLyrics, current_ms: u128, target_line: f32, scroll_y: f32) {
+    ui.label(format!("target_line: {target_line:.3}"));
+    ui.label(format!("scroll_y: {scroll_y:.1}"));
+    ui.label(format!("current_ms: {current_ms}"));
+    if let Some(next_ms) = usize::try_from(current_ms)
+        .ok()
+        .and_then(|current_ms| lyrics.time_until_next(current_ms))
+    {
+        ui.label(format!("next line in: {next_ms}ms"));
+    }
+}
+
+/// Right to left `text` wrapped to the available width like egui would, each row in the order
+/// it's drawn in
+fn rtl_rows(ui: &Ui, text: &str, size: f32) -> String {
+    let wrap_width = ui.available_width();
+    let galley = ui.fonts_mut(|fonts| {
+        fonts.layout(
+            text.to_owned(),
+            FontId::proportional(size),
+            Color32::WHITE,
+            wrap_width,
+        )
+    });
+    let mut rest = text;
+    let rows: Vec<String> = galley
+        .rows
+        .iter()
+        .map(|row| {
+            let len = rest
+                .char_indices()
+                .nth(row.char_count_including_newline())
+                .map_or(rest.len(), |(i, _)| i);
+            let (row_text, after) = rest.split_at(len);
+            rest = after;
+            visual_order(row_text.trim_end(), true)
+        })
+        .collect();
+    rows.join("\n")
+}
+
+/// `×3` next to a line shown once for `run` repeats, it doesn't take up layout space
+fn repeat_counter(ui: &Ui, line_rect: Rect, run: usize, size: f32, color: Color32) {
+    ui.painter().text(
+        line_rect.right_center() + Vec2::new(8.0, 0.0),
+        Align2::LEFT_CENTER,
+        format!("×{run}"),
+        FontId::proportional(size * 0.6),
+        color.gamma_multiply(0.7),
+    );
+}
+
 /// Draw progress
 fn draw_progress_bar(ui: &mut Ui, progress: f32, width: f32) {
     let height = 2.0;
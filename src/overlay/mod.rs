@@ -1,27 +1,66 @@
-use std::{sync::Arc, time::Instant};
+use std::sync::Arc;
 
-use egui::{Color32, RichText, Ui};
+use egui::{Color32, Key, KeyboardShortcut, Modifiers, RichText, Ui, Vec2};
 use tokio::sync::mpsc;
-use tracing::trace;
+use tracing::{trace, warn};
 
 use tokio::sync::RwLock as TokioRwLock;
 
-use crate::{
+use lyrics_overlay_core::{
     MessageToRT, MessageToUI,
-    lyrics_fetch::{LyricsRequestInfo, SongWithLyrics},
-    overlay::resize::handle_resize,
+    album_art::Palette,
+    lyrics_fetch::{LyricsRequestInfo, LyricsSource, SongWithLyrics, TranslationBackendKind},
+    paths,
+    playback_clock::PlaybackClock,
+    player::NowPlaying,
     settings::Settings,
-    spotify::CurrentlyPlayingResponse,
+    spotify::{AudioAnalysis, AudioFeatures, UpcomingTrack},
+};
+
+use crate::overlay::{
+    cache_ui::{CacheBrowser, SettingsTab},
+    hotkeys::Hotkeys,
+    placement::WindowPlacement,
+    resize::handle_resize,
+    search_ui::SearchDialog,
+    settings_panel::PlaylistPrefetch,
+    status_ui::{PublishState, SessionStatus},
+    theme::{Theme, Tint, TintFade, load_themes},
 };
 
 mod authentication_ui;
+mod cache_ui;
+mod fonts;
+mod hotkeys;
 mod lyrics_ui;
+mod placement;
 mod resize;
+mod search_ui;
 mod settings_panel;
+mod status_ui;
+mod theme;
+mod ticker;
+
+/// Looks the current track's lyrics up again, skipping the cache
+const REFRESH_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::R);
+
+/// State of the browser authentication flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AuthFlow {
+    Idle,
+    /// Waiting for the user to log in to spotify in the browser
+    Awaiting,
+    /// The last attempt failed or was cancelled
+    Failed,
+}
 
 pub struct LyricsAppUI {
     /// Are we currently authenticated with spotify
     is_auth: bool,
+    /// Progress of the browser authentication flow
+    auth_flow: AuthFlow,
+    /// Redirect uri used by the running authentication flow
+    auth_redirect_uri: String,
     /// Transimitter of communication between the UI and the runtime
     tx: mpsc::Sender<MessageToRT>,
     /// Receiver of communication between the runtimme and the UI
@@ -29,22 +68,58 @@ pub struct LyricsAppUI {
     /// If this contains something, we display it so the user knows what's going on
     error_string: Option<String>,
     /// The response to spotify's current lyrics
-    currently_playing: Option<CurrentlyPlayingResponse>,
+    currently_playing: Option<NowPlaying>,
 
     /// Container for the current song's lyrics
     current_song_with_lyrics: Option<SongWithLyrics>,
-    /// Time at which the last spotify request was received
-    time_of_last_req: Instant,
+    /// Estimated playback position between polls
+    playback_clock: PlaybackClock,
+    /// Next track in the spotify queue, if known
+    upcoming_track: Option<UpcomingTrack>,
+    /// Audio features of the current track, used for the dynamic theme
+    audio_features: Option<AudioFeatures>,
+    /// Colors of the album art, used for the album art theme
+    album_tint: TintFade,
+    /// Beats and bars of the current track, used for the beat pulse
+    audio_analysis: Option<Arc<AudioAnalysis>>,
 
     /// The `RWLock` for our setting struct
     settings: Arc<TokioRwLock<Settings>>,
     /// Cached settings to prevent hanging on blocking locks
     settings_cache: Settings,
+    /// Font files currently installed, `None` before the first frame
+    font_files: Option<Vec<String>>,
+    /// Minimum and maximum window size last set for the display mode
+    applied_size_limits: Option<(Vec2, Vec2)>,
+    /// Saving the window position and size
+    placement: WindowPlacement,
+    /// Themes to pick from by name, the built-in one first with an empty name
+    themes: Vec<(String, Theme)>,
     /// Is the settings window currenly open
     settings_open: bool,
+    /// Page shown in the settings window
+    settings_tab: SettingsTab,
+    /// Cached tracks listed in the cache tab
+    cache_browser: CacheBrowser,
+    /// Session state reported by the runtime
+    session_status: SessionStatus,
+    /// Is the session status panel open
+    status_open: bool,
+    /// Progress of uploading the shown lyrics to lrclib
+    publish_state: PublishState,
+    /// Manual lrclib search, `None` while closed
+    search_dialog: Option<SearchDialog>,
+    /// Caching the lyrics of a playlist, started from the settings
+    playlist_prefetch: PlaylistPrefetch,
+    /// System-wide hotkeys and the tray menu
+    hotkeys: Hotkeys,
+    /// Nudged by the offset hotkeys, positive shows the lyrics sooner
+    lyrics_offset_ms: i64,
 
     /// measured y of each line, updated every frame
     line_top_offsets: Vec<f32>,
+    /// Scroll offset shown last frame, following the current line
+    scroll_y: Option<f32>,
 }
 
 impl LyricsAppUI {
@@ -54,18 +129,41 @@ impl LyricsAppUI {
         rx: mpsc::Receiver<MessageToUI>,
         settings: &Arc<TokioRwLock<Settings>>,
     ) -> Self {
+        let settings_cache = settings.blocking_read().clone();
         Self {
             is_auth: false,
+            auth_flow: AuthFlow::Idle,
+            auth_redirect_uri: String::new(),
             tx,
             rx,
             currently_playing: None,
             error_string: None,
-            time_of_last_req: Instant::now(),
+            playback_clock: PlaybackClock::default(),
             current_song_with_lyrics: None,
+            upcoming_track: None,
+            audio_features: None,
+            audio_analysis: None,
+            album_tint: TintFade::default(),
             settings: settings.clone(),
-            settings_cache: settings.blocking_read().clone(),
+            hotkeys: Hotkeys::new(&settings_cache),
+            lyrics_offset_ms: 0,
+            settings_cache,
+            font_files: None,
+            placement: WindowPlacement::default(),
+            applied_size_limits: None,
+            themes: std::iter::once((String::new(), Theme::default()))
+                .chain(load_themes(&paths::theme_folder()))
+                .collect(),
             settings_open: false,
+            settings_tab: SettingsTab::default(),
+            cache_browser: CacheBrowser::default(),
+            session_status: SessionStatus::default(),
+            status_open: false,
+            publish_state: PublishState::Closed,
+            search_dialog: None,
+            playlist_prefetch: PlaylistPrefetch::default(),
             line_top_offsets: vec![],
+            scroll_y: None,
         }
     }
 
@@ -74,51 +172,274 @@ impl LyricsAppUI {
             match message {
                 MessageToUI::AuthenticationStateUpdate(new_state) => {
                     self.is_auth = new_state;
+                    self.auth_flow = AuthFlow::Idle;
                     if new_state {
                         self.tx.try_send(MessageToRT::GetCurrentTrack).unwrap();
+                    } else {
+                        self.playback_clock.pause();
                     }
                     /*else {
                         self.error_string =
                             Some("Authentication expired, please reauthenticate".into())
                     }*/
                 }
+                MessageToUI::AwaitingAuthCallback(redirect_uri) => {
+                    self.auth_flow = AuthFlow::Awaiting;
+                    self.auth_redirect_uri = redirect_uri;
+                }
+                MessageToUI::AuthenticationFailed(reason) => {
+                    self.auth_flow = AuthFlow::Failed;
+                    if !reason.is_empty() {
+                        self.error_string = Some(reason);
+                    }
+                }
                 MessageToUI::CurrentlyPlaying(data) => {
                     let same_track = &self
                         .currently_playing
                         .take()
-                        .is_some_and(|s| s.get_spotify_id() == data.get_spotify_id());
+                        .is_some_and(|s| s.track_id == data.track_id);
 
+                    // Spotify sampled the progress somewhere during the request, not when we received it
+                    let sampled_at = data
+                        .received_at
+                        .checked_sub(
+                            data.round_trip
+                                .mul_f32(self.settings_cache.latency_compensation.clamp(0.0, 1.0)),
+                        )
+                        .unwrap_or(data.received_at);
+                    if *same_track {
+                        self.playback_clock
+                            .sync(data.progress_ms, sampled_at, data.is_playing);
+                    } else {
+                        self.playback_clock
+                            .reset(data.progress_ms, sampled_at, data.is_playing);
+                    }
                     self.currently_playing = Some(data);
-                    // TODO: Also consider the time between request sent from spotify and the receiving of the request,
-                    // there's something about this in the spotify API docs
-                    self.time_of_last_req = Instant::now();
 
                     if !same_track {
-                        self.tx
-                            .try_send(MessageToRT::GetLyrics(
-                                LyricsRequestInfo::from_spotify_response(
-                                    &self.currently_playing.clone().unwrap(),
-                                )
-                                .unwrap(),
-                            ))
-                            .unwrap();
-                        self.line_top_offsets.clear();
+                        self.on_track_changed();
                     }
                 }
-                MessageToUI::DisplayError(err) => self.error_string = Some(err),
-                MessageToUI::GotLyrics(song) => {
+                MessageToUI::DisplayError(err) => {
+                    // The error bar explains what went wrong
+                    if self.publish_state == PublishState::Publishing {
+                        self.publish_state = PublishState::Closed;
+                    }
+                    if let Some(dialog) = self.search_dialog.as_mut() {
+                        dialog.search_failed();
+                    }
+                    self.playlist_prefetch.failed();
+                    self.cache_browser.failed();
+                    self.error_string = Some(err);
+                }
+                MessageToUI::GotLyrics(mut song) => {
                     trace!("Received SongWithLyrics!: {:?}", song);
+                    song.lyrics.add_readings();
+                    if song.source.is_some() {
+                        self.translate_lyrics(&song);
+                    }
                     self.current_song_with_lyrics = Some(song);
                 }
                 MessageToUI::NotCurrentlyPlaying(reason) => {
+                    // Spotify stops reporting the track when playback goes idle, which is a pause to us
+                    self.playback_clock.pause();
                     self.error_string = Some(format!("No track found! ({reason})"));
                 }
+                MessageToUI::UpcomingTrack(upcoming) => self.upcoming_track = upcoming,
+                MessageToUI::AudioFeatures(features) => self.audio_features = Some(features),
+                MessageToUI::AlbumPalette(url, palette) => self.set_album_palette(url, palette),
+                MessageToUI::AudioAnalysis(analysis) => self.audio_analysis = Some(analysis),
+                MessageToUI::Status(event) => self.session_status.apply(event),
+                MessageToUI::LyricsPublished => self.publish_state = PublishState::Published,
+                MessageToUI::PrefetchProgress { done, total, found } => {
+                    self.playlist_prefetch.set_progress(done, total, found);
+                }
+                MessageToUI::CacheListing(listing) => self.cache_browser.set_listing(listing),
+                MessageToUI::CacheArchived(status) => {
+                    self.cache_browser.archive_status = Some(status);
+                    self.tx.try_send(MessageToRT::ListCache).unwrap();
+                }
+                MessageToUI::LyricsSearchResults(results) => {
+                    if let Some(dialog) = self.search_dialog.as_mut() {
+                        dialog.set_results(results);
+                    }
+                }
+                MessageToUI::LyricsTranslated(translation) => {
+                    if let Some(song) = self.current_song_with_lyrics.as_mut() {
+                        song.apply_translation(translation);
+                    }
+                }
                 MessageToUI::RateLimitsExceeded => {
                     self.error_string = Some("Rate limits exceeded!".to_string());
                 }
             }
         }
     }
+
+    /// Reset per-track state and request everything we need for the new track
+    fn on_track_changed(&mut self) {
+        let Some(playing) = self.currently_playing.as_ref() else {
+            return;
+        };
+        self.tx
+            .try_send(MessageToRT::GetLyrics(LyricsRequestInfo::from_now_playing(
+                playing,
+            )))
+            .unwrap();
+        self.line_top_offsets.clear();
+        self.scroll_y = None;
+        self.lyrics_offset_ms = 0;
+
+        self.upcoming_track = None;
+        if self.settings_cache.show_upcoming_track
+            && self.settings_cache.oauth_scopes.can_read_playback_state()
+        {
+            self.send_optional(MessageToRT::GetQueue);
+        }
+
+        if self.settings_cache.album_art_theme && playing.album_art_url != self.album_tint.url {
+            match playing.album_art_url.clone() {
+                Some(url) => self.send_optional(MessageToRT::GetAlbumPalette(url)),
+                None => self.album_tint.fade_to(None, None, self.theme_tint()),
+            }
+        }
+
+        self.audio_features = None;
+        self.audio_analysis = None;
+        if let Some(id) = playing.spotify_id.clone() {
+            if self.settings_cache.dynamic_theme {
+                self.send_optional(MessageToRT::GetAudioFeatures(id.clone()));
+            }
+            if self.settings_cache.beat_pulse {
+                self.send_optional(MessageToRT::GetAudioAnalysis(id));
+            }
+        }
+    }
+
+    /// Send a request the overlay can do without, it's dropped if the runtime falls behind
+    fn send_optional(&self, message: MessageToRT) {
+        if let Err(err) = self.tx.try_send(message) {
+            warn!("Skipping a request to the runtime: {err}");
+        }
+    }
+
+    /// Look the current track's lyrics up again, skipping the cache
+    fn refetch_lyrics(&mut self) {
+        let Some(playing) = self.currently_playing.as_ref() else {
+            return;
+        };
+        self.tx
+            .try_send(MessageToRT::RefetchLyrics(
+                LyricsRequestInfo::from_now_playing(playing),
+            ))
+            .unwrap();
+        self.current_song_with_lyrics = None;
+    }
+
+    /// Skip the shown lyrics for the current track and look up the next best
+    fn reject_lyrics(&mut self, source: LyricsSource) {
+        let Some(playing) = self.currently_playing.as_ref() else {
+            return;
+        };
+        self.tx
+            .try_send(MessageToRT::RejectLyrics(
+                LyricsRequestInfo::from_now_playing(playing),
+                source,
+            ))
+            .unwrap();
+        self.current_song_with_lyrics = None;
+    }
+
+    /// Always use `pinned` for the current track, `None` unpins
+    fn pin_lyrics(&mut self, pinned: Option<LyricsSource>) {
+        let Some(playing) = self.currently_playing.as_ref() else {
+            return;
+        };
+        self.tx
+            .try_send(MessageToRT::PinLyrics(
+                LyricsRequestInfo::from_now_playing(playing),
+                pinned,
+            ))
+            .unwrap();
+    }
+
+    /// Ask for a translation of the lyrics, if a translation service is set up
+    fn translate_lyrics(&self, song: &SongWithLyrics) {
+        if self.settings_cache.translation_backend == TranslationBackendKind::Disabled {
+            return;
+        }
+        let Some(playing) = self.currently_playing.as_ref() else {
+            return;
+        };
+        let lines = song
+            .lyrics
+            .synced_lyrics
+            .iter()
+            .map(|line| line.text.clone())
+            .collect();
+        self.tx
+            .try_send(MessageToRT::TranslateLyrics(
+                LyricsRequestInfo::from_now_playing(playing),
+                lines,
+            ))
+            .unwrap();
+    }
+
+    /// Estimated playback position
+    fn current_progress_ms(&self) -> u128 {
+        self.playback_clock.position_ms()
+    }
+
+    /// Change and save the settings from outside the settings window
+    fn update_settings(&mut self, change: impl FnOnce(&mut Settings)) {
+        let mut settings = self.settings.blocking_write();
+        change(&mut settings);
+        if let Err(e) = settings.save() {
+            self.error_string = Some(e);
+        }
+        self.settings_cache = settings.clone();
+    }
+
+    /// Theme picked in the settings, the built-in one if it's gone
+    fn theme(&self) -> &Theme {
+        self.themes
+            .iter()
+            .find(|(name, _)| *name == self.settings_cache.theme)
+            .map_or(&self.themes[0].1, |(_, theme)| theme)
+    }
+
+    /// Fade to the colors of the album art, unless the track changed since asking for them
+    fn set_album_palette(&mut self, url: String, palette: Palette) {
+        if self
+            .currently_playing
+            .as_ref()
+            .is_some_and(|playing| playing.album_art_url.as_ref() == Some(&url))
+        {
+            let theme = self.theme_tint();
+            self.album_tint
+                .fade_to(Some(Tint::from_palette(palette)), Some(url), theme);
+        }
+    }
+
+    /// Line colors and background of the theme, what the album art tint fades from and to
+    fn theme_tint(&self) -> Tint {
+        Tint::from_theme(self.theme())
+    }
+
+    /// Background fill, tinted by the album art if enabled
+    fn background(&self) -> [u8; 3] {
+        if self.settings_cache.album_art_theme {
+            self.album_tint.current(self.theme_tint()).background
+        } else {
+            self.theme().background
+        }
+    }
+
+    /// Playback position the lyrics follow, shifted by the offset hotkeys
+    fn lyrics_position_ms(&self) -> u128 {
+        self.current_progress_ms()
+            .saturating_add_signed(i128::from(self.lyrics_offset_ms))
+    }
 }
 
 impl eframe::App for LyricsAppUI {
@@ -145,28 +466,21 @@ impl eframe::App for LyricsAppUI {
         if let Ok(s) = self.settings.try_read() {
             self.settings_cache = s.clone();
         }
+        self.update_fonts(ctx);
+        self.track_placement(ctx);
+        self.apply_display_mode(ctx);
 
         self.message_loop();
+        self.handle_hotkeys(ctx);
+        if self.hotkeys.hidden {
+            return;
+        }
 
-        // Exit button
-        egui::Area::new("exit".into())
-            .fixed_pos(egui::pos2(full_width - 25., 10.))
-            .show(ctx, |ui| {
-                let label = "X";
-                if ui
-                    .add(
-                        egui::Button::new(
-                            RichText::new(label)
-                                .size(14.0)
-                                .color(Color32::from_gray(160)),
-                        )
-                        .frame(false),
-                    )
-                    .clicked()
-                {
-                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-                }
-            });
+        if ctx.input_mut(|i| i.consume_shortcut(&REFRESH_SHORTCUT)) {
+            self.refetch_lyrics();
+        }
+
+        exit_button(ctx, full_width);
 
         // Settings button
         egui::Area::new("settings_overlay".into())
@@ -175,10 +489,20 @@ impl eframe::App for LyricsAppUI {
                 self.settings_ui(ui, ctx);
             });
 
+        // Session status button
+        egui::Area::new("status_overlay".into())
+            .fixed_pos(egui::pos2(full_width - 65., 10.))
+            .show(ctx, |ui| {
+                self.status_button(ui);
+            });
+
+        self.click_through_button(ctx, full_width);
+
         // Transparent outer frame, we use this for allowing dragging and resizing
+        let [margin_x, margin_y] = self.theme().margin;
         let frame = egui::Frame::new()
             .fill(Color32::from_rgba_unmultiplied(0, 0, 0, 0))
-            .inner_margin(egui::Margin::symmetric(24, 16));
+            .inner_margin(egui::Margin::symmetric(margin_x, margin_y));
 
         egui::CentralPanel::default()
             .frame(egui::Frame::NONE)
@@ -193,7 +517,11 @@ impl eframe::App for LyricsAppUI {
                 // Render stuff :)
                 frame.show(ui, |ui: &mut Ui| {
                     // Show either the authenticate button or lyrics
-                    if self.is_auth {
+                    if self.auth_flow == AuthFlow::Awaiting {
+                        self.awaiting_auth_ui(ui);
+                    } else if self.is_auth
+                        || !self.settings_cache.player_source.needs_spotify_auth()
+                    {
                         // Lyrics or "waiting for lyrics"
                         self.display_lyrics(ui);
                     } else {
@@ -202,6 +530,12 @@ impl eframe::App for LyricsAppUI {
                 });
             });
 
+        self.upcoming_track_ui(ctx, full_width, full_height);
+        self.status_panel(ctx, full_width);
+        self.publish_dialog(ctx);
+        self.search_dialog(ctx);
+        self.offset_label(ctx, full_height);
+
         egui::Area::new("error bar".into())
             .fixed_pos(egui::pos2(0., full_height - 20.))
             .show(ctx, |ui| {
@@ -225,6 +559,31 @@ impl eframe::App for LyricsAppUI {
     }
 
     fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
-        [0.0, 0.0, 0.0, self.settings_cache.opacity]
+        if self.hotkeys.hidden {
+            return [0.0; 4];
+        }
+        let [r, g, b] = self.background().map(|c| f32::from(c) / 255.);
+        [r, g, b, self.settings_cache.opacity]
     }
 }
+
+fn exit_button(ctx: &egui::Context, full_width: f32) {
+    egui::Area::new("exit".into())
+        .fixed_pos(egui::pos2(full_width - 25., 10.))
+        .show(ctx, |ui| {
+            let label = "X";
+            if ui
+                .add(
+                    egui::Button::new(
+                        RichText::new(label)
+                            .size(14.0)
+                            .color(Color32::from_gray(160)),
+                    )
+                    .frame(false),
+                )
+                .clicked()
+            {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        });
+}
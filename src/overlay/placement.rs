@@ -0,0 +1,89 @@
+//! Remembers where the window was put, separately for each monitor setup
+use std::time::{Duration, Instant};
+
+use egui::{Vec2, ViewportCommand};
+
+use crate::overlay::LyricsAppUI;
+
+/// Moves and resizes are saved once the window stayed put this long
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+pub struct WindowPlacement {
+    /// Placement that differs from the saved one, and since when
+    pending: Option<([f32; 4], Instant)>,
+    /// The placement restored on startup was checked against the monitor we're on
+    checked_monitor: bool,
+}
+
+/// Monitor setups are told apart by the size of the monitor the window is on
+pub fn monitor_key(size: Vec2) -> String {
+    format!("{}x{}", size.x.round(), size.y.round())
+}
+
+/// Within half a point, the window manager may round
+fn same_placement(a: &[f32; 4], b: &[f32; 4]) -> bool {
+    a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.5)
+}
+
+impl LyricsAppUI {
+    /// Saves the position and size after moves and resizes, restores them for this monitor once
+    pub(super) fn track_placement(&mut self, ctx: &egui::Context) {
+        let (outer, inner, monitor) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (
+                viewport.outer_rect,
+                viewport.inner_rect,
+                viewport.monitor_size,
+            )
+        });
+        // Wayland doesn't tell windows where they are
+        let (Some(outer), Some(inner), Some(monitor)) = (outer, inner, monitor) else {
+            return;
+        };
+        let monitor = monitor_key(monitor);
+
+        if !self.placement.checked_monitor {
+            self.placement.checked_monitor = true;
+            // Started with another monitor setup than last time, the window is where it was on that one
+            if monitor != self.settings_cache.last_monitor
+                && let Some(&[x, y, width, height]) =
+                    self.settings_cache.window_placements.get(&monitor)
+            {
+                ctx.send_viewport_cmd(ViewportCommand::OuterPosition(egui::pos2(x, y)));
+                ctx.send_viewport_cmd(ViewportCommand::InnerSize(egui::vec2(width, height)));
+                return;
+            }
+        }
+
+        let placement = [outer.min.x, outer.min.y, inner.width(), inner.height()];
+        if monitor == self.settings_cache.last_monitor
+            && self
+                .settings_cache
+                .window_placements
+                .get(&monitor)
+                .is_some_and(|saved| same_placement(saved, &placement))
+        {
+            self.placement.pending = None;
+            return;
+        }
+        match self.placement.pending {
+            Some((pending, since)) if same_placement(&pending, &placement) => {
+                if since.elapsed() >= SAVE_DELAY {
+                    self.placement.pending = None;
+                    self.save_placement(monitor, placement);
+                }
+            }
+            _ => self.placement.pending = Some((placement, Instant::now())),
+        }
+    }
+
+    fn save_placement(&mut self, monitor: String, placement: [f32; 4]) {
+        self.update_settings(|settings| {
+            settings
+                .window_placements
+                .insert(monitor.clone(), placement);
+            settings.last_monitor = monitor;
+        });
+    }
+}
@@ -0,0 +1,155 @@
+//! Dialog for searching lrclib by hand and picking the lyrics to use
+use egui::{Color32, RichText};
+
+use lyrics_overlay_core::{
+    MessageToRT,
+    lyrics_fetch::{LyricsRequestInfo, LyricsSearchQuery, LyricsSearchResult},
+};
+
+use crate::overlay::LyricsAppUI;
+
+/// State of the manual search dialog
+#[derive(Debug, Default)]
+pub struct SearchDialog {
+    query: LyricsSearchQuery,
+    /// `None` until the first search finished
+    results: Option<Vec<LyricsSearchResult>>,
+    searching: bool,
+}
+
+impl SearchDialog {
+    pub fn set_results(&mut self, results: Vec<LyricsSearchResult>) {
+        self.results = Some(results);
+        self.searching = false;
+    }
+
+    pub fn search_failed(&mut self) {
+        self.searching = false;
+    }
+}
+
+impl LyricsAppUI {
+    /// Opens the search dialog, prefilled with the playing track
+    pub(super) fn open_search_dialog(&mut self) {
+        let Some(playing) = self.currently_playing.as_ref() else {
+            return;
+        };
+        self.search_dialog = Some(SearchDialog {
+            query: LyricsSearchQuery {
+                track_name: playing.title.clone(),
+                artist_name: playing.artist.clone(),
+                album_name: playing.album.clone(),
+                duration_sec: playing.duration_sec,
+            },
+            ..SearchDialog::default()
+        });
+    }
+
+    pub(super) fn search_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = self.search_dialog.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let mut chosen = None;
+
+        egui::Window::new("Search lyrics")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Grid::new("search_query")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (label, value) in [
+                            ("Title", &mut dialog.query.track_name),
+                            ("Artist", &mut dialog.query.artist_name),
+                            ("Album", &mut dialog.query.album_name),
+                        ] {
+                            ui.label(label);
+                            ui.text_edit_singleline(value);
+                            ui.end_row();
+                        }
+                        ui.label("Duration");
+                        ui.add(
+                            egui::DragValue::new(&mut dialog.query.duration_sec)
+                                .range(0.0..=3600.0)
+                                .suffix(" s"),
+                        );
+                        ui.end_row();
+                    });
+
+                ui.horizontal(|ui| {
+                    let can_search =
+                        !dialog.searching && !dialog.query.track_name.trim().is_empty();
+                    if ui
+                        .add_enabled(can_search, egui::Button::new("Search"))
+                        .clicked()
+                    {
+                        self.tx
+                            .try_send(MessageToRT::SearchLyrics(dialog.query.clone()))
+                            .unwrap();
+                        dialog.searching = true;
+                    }
+                    if dialog.searching {
+                        ui.spinner();
+                    }
+                });
+
+                let Some(results) = dialog.results.as_ref() else {
+                    return;
+                };
+                ui.separator();
+                if results.is_empty() {
+                    ui.label("Nothing found, try a shorter title or leave the album out");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        for result in results {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Use").clicked() {
+                                    chosen = Some(result.id);
+                                }
+                                ui.label(format!(
+                                    "{} - {} ({})",
+                                    result.artist_name, result.track_name, result.album_name
+                                ));
+                                let mut details = format_duration(result.duration_sec);
+                                if !result.synced {
+                                    details.push_str(", not synced");
+                                }
+                                ui.label(
+                                    RichText::new(details)
+                                        .size(11.0)
+                                        .color(Color32::from_gray(160)),
+                                );
+                            });
+                        }
+                    });
+            });
+
+        if let Some(id) = chosen
+            && let Some(playing) = self.currently_playing.as_ref()
+        {
+            self.tx
+                .try_send(MessageToRT::ChooseLyrics(
+                    LyricsRequestInfo::from_now_playing(playing),
+                    id,
+                ))
+                .unwrap();
+            self.current_song_with_lyrics = None;
+            open = false;
+        }
+        if !open {
+            self.search_dialog = None;
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(super) fn format_duration(duration_sec: f64) -> String {
+    let total = duration_sec.round() as u64;
+    format!("{}:{:02}", total / 60, total % 60)
+}
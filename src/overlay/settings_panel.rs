@@ -1,6 +1,20 @@
+use std::str::FromStr;
+
 use egui::{Color32, RichText, Ui};
+use global_hotkey::hotkey::HotKey;
+use tokio::sync::mpsc;
+
+use lyrics_overlay_core::{
+    MessageToRT,
+    lyrics_fetch::{LyricsProviderKind, TranslationBackendKind},
+    paths,
+    player::PlayerSourceKind,
+    settings::{
+        AdlibDisplay, DisplayMode, EasingModes, OAuthScopes, ProgressBarPosition, Settings,
+    },
+};
 
-use crate::settings::{EasingModes, ProgressBarPosition, Settings};
+use crate::overlay::{cache_ui::SettingsTab, theme::Theme};
 
 // TODO: Separate settings and theming (basically, color presets), might as well separate settings and state and settings into sub-structs while we are at it.
 fn section_label(ui: &mut Ui, text: &str) {
@@ -65,13 +79,27 @@ impl super::LyricsAppUI {
                 }
 
                 egui::CentralPanel::default().show(ctx, |ui| {
+                    self.settings_tabs(ui);
+                    if self.settings_tab == SettingsTab::Cache {
+                        self.cache_tab(ui);
+                        return;
+                    }
+
                     let mut settings = self.settings.blocking_read().clone();
                     let snapshot = format!("{settings:?}");
 
                     egui::ScrollArea::vertical().show(ui, |ui| {
-                        display_settings(ui, &mut settings);
-                        behaviour_settings(ui, &mut settings);
+                        display_settings(ui, &mut settings, &self.themes);
+                        behaviour_settings(ui, &mut settings, &self.tx);
+                        hotkey_settings(ui, &mut settings);
+                        translation_settings(ui, &mut settings);
+                        if settings.caching_enabled && self.is_auth {
+                            self.prefetch_settings(ui);
+                        }
+                        network_settings(ui, &mut settings);
                         authentication_settings(ui, &mut settings);
+                        scope_settings(ui, &mut settings, &self.tx);
+                        account_settings(ui, &settings, &self.tx);
                     });
 
                     reset_defaults(ui, &mut settings);
@@ -86,19 +114,79 @@ impl super::LyricsAppUI {
             },
         );
     }
+
+    fn prefetch_settings(&mut self, ui: &mut Ui) {
+        section_label(ui, "Prefetch");
+        let prefetch = &mut self.playlist_prefetch;
+        settings_row(
+            ui,
+            "Playlist",
+            "Cache the lyrics of every track in a spotify playlist, for using the overlay offline",
+            |ui| {
+                let can_start = !prefetch.running && !prefetch.playlist.trim().is_empty();
+                if ui
+                    .add_enabled(can_start, egui::Button::new("Prefetch"))
+                    .clicked()
+                {
+                    self.tx
+                        .try_send(MessageToRT::PrefetchPlaylist(prefetch.playlist.clone()))
+                        .unwrap();
+                    prefetch.running = true;
+                    prefetch.progress = None;
+                }
+                ui.add(
+                    egui::TextEdit::singleline(&mut prefetch.playlist)
+                        .hint_text("Playlist url")
+                        .desired_width(120.0)
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+        if let Some((done, total, found)) = prefetch.progress {
+            let status = if done == total {
+                format!("Done, found lyrics for {found} of {total} tracks")
+            } else {
+                format!("{done} of {total} tracks, found {found}")
+            };
+            ui.label(
+                RichText::new(status)
+                    .size(11.0)
+                    .color(Color32::from_gray(160)),
+            );
+        } else if prefetch.running {
+            ui.spinner();
+        }
+    }
+}
+
+/// State of the playlist prefetch in the settings window
+#[derive(Debug, Default)]
+pub struct PlaylistPrefetch {
+    /// Url or id as typed
+    playlist: String,
+    running: bool,
+    /// Tracks done, total and found
+    progress: Option<(usize, usize, usize)>,
+}
+
+impl PlaylistPrefetch {
+    pub fn set_progress(&mut self, done: usize, total: usize, found: usize) {
+        self.progress = Some((done, total, found));
+        self.running = done < total;
+    }
+
+    pub fn failed(&mut self) {
+        self.running = false;
+    }
 }
 
-fn display_settings(ui: &mut Ui, settings: &mut Settings) {
+fn display_settings(ui: &mut Ui, settings: &mut Settings, themes: &[(String, Theme)]) {
     section_label(ui, "Display");
 
-    settings_row(ui, "Font size", "Size of the font used for lyrics", |ui| {
-        ui.add(
-            egui::Slider::new(&mut settings.font_size, 10.0..=72.0)
-                .step_by(1.0)
-                .suffix(" px")
-                .text_color(Color32::from_gray(200)),
-        );
-    });
+    layout_settings(ui, settings);
+    theme_settings(ui, settings, themes);
+
+    font_settings(ui, settings);
     settings_row(
         ui,
         "Background opacity",
@@ -120,6 +208,22 @@ fn display_settings(ui: &mut Ui, settings: &mut Settings) {
             ui.checkbox(&mut settings.dim_distant_lines, "");
         },
     );
+    settings_row(
+        ui,
+        "Highlight words",
+        "Fill in the current line word by word as it's sung, for lyrics with word timings",
+        |ui| {
+            ui.checkbox(&mut settings.word_highlight, "");
+        },
+    );
+    settings_row(
+        ui,
+        "Sweep lines",
+        "Light up the current line from left to right until the next one, for lyrics without word timings",
+        |ui| {
+            ui.checkbox(&mut settings.line_sweep, "");
+        },
+    );
     settings_row(
         ui,
         "Scroll smoothly",
@@ -131,7 +235,7 @@ fn display_settings(ui: &mut Ui, settings: &mut Settings) {
     settings_row(
         ui,
         "Transition time",
-        "Time spent transitioning from one line to the next (if scrolling smoothly)",
+        "Time spent transitioning from one line to the next (if scrolling smoothly with the dynamic theme)",
         |ui| {
             ui.add(
                 egui::Slider::new(&mut settings.line_transition_ms, 0..=1000)
@@ -141,147 +245,1193 @@ fn display_settings(ui: &mut Ui, settings: &mut Settings) {
             );
         },
     );
+    settings_row(
+        ui,
+        "Pulse on beat",
+        "Subtly grow the current line on every beat of the track",
+        |ui| {
+            ui.checkbox(&mut settings.beat_pulse, "");
+        },
+    );
+    settings_row(
+        ui,
+        "Show lyrics source",
+        "Show which provider the lyrics came from, hover it for the id",
+        |ui| {
+            ui.checkbox(&mut settings.show_lyrics_source, "");
+        },
+    );
+    lyric_text_settings(ui, settings);
     settings_row(ui, "Show debug stuff", "Do we show debug stuff?", |ui| {
         ui.checkbox(&mut settings.draw_debug_stuff, "");
     });
 }
 
-// TODO split ProgressBarPosition and Easing to separate functions for cleaner
-fn behaviour_settings(ui: &mut Ui, settings: &mut Settings) {
-    section_label(ui, "Behaviour");
+/// How the text of the lines is shown
+fn lyric_text_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "Scroll unsynced lyrics",
+        "Follow lyrics without timings at estimated times, or show them all to scroll by hand",
+        |ui| {
+            ui.checkbox(&mut settings.scroll_unsynced_lyrics, "");
+        },
+    );
+    settings_row(
+        ui,
+        "Fold repeated lines",
+        "Show a line sung several times in a row once, with a ×3 counter",
+        |ui| {
+            ui.checkbox(&mut settings.fold_repeated_lines, "");
+        },
+    );
+    settings_row(
+        ui,
+        "Instrumental breaks",
+        "Show ♪ in silences between lines at least this long",
+        |ui| {
+            ui.add(
+                egui::Slider::new(&mut settings.instrumental_break_sec, 0..=30)
+                    .custom_formatter(|v, _| {
+                        if v == 0. {
+                            "Off".to_owned()
+                        } else {
+                            format!("{v}s")
+                        }
+                    })
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "Split long lines",
+        "Split lines longer than this many characters at punctuation, for narrow windows",
+        |ui| {
+            ui.add(
+                egui::Slider::new(&mut settings.max_line_chars, 0..=120)
+                    .custom_formatter(|v, _| {
+                        if v == 0. {
+                            "Off".to_owned()
+                        } else {
+                            format!("{v}")
+                        }
+                    })
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "Romanize lyrics",
+        "Show a romanized line under Japanese, Chinese and Korean lyrics",
+        |ui| {
+            ui.checkbox(&mut settings.romanize_lyrics, "");
+        },
+    );
+    settings_row(
+        ui,
+        "Backing vocals",
+        "How adlibs like (ooh) and section markers like [Chorus] are shown",
+        |ui| {
+            egui::ComboBox::from_id_salt("adlibs")
+                .selected_text(settings.adlibs.as_str())
+                .show_ui(ui, |ui| {
+                    for adlibs in [AdlibDisplay::Show, AdlibDisplay::Dim, AdlibDisplay::Hide] {
+                        ui.selectable_value(&mut settings.adlibs, adlibs, adlibs.as_str());
+                    }
+                });
+        },
+    );
+    settings_row(
+        ui,
+        "Reading hints",
+        "Show pinyin above Chinese lyrics, and readings above kanji when the lyrics have them",
+        |ui| {
+            ui.checkbox(&mut settings.ruby_annotations, "");
+        },
+    );
+    settings_row(
+        ui,
+        "Color duet parts",
+        "Tint lines by who sings them, for lyrics with M:, F: and D: markers",
+        |ui| {
+            ui.checkbox(&mut settings.color_duet_parts, "");
+        },
+    );
+    profanity_settings(ui, settings);
+}
 
-    settings_row(ui, "Refresh interval", "", |ui| {
+/// System-wide hotkeys, registered on startup
+fn hotkey_settings(ui: &mut Ui, settings: &mut Settings) {
+    section_label(ui, "Hotkeys");
+
+    hotkey_row(
+        ui,
+        "Show or hide",
+        "Hide the overlay without closing it",
+        &mut settings.visibility_hotkey,
+    );
+    hotkey_row(
+        ui,
+        "Click-through",
+        "Make the overlay ignore the mouse and back",
+        &mut settings.click_through_hotkey,
+    );
+    hotkey_row(
+        ui,
+        "Lyrics earlier",
+        "Show the lyrics 0.1s sooner, until the track changes",
+        &mut settings.offset_earlier_hotkey,
+    );
+    hotkey_row(
+        ui,
+        "Lyrics later",
+        "Show the lyrics 0.1s later, until the track changes",
+        &mut settings.offset_later_hotkey,
+    );
+    hotkey_row(
+        ui,
+        "Play or pause",
+        "Play or pause the player the lyrics follow",
+        &mut settings.play_pause_hotkey,
+    );
+}
+
+/// Hotkey text like `CmdOrCtrl+Shift+L`, red while it doesn't parse
+fn hotkey_row(ui: &mut Ui, label: &str, hover: &str, hotkey: &mut String) {
+    settings_row(
+        ui,
+        label,
+        &format!("{hover}. Leave empty to disable, applied on restart"),
+        |ui| {
+            let valid = hotkey.trim().is_empty() || HotKey::from_str(hotkey).is_ok();
+            ui.add(
+                egui::TextEdit::singleline(hotkey)
+                    .desired_width(120.0)
+                    .text_color(if valid {
+                        Color32::from_gray(200)
+                    } else {
+                        Color32::from_rgb(255, 80, 80)
+                    }),
+            );
+        },
+    );
+}
+
+fn font_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(ui, "Font size", "Size of the font used for lyrics", |ui| {
         ui.add(
-            egui::Slider::new(&mut settings.poll_interval_ms, 1000..=10000)
-                .suffix(" ms")
+            egui::Slider::new(&mut settings.font_size, 10.0..=72.0)
+                .step_by(1.0)
+                .suffix(" px")
                 .text_color(Color32::from_gray(200)),
         );
     });
+    let files = &mut settings.font_files;
     settings_row(
         ui,
-        "Cache lyrics",
-        "Do we cache any requested lyrics, improves future responsiveness and reduces load on the LRC lib",
+        "Fonts",
+        "Paths of .ttf, .otf or .ttc files, tried in order before the built-in fonts. A system font for Chinese, Japanese and Korean is used for what none of them cover",
         |ui| {
-            ui.checkbox(&mut settings.caching_enabled, "");
+            ui.vertical(|ui| {
+                let mut remove = None;
+                for (i, file) in files.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(file)
+                                .desired_width(140.0)
+                                .text_color(Color32::from_gray(200)),
+                        );
+                        if ui.button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    files.remove(i);
+                }
+                if ui.button("Add font").clicked() {
+                    files.push(String::new());
+                }
+            });
         },
     );
-    if settings.caching_enabled {
+}
+
+/// Where the colors of the lyrics come from
+fn layout_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "Display mode",
+        "Scroll through all the lines, show only the current one in a slim bar, the lines around it in a small window, or the current and the next one karaoke style",
+        |ui| {
+            egui::ComboBox::from_id_salt("display_mode")
+                .selected_text(settings.display_mode.as_str())
+                .show_ui(ui, |ui| {
+                    for mode in DisplayMode::ALL {
+                        ui.selectable_value(&mut settings.display_mode, mode, mode.as_str());
+                    }
+                });
+        },
+    );
+    if settings.display_mode == DisplayMode::Ticker {
         settings_row(
             ui,
-            "Cache folder",
-            "Where do you want to store cache?",
+            "Marquee",
+            "Pan across lines too long for the ticker while they're sung, instead of cutting them off",
             |ui| {
-                ui.add(
-                    egui::TextEdit::singleline(&mut settings.cache_folder)
-                        .desired_width(120.0)
-                        .text_color(Color32::from_gray(200)),
-                );
+                ui.checkbox(&mut settings.ticker_marquee, "");
             },
         );
     }
-    settings_row(ui, "Log level", "Log level, what more can I say", |ui| {
-        egui::ComboBox::from_id_salt("log_level")
-            .selected_text(settings.log_level.as_str())
-            .show_ui(ui, |ui| {
-                for level in ["error", "warn", "info", "debug", "trace"] {
-                    ui.selectable_value(&mut settings.log_level, level.to_string(), level);
-                }
-            });
-    });
+}
+
+fn theme_settings(ui: &mut Ui, settings: &mut Settings, themes: &[(String, Theme)]) {
     settings_row(
         ui,
-        "Line progress bar",
-        "Shows a bar with progress/duration of current line",
+        "Theme",
+        &format!(
+            "Colors, sizes and margins of the lyrics, from the .toml files in {}, loaded on startup",
+            paths::theme_folder().display()
+        ),
         |ui| {
-            egui::ComboBox::from_id_salt("progress_bar_position")
-                .selected_text(settings.line_progress_bar_position.as_str())
+            egui::ComboBox::from_id_salt("theme")
+                .selected_text(theme_label(&settings.theme))
                 .show_ui(ui, |ui| {
-                    for pos in [
-                        ProgressBarPosition::BelowCurrentLine,
-                        ProgressBarPosition::Bottom,
-                        ProgressBarPosition::Hidden,
-                    ] {
-                        ui.selectable_value(
-                            &mut settings.line_progress_bar_position,
-                            pos,
-                            pos.as_str(),
-                        );
+                    for (name, _) in themes {
+                        ui.selectable_value(&mut settings.theme, name.clone(), theme_label(name));
                     }
                 });
         },
     );
     settings_row(
         ui,
-        "Song progress bar",
-        "Shows a bar with progress/duration of current song",
+        "Album art colors",
+        "Tint the lines and background with the colors of the album art, fading between tracks",
         |ui| {
-            egui::ComboBox::from_id_salt("song_progress_bar_position")
-                .selected_text(settings.song_progress_bar_position.as_str())
-                .show_ui(ui, |ui| {
-                    for pos in [
-                        ProgressBarPosition::BelowCurrentLine,
-                        ProgressBarPosition::Bottom,
-                        ProgressBarPosition::Hidden,
-                    ] {
-                        ui.selectable_value(
-                            &mut settings.song_progress_bar_position,
-                            pos,
-                            pos.as_str(),
-                        );
-                    }
-                });
+            ui.checkbox(&mut settings.album_art_theme, "");
         },
     );
+    settings_row(
+        ui,
+        "Dynamic theme",
+        "Derive line colors and transition speed from the energy, mood and tempo of the track",
+        |ui| {
+            ui.checkbox(&mut settings.dynamic_theme, "");
+        },
+    );
+}
 
-    settings_row(ui, "Position easing", "Ease position?", |ui| {
-        egui::ComboBox::from_id_salt("position_ease")
-            .selected_text(settings.ease_position.as_str())
-            .show_ui(ui, |ui| {
-                for mode in [EasingModes::Cubic, EasingModes::Linear] {
-                    ui.selectable_value(&mut settings.ease_position, mode, mode.as_str());
-                }
-            });
-    });
-
-    settings_row(ui, "Color easing", "Ease color?", |ui| {
-        egui::ComboBox::from_id_salt("color_ease")
-            .selected_text(settings.ease_color.as_str())
-            .show_ui(ui, |ui| {
-                for mode in [EasingModes::Cubic, EasingModes::Linear] {
-                    ui.selectable_value(&mut settings.ease_color, mode, mode.as_str());
-                }
-            });
-    });
+fn theme_label(name: &str) -> &str {
+    if name.is_empty() { "Built-in" } else { name }
 }
 
-fn authentication_settings(ui: &mut Ui, settings: &mut Settings) {
-    section_label(ui, "Authentication");
+fn behaviour_settings(ui: &mut Ui, settings: &mut Settings, tx: &mpsc::Sender<MessageToRT>) {
+    section_label(ui, "Behaviour");
 
+    player_settings(ui, settings, tx);
+    settings_row(ui, "Refresh interval", "", |ui| {
+        ui.add(
+            egui::Slider::new(&mut settings.poll_interval_ms, 1000..=10000)
+                .suffix(" ms")
+                .text_color(Color32::from_gray(200)),
+        );
+    });
     settings_row(
         ui,
-        "Spotify developer Client ID",
-        "Client ID as found in spotify developer dashboard",
+        "Latency compensation",
+        "Part of the request round trip added to the playback position, 0.5 assumes spotify answered halfway",
         |ui| {
             ui.add(
-                egui::TextEdit::singleline(&mut settings.client_id)
-                    .desired_width(120.0)
+                egui::Slider::new(&mut settings.latency_compensation, 0.0..=1.0)
                     .text_color(Color32::from_gray(200)),
             );
         },
     );
-
     settings_row(
         ui,
-        "Spotify developer Client Secret",
-        "Client Secret as found in spotify developer dashboard",
+        "Show upcoming track",
+        "Show the next track in the queue near the end of the current one",
         |ui| {
-            ui.add(
-                egui::TextEdit::singleline(&mut settings.client_secret)
-                    .desired_width(120.0)
-                    .text_color(Color32::from_gray(200)),
-            );
+            ui.checkbox(&mut settings.show_upcoming_track, "");
         },
     );
+    if settings.show_upcoming_track && settings.oauth_scopes.can_read_playback_state() {
+        settings_row(
+            ui,
+            "Upcoming track lead",
+            "How long before the end of a track the next one is shown",
+            |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.upcoming_track_lead_sec, 5..=60)
+                        .suffix(" s")
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+    }
+    lyrics_provider_settings(ui, settings);
+    cache_settings(ui, settings);
+    settings_row(ui, "Log level", "Log level, what more can I say", |ui| {
+        egui::ComboBox::from_id_salt("log_level")
+            .selected_text(settings.log_level.as_str())
+            .show_ui(ui, |ui| {
+                for level in ["error", "warn", "info", "debug", "trace"] {
+                    ui.selectable_value(&mut settings.log_level, level.to_string(), level);
+                }
+            });
+    });
+    progress_bar_settings(ui, settings);
+    easing_settings(ui, settings);
+}
+
+fn lyrics_provider_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "Lyrics sources",
+        "Tried from top to bottom until one has synced lyrics",
+        |ui| {
+            ui.vertical(|ui| {
+                let providers = &mut settings.lyrics_providers;
+                let mut swap = None;
+                let mut remove = None;
+                for (i, provider) in providers.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(provider.as_str());
+                        if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                            swap = Some(i - 1);
+                        }
+                        if ui
+                            .add_enabled(i + 1 < providers.len(), egui::Button::new("⏷"))
+                            .clicked()
+                        {
+                            swap = Some(i);
+                        }
+                        if ui.button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = swap {
+                    providers.swap(i, i + 1);
+                }
+                if let Some(i) = remove {
+                    providers.remove(i);
+                }
+
+                let missing: Vec<_> = LyricsProviderKind::ALL
+                    .iter()
+                    .filter(|kind| !providers.contains(kind))
+                    .collect();
+                if !missing.is_empty() {
+                    egui::ComboBox::from_id_salt("add_lyrics_provider")
+                        .selected_text("Add")
+                        .show_ui(ui, |ui| {
+                            for &&kind in &missing {
+                                if ui.selectable_label(false, kind.as_str()).clicked() {
+                                    providers.push(kind);
+                                }
+                            }
+                        });
+                }
+            });
+        },
+    );
+    if settings
+        .lyrics_providers
+        .contains(&LyricsProviderKind::LocalLrc)
+    {
+        lrc_folder_settings(ui, &mut settings.lrc_folders);
+    }
+    if settings
+        .lyrics_providers
+        .contains(&LyricsProviderKind::Musixmatch)
+    {
+        settings_row(
+            ui,
+            "Musixmatch token",
+            "The usertoken the Musixmatch desktop app sends with its requests",
+            |ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut settings.musixmatch_token)
+                        .desired_width(120.0)
+                        .password(true)
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+    }
+    settings_row(
+        ui,
+        "Duration tolerance",
+        "How far off a searched song's duration may be before it's considered a different recording",
+        |ui| {
+            ui.add(
+                egui::Slider::new(&mut settings.duration_tolerance_sec, 0.0..=15.0)
+                    .suffix(" s")
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+}
+
+fn translation_settings(ui: &mut Ui, settings: &mut Settings) {
+    section_label(ui, "Translation");
+
+    settings_row(
+        ui,
+        "Translate lyrics",
+        "Show a machine translation under each line, translated once per song and cached",
+        |ui| {
+            egui::ComboBox::from_id_salt("translation_backend")
+                .selected_text(settings.translation_backend.as_str())
+                .show_ui(ui, |ui| {
+                    for backend in [
+                        TranslationBackendKind::Disabled,
+                        TranslationBackendKind::LibreTranslate,
+                        TranslationBackendKind::DeepL,
+                        TranslationBackendKind::Google,
+                    ] {
+                        ui.selectable_value(
+                            &mut settings.translation_backend,
+                            backend,
+                            backend.as_str(),
+                        );
+                    }
+                });
+        },
+    );
+    if settings.translation_backend == TranslationBackendKind::Disabled {
+        return;
+    }
+    if settings.translation_backend == TranslationBackendKind::LibreTranslate {
+        settings_row(ui, "LibreTranslate server", "", |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.translation_url)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        });
+    }
+    settings_row(
+        ui,
+        "API key",
+        "Key for the translation service, optional for self hosted LibreTranslate",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.translation_api_key)
+                    .desired_width(120.0)
+                    .password(true)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "Language",
+        "Language code to translate to, like en, de or ja",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.translation_language)
+                    .desired_width(40.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+}
+
+fn cache_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "Cache lyrics",
+        "Do we cache any requested lyrics, improves future responsiveness and reduces load on the LRC lib",
+        |ui| {
+            ui.checkbox(&mut settings.caching_enabled, "");
+        },
+    );
+    if settings.caching_enabled {
+        settings_row(
+            ui,
+            "Cache folder",
+            "Where do you want to store cache?",
+            |ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut settings.cache_folder)
+                        .desired_width(120.0)
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+        settings_row(
+            ui,
+            "Remember missing lyrics",
+            "How long a track without lyrics is skipped before looking again, 0 to always look",
+            |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.not_found_ttl_hours, 0..=168)
+                        .suffix(" h")
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+        settings_row(
+            ui,
+            "Refresh cached lyrics",
+            "Age after which cached lrclib lyrics are fetched again in the background, picking up fixes. 0 never refreshes",
+            |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.cache_refresh_days, 0..=365)
+                        .suffix(" days")
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+        settings_row(
+            ui,
+            "Cache size limit",
+            "Least recently shown tracks are removed from the cache above this size, pinned lyrics are kept. 0 for no limit",
+            |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.cache_max_mb, 0..=2000)
+                        .suffix(" MB")
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+    }
+}
+
+fn lrc_folder_settings(ui: &mut Ui, folders: &mut Vec<String>) {
+    settings_row(
+        ui,
+        "LRC folders",
+        "Folders with lrc, srt, vtt or ttml files named like \"Artist - Title.lrc\", subfolders are searched too",
+        |ui| {
+            ui.vertical(|ui| {
+                let mut remove = None;
+                for (i, folder) in folders.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(folder)
+                                .desired_width(160.0)
+                                .text_color(Color32::from_gray(200)),
+                        );
+                        if ui.button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    folders.remove(i);
+                }
+                if ui.button("Add folder").clicked() {
+                    folders.push(String::new());
+                }
+            });
+        },
+    );
+}
+
+fn profanity_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "Filter profanity",
+        "Mask swear words in the shown lyrics, like f**k",
+        |ui| {
+            ui.checkbox(&mut settings.profanity_filter, "");
+        },
+    );
+    if !settings.profanity_filter {
+        return;
+    }
+    let words = &mut settings.profanity_words;
+    settings_row(
+        ui,
+        "Masked words",
+        "A trailing * also masks longer words, fuck* masks fucking too",
+        |ui| {
+            ui.vertical(|ui| {
+                let mut remove = None;
+                for (i, word) in words.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(word)
+                                .desired_width(100.0)
+                                .text_color(Color32::from_gray(200)),
+                        );
+                        if ui.button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    words.remove(i);
+                }
+                if ui.button("Add word").clicked() {
+                    words.push(String::new());
+                }
+            });
+        },
+    );
+}
+
+fn progress_bar_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "Line progress bar",
+        "Shows a bar with progress/duration of current line",
+        |ui| {
+            egui::ComboBox::from_id_salt("progress_bar_position")
+                .selected_text(settings.line_progress_bar_position.as_str())
+                .show_ui(ui, |ui| {
+                    for pos in [
+                        ProgressBarPosition::BelowCurrentLine,
+                        ProgressBarPosition::Bottom,
+                        ProgressBarPosition::Hidden,
+                    ] {
+                        ui.selectable_value(
+                            &mut settings.line_progress_bar_position,
+                            pos,
+                            pos.as_str(),
+                        );
+                    }
+                });
+        },
+    );
+    settings_row(
+        ui,
+        "Song progress bar",
+        "Shows a bar with progress/duration of current song",
+        |ui| {
+            egui::ComboBox::from_id_salt("song_progress_bar_position")
+                .selected_text(settings.song_progress_bar_position.as_str())
+                .show_ui(ui, |ui| {
+                    for pos in [
+                        ProgressBarPosition::BelowCurrentLine,
+                        ProgressBarPosition::Bottom,
+                        ProgressBarPosition::Hidden,
+                    ] {
+                        ui.selectable_value(
+                            &mut settings.song_progress_bar_position,
+                            pos,
+                            pos.as_str(),
+                        );
+                    }
+                });
+        },
+    );
+}
+
+fn easing_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(ui, "Position easing", "Ease position?", |ui| {
+        egui::ComboBox::from_id_salt("position_ease")
+            .selected_text(settings.ease_position.as_str())
+            .show_ui(ui, |ui| {
+                for mode in [EasingModes::Cubic, EasingModes::Linear] {
+                    ui.selectable_value(&mut settings.ease_position, mode, mode.as_str());
+                }
+            });
+    });
+
+    settings_row(ui, "Color easing", "Ease color?", |ui| {
+        egui::ComboBox::from_id_salt("color_ease")
+            .selected_text(settings.ease_color.as_str())
+            .show_ui(ui, |ui| {
+                for mode in [EasingModes::Cubic, EasingModes::Linear] {
+                    ui.selectable_value(&mut settings.ease_color, mode, mode.as_str());
+                }
+            });
+    });
+}
+
+fn player_settings(ui: &mut Ui, settings: &mut Settings, tx: &mpsc::Sender<MessageToRT>) {
+    settings_row(
+        ui,
+        "Player",
+        "Where the current track comes from, applied after a restart",
+        |ui| {
+            egui::ComboBox::from_id_salt("player_source")
+                .selected_text(settings.player_source.as_str())
+                .show_ui(ui, |ui| {
+                    for &source in PlayerSourceKind::ALL {
+                        ui.selectable_value(&mut settings.player_source, source, source.as_str());
+                    }
+                });
+        },
+    );
+    if settings.player_source == PlayerSourceKind::Mpd {
+        mpd_settings(ui, settings);
+    }
+    if settings.player_source == PlayerSourceKind::YoutubeMusic {
+        youtube_music_settings(ui, settings);
+    }
+    if settings.player_source == PlayerSourceKind::Deezer {
+        deezer_settings(ui, settings, tx);
+    }
+    if settings.player_source == PlayerSourceKind::Plex {
+        plex_settings(ui, settings);
+    }
+    if settings.player_source == PlayerSourceKind::Subsonic {
+        subsonic_settings(ui, settings);
+    }
+    if settings.player_source == PlayerSourceKind::WebNowPlaying {
+        settings_row(
+            ui,
+            "Extension port",
+            "Set the same port in the WebNowPlaying extension, applied after a restart",
+            |ui| {
+                ui.add(egui::DragValue::new(&mut settings.web_now_playing_port));
+            },
+        );
+    }
+    if settings.player_source == PlayerSourceKind::HomeAssistant {
+        home_assistant_settings(ui, settings);
+    }
+    #[cfg(feature = "fingerprint")]
+    if settings.player_source == PlayerSourceKind::Fingerprint {
+        fingerprint_settings(ui, settings);
+    }
+    #[cfg(target_os = "linux")]
+    if settings.player_source == PlayerSourceKind::Mpris {
+        settings_row(
+            ui,
+            "MPRIS player",
+            "Only follow players whose name contains this, e.g. vlc or spotify. Empty for any",
+            |ui| {
+                ui.text_edit_singleline(&mut settings.mpris_player);
+            },
+        );
+    }
+}
+
+fn mpd_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(ui, "MPD host", "Applied after a restart", |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.mpd_host)
+                .desired_width(120.0)
+                .text_color(Color32::from_gray(200)),
+        );
+    });
+    settings_row(ui, "MPD port", "", |ui| {
+        ui.add(egui::DragValue::new(&mut settings.mpd_port));
+    });
+    settings_row(
+        ui,
+        "MPD password",
+        "Leave empty if the server needs none",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.mpd_password)
+                    .desired_width(120.0)
+                    .password(true)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "MPD music folder",
+        "The music_directory from the MPD config, for lyrics embedded in the files",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.mpd_music_dir)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+}
+
+fn youtube_music_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "API server",
+        "Address of the app's API server plugin, applied after a restart",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.youtube_music_url)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "API token",
+        "Leave empty to request one, the app asks you to approve it",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.youtube_music_token)
+                    .desired_width(120.0)
+                    .password(true)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+}
+
+fn deezer_settings(ui: &mut Ui, settings: &mut Settings, tx: &mpsc::Sender<MessageToRT>) {
+    settings_row(
+        ui,
+        "Deezer app id",
+        "From your app on developers.deezer.com, its domain should match the redirect host",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.deezer_app_id)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(ui, "Deezer secret", "", |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.deezer_secret)
+                .desired_width(120.0)
+                .password(true)
+                .text_color(Color32::from_gray(200)),
+        );
+    });
+    let label = if settings.deezer_token.is_empty() {
+        "Log in"
+    } else {
+        "Log in again"
+    };
+    settings_row(
+        ui,
+        "Deezer account",
+        "Uses the callback settings under Authentication",
+        |ui| {
+            if ui.button(label).clicked() {
+                tx.try_send(MessageToRT::PlayerLogin).unwrap();
+            }
+        },
+    );
+}
+
+fn plex_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(ui, "Plex server", "Applied after a restart", |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.plex_url)
+                .desired_width(120.0)
+                .text_color(Color32::from_gray(200)),
+        );
+    });
+    settings_row(
+        ui,
+        "Plex token",
+        "The X-Plex-Token of your account, see Plex's support article on finding it",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.plex_token)
+                    .desired_width(120.0)
+                    .password(true)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+}
+
+fn subsonic_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "Subsonic server",
+        "e.g. your Navidrome address, applied after a restart",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.subsonic_url)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(ui, "Subsonic user", "", |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.subsonic_username)
+                .desired_width(120.0)
+                .text_color(Color32::from_gray(200)),
+        );
+    });
+    settings_row(ui, "Subsonic password", "", |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.subsonic_password)
+                .desired_width(120.0)
+                .password(true)
+                .text_color(Color32::from_gray(200)),
+        );
+    });
+}
+
+fn home_assistant_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(ui, "Home Assistant", "Applied after a restart", |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.home_assistant_url)
+                .desired_width(120.0)
+                .text_color(Color32::from_gray(200)),
+        );
+    });
+    settings_row(
+        ui,
+        "Access token",
+        "A long-lived access token, created on your Home Assistant profile page",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.home_assistant_token)
+                    .desired_width(120.0)
+                    .password(true)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "Media player",
+        "Entity id, e.g. media_player.living_room",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.home_assistant_entity)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+}
+
+#[cfg(feature = "fingerprint")]
+fn fingerprint_settings(ui: &mut Ui, settings: &mut Settings) {
+    settings_row(
+        ui,
+        "AcoustID key",
+        "Register an application on acoustid.org to get one",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.acoustid_key)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "fpcalc path",
+        "Chromaprint's fpcalc, leave empty if it is on your path",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.fpcalc_path)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+}
+
+fn network_settings(ui: &mut Ui, settings: &mut Settings) {
+    section_label(ui, "Network");
+
+    settings_row(
+        ui,
+        "Proxy",
+        "http://, https:// or socks5:// proxy used for all requests, applied after a restart",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.proxy_url)
+                    .desired_width(120.0)
+                    .hint_text("none")
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "Connect timeout",
+        "How long to wait for a connection, applied after a restart",
+        |ui| {
+            ui.add(
+                egui::Slider::new(&mut settings.connect_timeout_ms, 1000..=30000)
+                    .suffix(" ms")
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    settings_row(
+        ui,
+        "Request timeout",
+        "How long to wait for a complete response, applied after a restart",
+        |ui| {
+            ui.add(
+                egui::Slider::new(&mut settings.request_timeout_ms, 1000..=60000)
+                    .suffix(" ms")
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+    if !settings.proxy_url.is_empty() {
+        settings_row(
+            ui,
+            "Proxy username",
+            "Leave empty if the proxy needs no login",
+            |ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut settings.proxy_username)
+                        .desired_width(120.0)
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+        settings_row(ui, "Proxy password", "", |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.proxy_password)
+                    .desired_width(120.0)
+                    .password(true)
+                    .text_color(Color32::from_gray(200)),
+            );
+        });
+    }
+}
+
+fn authentication_settings(ui: &mut Ui, settings: &mut Settings) {
+    section_label(ui, "Authentication");
+
+    settings_row(
+        ui,
+        "Spotify developer Client ID",
+        "Client ID as found in spotify developer dashboard",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.client_id)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+
+    settings_row(
+        ui,
+        "Spotify developer Client Secret",
+        "Client Secret as found in spotify developer dashboard",
+        |ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.client_secret)
+                    .desired_width(120.0)
+                    .text_color(Color32::from_gray(200)),
+            );
+        },
+    );
+
+    #[cfg(feature = "webview-auth")]
+    settings_row(
+        ui,
+        "Embedded login",
+        "Log in to spotify in a window of this app instead of the browser",
+        |ui| {
+            ui.checkbox(&mut settings.embedded_auth, "");
+        },
+    );
+
+    settings_row(
+        ui,
+        "Callback port",
+        "Port the login callback listens on, 0 picks any free port",
+        |ui| {
+            ui.add(egui::DragValue::new(&mut settings.port).speed(1.0));
+        },
+    );
+    if settings.port != 0 {
+        settings_row(
+            ui,
+            "Fallback ports",
+            "Ports after the callback port to try when it is in use",
+            |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.port_fallback_range, 0..=10)
+                        .text_color(Color32::from_gray(200)),
+                );
+            },
+        );
+    }
+    ui.label(
+        RichText::new("Redirect URIs to register in the spotify dashboard:")
+            .size(11.0)
+            .color(Color32::from_gray(140)),
+    );
+    for uri in settings.redirect_uris() {
+        ui.label(
+            RichText::new(uri)
+                .size(11.0)
+                .monospace()
+                .color(Color32::from_gray(200)),
+        );
+    }
+}
+
+fn scope_settings(ui: &mut Ui, settings: &mut Settings, tx: &mpsc::Sender<MessageToRT>) {
+    settings_row(
+        ui,
+        "Permissions",
+        "What this app may access on your Spotify account, changing this requires reconnecting",
+        |ui| {
+            egui::ComboBox::from_id_salt("oauth_scopes")
+                .selected_text(settings.oauth_scopes.as_str())
+                .show_ui(ui, |ui| {
+                    for scopes in [
+                        OAuthScopes::Minimal,
+                        OAuthScopes::Standard,
+                        OAuthScopes::PlaybackControl,
+                    ] {
+                        ui.selectable_value(&mut settings.oauth_scopes, scopes, scopes.as_str())
+                            .on_hover_text(scopes.scopes().join(", "));
+                    }
+                });
+        },
+    );
+    if settings
+        .active_account()
+        .is_some_and(|account| !account.has_scopes(settings.oauth_scopes.scopes()))
+    {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new("Reconnect to grant the new permissions")
+                    .size(11.0)
+                    .color(Color32::from_gray(110)),
+            );
+            if ui.button("Reconnect").clicked() {
+                tx.try_send(MessageToRT::Authenticate).unwrap();
+            }
+        });
+    }
+}
+
+fn account_settings(ui: &mut Ui, settings: &Settings, tx: &mpsc::Sender<MessageToRT>) {
+    settings_row(
+        ui,
+        "Spotify account",
+        "Account used for the currently playing information",
+        |ui| {
+            egui::ComboBox::from_id_salt("spotify_account")
+                .selected_text(
+                    settings
+                        .active_account()
+                        .map_or("None", |account| account.name.as_str()),
+                )
+                .show_ui(ui, |ui| {
+                    for (i, account) in settings.accounts.iter().enumerate() {
+                        let active = i == settings.active_account;
+                        if ui.selectable_label(active, &account.name).clicked() && !active {
+                            tx.try_send(MessageToRT::SwitchAccount(i)).unwrap();
+                        }
+                    }
+                });
+        },
+    );
+    ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+        if ui.button("Add account").clicked() {
+            tx.try_send(MessageToRT::AddAccount).unwrap();
+        }
+        if settings.active_account().is_some() && ui.button("Remove account").clicked() {
+            tx.try_send(MessageToRT::RemoveAccount(settings.active_account))
+                .unwrap();
+        }
+    });
 }
 
 fn reset_defaults(ui: &mut Ui, settings: &mut Settings) {
@@ -302,10 +1452,14 @@ fn reset_defaults(ui: &mut Ui, settings: &mut Settings) {
             let client_id = settings.client_id.clone();
             let client_secret = settings.client_secret.clone();
             let sp_dc = settings.sp_dc.clone();
+            let accounts = std::mem::take(&mut settings.accounts);
+            let active_account = settings.active_account;
             settings.reset();
             settings.client_id = client_id;
             settings.client_secret = client_secret;
             settings.sp_dc = sp_dc;
+            settings.accounts = accounts;
+            settings.active_account = active_account;
         }
     });
 }
@@ -0,0 +1,268 @@
+//! Small panel showing the state of the spotify session
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use egui::{Color32, RichText, Ui};
+
+use lyrics_overlay_core::{
+    MessageToRT, StatusEvent,
+    player::{PlaybackControl, PlayerSourceKind},
+};
+
+use crate::overlay::LyricsAppUI;
+
+/// Steps of uploading the shown lyrics to lrclib
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishState {
+    Closed,
+    /// Asking the user to confirm, uploads are public
+    Confirming,
+    Publishing,
+    Published,
+}
+
+/// What the runtime last reported about the session
+#[derive(Debug, Default)]
+pub struct SessionStatus {
+    /// Access token expiry as unix time
+    token_expiry_unix: Option<u64>,
+    /// Time of the last successful spotify API call
+    last_api_call: Option<Instant>,
+    /// Time of the last lyrics fetch and whether it found lyrics
+    last_lyrics_fetch: Option<(Instant, bool)>,
+}
+
+impl SessionStatus {
+    pub fn apply(&mut self, event: StatusEvent) {
+        match event {
+            StatusEvent::TokenExpiry(expiry) => self.token_expiry_unix = expiry,
+            StatusEvent::ApiCallSucceeded => self.last_api_call = Some(Instant::now()),
+            StatusEvent::LyricsFetched { found } => {
+                self.last_lyrics_fetch = Some((Instant::now(), found));
+            }
+        }
+    }
+
+    /// Did the last lyrics fetch find any, None before the first one
+    pub fn lyrics_found(&self) -> Option<bool> {
+        self.last_lyrics_fetch.map(|(_, found)| found)
+    }
+}
+
+impl LyricsAppUI {
+    pub(super) fn status_button(&mut self, ui: &mut Ui) {
+        let color = if self.is_auth {
+            Color32::from_rgb(80, 200, 120)
+        } else {
+            Color32::from_rgb(255, 80, 80)
+        };
+        if ui
+            .add(egui::Button::new(RichText::new("●").size(12.0).color(color)).frame(false))
+            .on_hover_text("Session status")
+            .clicked()
+        {
+            self.status_open = !self.status_open;
+        }
+    }
+
+    pub(super) fn status_panel(&mut self, ctx: &egui::Context, full_width: f32) {
+        if !self.status_open {
+            return;
+        }
+        let status = &self.session_status;
+
+        let account = self
+            .settings_cache
+            .active_account()
+            .map_or_else(|| "none".to_owned(), |account| account.name.clone());
+        let authenticated = if self.is_auth { "yes" } else { "no" };
+        let token = match status.token_expiry_unix {
+            Some(expiry) => format_expiry(expiry),
+            None => "no token".to_owned(),
+        };
+        let api_call = status
+            .last_api_call
+            .map_or_else(|| "never".to_owned(), format_ago);
+        let lyrics_fetch = match status.last_lyrics_fetch {
+            Some((at, true)) => format!("{}, found", format_ago(at)),
+            Some((at, false)) => format!("{}, not found", format_ago(at)),
+            None => "never".to_owned(),
+        };
+
+        egui::Area::new("session_status".into())
+            .fixed_pos(egui::pos2(full_width - 240., 30.))
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(Color32::from_rgba_unmultiplied(20, 20, 20, 230))
+                    .corner_radius(4.0)
+                    .inner_margin(egui::Margin::same(8))
+                    .show(ui, |ui| {
+                        ui.set_width(220.);
+                        for (label, value) in [
+                            ("Authenticated", authenticated.to_owned()),
+                            ("Account", account),
+                            ("Token", token),
+                            ("Last API call", api_call),
+                            ("Last lyrics fetch", lyrics_fetch),
+                        ] {
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    RichText::new(label)
+                                        .size(11.0)
+                                        .color(Color32::from_gray(130)),
+                                );
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.label(
+                                            RichText::new(value)
+                                                .size(11.0)
+                                                .color(Color32::from_gray(200)),
+                                        );
+                                    },
+                                );
+                            });
+                        }
+                        if self.controls_available() {
+                            ui.add_space(4.0);
+                            self.playback_controls(ui);
+                        }
+                        if self.currently_playing.is_some() {
+                            ui.add_space(4.0);
+                            if ui.button("Search lyrics manually").clicked() {
+                                self.open_search_dialog();
+                            }
+                        }
+                        if self.can_publish() {
+                            ui.add_space(4.0);
+                            if ui.button("Publish lyrics to lrclib").clicked() {
+                                self.publish_state = PublishState::Confirming;
+                            }
+                        }
+                    });
+            });
+    }
+
+    /// Can the selected player source be controlled with what we're allowed to do
+    fn controls_available(&self) -> bool {
+        match self.settings_cache.player_source {
+            PlayerSourceKind::Spotify => {
+                self.is_auth && self.settings_cache.oauth_scopes.can_control_playback()
+            }
+            PlayerSourceKind::Mpd
+            | PlayerSourceKind::YoutubeMusic
+            | PlayerSourceKind::WebNowPlaying
+            | PlayerSourceKind::HomeAssistant => true,
+            // These APIs have no playback controls, Plex only controls players through their own connection
+            PlayerSourceKind::Deezer | PlayerSourceKind::Plex | PlayerSourceKind::Subsonic => false,
+            #[cfg(feature = "fingerprint")]
+            PlayerSourceKind::Fingerprint => false,
+            #[cfg(target_os = "linux")]
+            PlayerSourceKind::Mpris => true,
+            #[cfg(any(windows, target_os = "macos"))]
+            PlayerSourceKind::System | PlayerSourceKind::AppleMusic => true,
+        }
+    }
+
+    /// Only synced lyrics that were actually found, not our placeholder text
+    fn can_publish(&self) -> bool {
+        self.session_status.lyrics_found() == Some(true)
+            && self
+                .current_song_with_lyrics
+                .as_ref()
+                .is_some_and(|song| !song.lyrics.unsynced && !song.lyrics.synced_lyrics.is_empty())
+    }
+
+    pub(super) fn publish_dialog(&mut self, ctx: &egui::Context) {
+        if self.publish_state == PublishState::Closed {
+            return;
+        }
+        let Some(song) = self.current_song_with_lyrics.as_ref() else {
+            self.publish_state = PublishState::Closed;
+            return;
+        };
+
+        egui::Window::new("Publish lyrics")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| match self.publish_state {
+                PublishState::Confirming => {
+                    ui.label(format!(
+                        "Upload the lyrics for {} - {} to lrclib.net?",
+                        song.artist_name, song.track_name
+                    ));
+                    ui.label(
+                        RichText::new(
+                            "They will be public and used by everyone looking up this song",
+                        )
+                        .size(11.0)
+                        .color(Color32::from_gray(160)),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Publish").clicked() {
+                            self.tx
+                                .try_send(MessageToRT::PublishLyrics(song.publish_request()))
+                                .unwrap();
+                            self.publish_state = PublishState::Publishing;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.publish_state = PublishState::Closed;
+                        }
+                    });
+                }
+                PublishState::Publishing => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Publishing...");
+                    });
+                }
+                PublishState::Published => {
+                    ui.label("Published, thanks for contributing!");
+                    if ui.button("Close").clicked() {
+                        self.publish_state = PublishState::Closed;
+                    }
+                }
+                PublishState::Closed => {}
+            });
+    }
+
+    fn playback_controls(&self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            for (label, command) in [
+                ("⏮", PlaybackControl::Previous),
+                ("⏯", PlaybackControl::PlayPause),
+                ("⏭", PlaybackControl::Next),
+            ] {
+                if ui.button(label).clicked() {
+                    self.tx
+                        .try_send(MessageToRT::PlayerControl(command))
+                        .unwrap();
+                }
+            }
+        });
+    }
+}
+
+fn format_ago(at: Instant) -> String {
+    format!("{} ago", format_secs(at.elapsed().as_secs()))
+}
+
+fn format_expiry(expiry_unix: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    if expiry_unix > now {
+        format!("expires in {}", format_secs(expiry_unix - now))
+    } else {
+        "expired".to_owned()
+    }
+}
+
+fn format_secs(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs} s"),
+        60..3600 => format!("{} min", secs / 60),
+        _ => format!("{} h", secs / 3600),
+    }
+}
@@ -0,0 +1,231 @@
+//! Colors, sizes and timing used when rendering the lyric lines
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use egui::{Color32, ecolor::Hsva};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use lyrics_overlay_core::{album_art::Palette, spotify::AudioFeatures};
+
+/// Time the album art colors take to fade into the next track's
+const TINT_FADE: Duration = Duration::from_millis(1500);
+
+/// Look of the lyrics, from a toml file in the themes folder. Missing keys keep the built-in value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Replaced by the track's colors while the dynamic theme is on
+    pub colors: LineColors,
+    /// Fill behind the lyrics, the background opacity setting is its alpha
+    pub background: [u8; 3],
+    /// Opacity of the lines farthest from the current one
+    pub min_alpha: f32,
+    /// Lines away from the current one at which they are faded the most
+    pub fade_lines: f32,
+    /// Growth of the current line on a beat
+    pub beat_pulse_scale: f32,
+    /// Growth of the current line over the others, eased in and out with the scrolling
+    pub current_line_growth: f32,
+    /// Size of romanizations and translations, relative to their line
+    pub secondary_scale: f32,
+    /// Size of lyrics without timings, relative to the font size
+    pub plain_scale: f32,
+    /// Where the current line is held, as a fraction of the height from the top
+    pub current_line_height: f32,
+    /// Space around the lyrics, horizontal and vertical
+    pub margin: [i8; 2],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            colors: LineColors::default(),
+            background: [0, 0, 0],
+            min_alpha: 0.2,
+            fade_lines: 3.5,
+            beat_pulse_scale: 0.06,
+            current_line_growth: 0.08,
+            secondary_scale: 0.65,
+            plain_scale: 0.8,
+            current_line_height: 0.125,
+            margin: [24, 16],
+        }
+    }
+}
+
+/// Every `.toml` theme in `folder` by file name, sorted, skipping the ones that don't parse
+pub fn load_themes(folder: &Path) -> Vec<(String, Theme)> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return vec![];
+    };
+    let mut themes: Vec<(String, Theme)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().into_owned();
+            let theme = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| toml::from_str(&text).map_err(|err| err.to_string()))
+                .inspect_err(|err| warn!("Skipping theme {}: {err}", path.display()))
+                .ok()?;
+            Some((name, theme))
+        })
+        .collect();
+    themes.sort_by(|a, b| a.0.cmp(&b.0));
+    themes
+}
+
+/// Colors of the lines before, at and after the current line
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LineColors {
+    pub past: [u8; 3],
+    pub current: [u8; 3],
+    pub future: [u8; 3],
+}
+
+impl Default for LineColors {
+    fn default() -> Self {
+        Self {
+            past: [200, 180, 255],
+            current: [255, 255, 255],
+            future: [180, 210, 255],
+        }
+    }
+}
+
+impl LineColors {
+    /// Sad tracks get cool blues, happy ones warm oranges, energy adds saturation and brightness
+    pub fn from_audio_features(features: &AudioFeatures) -> Self {
+        let valence = features.valence.clamp(0.0, 1.0);
+        let energy = features.energy.clamp(0.0, 1.0);
+
+        let future_hue = 0.62 + (0.08 - 0.62) * valence;
+        let past_hue = (future_hue + 0.12).fract();
+        let saturation = 0.15 + 0.35 * energy;
+        let value = 0.85 + 0.15 * energy;
+
+        Self {
+            past: hsv_to_rgb(past_hue, saturation, value),
+            current: hsv_to_rgb(future_hue, saturation * 0.2, 1.0),
+            future: hsv_to_rgb(future_hue, saturation, value),
+        }
+    }
+}
+
+/// Colors the album art theme changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tint {
+    pub colors: LineColors,
+    pub background: [u8; 3],
+}
+
+impl Tint {
+    pub fn from_theme(theme: &Theme) -> Self {
+        Self {
+            colors: theme.colors,
+            background: theme.background,
+        }
+    }
+
+    /// Lines are the cover's colors lightened so they stay readable, the background darkened
+    pub fn from_palette(palette: Palette) -> Self {
+        const WHITE: [u8; 3] = [255; 3];
+        Self {
+            colors: LineColors {
+                past: mix(palette.dominant, WHITE, 0.6),
+                current: mix(palette.accent, WHITE, 0.85),
+                future: mix(palette.accent, WHITE, 0.5),
+            },
+            background: mix(palette.dominant, [0; 3], 0.7),
+        }
+    }
+
+    fn mix(&self, other: &Self, t: f32) -> Self {
+        Self {
+            colors: LineColors {
+                past: mix(self.colors.past, other.colors.past, t),
+                current: mix(self.colors.current, other.colors.current, t),
+                future: mix(self.colors.future, other.colors.future, t),
+            },
+            background: mix(self.background, other.background, t),
+        }
+    }
+}
+
+/// Album art tint crossfading from the last track's to the current one's, `None` is the theme's
+#[derive(Debug)]
+pub struct TintFade {
+    from: Option<Tint>,
+    to: Option<Tint>,
+    started: Instant,
+    /// Album art the tint is from
+    pub url: Option<String>,
+}
+
+impl Default for TintFade {
+    fn default() -> Self {
+        Self {
+            from: None,
+            to: None,
+            started: Instant::now(),
+            url: None,
+        }
+    }
+}
+
+impl TintFade {
+    /// Fade from wherever the fade is at
+    pub fn fade_to(&mut self, to: Option<Tint>, url: Option<String>, theme: Tint) {
+        self.from = Some(self.current(theme));
+        self.to = to;
+        self.started = Instant::now();
+        self.url = url;
+    }
+
+    pub fn current(&self, theme: Tint) -> Tint {
+        let t = (self.started.elapsed().as_secs_f32() / TINT_FADE.as_secs_f32()).min(1.0);
+        self.from.unwrap_or(theme).mix(&self.to.unwrap_or(theme), t)
+    }
+}
+
+/// Multiplier for the line transition time, calm and slow tracks fade slower than energetic ones
+pub fn transition_scale(features: &AudioFeatures) -> f32 {
+    let tempo_scale = if features.tempo > 0.0 {
+        (120.0 / features.tempo).clamp(0.5, 2.0)
+    } else {
+        1.0
+    };
+    tempo_scale * (1.5 - features.energy.clamp(0.0, 1.0))
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn mix(a: [u8; 3], b: [u8; 3], t: f32) -> [u8; 3] {
+    std::array::from_fn(|i| (f32::from(a[i]) + (f32::from(b[i]) - f32::from(a[i])) * t) as u8)
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [u8; 3] {
+    let color = Color32::from(Hsva::new(h, s, v, 1.0));
+    [color.r(), color.g(), color.b()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_theme_keeps_defaults() {
+        let theme: Theme =
+            toml::from_str("background = [10, 20, 30]\n[colors]\ncurrent = [255, 200, 0]\n")
+                .unwrap();
+        assert_eq!(theme.background, [10, 20, 30]);
+        assert_eq!(theme.colors.current, [255, 200, 0]);
+        assert_eq!(theme.colors.past, LineColors::default().past);
+        assert_eq!(theme.margin, Theme::default().margin);
+    }
+}
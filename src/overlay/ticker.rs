@@ -0,0 +1,90 @@
+//! Ticker display mode, only the current line in a slim bar
+use egui::{Color32, Sense, TextStyle, TextWrapMode, Ui, Vec2, ViewportCommand};
+
+use lyrics_overlay_core::{lyrics_parser::LyricLine, settings::DisplayMode};
+
+use crate::overlay::{
+    LyricsAppUI,
+    lyrics_ui::{LineFill, fixed_rows, row_height},
+};
+
+/// Part of the line the marquee holds still at its start and at its end
+const MARQUEE_HOLD: f32 = 0.15;
+
+impl LyricsAppUI {
+    /// Lets the window shrink to a bar in ticker mode, and to the rows of the fixed row modes.
+    /// Compact mode also holds the window at its rows' height, the others let it grow again
+    pub(super) fn apply_display_mode(&mut self, ctx: &egui::Context) {
+        let min_height = match self.settings_cache.display_mode {
+            DisplayMode::Scrolling => 160.0,
+            DisplayMode::Ticker => 40.0,
+            mode @ (DisplayMode::Compact | DisplayMode::Karaoke) => {
+                fixed_rows(mode)
+                    .iter()
+                    .map(|(_, scale)| row_height(self.settings_cache.font_size * scale))
+                    .sum::<f32>()
+                    + 16.0
+            }
+        };
+        let min_size = Vec2::new(320.0, min_height);
+        let compact = self.settings_cache.display_mode == DisplayMode::Compact;
+        let max_size = if compact {
+            Vec2::new(f32::INFINITY, min_height)
+        } else {
+            Vec2::INFINITY
+        };
+        if self.applied_size_limits == Some((min_size, max_size)) {
+            return;
+        }
+        self.applied_size_limits = Some((min_size, max_size));
+        ctx.send_viewport_cmd(ViewportCommand::MinInnerSize(min_size));
+        ctx.send_viewport_cmd(ViewportCommand::MaxInnerSize(max_size));
+        if compact && let Some(inner) = ctx.input(|i| i.viewport().inner_rect) {
+            ctx.send_viewport_cmd(ViewportCommand::InnerSize(Vec2::new(
+                inner.width(),
+                min_height,
+            )));
+        }
+    }
+
+    /// The line on one row. Too long ones are panned across while they're sung if the marquee is
+    /// on, cut off otherwise
+    pub(super) fn ticker(
+        &self,
+        ui: &mut Ui,
+        line: &LyricLine,
+        progress: f32,
+        color: Color32,
+        fill: Option<LineFill>,
+    ) {
+        let galley = self
+            .line_text(
+                ui,
+                line,
+                self.settings_cache.font_size,
+                self.part_color(line.part, color),
+                fill,
+            )
+            .into_galley(
+                ui,
+                Some(TextWrapMode::Extend),
+                f32::INFINITY,
+                TextStyle::Body,
+            );
+        let (rect, _) = ui.allocate_exact_size(
+            Vec2::new(ui.available_width(), galley.size().y),
+            Sense::hover(),
+        );
+        let overflow = galley.size().x - rect.width();
+        let x = if overflow <= 0.0 {
+            rect.center().x - galley.size().x / 2.0
+        } else if self.settings_cache.ticker_marquee {
+            let pan = ((progress - MARQUEE_HOLD) / (1.0 - 2.0 * MARQUEE_HOLD)).clamp(0.0, 1.0);
+            rect.left() - overflow * pan
+        } else {
+            rect.left()
+        };
+        ui.painter_at(rect)
+            .galley(egui::pos2(x, rect.top()), galley, color);
+    }
+}